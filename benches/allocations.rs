@@ -0,0 +1,95 @@
+//! Heap allocations per request for common request shapes.
+//!
+//! Run with `cargo bench --bench allocations`; prints a table rather than
+//! timing, since allocation counts are deterministic.
+
+use rust_api::cors::CorsConfig;
+use rust_api::test::{TestClient, TestRequest};
+use rust_api::{Headers, Path, Req, Res, RustApi};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const ROUNDS: usize = 1000;
+
+type Case = (&'static str, fn(&TestClient) -> TestRequest<'_, ()>);
+
+fn app() -> RustApi {
+    let mut app = RustApi::new();
+    app.attach_pre_routing(
+        CorsConfig::new()
+            .allow_origins(["https://app.example.com"])
+            .allow_headers(["content-type", "x-token"])
+            .expose_headers(["x-request-id"]),
+    );
+    app.get("/plain", |_req: Req| async { Res::text("ok") });
+    app.get(
+        "/users/{id}",
+        |Path(params): Path<HashMap<String, String>>| async move { Res::text(params["id"].clone()) },
+    );
+    app.get("/headers", |Headers(headers): Headers| async move {
+        Res::text(headers.len().to_string())
+    });
+    app
+}
+
+fn main() {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let client = TestClient::new(app());
+
+    let cases: [Case; 5] = [
+        ("plain", |c| c.get("/plain")),
+        ("path_param", |c| c.get("/users/42")),
+        ("headers_extractor", |c| {
+            c.get("/headers")
+                .header("accept", "application/json")
+                .header("user-agent", "bench")
+                .header("x-token", "abc")
+        }),
+        ("cors_simple", |c| {
+            c.get("/plain").header("origin", "https://app.example.com")
+        }),
+        ("cors_preflight", |c| {
+            c.request(hyper::Method::OPTIONS, "/plain")
+                .header("origin", "https://app.example.com")
+                .header("access-control-request-method", "GET")
+                .header("access-control-request-headers", "x-token")
+        }),
+    ];
+
+    println!("{:<20} {:>12}", "case", "allocs/req");
+    for (name, build) in cases {
+        rt.block_on(build(&client).send());
+        let before = ALLOCATIONS.load(Ordering::Relaxed);
+        for _ in 0..ROUNDS {
+            rt.block_on(build(&client).send());
+        }
+        let per_request = (ALLOCATIONS.load(Ordering::Relaxed) - before) as f64 / ROUNDS as f64;
+        println!("{:<20} {:>12.1}", name, per_request);
+    }
+}
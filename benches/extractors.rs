@@ -0,0 +1,82 @@
+//! Extractor pipeline on requests built in memory.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use hyper::Method;
+use rust_api::{FromRequest, Json, Path, Query, Req};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct Params {
+    id: String,
+}
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct User {
+    name: String,
+    email: String,
+    age: u32,
+}
+
+fn request() -> Req {
+    Req::builder()
+        .method(Method::POST)
+        .uri("/users/42?page=2&per_page=50")
+        .param("id", "42")
+        .json(&serde_json::json!({
+            "name": "alice",
+            "email": "alice@example.com",
+            "age": 30,
+        }))
+        .build()
+}
+
+fn bench_extractors(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let state = Arc::new(());
+    let mut group = c.benchmark_group("extractors");
+
+    group.bench_function("path", |b| {
+        b.to_async(&rt).iter(|| async {
+            let mut req = request();
+            Path::<Params>::from_request(&mut req, &state)
+                .await
+                .unwrap()
+        })
+    });
+    group.bench_function("query", |b| {
+        b.to_async(&rt).iter(|| async {
+            let mut req = request();
+            Query::<HashMap<String, String>>::from_request(&mut req, &state)
+                .await
+                .unwrap()
+        })
+    });
+    group.bench_function("json", |b| {
+        b.to_async(&rt).iter(|| async {
+            let mut req = request();
+            Json::<User>::from_request(&mut req, &state).await.unwrap()
+        })
+    });
+    group.bench_function("path_query_json", |b| {
+        b.to_async(&rt).iter(|| async {
+            let mut req = request();
+            let path = Path::<Params>::from_request(&mut req, &state)
+                .await
+                .unwrap();
+            let query = Query::<HashMap<String, String>>::from_request(&mut req, &state)
+                .await
+                .unwrap();
+            let body = Json::<User>::from_request(&mut req, &state).await.unwrap();
+            (path, query, body)
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_extractors);
+criterion_main!(benches);
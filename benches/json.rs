@@ -0,0 +1,40 @@
+//! JSON response encoding.
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use rust_api::Res;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct Item {
+    id: u64,
+    name: String,
+    tags: Vec<&'static str>,
+    active: bool,
+}
+
+fn items(n: usize) -> Vec<Item> {
+    (0..n as u64)
+        .map(|id| Item {
+            id,
+            name: format!("item-{}", id),
+            tags: vec!["alpha", "beta"],
+            active: id % 2 == 0,
+        })
+        .collect()
+}
+
+fn bench_json(c: &mut Criterion) {
+    let mut group = c.benchmark_group("json");
+
+    for n in [1, 100, 10_000] {
+        let items = items(n);
+        group.throughput(Throughput::Elements(n as u64));
+        group.bench_with_input(BenchmarkId::new("res_json", n), &items, |b, items| {
+            b.iter(|| Res::json(items))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_json);
+criterion_main!(benches);
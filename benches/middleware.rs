@@ -0,0 +1,34 @@
+//! Middleware chain overhead per request.
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use rust_api::test::TestClient;
+use rust_api::{Next, Req, Res, RustApi, from_fn};
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+fn app(layers: usize) -> RustApi {
+    let mut app = RustApi::new();
+    for _ in 0..layers {
+        app.attach(from_fn(
+            |req: Req, _state: Arc<()>, next: Next<()>| async move { next.run(req).await },
+        ));
+    }
+    app.get("/", |_req: Req| async { Res::text("ok") });
+    app
+}
+
+fn bench_middleware(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("middleware");
+
+    for layers in [0, 1, 4, 16] {
+        let client = TestClient::new(app(layers));
+        group.bench_with_input(BenchmarkId::new("chain", layers), &layers, |b, _| {
+            b.to_async(&rt).iter(|| client.get("/").send())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_middleware);
+criterion_main!(benches);
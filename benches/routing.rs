@@ -0,0 +1,41 @@
+//! Route lookup through the in-process pipeline.
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use rust_api::test::TestClient;
+use rust_api::{Req, Res, RustApi};
+use tokio::runtime::Runtime;
+
+fn app(routes: usize) -> RustApi {
+    let mut app = RustApi::new();
+    app.get("/health", |_req: Req| async { Res::text("ok") });
+    for i in 0..routes {
+        app.get(&format!("/r{}/items/{{id}}", i), |_req: Req| async {
+            Res::text("item")
+        });
+    }
+    app
+}
+
+fn bench_routing(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("routing");
+
+    for routes in [10, 100, 1000] {
+        let client = TestClient::new(app(routes));
+        let last = format!("/r{}/items/42", routes - 1);
+
+        group.bench_with_input(BenchmarkId::new("static", routes), &routes, |b, _| {
+            b.to_async(&rt).iter(|| client.get("/health").send())
+        });
+        group.bench_with_input(BenchmarkId::new("param", routes), &routes, |b, _| {
+            b.to_async(&rt).iter(|| client.get(&last).send())
+        });
+        group.bench_with_input(BenchmarkId::new("not_found", routes), &routes, |b, _| {
+            b.to_async(&rt).iter(|| client.get("/missing").send())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_routing);
+criterion_main!(benches);
@@ -0,0 +1,61 @@
+//! WebSocket frame encoding and decoding.
+
+use bytes::BytesMut;
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use rust_api::Message;
+use rust_api::websocket::codec;
+
+fn bench_codec(c: &mut Criterion) {
+    let mut group = c.benchmark_group("websocket");
+
+    for size in [16, 1024, 64 * 1024] {
+        let text = Message::Text("x".repeat(size));
+        let frame = codec::encode(&text).unwrap();
+        group.throughput(Throughput::Bytes(size as u64));
+
+        group.bench_with_input(BenchmarkId::new("encode_text", size), &text, |b, text| {
+            b.iter(|| codec::encode(text).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("decode_text", size), &frame, |b, frame| {
+            b.iter(|| {
+                let mut buffer = BytesMut::from(&frame[..]);
+                codec::decode(&mut buffer).unwrap()
+            })
+        });
+
+        let masked = mask_frame(&frame, [0x37, 0xfa, 0x21, 0x3d]);
+        group.bench_with_input(
+            BenchmarkId::new("decode_masked", size),
+            &masked,
+            |b, frame| {
+                b.iter(|| {
+                    let mut buffer = BytesMut::from(&frame[..]);
+                    codec::decode(&mut buffer).unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Turn an unmasked server frame into a masked client frame.
+fn mask_frame(frame: &[u8], mask: [u8; 4]) -> Vec<u8> {
+    let header_len = match frame[1] {
+        126 => 4,
+        127 => 10,
+        _ => 2,
+    };
+    let mut masked = frame[..header_len].to_vec();
+    masked[1] |= 0x80;
+    masked.extend_from_slice(&mask);
+    masked.extend(
+        frame[header_len..]
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| byte ^ mask[i % 4]),
+    );
+    masked
+}
+
+criterion_group!(benches, bench_codec);
+criterion_main!(benches);
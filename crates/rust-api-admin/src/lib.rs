@@ -0,0 +1,591 @@
+//! Admin dashboard for rust-api applications.
+//!
+//! [`Admin::mount`] adds a dashboard page and a JSON endpoint showing live
+//! metrics, open WebSocket connections, recent 5xx errors, the route table
+//! and the server configuration, plus a switch for maintenance mode and any
+//! runtime controls registered with [`Admin::control`] or
+//! [`Admin::runtime_config`]. The API endpoints are protected by their own
+//! bearer token or basic-auth credentials. With basic auth the page is
+//! protected too; with a token it is served to anyone, since browsers
+//! cannot send one on page loads, and prompts for the token before loading
+//! any data.
+//!
+//! ```rust,no_run
+//! use rust_api::{Req, Res, RustApi};
+//! use rust_api_admin::Admin;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let mut app = RustApi::new();
+//!     app.get("/users", |_req: Req| async { Res::text("users") });
+//!
+//!     // Mount last so the route table is complete.
+//!     Admin::with_basic_auth("admin", "s3cret").mount(&mut app, "/_admin");
+//!     app.listen(([127, 0, 0, 1], 3000)).await.unwrap();
+//! }
+//! ```
+
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD;
+use rust_api::admin::RuntimeConfig;
+use rust_api::maintenance::MaintenanceSwitch;
+use rust_api::metrics::{ConnectionStats, InMemoryMetrics, Metrics, RequestTimings, RuntimeStats};
+use rust_api::{
+    Error, IntoRes, Middleware, Next, Req, Res, RouteInfo, RustApi, ServerConfig, from_fn,
+};
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+const DASHBOARD_HTML: &str = include_str!("dashboard.html");
+
+type ReadFn = Arc<dyn Fn() -> serde_json::Value + Send + Sync>;
+type WriteFn = Arc<dyn Fn(serde_json::Value) -> rust_api::Result<serde_json::Value> + Send + Sync>;
+
+/// Runtime setting exposed through the admin API.
+#[derive(Clone)]
+struct Control {
+    name: String,
+    read: ReadFn,
+    write: WriteFn,
+}
+
+#[derive(Clone)]
+enum Auth {
+    Token(String),
+    Basic(String),
+}
+
+impl Auth {
+    /// Check an `Authorization` header value.
+    fn check(&self, authorization: Option<&str>) -> bool {
+        let Some(authorization) = authorization else {
+            return false;
+        };
+        let (expected, given) = match self {
+            Auth::Token(token) => (token, authorization.strip_prefix("Bearer ")),
+            Auth::Basic(encoded) => (encoded, authorization.strip_prefix("Basic ")),
+        };
+        given.is_some_and(|given| constant_time_eq(expected.as_bytes(), given.trim().as_bytes()))
+    }
+
+    fn challenge(&self) -> &'static str {
+        match self {
+            Auth::Token(_) => "Bearer realm=\"admin\"",
+            Auth::Basic(_) => "Basic realm=\"admin\"",
+        }
+    }
+
+    /// Middleware rejecting requests without valid credentials.
+    fn require<S: Send + Sync + 'static>(self) -> impl Middleware<S> {
+        from_fn(move |req: Req, _state: Arc<S>, next: Next<S>| {
+            let auth = self.clone();
+            async move {
+                if auth.check(req.header("authorization")) {
+                    return next.run(req).await;
+                }
+                let mut res = Error::unauthorized("Admin credentials required").into_res();
+                if let Ok(value) = auth.challenge().parse() {
+                    res.headers_mut().insert("www-authenticate", value);
+                }
+                res
+            }
+        })
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Admin dashboard configuration.
+pub struct Admin {
+    auth: Auth,
+    error_capacity: usize,
+    forward: Option<Arc<dyn Metrics>>,
+    controls: Vec<Control>,
+}
+
+impl Admin {
+    /// Protect the dashboard with `Authorization: Bearer <token>`.
+    pub fn with_token(token: impl Into<String>) -> Self {
+        Self::new(Auth::Token(token.into()))
+    }
+
+    /// Protect the dashboard with HTTP basic auth.
+    pub fn with_basic_auth(user: &str, password: &str) -> Self {
+        Self::new(Auth::Basic(
+            STANDARD.encode(format!("{}:{}", user, password)),
+        ))
+    }
+
+    fn new(auth: Auth) -> Self {
+        Self {
+            auth,
+            error_capacity: 50,
+            forward: None,
+            controls: Vec::new(),
+        }
+    }
+
+    /// Number of recent errors kept (default 50).
+    pub fn recent_errors(mut self, capacity: usize) -> Self {
+        self.error_capacity = capacity;
+        self
+    }
+
+    /// Keep reporting to an existing metrics sink.
+    ///
+    /// Mounting installs the dashboard as the app's metrics sink, replacing
+    /// any sink set earlier.
+    pub fn forward_metrics<M: Metrics>(mut self, metrics: M) -> Self {
+        self.forward = Some(Arc::new(metrics));
+        self
+    }
+
+    /// Expose a runtime setting at `{prefix}/api/{name}`.
+    ///
+    /// `GET` returns `read()`, `PUT` passes the JSON body to `write` and
+    /// returns its result. The current value also appears in the dashboard
+    /// data under `controls`.
+    ///
+    /// ```rust,ignore
+    /// // Adjust proxy upstream weights (rust-api-client) at runtime.
+    /// let upstreams = proxy.upstreams();
+    /// let status = upstreams.clone();
+    /// Admin::with_token("s3cret")
+    ///     .control(
+    ///         "upstreams",
+    ///         move || serde_json::json!(status.status()),
+    ///         move |body| {
+    ///             let url = body["url"].as_str().unwrap_or_default();
+    ///             let weight = body["weight"].as_u64().unwrap_or_default() as u32;
+    ///             if !upstreams.set_weight(url, weight) {
+    ///                 return Err(Error::not_found("Unknown upstream"));
+    ///             }
+    ///             Ok(serde_json::json!(upstreams.status()))
+    ///         },
+    ///     )
+    ///     .mount(&mut app, "/_admin");
+    /// ```
+    pub fn control<R, W>(mut self, name: impl Into<String>, read: R, write: W) -> Self
+    where
+        R: Fn() -> serde_json::Value + Send + Sync + 'static,
+        W: Fn(serde_json::Value) -> rust_api::Result<serde_json::Value> + Send + Sync + 'static,
+    {
+        self.controls.push(Control {
+            name: name.into(),
+            read: Arc::new(read),
+            write: Arc::new(write),
+        });
+        self
+    }
+
+    /// Expose `config` at `{prefix}/api/config`.
+    ///
+    /// `PUT` takes a JSON merge patch, e.g.
+    /// `{"log_level": "debug", "flags": {"beta": null}}`, and returns the
+    /// updated settings.
+    pub fn runtime_config(self, config: RuntimeConfig) -> Self {
+        let read = config.clone();
+        self.control(
+            "config",
+            move || json!(read.current()),
+            move |patch| Ok(json!(config.patch(&patch)?)),
+        )
+    }
+
+    /// Mount the dashboard at `prefix` (page) and `{prefix}/api` (data).
+    ///
+    /// `PUT {prefix}/api/maintenance` with `{"enabled": true}` switches the
+    /// app's maintenance mode; `prefix` stays reachable while it is on.
+    ///
+    /// Call after registering application routes so the route table is complete.
+    pub fn mount<S: Send + Sync + 'static>(self, app: &mut RustApi<S>, prefix: &str) {
+        let prefix = prefix.trim_end_matches('/').to_string();
+        let maintenance = app.maintenance().clone().allow(prefix.as_str());
+        app.set_maintenance(maintenance);
+        let recorder = Arc::new(Recorder {
+            counters: InMemoryMetrics::new(),
+            errors: Mutex::new(VecDeque::with_capacity(self.error_capacity)),
+            capacity: self.error_capacity,
+            forward: self.forward,
+        });
+        app.set_metrics(Arc::clone(&recorder));
+
+        let api_path = format!("{}/api", prefix);
+        let dashboard = Arc::new(Dashboard {
+            recorder,
+            config: app.config(),
+            maintenance: app.maintenance_switch(),
+            controls: self.controls.clone(),
+            routes: OnceLock::new(),
+            started: Instant::now(),
+        });
+
+        let page = DASHBOARD_HTML.replace("{{API_PATH}}", &api_path);
+        let page_route = app
+            .get(&prefix, move |_req: Req| {
+                let page = page.clone();
+                async move { Res::html(page) }
+            })
+            .describe("Admin dashboard")
+            .tag("admin");
+        // Browsers cannot attach a bearer token to a page load; the page
+        // then asks for the token and holds no data itself.
+        if let Auth::Basic(_) = self.auth {
+            page_route.attach(self.auth.clone().require());
+        }
+
+        let handler_dashboard = Arc::clone(&dashboard);
+        app.get(&api_path, move |_req: Req| {
+            let dashboard = Arc::clone(&handler_dashboard);
+            async move { Res::json(&dashboard.report()) }
+        })
+        .describe("Admin dashboard data")
+        .tag("admin")
+        .attach(self.auth.clone().require());
+
+        let switch = app.maintenance_switch();
+        app.put(&format!("{}/maintenance", api_path), move |mut req: Req| {
+            let switch = switch.clone();
+            async move {
+                let toggle: MaintenanceToggle = serde_json::from_slice(&req.bytes().await?)
+                    .map_err(|e| Error::bad_request(format!("Invalid body: {}", e)))?;
+                if toggle.enabled {
+                    switch.enable();
+                } else {
+                    switch.disable();
+                }
+                Ok::<_, Error>(Res::json(&json!({ "maintenance": switch.is_enabled() })))
+            }
+        })
+        .describe("Switch maintenance mode")
+        .tag("admin")
+        .attach(self.auth.clone().require());
+
+        for control in self.controls {
+            let path = format!("{}/{}", api_path, control.name);
+            let read = control.read;
+            app.get(&path, move |_req: Req| {
+                let read = Arc::clone(&read);
+                async move { Res::json(&read()) }
+            })
+            .tag("admin")
+            .attach(self.auth.clone().require());
+
+            let write = control.write;
+            app.put(&path, move |mut req: Req| {
+                let write = Arc::clone(&write);
+                async move {
+                    let body = serde_json::from_slice(&req.bytes().await?)
+                        .map_err(|e| Error::bad_request(format!("Invalid body: {}", e)))?;
+                    Ok::<_, Error>(Res::json(&write(body)?))
+                }
+            })
+            .tag("admin")
+            .attach(self.auth.clone().require());
+        }
+
+        dashboard.routes.set(app.routes()).ok();
+    }
+}
+
+/// Metrics sink feeding the dashboard.
+struct Recorder {
+    counters: InMemoryMetrics,
+    errors: Mutex<VecDeque<ErrorEntry>>,
+    capacity: usize,
+    forward: Option<Arc<dyn Metrics>>,
+}
+
+#[derive(Clone, Serialize)]
+struct ErrorEntry {
+    at: u64,
+    method: String,
+    route: Option<String>,
+    status: u16,
+    total_micros: u64,
+}
+
+impl Metrics for Recorder {
+    fn connection_accepted(&self, peer: SocketAddr) {
+        self.counters.connection_accepted(peer);
+        if let Some(forward) = &self.forward {
+            forward.connection_accepted(peer);
+        }
+    }
+
+    fn connection_rejected(&self, peer: SocketAddr) {
+        self.counters.connection_rejected(peer);
+        if let Some(forward) = &self.forward {
+            forward.connection_rejected(peer);
+        }
+    }
+
+    fn connection_closed(&self, stats: &ConnectionStats) {
+        self.counters.connection_closed(stats);
+        if let Some(forward) = &self.forward {
+            forward.connection_closed(stats);
+        }
+    }
+
+    fn request_completed(&self, timings: &RequestTimings) {
+        self.counters.request_completed(timings);
+        if timings.status >= 500 && self.capacity > 0 {
+            let mut errors = self.errors.lock().unwrap();
+            if errors.len() == self.capacity {
+                errors.pop_front();
+            }
+            errors.push_back(ErrorEntry {
+                at: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
+                method: timings.method.to_string(),
+                route: timings.route.as_deref().map(str::to_string),
+                status: timings.status,
+                total_micros: timings.total.as_micros() as u64,
+            });
+        }
+        if let Some(forward) = &self.forward {
+            forward.request_completed(timings);
+        }
+    }
+
+    fn websocket_opened(&self) {
+        self.counters.websocket_opened();
+        if let Some(forward) = &self.forward {
+            forward.websocket_opened();
+        }
+    }
+
+    fn websocket_closed(&self) {
+        self.counters.websocket_closed();
+        if let Some(forward) = &self.forward {
+            forward.websocket_closed();
+        }
+    }
+
+    fn runtime_sampled(&self, stats: &RuntimeStats) {
+        self.counters.runtime_sampled(stats);
+        if let Some(forward) = &self.forward {
+            forward.runtime_sampled(stats);
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct MaintenanceToggle {
+    enabled: bool,
+}
+
+struct Dashboard {
+    recorder: Arc<Recorder>,
+    config: ServerConfig,
+    maintenance: MaintenanceSwitch,
+    controls: Vec<Control>,
+    routes: OnceLock<Vec<RouteInfo>>,
+    started: Instant,
+}
+
+impl Dashboard {
+    fn report(&self) -> serde_json::Value {
+        let routes: Vec<_> = self
+            .routes
+            .get()
+            .into_iter()
+            .flatten()
+            .map(|route| {
+                json!({
+                    "method": route.method.as_str(),
+                    "pattern": route.pattern,
+                    "name": route.name,
+                    "description": route.description,
+                    "tags": route.tags,
+                })
+            })
+            .collect();
+        let errors: Vec<ErrorEntry> = self
+            .recorder
+            .errors
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .cloned()
+            .collect();
+
+        json!({
+            "uptime_secs": self.started.elapsed().as_secs(),
+            "maintenance": self.maintenance.is_enabled(),
+            "controls": self
+                .controls
+                .iter()
+                .map(|control| (control.name.clone(), (control.read)()))
+                .collect::<serde_json::Map<_, _>>(),
+            "metrics": self.recorder.counters.snapshot(),
+            "recent_errors": errors,
+            "routes": routes,
+            "config": self.config,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auth() {
+        let basic = Admin::with_basic_auth("admin", "pw").auth;
+        assert!(basic.check(Some("Basic YWRtaW46cHc=")));
+        assert!(!basic.check(Some("Basic YWRtaW46cHg=")));
+        assert!(!basic.check(None));
+
+        let token = Admin::with_token("t0k").auth;
+        assert!(token.check(Some("Bearer t0k")));
+        assert!(!token.check(Some("Bearer t0")));
+        assert!(!token.check(Some("Basic t0k")));
+    }
+
+    #[tokio::test]
+    async fn test_page_auth() {
+        use rust_api::test::{TestClient, assert_status};
+
+        let mut app = RustApi::new();
+        Admin::with_basic_auth("admin", "pw").mount(&mut app, "/_admin");
+        let client = TestClient::new(app);
+        assert_status(&client.get("/_admin").send().await, 401);
+        let res = client
+            .get("/_admin")
+            .header("authorization", "Basic YWRtaW46cHc=")
+            .send()
+            .await;
+        assert_status(&res, 200);
+
+        let mut app = RustApi::new();
+        Admin::with_token("t0k").mount(&mut app, "/_admin");
+        let client = TestClient::new(app);
+        assert_status(&client.get("/_admin").send().await, 200);
+        assert_status(&client.get("/_admin/api").send().await, 401);
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_toggle() {
+        use rust_api::test::{TestClient, assert_status, body_json};
+
+        let mut app = RustApi::new();
+        app.get("/users", |_req: Req| async { "users" });
+        Admin::with_token("t0k").mount(&mut app, "/_admin");
+        let client = TestClient::new(app);
+
+        let res = client
+            .put("/_admin/api/maintenance")
+            .header("authorization", "Bearer t0k")
+            .json(&json!({ "enabled": true }))
+            .send()
+            .await;
+        assert_status(&res, 200);
+        assert_status(&client.get("/users").send().await, 503);
+
+        let res = client
+            .get("/_admin/api")
+            .header("authorization", "Bearer t0k")
+            .send()
+            .await;
+        assert_eq!(
+            body_json::<serde_json::Value>(res).await["maintenance"],
+            true
+        );
+
+        let res = client
+            .put("/_admin/api/maintenance")
+            .json(&json!({ "enabled": false }))
+            .send()
+            .await;
+        assert_status(&res, 401);
+    }
+
+    #[tokio::test]
+    async fn test_control() {
+        use rust_api::test::{TestClient, assert_status, body_json};
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        let weight = Arc::new(AtomicU64::new(5));
+        let (read, write) = (Arc::clone(&weight), Arc::clone(&weight));
+        let mut app = RustApi::new();
+        Admin::with_token("t0k")
+            .control(
+                "weight",
+                move || json!(read.load(Ordering::Relaxed)),
+                move |body| {
+                    let value = body.as_u64().ok_or_else(|| Error::bad_request("number"))?;
+                    write.store(value, Ordering::Relaxed);
+                    Ok(json!(value))
+                },
+            )
+            .mount(&mut app, "/_admin");
+        let client = TestClient::new(app);
+
+        let res = client
+            .put("/_admin/api/weight")
+            .header("authorization", "Bearer t0k")
+            .json(&json!(50))
+            .send()
+            .await;
+        assert_eq!(body_json::<u64>(res).await, 50);
+        assert_eq!(weight.load(Ordering::Relaxed), 50);
+
+        let res = client
+            .get("/_admin/api")
+            .header("authorization", "Bearer t0k")
+            .send()
+            .await;
+        assert_eq!(
+            body_json::<serde_json::Value>(res).await["controls"]["weight"],
+            50
+        );
+        assert_status(&client.get("/_admin/api/weight").send().await, 401);
+    }
+
+    #[tokio::test]
+    async fn test_runtime_config() {
+        use rust_api::admin::Settings;
+        use rust_api::flags::FlagProvider;
+        use rust_api::test::{TestClient, assert_status, body_json};
+
+        let config = RuntimeConfig::new(Settings::default());
+        let mut app = RustApi::new();
+        Admin::with_token("t0k")
+            .runtime_config(config.clone())
+            .mount(&mut app, "/_admin");
+        let client = TestClient::new(app);
+
+        let patch = json!({ "flags": { "beta": true } });
+        let res = client.put("/_admin/api/config").json(&patch).send().await;
+        assert_status(&res, 401);
+        let res = client
+            .put("/_admin/api/config")
+            .header("authorization", "Bearer t0k")
+            .json(&patch)
+            .send()
+            .await;
+        assert_eq!(
+            body_json::<serde_json::Value>(res).await["flags"]["beta"],
+            true
+        );
+        assert!(config.is_enabled("beta"));
+        let res = client
+            .put("/_admin/api/config")
+            .header("authorization", "Bearer t0k")
+            .json(&json!({ "log_level": "loud" }))
+            .send()
+            .await;
+        assert_status(&res, 400);
+    }
+}
@@ -0,0 +1,230 @@
+//! Cluster-wide publish/subscribe for rust-api real-time handlers.
+//!
+//! [`Broadcast`] fans messages out to every subscriber of a topic, on every
+//! server instance. Instances exchange messages through a [`Backend`]:
+//! [`MemoryBackend`] for a single process, `RedisBackend` (Redis Pub/Sub,
+//! `redis` feature) or `NatsBackend` (`nats` feature) for clusters.
+//! [`stomp::Stomp`] exposes topics to STOMP clients over WebSocket.
+//!
+//! ```rust,no_run
+//! use rust_api::{RustApi, live::Live};
+//! use rust_api_broadcast::{Broadcast, MemoryBackend};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let chat = Broadcast::new(MemoryBackend::new());
+//!
+//!     let mut app = RustApi::new();
+//!     let room = chat.clone();
+//!     Live::new(move |mut conn| {
+//!         let room = room.clone();
+//!         async move {
+//!             let mut messages = room.subscribe("lobby");
+//!             loop {
+//!                 tokio::select! {
+//!                     incoming = conn.receive() => match incoming {
+//!                         Ok(Some(text)) => { room.publish("lobby", text).await.ok(); }
+//!                         _ => break,
+//!                     },
+//!                     Some(message) = messages.recv() => {
+//!                         let text = String::from_utf8_lossy(&message).into_owned();
+//!                         if conn.send(text).await.is_err() {
+//!                             break;
+//!                         }
+//!                     }
+//!                 }
+//!             }
+//!         }
+//!     })
+//!     .install(&mut app, "/chat");
+//!     app.listen(([127, 0, 0, 1], 3000)).await.unwrap();
+//! }
+//! ```
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use rust_api::Result;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+mod memory;
+#[cfg(feature = "nats")]
+mod nats;
+#[cfg(feature = "redis")]
+mod redis;
+pub mod stomp;
+
+pub use memory::MemoryBackend;
+#[cfg(feature = "nats")]
+pub use nats::NatsBackend;
+#[cfg(feature = "redis")]
+pub use redis::RedisBackend;
+
+/// Transport carrying messages between server instances.
+#[async_trait]
+pub trait Backend: Send + Sync + 'static {
+    /// Send `payload` on `topic` to every instance, including this one.
+    async fn publish(&self, topic: &str, payload: Bytes) -> Result<()>;
+
+    /// Pass every message published by any instance to `deliver`.
+    ///
+    /// Runs until the connection fails (it is then called again after a
+    /// short delay) or `deliver` returns `false`.
+    async fn listen(&self, deliver: &Deliver) -> Result<()>;
+}
+
+type Topics = Mutex<HashMap<String, broadcast::Sender<Bytes>>>;
+
+/// Sink handed to [`Backend::listen`] for incoming messages.
+pub struct Deliver {
+    topics: Weak<Topics>,
+}
+
+impl Deliver {
+    /// Fan `payload` out to local subscribers of `topic`.
+    ///
+    /// Returns `false` once the [`Broadcast`] is dropped and listening
+    /// should stop.
+    pub fn deliver(&self, topic: &str, payload: Bytes) -> bool {
+        let Some(topics) = self.topics.upgrade() else {
+            return false;
+        };
+        let mut topics = topics.lock().unwrap();
+        let gone = match topics.get(topic) {
+            Some(tx) => tx.send(payload).is_err(),
+            None => false,
+        };
+        if gone {
+            // Every subscriber is gone.
+            topics.remove(topic);
+        }
+        true
+    }
+}
+
+/// Handle for publishing and subscribing to topics.
+///
+/// Clones share the same subscriptions and backend connection.
+#[derive(Clone)]
+pub struct Broadcast {
+    backend: Arc<dyn Backend>,
+    topics: Arc<Topics>,
+    capacity: usize,
+}
+
+impl Broadcast {
+    /// Connect local subscribers to `backend`.
+    ///
+    /// Spawns the listening task, so call it inside the runtime. Each
+    /// subscriber buffers 256 messages; slower ones skip ahead.
+    pub fn new<B: Backend>(backend: B) -> Self {
+        Self::with_capacity(backend, 256)
+    }
+
+    /// Like [`new`](Self::new) with `capacity` messages buffered per
+    /// subscriber.
+    pub fn with_capacity<B: Backend>(backend: B, capacity: usize) -> Self {
+        let backend: Arc<dyn Backend> = Arc::new(backend);
+        let topics: Arc<Topics> = Arc::default();
+        let deliver = Deliver {
+            topics: Arc::downgrade(&topics),
+        };
+        let listener = Arc::clone(&backend);
+        tokio::spawn(async move {
+            loop {
+                let result = listener.listen(&deliver).await;
+                if deliver.topics.strong_count() == 0 {
+                    break;
+                }
+                match result {
+                    Ok(()) => log::warn!("broadcast backend stopped listening; reconnecting"),
+                    Err(e) => log::warn!("broadcast backend disconnected: {}", e),
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        });
+        Self {
+            backend,
+            topics,
+            capacity,
+        }
+    }
+
+    /// Publish `payload` to subscribers of `topic` on every instance.
+    pub async fn publish(&self, topic: &str, payload: impl Into<Bytes>) -> Result<()> {
+        self.backend.publish(topic, payload.into()).await
+    }
+
+    /// Receive messages published to `topic` from now on.
+    pub fn subscribe(&self, topic: &str) -> Subscription {
+        let mut topics = self.topics.lock().unwrap();
+        let rx = match topics.get(topic) {
+            Some(tx) => tx.subscribe(),
+            None => {
+                let (tx, rx) = broadcast::channel(self.capacity);
+                topics.insert(topic.to_string(), tx);
+                rx
+            }
+        };
+        Subscription { rx }
+    }
+
+    /// Number of local subscribers to `topic`.
+    pub fn subscriber_count(&self, topic: &str) -> usize {
+        self.topics
+            .lock()
+            .unwrap()
+            .get(topic)
+            .map_or(0, broadcast::Sender::receiver_count)
+    }
+}
+
+/// Stream of messages on one topic.
+pub struct Subscription {
+    rx: broadcast::Receiver<Bytes>,
+}
+
+impl Subscription {
+    /// Next message, or `None` once the [`Broadcast`] is dropped.
+    ///
+    /// A subscriber that falls more than the buffer capacity behind skips
+    /// the oldest messages.
+    pub async fn recv(&mut self) -> Option<Bytes> {
+        loop {
+            match self.rx.recv().await {
+                Ok(message) => return Some(message),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    log::warn!("broadcast subscriber lagged, skipped {} messages", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fan_out_across_instances() {
+        let backend = MemoryBackend::new();
+        let first = Broadcast::new(backend.clone());
+        let second = Broadcast::new(backend);
+
+        let mut a = first.subscribe("room");
+        let mut b = second.subscribe("room");
+        let mut other = second.subscribe("other");
+        tokio::task::yield_now().await;
+
+        first.publish("room", "hello").await.unwrap();
+        assert_eq!(a.recv().await.unwrap(), "hello");
+        assert_eq!(b.recv().await.unwrap(), "hello");
+
+        second.publish("other", "ping").await.unwrap();
+        assert_eq!(other.recv().await.unwrap(), "ping");
+        assert_eq!(first.subscriber_count("room"), 1);
+    }
+}
@@ -0,0 +1,73 @@
+//! In-process backend.
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use rust_api::Result;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+
+use crate::{Backend, Deliver};
+
+/// Backend connecting [`Broadcast`](crate::Broadcast) handles in one
+/// process.
+///
+/// Clones share the same channel, so each clone behaves like another
+/// server instance (useful in tests).
+pub struct MemoryBackend {
+    tx: broadcast::Sender<(String, Bytes)>,
+    // Subscribed up front so nothing published before `listen` is lost.
+    rx: Mutex<Option<broadcast::Receiver<(String, Bytes)>>>,
+}
+
+impl MemoryBackend {
+    /// Create a backend buffering up to 1024 undelivered messages.
+    pub fn new() -> Self {
+        let (tx, rx) = broadcast::channel(1024);
+        Self {
+            tx,
+            rx: Mutex::new(Some(rx)),
+        }
+    }
+}
+
+impl Default for MemoryBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clone for MemoryBackend {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            rx: Mutex::new(Some(self.tx.subscribe())),
+        }
+    }
+}
+
+#[async_trait]
+impl Backend for MemoryBackend {
+    async fn publish(&self, topic: &str, payload: Bytes) -> Result<()> {
+        // No receivers only means no instance is listening yet.
+        let _ = self.tx.send((topic.to_string(), payload));
+        Ok(())
+    }
+
+    async fn listen(&self, deliver: &Deliver) -> Result<()> {
+        let taken = self.rx.lock().unwrap().take();
+        let mut rx = taken.unwrap_or_else(|| self.tx.subscribe());
+        loop {
+            match rx.recv().await {
+                Ok((topic, payload)) => {
+                    if !deliver.deliver(&topic, payload) {
+                        return Ok(());
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    log::warn!("memory broadcast lagged, dropped {} messages", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            }
+        }
+    }
+}
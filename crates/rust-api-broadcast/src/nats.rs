@@ -0,0 +1,71 @@
+//! NATS backend.
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures_util::StreamExt;
+use rust_api::{Error, Result};
+
+use crate::{Backend, Deliver};
+
+/// Backend publishing through NATS core subjects (`nats` feature).
+///
+/// Topics map to subjects `{prefix}.{topic}`, so they must be valid
+/// subject tokens (dots add hierarchy); every instance subscribes to
+/// `{prefix}.>`.
+pub struct NatsBackend {
+    client: async_nats::Client,
+    prefix: String,
+}
+
+impl NatsBackend {
+    /// Connect to `url` (e.g. `nats://127.0.0.1:4222`) with subject prefix
+    /// `broadcast`.
+    pub async fn connect(url: &str) -> Result<Self> {
+        let client = async_nats::connect(url)
+            .await
+            .map_err(|e| Error::Custom(format!("NATS connect error: {}", e)))?;
+        Ok(Self::from_client(client))
+    }
+
+    /// Use an existing client, e.g. one configured with credentials.
+    pub fn from_client(client: async_nats::Client) -> Self {
+        Self {
+            client,
+            prefix: "broadcast".to_string(),
+        }
+    }
+
+    /// Use `prefix` for subjects, to share a NATS server between apps.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+}
+
+#[async_trait]
+impl Backend for NatsBackend {
+    async fn publish(&self, topic: &str, payload: Bytes) -> Result<()> {
+        self.client
+            .publish(format!("{}.{}", self.prefix, topic), payload)
+            .await
+            .map_err(|e| Error::Custom(format!("NATS publish error: {}", e)))
+    }
+
+    async fn listen(&self, deliver: &Deliver) -> Result<()> {
+        let mut subscriber = self
+            .client
+            .subscribe(format!("{}.>", self.prefix))
+            .await
+            .map_err(|e| Error::Custom(format!("NATS subscribe error: {}", e)))?;
+        let prefix = format!("{}.", self.prefix);
+        while let Some(message) = subscriber.next().await {
+            let Some(topic) = message.subject.as_str().strip_prefix(&prefix) else {
+                continue;
+            };
+            if !deliver.deliver(topic, message.payload) {
+                return Ok(());
+            }
+        }
+        Err(Error::Custom("NATS subscription closed".into()))
+    }
+}
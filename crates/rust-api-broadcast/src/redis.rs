@@ -0,0 +1,77 @@
+//! Redis Pub/Sub backend.
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures_util::StreamExt;
+use redis::AsyncCommands;
+use redis::aio::ConnectionManager;
+use rust_api::{Error, Result};
+
+use crate::{Backend, Deliver};
+
+/// Backend publishing through Redis Pub/Sub (`redis` feature).
+///
+/// Topics map to channels `{prefix}{topic}`; every instance subscribes to
+/// the pattern `{prefix}*`. Messages published while an instance is
+/// disconnected are not replayed to it.
+pub struct RedisBackend {
+    client: redis::Client,
+    publisher: ConnectionManager,
+    prefix: String,
+}
+
+impl RedisBackend {
+    /// Connect to `url` (e.g. `redis://127.0.0.1/`) with channel prefix
+    /// `broadcast:`.
+    pub async fn connect(url: &str) -> Result<Self> {
+        let client = redis::Client::open(url).map_err(redis_error)?;
+        let publisher = ConnectionManager::new(client.clone())
+            .await
+            .map_err(redis_error)?;
+        Ok(Self {
+            client,
+            publisher,
+            prefix: "broadcast:".to_string(),
+        })
+    }
+
+    /// Use `prefix` for channel names, to share a Redis between apps.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+}
+
+#[async_trait]
+impl Backend for RedisBackend {
+    async fn publish(&self, topic: &str, payload: Bytes) -> Result<()> {
+        let mut publisher = self.publisher.clone();
+        publisher
+            .publish::<_, _, ()>(format!("{}{}", self.prefix, topic), payload.as_ref())
+            .await
+            .map_err(redis_error)
+    }
+
+    async fn listen(&self, deliver: &Deliver) -> Result<()> {
+        let mut pubsub = self.client.get_async_pubsub().await.map_err(redis_error)?;
+        pubsub
+            .psubscribe(format!("{}*", self.prefix))
+            .await
+            .map_err(redis_error)?;
+        let mut messages = pubsub.on_message();
+        while let Some(message) = messages.next().await {
+            let Some(topic) = message.get_channel_name().strip_prefix(&self.prefix) else {
+                continue;
+            };
+            let payload = Bytes::copy_from_slice(message.get_payload_bytes());
+            if !deliver.deliver(topic, payload) {
+                return Ok(());
+            }
+        }
+        Err(Error::Custom("Redis Pub/Sub connection closed".into()))
+    }
+}
+
+fn redis_error(e: redis::RedisError) -> Error {
+    Error::Custom(format!("Redis error: {}", e))
+}
@@ -0,0 +1,513 @@
+//! STOMP over WebSocket, backed by [`Broadcast`].
+//!
+//! Lets STOMP clients (stomp.js, Spring's STOMP client, ...) publish and
+//! subscribe without a separate broker: `SEND` publishes the frame body to
+//! the destination topic and `SUBSCRIBE` delivers every message on that
+//! topic as a `MESSAGE` frame. Acknowledgements are implicit (`ack:auto`)
+//! and transactions are not supported.
+//!
+//! ```rust,no_run
+//! use rust_api::{RustApi, SubProtocols};
+//! use rust_api_broadcast::{Broadcast, MemoryBackend, stomp::Stomp};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let broadcast = Broadcast::new(MemoryBackend::new());
+//!     let mut app = RustApi::new();
+//!     SubProtocols::new()
+//!         .protocol(Stomp::new(broadcast).allow(|destination| destination.starts_with("/topic/")))
+//!         .install(&mut app, "/stomp");
+//!     app.listen(([127, 0, 0, 1], 3000)).await.unwrap();
+//! }
+//! ```
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use rust_api::{Message, SubProtocol, WebSocket};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::Broadcast;
+
+type DestinationFilter = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// Largest frame accepted from a client.
+const MAX_FRAME: usize = 1024 * 1024;
+
+/// STOMP 1.0–1.2 [`SubProtocol`] mapping destinations to topics.
+pub struct Stomp {
+    broadcast: Broadcast,
+    allow: Option<DestinationFilter>,
+}
+
+impl Stomp {
+    /// Serve destinations as topics of `broadcast`.
+    pub fn new(broadcast: Broadcast) -> Self {
+        Self {
+            broadcast,
+            allow: None,
+        }
+    }
+
+    /// Only allow `SEND` and `SUBSCRIBE` to destinations accepted by
+    /// `filter`; others get an `ERROR` frame.
+    pub fn allow<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.allow = Some(Arc::new(filter));
+        self
+    }
+
+    fn allowed(&self, destination: &str) -> bool {
+        self.allow.as_ref().is_none_or(|allow| allow(destination))
+    }
+}
+
+#[async_trait]
+impl SubProtocol for Stomp {
+    fn names(&self) -> &[&'static str] {
+        &["v12.stomp", "v11.stomp", "v10.stomp"]
+    }
+
+    async fn serve(&self, _name: &str, mut socket: WebSocket) {
+        let (tx, mut outgoing) = mpsc::channel(64);
+        let mut session = Session {
+            stomp: self,
+            tx,
+            connected: false,
+            disconnected: false,
+            subscriptions: HashMap::new(),
+        };
+        let mut buffer = Vec::new();
+
+        'connection: loop {
+            tokio::select! {
+                incoming = socket.receive() => {
+                    match incoming {
+                        Ok(Some(Message::Text(text))) => buffer.extend_from_slice(text.as_bytes()),
+                        Ok(Some(Message::Binary(data))) => buffer.extend_from_slice(&data),
+                        Ok(Some(Message::Ping(payload))) => {
+                            if socket.send(Message::Pong(payload)).await.is_err() {
+                                break;
+                            }
+                            continue;
+                        }
+                        Ok(Some(Message::Pong(_))) => continue,
+                        _ => break,
+                    }
+                    loop {
+                        let frame = match Frame::parse(&mut buffer) {
+                            Ok(Some(frame)) => frame,
+                            Ok(None) => break,
+                            Err(message) => {
+                                send(&mut socket, Frame::error(message, None)).await;
+                                break 'connection;
+                            }
+                        };
+                        for reply in session.handle(frame).await {
+                            let closing = reply.command != "RECEIPT" && reply.command != "CONNECTED";
+                            if !send(&mut socket, reply).await || closing {
+                                break 'connection;
+                            }
+                        }
+                        if session.disconnected {
+                            break 'connection;
+                        }
+                    }
+                }
+                Some(frame) = outgoing.recv() => {
+                    if !send(&mut socket, frame).await {
+                        break;
+                    }
+                }
+            }
+        }
+
+        for task in session.subscriptions.into_values() {
+            task.abort();
+        }
+        let _ = socket.close().await;
+    }
+}
+
+async fn send(socket: &mut WebSocket, frame: Frame) -> bool {
+    let encoded = frame.encode();
+    let message = match String::from_utf8(encoded) {
+        Ok(text) => Message::Text(text),
+        Err(e) => Message::Binary(e.into_bytes().into()),
+    };
+    socket.send(message).await.is_ok()
+}
+
+/// State of one STOMP connection.
+struct Session<'a> {
+    stomp: &'a Stomp,
+    tx: mpsc::Sender<Frame>,
+    connected: bool,
+    disconnected: bool,
+    subscriptions: HashMap<String, JoinHandle<()>>,
+}
+
+impl Session<'_> {
+    /// Handle one client frame, returning the frames to send back. Any
+    /// reply other than `CONNECTED` or `RECEIPT` ends the connection.
+    async fn handle(&mut self, frame: Frame) -> Vec<Frame> {
+        let receipt = frame.header("receipt").map(str::to_string);
+        let command = frame.command.as_str();
+        if !self.connected && command != "CONNECT" && command != "STOMP" {
+            return vec![Frame::error("not connected", receipt)];
+        }
+
+        match command {
+            "CONNECT" | "STOMP" => {
+                let version = frame
+                    .header("accept-version")
+                    .map(|versions| {
+                        ["1.2", "1.1", "1.0"]
+                            .into_iter()
+                            .find(|v| versions.split(',').any(|offered| offered.trim() == *v))
+                    })
+                    .unwrap_or(Some("1.0"));
+                let Some(version) = version else {
+                    return vec![Frame::error("supported versions are 1.0, 1.1, 1.2", None)];
+                };
+                self.connected = true;
+                return vec![Frame::new(
+                    "CONNECTED",
+                    vec![
+                        ("version".into(), version.into()),
+                        ("heart-beat".into(), "0,0".into()),
+                        ("server".into(), "rust-api".into()),
+                    ],
+                    Bytes::new(),
+                )];
+            }
+            "SUBSCRIBE" => {
+                let Some(destination) = frame.header("destination") else {
+                    return vec![Frame::error("SUBSCRIBE requires a destination", receipt)];
+                };
+                if !self.stomp.allowed(destination) {
+                    return vec![Frame::error("access denied", receipt)];
+                }
+                let id = frame.header("id").unwrap_or(destination).to_string();
+                let task = self.forward(id.clone(), destination.to_string());
+                if let Some(previous) = self.subscriptions.insert(id, task) {
+                    previous.abort();
+                }
+            }
+            "UNSUBSCRIBE" => {
+                let id = frame.header("id").or(frame.header("destination"));
+                if let Some(task) = id.and_then(|id| self.subscriptions.remove(id)) {
+                    task.abort();
+                }
+            }
+            "SEND" => {
+                let Some(destination) = frame.header("destination") else {
+                    return vec![Frame::error("SEND requires a destination", receipt)];
+                };
+                if !self.stomp.allowed(destination) {
+                    return vec![Frame::error("access denied", receipt)];
+                }
+                let body = frame.body.clone();
+                if let Err(e) = self.stomp.broadcast.publish(destination, body).await {
+                    log::warn!("stomp publish to {} failed: {}", destination, e);
+                    return vec![Frame::error("publish failed", receipt)];
+                }
+            }
+            "ACK" | "NACK" => {}
+            "DISCONNECT" => self.disconnected = true,
+            "BEGIN" | "COMMIT" | "ABORT" => {
+                return vec![Frame::error("transactions are not supported", receipt)];
+            }
+            _ => return vec![Frame::error("unknown command", receipt)],
+        }
+
+        let mut replies = Vec::new();
+        if let Some(receipt) = receipt {
+            replies.push(Frame::new(
+                "RECEIPT",
+                vec![("receipt-id".into(), receipt)],
+                Bytes::new(),
+            ));
+        }
+        replies
+    }
+
+    /// Forward messages on `destination` as `MESSAGE` frames.
+    fn forward(&self, id: String, destination: String) -> JoinHandle<()> {
+        let mut subscription = self.stomp.broadcast.subscribe(&destination);
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            let mut sequence = 0u64;
+            while let Some(payload) = subscription.recv().await {
+                sequence += 1;
+                let frame = Frame::new(
+                    "MESSAGE",
+                    vec![
+                        ("subscription".into(), id.clone()),
+                        ("message-id".into(), format!("{}-{}", id, sequence)),
+                        ("destination".into(), destination.clone()),
+                    ],
+                    payload,
+                );
+                if tx.send(frame).await.is_err() {
+                    break;
+                }
+            }
+        })
+    }
+}
+
+/// A STOMP frame.
+#[derive(Debug, Clone, PartialEq)]
+struct Frame {
+    command: String,
+    headers: Vec<(String, String)>,
+    body: Bytes,
+}
+
+impl Frame {
+    fn new(command: &str, headers: Vec<(String, String)>, body: Bytes) -> Self {
+        Self {
+            command: command.to_string(),
+            headers,
+            body,
+        }
+    }
+
+    fn error(message: &str, receipt: Option<String>) -> Self {
+        let mut headers = vec![("message".to_string(), message.to_string())];
+        if let Some(receipt) = receipt {
+            headers.push(("receipt-id".to_string(), receipt));
+        }
+        Self::new("ERROR", headers, Bytes::new())
+    }
+
+    /// First value of header `name` (repeated headers keep the first).
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Take one complete frame off the front of `buffer`.
+    ///
+    /// Skips heart-beat newlines. Returns `Ok(None)` until a whole frame
+    /// has arrived.
+    fn parse(buffer: &mut Vec<u8>) -> Result<Option<Frame>, &'static str> {
+        let start = buffer
+            .iter()
+            .position(|&b| b != b'\n' && b != b'\r')
+            .unwrap_or(buffer.len());
+        buffer.drain(..start);
+        if buffer.is_empty() {
+            return Ok(None);
+        }
+
+        let Some(head_end) = find(buffer, b"\n\n")
+            .map(|i| (i, i + 2))
+            .into_iter()
+            .chain(find(buffer, b"\r\n\r\n").map(|i| (i, i + 4)))
+            .min()
+        else {
+            return if buffer.len() > MAX_FRAME {
+                Err("frame too large")
+            } else {
+                Ok(None)
+            };
+        };
+        let (head, body_start) = head_end;
+        let head = std::str::from_utf8(&buffer[..head]).map_err(|_| "headers must be UTF-8")?;
+        let mut lines = head.lines().map(|line| line.trim_end_matches('\r'));
+        let command = lines.next().unwrap_or_default().to_string();
+        let connect = command == "CONNECT" || command == "CONNECTED";
+        let mut headers = Vec::new();
+        for line in lines {
+            let (name, value) = line.split_once(':').ok_or("malformed header")?;
+            if connect {
+                headers.push((name.to_string(), value.to_string()));
+            } else {
+                headers.push((unescape(name)?, unescape(value)?));
+            }
+        }
+
+        let length = headers
+            .iter()
+            .find(|(name, _)| name == "content-length")
+            .map(|(_, value)| value.parse::<usize>().map_err(|_| "invalid content-length"))
+            .transpose()?;
+        let body_end = match length {
+            Some(length) if length > MAX_FRAME => return Err("frame too large"),
+            Some(length) => {
+                if buffer.len() < body_start + length + 1 {
+                    return Ok(None);
+                }
+                if buffer[body_start + length] != 0 {
+                    return Err("frame body must end with NUL");
+                }
+                body_start + length
+            }
+            None => match buffer[body_start..].iter().position(|&b| b == 0) {
+                Some(nul) => body_start + nul,
+                None if buffer.len() > MAX_FRAME => return Err("frame too large"),
+                None => return Ok(None),
+            },
+        };
+
+        let body = Bytes::copy_from_slice(&buffer[body_start..body_end]);
+        buffer.drain(..=body_end);
+        Ok(Some(Frame {
+            command,
+            headers,
+            body,
+        }))
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.body.len() + 64);
+        out.extend_from_slice(self.command.as_bytes());
+        out.push(b'\n');
+        for (name, value) in &self.headers {
+            out.extend_from_slice(escape(name).as_bytes());
+            out.push(b':');
+            out.extend_from_slice(escape(value).as_bytes());
+            out.push(b'\n');
+        }
+        if !self.body.is_empty() {
+            out.extend_from_slice(format!("content-length:{}\n", self.body.len()).as_bytes());
+        }
+        out.push(b'\n');
+        out.extend_from_slice(&self.body);
+        out.push(0);
+        out
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            ':' => out.push_str("\\c"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn unescape(text: &str) -> Result<String, &'static str> {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => out.push('\\'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('c') => out.push(':'),
+            _ => return Err("invalid header escape"),
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryBackend;
+
+    #[test]
+    fn test_frame_parse_and_encode() {
+        let mut buffer = b"\nSEND\r\ndestination:/topic/a\\cb\r\n\r\nhi\0\nSUBSCRIBE\nid:1\ndestination:/q\n\n\0SEND\ncontent-length:3\n\na\0b\0".to_vec();
+        let send = Frame::parse(&mut buffer).unwrap().unwrap();
+        assert_eq!(send.command, "SEND");
+        assert_eq!(send.header("destination"), Some("/topic/a:b"));
+        assert_eq!(send.body, "hi");
+        let subscribe = Frame::parse(&mut buffer).unwrap().unwrap();
+        assert_eq!(subscribe.header("id"), Some("1"));
+        let binary = Frame::parse(&mut buffer).unwrap().unwrap();
+        assert_eq!(binary.body, &b"a\0b"[..]);
+        assert!(Frame::parse(&mut buffer).unwrap().is_none());
+
+        let mut partial = b"SEND\ndestination:/q\n\nhal".to_vec();
+        assert!(Frame::parse(&mut partial).unwrap().is_none());
+        assert!(Frame::parse(&mut b"SEND\nbad\n\n\0".to_vec()).is_err());
+
+        let encoded = Frame::new(
+            "MESSAGE",
+            vec![("destination".into(), "a:b".into())],
+            Bytes::from("x"),
+        )
+        .encode();
+        assert_eq!(
+            encoded,
+            b"MESSAGE\ndestination:a\\cb\ncontent-length:1\n\nx\0".to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_session_maps_frames_to_topics() {
+        let broadcast = Broadcast::new(MemoryBackend::new());
+        let stomp = Stomp::new(broadcast.clone()).allow(|d| d.starts_with("/topic/"));
+        let (tx, mut outgoing) = mpsc::channel(8);
+        let mut session = Session {
+            stomp: &stomp,
+            tx,
+            connected: false,
+            disconnected: false,
+            subscriptions: HashMap::new(),
+        };
+        let frame = |raw: &[u8]| Frame::parse(&mut raw.to_vec()).unwrap().unwrap();
+
+        assert_eq!(
+            session
+                .handle(frame(b"SEND\ndestination:/topic/a\n\n\0"))
+                .await[0]
+                .command,
+            "ERROR"
+        );
+        let connected = session
+            .handle(frame(b"CONNECT\naccept-version:1.1,1.2\nhost:x\n\n\0"))
+            .await;
+        assert_eq!(connected[0].header("version"), Some("1.2"));
+        assert!(
+            session
+                .handle(frame(b"SUBSCRIBE\nid:s1\ndestination:/topic/a\n\n\0"))
+                .await
+                .is_empty()
+        );
+        assert_eq!(
+            session
+                .handle(frame(b"SUBSCRIBE\nid:s2\ndestination:/private\n\n\0"))
+                .await[0]
+                .command,
+            "ERROR"
+        );
+        tokio::task::yield_now().await;
+
+        let receipt = session
+            .handle(frame(b"SEND\ndestination:/topic/a\nreceipt:r1\n\nhello\0"))
+            .await;
+        assert_eq!(receipt[0].header("receipt-id"), Some("r1"));
+        let message = outgoing.recv().await.unwrap();
+        assert_eq!(message.command, "MESSAGE");
+        assert_eq!(message.header("subscription"), Some("s1"));
+        assert_eq!(message.body, "hello");
+
+        session.handle(frame(b"DISCONNECT\n\n\0")).await;
+        assert!(session.disconnected);
+    }
+}
@@ -0,0 +1,180 @@
+//! Outbound HTTP client for rust-api handlers.
+//!
+//! [`Client`] wraps `reqwest` with a [`RetryPolicy`] so gateway and proxy
+//! handlers get resilient upstream calls, and maps failures to framework
+//! errors (502 for upstream errors, 504 for timeouts). [`Proxy`] is a
+//! ready-made gateway handler with weighted, health-checked upstreams, and
+//! [`Mirror`] copies sampled traffic to a shadow deployment.
+//!
+//! ```rust,no_run
+//! use rust_api::{Req, Res, Result, RustApi};
+//! use rust_api_client::{Client, RetryPolicy};
+//! use std::time::Duration;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let client = Client::new().retry(
+//!         RetryPolicy::new()
+//!             .max_attempts(4)
+//!             .backoff(Duration::from_millis(50), Duration::from_secs(2)),
+//!     );
+//!
+//!     let mut app = RustApi::new();
+//!     app.get("/weather", move |_req: Req| {
+//!         let client = client.clone();
+//!         async move {
+//!             let upstream = client.send(client.get("http://weather.internal/today")).await?;
+//!             let body = upstream.text().await.unwrap_or_default();
+//!             Result::Ok(Res::text(body))
+//!         }
+//!     });
+//!     app.listen(([127, 0, 0, 1], 3000)).await.unwrap();
+//! }
+//! ```
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Method, RequestBuilder, Response, StatusCode};
+use rust_api::{Error, Result};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+mod mirror;
+mod proxy;
+mod retry;
+
+pub use mirror::{Mirror, Mirrored};
+pub use proxy::{Proxy, UpstreamStatus, Upstreams};
+pub use retry::RetryPolicy;
+
+/// HTTP client with retries.
+#[derive(Clone, Default)]
+pub struct Client {
+    inner: reqwest::Client,
+    policy: Arc<RetryPolicy>,
+}
+
+impl Client {
+    /// Client with the default retry policy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Client using a preconfigured `reqwest` client.
+    pub fn from_reqwest(inner: reqwest::Client) -> Self {
+        Self {
+            inner,
+            policy: Arc::default(),
+        }
+    }
+
+    /// Set the retry policy.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.policy = Arc::new(policy);
+        self
+    }
+
+    /// Start a request.
+    pub fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        self.inner.request(method, url)
+    }
+
+    /// Start a GET request.
+    pub fn get(&self, url: &str) -> RequestBuilder {
+        self.request(Method::GET, url)
+    }
+
+    /// Start a POST request.
+    pub fn post(&self, url: &str) -> RequestBuilder {
+        self.request(Method::POST, url)
+    }
+
+    /// Send a request, retrying according to the policy.
+    ///
+    /// Requests with streaming bodies cannot be cloned and are sent once.
+    /// The final response is returned whatever its status.
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response> {
+        let request = request.build().map_err(upstream_error)?;
+        let policy = &self.policy;
+        let retryable = policy.allows(request.method(), request.headers());
+
+        let mut attempt = 1;
+        loop {
+            let next = if retryable && attempt < policy.max_attempts {
+                request.try_clone()
+            } else {
+                None
+            };
+            let Some(next) = next else {
+                return self.inner.execute(request).await.map_err(upstream_error);
+            };
+
+            let delay = match self.inner.execute(next).await {
+                Ok(response) => {
+                    if !policy.retries_status(response.status()) {
+                        return Ok(response);
+                    }
+                    match retry_after(response.headers(), SystemTime::now()) {
+                        Some(wait) if wait > policy.max_delay => return Ok(response),
+                        Some(wait) => wait.max(policy.delay(attempt)),
+                        None => policy.delay(attempt),
+                    }
+                }
+                Err(e) if e.is_connect() || e.is_timeout() => policy.delay(attempt),
+                Err(e) => return Err(upstream_error(e)),
+            };
+
+            log::debug!(
+                "retrying {} {} in {:?} (attempt {})",
+                request.method(),
+                request.url(),
+                delay,
+                attempt + 1
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Map a client error to 504 (timeout) or 502.
+fn upstream_error(error: reqwest::Error) -> Error {
+    let status = if error.is_timeout() {
+        StatusCode::GATEWAY_TIMEOUT
+    } else {
+        StatusCode::BAD_GATEWAY
+    };
+    Error::Status(
+        status.as_u16(),
+        Some(format!("Upstream request failed: {}", error)),
+    )
+}
+
+/// Parse `Retry-After` as delay seconds or an HTTP date.
+fn retry_after(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = httpdate::parse_http_date(value).ok()?;
+    Some(at.duration_since(now).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_retry_after() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers, now), None);
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("7"));
+        assert_eq!(retry_after(&headers, now), Some(Duration::from_secs(7)));
+
+        let later = httpdate::fmt_http_date(now + Duration::from_secs(30));
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(&later).unwrap());
+        assert_eq!(retry_after(&headers, now), Some(Duration::from_secs(30)));
+    }
+}
@@ -0,0 +1,281 @@
+//! Shadow traffic middleware.
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use reqwest::header::HeaderValue;
+use rust_api::{IntoRes, Middleware, Next, Req, Res};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+use crate::proxy::forwarded_headers;
+use crate::{Client, RetryPolicy};
+
+type MirrorHook = Arc<dyn Fn(&Mirrored) + Send + Sync>;
+
+/// Primary and shadow outcome of a mirrored request.
+#[derive(Debug, Clone)]
+pub struct Mirrored {
+    /// Request method.
+    pub method: reqwest::Method,
+    /// Request path and query.
+    pub path: String,
+    /// Status returned to the client.
+    pub primary_status: u16,
+    /// Primary response body, when `compare_bodies` is on.
+    pub primary_body: Option<Bytes>,
+    /// Shadow response status, or the error that prevented one.
+    pub shadow: Result<(u16, Bytes), String>,
+    /// Time the shadow took to answer.
+    pub shadow_latency: Duration,
+}
+
+impl Mirrored {
+    /// Whether the shadow answered with the same status (and body, when
+    /// bodies are compared).
+    pub fn matches(&self) -> bool {
+        match &self.shadow {
+            Ok((status, body)) => {
+                *status == self.primary_status
+                    && self
+                        .primary_body
+                        .as_ref()
+                        .is_none_or(|primary| primary == body)
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+/// Middleware copying a sample of requests to a shadow upstream.
+///
+/// Copies are sent in the background after the primary response is ready
+/// and never affect it; shadow responses are discarded unless a hook is
+/// set. Shadow requests carry `x-shadow-request: 1`. When `max_in_flight`
+/// copies are pending, further requests are not mirrored.
+///
+/// ```rust,no_run
+/// use rust_api::RustApi;
+/// use rust_api_client::Mirror;
+///
+/// let mut app = RustApi::new();
+/// app.attach(
+///     Mirror::new("http://orders-v2.internal:8080")
+///         .sample(0.05)
+///         .on_response(|mirrored| {
+///             if !mirrored.matches() {
+///                 eprintln!("shadow mismatch on {}", mirrored.path);
+///             }
+///         }),
+/// );
+/// ```
+#[derive(Clone)]
+pub struct Mirror {
+    upstream: Arc<str>,
+    client: Client,
+    sample: f64,
+    timeout: Duration,
+    compare_bodies: bool,
+    in_flight: Arc<Semaphore>,
+    hook: Option<MirrorHook>,
+}
+
+impl Mirror {
+    /// Mirror every request to `upstream` (e.g. `http://10.0.0.9:8080`).
+    pub fn new(upstream: impl Into<String>) -> Self {
+        let upstream = upstream.into();
+        Self {
+            upstream: upstream.trim_end_matches('/').into(),
+            client: Client::new().retry(RetryPolicy::none()),
+            sample: 1.0,
+            timeout: Duration::from_secs(5),
+            compare_bodies: false,
+            in_flight: Arc::new(Semaphore::new(64)),
+            hook: None,
+        }
+    }
+
+    /// Mirror this fraction of requests (0.0 to 1.0).
+    pub fn sample(mut self, fraction: f64) -> Self {
+        self.sample = fraction.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Give up on shadow requests after `timeout` (default 5 seconds).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Pending shadow requests allowed before mirroring pauses (default 64).
+    pub fn max_in_flight(mut self, max: usize) -> Self {
+        self.in_flight = Arc::new(Semaphore::new(max));
+        self
+    }
+
+    /// Send shadow requests through `client` (default: no retries).
+    pub fn client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Buffer mirrored primary responses so hooks can compare bodies.
+    pub fn compare_bodies(mut self) -> Self {
+        self.compare_bodies = true;
+        self
+    }
+
+    /// Call `hook` with both outcomes of each mirrored request.
+    pub fn on_response<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Mirrored) + Send + Sync + 'static,
+    {
+        self.hook = Some(Arc::new(hook));
+        self
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> Middleware<S> for Mirror {
+    async fn handle(&self, mut req: Req, _state: Arc<S>, next: Next<S>) -> Res {
+        if self.sample < 1.0 && fastrand::f64() >= self.sample {
+            return next.run(req).await;
+        }
+        let Ok(permit) = Arc::clone(&self.in_flight).try_acquire_owned() else {
+            return next.run(req).await;
+        };
+        let body = match req.peek_body().await {
+            Ok(body) => body,
+            Err(e) => return e.into_res(),
+        };
+        let method = req.method().clone();
+        let path = req
+            .uri()
+            .path_and_query()
+            .map_or("/", |p| p.as_str())
+            .to_string();
+        let mut headers = forwarded_headers(req.headers());
+        headers.insert("x-shadow-request", HeaderValue::from_static("1"));
+
+        let mut res = next.run(req).await;
+        let primary_status = res.status_code().as_u16();
+        let buffer = self.compare_bodies && self.hook.is_some() && primary_status != 101;
+        let primary_body = if buffer {
+            let (parts, body) = res.into_hyper().into_parts();
+            match body.collect().await {
+                Ok(collected) => {
+                    let trailers = collected.trailers().cloned();
+                    let bytes = collected.to_bytes();
+                    let body = Full::new(bytes.clone())
+                        .with_trailers(async move { trailers.map(Ok) })
+                        .map_err(|e| match e {})
+                        .boxed();
+                    res = Res::from_hyper(http::Response::from_parts(parts, body));
+                    Some(bytes)
+                }
+                Err(e) => {
+                    log::error!("failed to buffer response for mirroring: {}", e);
+                    return rust_api::Error::internal("Response body failed").into_res();
+                }
+            }
+        } else {
+            None
+        };
+
+        let request = self
+            .client
+            .request(method.clone(), &format!("{}{}", self.upstream, path))
+            .headers(headers)
+            .timeout(self.timeout)
+            .body(body);
+        let client = self.client.clone();
+        let hook = self.hook.clone();
+        tokio::spawn(async move {
+            let started = Instant::now();
+            let shadow = match client.send(request).await {
+                Ok(response) => {
+                    let status = response.status().as_u16();
+                    match response.bytes().await {
+                        Ok(body) => Ok((status, body)),
+                        Err(e) => Err(e.to_string()),
+                    }
+                }
+                Err(e) => Err(e.to_string()),
+            };
+            drop(permit);
+            if let Err(e) = &shadow {
+                log::debug!("shadow request {} {} failed: {}", method, path, e);
+            }
+            if let Some(hook) = hook {
+                hook(&Mirrored {
+                    method,
+                    path,
+                    primary_status,
+                    primary_body,
+                    shadow,
+                    shadow_latency: started.elapsed(),
+                });
+            }
+        });
+        res
+    }
+
+    fn name(&self) -> &'static str {
+        "Mirror"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_api::test::{TestClient, assert_status, body_text};
+    use rust_api::{Error, ErrorHandler, RustApi};
+
+    struct Apology;
+
+    impl ErrorHandler for Apology {
+        fn handle(&self, error: Error) -> Res {
+            Res::builder().status(503).text(format!("sorry: {}", error))
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        let mut mirrored = Mirrored {
+            method: reqwest::Method::GET,
+            path: "/orders".into(),
+            primary_status: 200,
+            primary_body: None,
+            shadow: Ok((200, Bytes::from_static(b"[]"))),
+            shadow_latency: Duration::ZERO,
+        };
+        assert!(mirrored.matches());
+
+        mirrored.primary_body = Some(Bytes::from_static(b"[1]"));
+        assert!(!mirrored.matches());
+
+        mirrored.primary_body = None;
+        mirrored.shadow = Err("connection refused".into());
+        assert!(!mirrored.matches());
+    }
+
+    #[tokio::test]
+    async fn test_buffered_error_reaches_error_handler() {
+        let mut app = RustApi::new();
+        app.set_error_handler(Apology);
+        app.attach(
+            Mirror::new("http://127.0.0.1:9")
+                .compare_bodies()
+                .on_response(|_| {}),
+        );
+        app.get("/orders", |_req: Req| async {
+            rust_api::Result::<&str>::Err(Error::not_found("no orders"))
+        });
+        let client = TestClient::new(app);
+
+        let res = client.get("/orders").send().await;
+        assert_status(&res, 503);
+        assert_eq!(body_text(res).await, "sorry: HTTP 404: no orders");
+    }
+}
@@ -0,0 +1,431 @@
+//! Reverse proxy handler with weighted upstreams.
+
+use async_trait::async_trait;
+use reqwest::header::{self, HeaderMap, HeaderName};
+use rust_api::{Error, Handler, IntoRes, Req, Res, Result};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::time::{Duration, Instant};
+
+use crate::Client;
+
+/// Headers describing a single connection, never forwarded.
+const HOP_BY_HOP: [HeaderName; 8] = [
+    header::CONNECTION,
+    HeaderName::from_static("keep-alive"),
+    header::PROXY_AUTHENTICATE,
+    header::PROXY_AUTHORIZATION,
+    header::TE,
+    header::TRAILER,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+];
+
+/// Handler forwarding requests to weighted upstreams.
+///
+/// Each request goes to a healthy upstream picked at random by weight, so
+/// a canary with weight 5 next to a stable upstream with weight 95 gets
+/// about 5% of traffic. Upstreams failing `eject_after` requests in a row
+/// (connection errors or 502/503/504) are skipped for a while; when every
+/// upstream is ejected, all are tried again.
+///
+/// Bodies are buffered in both directions. Clone the proxy to register it
+/// for several methods; clones share upstreams and health state.
+///
+/// ```rust,no_run
+/// use rust_api::RustApi;
+/// use rust_api_client::Proxy;
+/// use std::time::Duration;
+///
+/// let proxy = Proxy::new()
+///     .upstream("http://10.0.0.1:8080", 95)
+///     .upstream("http://10.0.0.2:8080", 5)
+///     .health_check("/health", Duration::from_secs(5));
+/// let upstreams = proxy.upstreams();
+///
+/// let mut app = RustApi::new();
+/// app.get("/{*path}", proxy.clone());
+/// app.post("/{*path}", proxy);
+///
+/// // later, promote the canary:
+/// upstreams.set_weight("http://10.0.0.2:8080", 50);
+/// ```
+#[derive(Clone)]
+pub struct Proxy {
+    client: Client,
+    upstreams: Upstreams,
+}
+
+impl Default for Proxy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Proxy {
+    /// Proxy with no upstreams, using the default [`Client`].
+    pub fn new() -> Self {
+        Self {
+            client: Client::new(),
+            upstreams: Upstreams(Arc::new(Pool {
+                backends: RwLock::new(Vec::new()),
+                eject_after: 3,
+                eject_for: Duration::from_secs(30),
+                health_check: None,
+                health_started: AtomicBool::new(false),
+            })),
+        }
+    }
+
+    /// Send upstream requests through `client`.
+    pub fn client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Add an upstream base URL (e.g. `http://10.0.0.1:8080`) with a weight.
+    pub fn upstream(self, url: impl Into<String>, weight: u32) -> Self {
+        self.upstreams.add(url.into(), weight);
+        self
+    }
+
+    /// Eject an upstream for `duration` after `failures` failed requests in
+    /// a row (default 3 and 30 seconds).
+    ///
+    /// # Panics
+    ///
+    /// Panics if called after the proxy was cloned.
+    pub fn eject_after(mut self, failures: u32, duration: Duration) -> Self {
+        let pool = self.pool_mut();
+        pool.eject_after = failures.max(1);
+        pool.eject_for = duration;
+        self
+    }
+
+    /// Probe `GET {upstream}{path}` every `interval`, ejecting upstreams
+    /// that fail and restoring those that answer 2xx.
+    ///
+    /// Probing starts with the first proxied request.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called after the proxy was cloned.
+    pub fn health_check(mut self, path: impl Into<String>, interval: Duration) -> Self {
+        self.pool_mut().health_check = Some((path.into(), interval));
+        self
+    }
+
+    /// Shared handle for reading and changing upstream weights.
+    pub fn upstreams(&self) -> Upstreams {
+        self.upstreams.clone()
+    }
+
+    fn pool_mut(&mut self) -> &mut Pool {
+        Arc::get_mut(&mut self.upstreams.0).expect("configure the proxy before cloning it")
+    }
+
+    async fn forward(&self, mut req: Req) -> Result<Res> {
+        self.upstreams.start_health_checks(&self.client);
+        let backend = self
+            .upstreams
+            .pick()
+            .ok_or_else(|| Error::Status(503, Some("No upstream available".into())))?;
+
+        let path = req.uri().path_and_query().map_or("/", |p| p.as_str());
+        let url = format!("{}{}", backend.url, path);
+        let mut headers = forwarded_headers(req.headers());
+        if let Some(host) = req.header("host") {
+            if let Ok(value) = host.parse() {
+                headers.insert("x-forwarded-host", value);
+            }
+        }
+        let body = req.bytes().await?;
+        let request = self
+            .client
+            .request(req.method().clone(), &url)
+            .headers(headers)
+            .body(body);
+
+        backend.requests.fetch_add(1, Ordering::Relaxed);
+        let response = match self.client.send(request).await {
+            Ok(response) => response,
+            Err(e) => {
+                self.upstreams.record(&backend, false);
+                return Err(e);
+            }
+        };
+        let status = response.status();
+        self.upstreams
+            .record(&backend, !matches!(status.as_u16(), 502..=504));
+
+        let headers = forwarded_headers(response.headers());
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| Error::Status(502, Some(format!("Upstream body failed: {}", e))))?;
+        let mut res = Res::builder().status(status.as_u16()).body(body);
+        res.headers_mut().extend(headers);
+        Ok(res)
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> Handler<S> for Proxy {
+    async fn call(&self, req: Req, _state: Arc<S>) -> Res {
+        self.forward(req).await.into_res()
+    }
+}
+
+/// Copy headers that may cross the proxy.
+pub(crate) fn forwarded_headers(headers: &HeaderMap) -> HeaderMap {
+    let mut forwarded = headers.clone();
+    for name in &HOP_BY_HOP {
+        forwarded.remove(name);
+    }
+    forwarded.remove(header::HOST);
+    forwarded.remove(header::CONTENT_LENGTH);
+    forwarded
+}
+
+/// Current state of one upstream.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UpstreamStatus {
+    /// Base URL.
+    pub url: String,
+    /// Relative share of traffic.
+    pub weight: u32,
+    /// Whether the upstream is receiving traffic (not ejected).
+    pub healthy: bool,
+    /// Requests sent.
+    pub requests: u64,
+    /// Requests that failed.
+    pub failures: u64,
+}
+
+/// Shared, runtime-adjustable set of proxy upstreams.
+///
+/// To change weights over HTTP, expose `status` and `set_weight` through
+/// `rust_api_admin::Admin::control`.
+#[derive(Clone)]
+pub struct Upstreams(Arc<Pool>);
+
+struct Pool {
+    backends: RwLock<Vec<Arc<Backend>>>,
+    eject_after: u32,
+    eject_for: Duration,
+    health_check: Option<(String, Duration)>,
+    health_started: AtomicBool,
+}
+
+struct Backend {
+    url: String,
+    weight: AtomicU32,
+    consecutive_failures: AtomicU32,
+    ejected_until: Mutex<Option<Instant>>,
+    requests: AtomicU64,
+    failures: AtomicU64,
+}
+
+impl Backend {
+    fn is_ejected(&self, now: Instant) -> bool {
+        self.ejected_until
+            .lock()
+            .unwrap()
+            .is_some_and(|until| until > now)
+    }
+}
+
+impl Upstreams {
+    /// Add an upstream, or update its weight if already present.
+    pub fn add(&self, url: String, weight: u32) {
+        let url = url.trim_end_matches('/').to_string();
+        if self.set_weight(&url, weight) {
+            return;
+        }
+        self.0.backends.write().unwrap().push(Arc::new(Backend {
+            url,
+            weight: AtomicU32::new(weight),
+            consecutive_failures: AtomicU32::new(0),
+            ejected_until: Mutex::new(None),
+            requests: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+        }));
+    }
+
+    /// Change an upstream's weight; 0 stops traffic to it.
+    ///
+    /// Returns `false` if no upstream has this URL.
+    pub fn set_weight(&self, url: &str, weight: u32) -> bool {
+        let url = url.trim_end_matches('/');
+        let backends = self.0.backends.read().unwrap();
+        match backends.iter().find(|backend| backend.url == url) {
+            Some(backend) => {
+                backend.weight.store(weight, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove an upstream. Returns `false` if no upstream has this URL.
+    pub fn remove(&self, url: &str) -> bool {
+        let url = url.trim_end_matches('/');
+        let mut backends = self.0.backends.write().unwrap();
+        let before = backends.len();
+        backends.retain(|backend| backend.url != url);
+        backends.len() != before
+    }
+
+    /// Status of every upstream.
+    pub fn status(&self) -> Vec<UpstreamStatus> {
+        let now = Instant::now();
+        self.0
+            .backends
+            .read()
+            .unwrap()
+            .iter()
+            .map(|backend| UpstreamStatus {
+                url: backend.url.clone(),
+                weight: backend.weight.load(Ordering::Relaxed),
+                healthy: !backend.is_ejected(now),
+                requests: backend.requests.load(Ordering::Relaxed),
+                failures: backend.failures.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    /// Pick an upstream by weight, preferring those not ejected.
+    fn pick(&self) -> Option<Arc<Backend>> {
+        let now = Instant::now();
+        let backends = self.0.backends.read().unwrap();
+        let weighted = |backend: &&Arc<Backend>| backend.weight.load(Ordering::Relaxed) > 0;
+        let healthy: Vec<_> = backends
+            .iter()
+            .filter(weighted)
+            .filter(|backend| !backend.is_ejected(now))
+            .collect();
+        let candidates = if healthy.is_empty() {
+            backends.iter().filter(weighted).collect()
+        } else {
+            healthy
+        };
+
+        let total: u64 = candidates
+            .iter()
+            .map(|backend| u64::from(backend.weight.load(Ordering::Relaxed)))
+            .sum();
+        if total == 0 {
+            return None;
+        }
+        let mut ticket = fastrand::u64(0..total);
+        for backend in &candidates {
+            let weight = u64::from(backend.weight.load(Ordering::Relaxed));
+            if ticket < weight {
+                return Some(Arc::clone(backend));
+            }
+            ticket -= weight;
+        }
+        candidates.last().map(|backend| Arc::clone(backend))
+    }
+
+    /// Record a request outcome, ejecting after repeated failures.
+    fn record(&self, backend: &Backend, ok: bool) {
+        if ok {
+            backend.consecutive_failures.store(0, Ordering::Relaxed);
+            return;
+        }
+        backend.failures.fetch_add(1, Ordering::Relaxed);
+        let failures = backend.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures >= self.0.eject_after {
+            backend.consecutive_failures.store(0, Ordering::Relaxed);
+            *backend.ejected_until.lock().unwrap() = Some(Instant::now() + self.0.eject_for);
+            log::warn!(
+                "ejecting upstream {} for {:?} after {} failures",
+                backend.url,
+                self.0.eject_for,
+                failures
+            );
+        }
+    }
+
+    /// Spawn the health check task once, if configured.
+    fn start_health_checks(&self, client: &Client) {
+        let Some((path, interval)) = self.0.health_check.clone() else {
+            return;
+        };
+        if self.0.health_started.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        let pool = Arc::downgrade(&self.0);
+        let client = client.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let Some(backends) = snapshot(&pool) else {
+                    break;
+                };
+                for backend in backends {
+                    let url = format!("{}{}", backend.url, path);
+                    let healthy = client
+                        .get(&url)
+                        .timeout(interval)
+                        .send()
+                        .await
+                        .is_ok_and(|response| response.status().is_success());
+                    let mut ejected_until = backend.ejected_until.lock().unwrap();
+                    if healthy {
+                        *ejected_until = None;
+                    } else {
+                        if ejected_until.is_none() {
+                            log::warn!("upstream {} failed its health check", backend.url);
+                        }
+                        *ejected_until = Some(Instant::now() + interval);
+                    }
+                }
+            }
+        });
+    }
+}
+
+fn snapshot(pool: &Weak<Pool>) -> Option<Vec<Arc<Backend>>> {
+    Some(pool.upgrade()?.backends.read().unwrap().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weights_and_ejection() {
+        let proxy = Proxy::new()
+            .upstream("http://stable/", 95)
+            .upstream("http://canary", 5)
+            .eject_after(2, Duration::from_secs(60));
+        let upstreams = proxy.upstreams();
+
+        let canary = (0..10_000)
+            .filter(|_| upstreams.pick().unwrap().url == "http://canary")
+            .count();
+        assert!((300..700).contains(&canary), "{}", canary);
+
+        let stable = upstreams.pick_url("http://stable");
+        upstreams.record(&stable, false);
+        assert!(upstreams.status()[0].healthy);
+        upstreams.record(&stable, false);
+        assert!(!upstreams.status()[0].healthy);
+        assert!((0..100).all(|_| upstreams.pick().unwrap().url == "http://canary"));
+
+        assert!(upstreams.set_weight("http://canary", 0));
+        assert_eq!(upstreams.pick().unwrap().url, "http://stable");
+        assert!(!upstreams.set_weight("http://missing", 1));
+    }
+
+    impl Upstreams {
+        fn pick_url(&self, url: &str) -> Arc<Backend> {
+            let backends = self.0.backends.read().unwrap();
+            Arc::clone(backends.iter().find(|b| b.url == url).unwrap())
+        }
+    }
+}
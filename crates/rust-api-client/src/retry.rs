@@ -0,0 +1,147 @@
+//! Retry policy.
+
+use reqwest::header::HeaderMap;
+use reqwest::{Method, StatusCode};
+use std::sync::Arc;
+use std::time::Duration;
+
+type StatusPredicate = Arc<dyn Fn(StatusCode) -> bool + Send + Sync>;
+
+/// When and how often to retry a request.
+///
+/// Only idempotent methods (or requests carrying an `Idempotency-Key`) are
+/// retried unless [`retry_non_idempotent`](Self::retry_non_idempotent) is set.
+/// Connection errors, timeouts and 429/502/503/504 responses are retried with
+/// exponential backoff and full jitter; `Retry-After` is honored.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    pub(crate) max_attempts: u32,
+    base_delay: Duration,
+    pub(crate) max_delay: Duration,
+    jitter: bool,
+    non_idempotent: bool,
+    retry_on: StatusPredicate,
+}
+
+impl RetryPolicy {
+    /// Three attempts, 100ms base delay, 10s cap, jitter on.
+    pub fn new() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            jitter: true,
+            non_idempotent: false,
+            retry_on: Arc::new(|status| matches!(status.as_u16(), 429 | 502 | 503 | 504)),
+        }
+    }
+
+    /// Never retry.
+    pub fn none() -> Self {
+        Self::new().max_attempts(1)
+    }
+
+    /// Total attempts including the first (minimum 1).
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Backoff starting at `base` and doubling up to `max`.
+    ///
+    /// `max` also caps `Retry-After`: longer waits return the response as is.
+    pub fn backoff(mut self, base: Duration, max: Duration) -> Self {
+        self.base_delay = base;
+        self.max_delay = max.max(base);
+        self
+    }
+
+    /// Randomize delays between zero and the backoff (default true).
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Also retry POST/PATCH requests without an `Idempotency-Key`.
+    pub fn retry_non_idempotent(mut self, retry: bool) -> Self {
+        self.non_idempotent = retry;
+        self
+    }
+
+    /// Decide which response statuses are retried.
+    pub fn retry_on<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(StatusCode) -> bool + Send + Sync + 'static,
+    {
+        self.retry_on = Arc::new(predicate);
+        self
+    }
+
+    /// Whether a request may be retried at all.
+    pub(crate) fn allows(&self, method: &Method, headers: &HeaderMap) -> bool {
+        self.non_idempotent
+            || headers.contains_key("idempotency-key")
+            || matches!(
+                *method,
+                Method::GET
+                    | Method::HEAD
+                    | Method::OPTIONS
+                    | Method::TRACE
+                    | Method::PUT
+                    | Method::DELETE
+            )
+    }
+
+    pub(crate) fn retries_status(&self, status: StatusCode) -> bool {
+        (self.retry_on)(status)
+    }
+
+    /// Delay after failed attempt number `attempt` (1-based).
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        if self.jitter {
+            delay.mul_f64(fastrand::f64())
+        } else {
+            delay
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::new()
+            .backoff(Duration::from_millis(100), Duration::from_millis(350))
+            .jitter(false);
+        let delays: Vec<_> = (1..=4).map(|n| policy.delay(n).as_millis()).collect();
+        assert_eq!(delays, [100, 200, 350, 350]);
+
+        let jittered = policy.jitter(true).delay(3);
+        assert!(jittered <= Duration::from_millis(350));
+    }
+
+    #[test]
+    fn test_idempotency() {
+        let policy = RetryPolicy::new();
+        let mut headers = HeaderMap::new();
+        assert!(policy.allows(&Method::GET, &headers));
+        assert!(!policy.allows(&Method::POST, &headers));
+        headers.insert("idempotency-key", "abc".parse().unwrap());
+        assert!(policy.allows(&Method::POST, &headers));
+        assert!(
+            RetryPolicy::new()
+                .retry_non_idempotent(true)
+                .allows(&Method::PATCH, &HeaderMap::new())
+        );
+    }
+}
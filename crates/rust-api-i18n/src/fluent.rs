@@ -0,0 +1,131 @@
+//! Catalog from Fluent `.ftl` files.
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource, FluentValue};
+use rust_api::{Error, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use unic_langid::LanguageIdentifier;
+
+use crate::gettext::catalog_files;
+use crate::{Arg, Catalog};
+
+/// Translations from Fluent `.ftl` files, keyed by message id.
+///
+/// `message.attribute` keys read an attribute. Unicode isolation marks
+/// around arguments are turned off, as they would end up in JSON
+/// responses.
+#[derive(Default)]
+pub struct FluentCatalog {
+    bundles: HashMap<String, FluentBundle<FluentResource>>,
+}
+
+impl FluentCatalog {
+    /// Create an empty catalog.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the messages of `.ftl` source `ftl` to `locale`.
+    pub fn add_ftl(mut self, locale: &str, ftl: &str) -> Result<Self> {
+        let resource = FluentResource::try_new(ftl.to_string()).map_err(|(_, errors)| {
+            Error::internal(format!("invalid Fluent syntax: {:?}", errors[0]))
+        })?;
+        if !self.bundles.contains_key(locale) {
+            let id: LanguageIdentifier = locale
+                .parse()
+                .map_err(|_| Error::internal(format!("invalid locale {}", locale)))?;
+            let mut bundle = FluentBundle::new_concurrent(vec![id]);
+            bundle.set_use_isolating(false);
+            self.bundles.insert(locale.to_string(), bundle);
+        }
+        let bundle = self.bundles.get_mut(locale).expect("bundle inserted above");
+        bundle.add_resource(resource).map_err(|errors| {
+            Error::internal(format!("invalid Fluent resource: {:?}", errors[0]))
+        })?;
+        Ok(self)
+    }
+
+    /// Load `dir/<locale>.ftl` files and `.ftl` files in `dir/<locale>/`.
+    pub fn load_dir(dir: impl AsRef<Path>) -> Result<Self> {
+        let mut catalog = Self::new();
+        for (locale, path) in catalog_files(dir.as_ref(), "ftl")? {
+            let source = std::fs::read_to_string(&path)?;
+            catalog = catalog
+                .add_ftl(&locale, &source)
+                .map_err(|e| Error::internal(format!("{}: {}", path.display(), e)))?;
+        }
+        Ok(catalog)
+    }
+}
+
+impl Catalog for FluentCatalog {
+    fn locales(&self) -> Vec<String> {
+        self.bundles.keys().cloned().collect()
+    }
+
+    fn translate(&self, locale: &str, key: &str, args: &[(&str, Arg)]) -> Option<String> {
+        let bundle = self.bundles.get(locale)?;
+        let (id, attribute) = match key.split_once('.') {
+            Some((id, attribute)) => (id, Some(attribute)),
+            None => (key, None),
+        };
+        let message = bundle.get_message(id)?;
+        let pattern = match attribute {
+            Some(attribute) => message.get_attribute(attribute)?.value(),
+            None => message.value()?,
+        };
+        let mut fluent_args = FluentArgs::new();
+        for (name, value) in args {
+            let value = match value {
+                Arg::Str(s) => FluentValue::from(s.clone()),
+                Arg::Number(n) => FluentValue::from(*n),
+            };
+            fluent_args.set(name.to_string(), value);
+        }
+        let mut errors = Vec::new();
+        let text = bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+        Some(text.into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FTL: &str = r#"
+hello = Habari, { $name }!
+new-messages = { $count ->
+    [one] Una ujumbe mmoja
+   *[other] Una jumbe { $count }
+}
+login = Ingia
+    .title = Ingia kwenye akaunti
+"#;
+
+    #[test]
+    fn test_fluent_catalog() {
+        let catalog = FluentCatalog::new().add_ftl("sw", FTL).unwrap();
+        let name = [("name", Arg::from("Ada"))];
+        assert_eq!(
+            catalog.translate("sw", "hello", &name).unwrap(),
+            "Habari, Ada!"
+        );
+        let one = [("count", Arg::from(1))];
+        let many = [("count", Arg::from(4))];
+        assert_eq!(
+            catalog.translate("sw", "new-messages", &one).unwrap(),
+            "Una ujumbe mmoja"
+        );
+        assert_eq!(
+            catalog.translate("sw", "new-messages", &many).unwrap(),
+            "Una jumbe 4"
+        );
+        assert_eq!(
+            catalog.translate("sw", "login.title", &[]).unwrap(),
+            "Ingia kwenye akaunti"
+        );
+        assert!(catalog.translate("sw", "missing", &[]).is_none());
+        assert!(FluentCatalog::new().add_ftl("sw", "= broken").is_err());
+    }
+}
@@ -0,0 +1,248 @@
+//! Catalog from gettext `.po` files.
+
+use rust_api::{Error, Result};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::{Arg, Catalog, interpolate};
+
+/// Translations from gettext `.po` files, keyed by `msgid`.
+///
+/// Placeholders like `{name}` are filled from the arguments. For plural
+/// entries a numeric `count` argument picks `msgstr[0]` when it is 1 and
+/// `msgstr[1]` otherwise; `Plural-Forms` headers are not evaluated.
+/// Entries with a `msgctxt` are keyed `"context\u{4}msgid"`, as in gettext.
+#[derive(Debug, Clone, Default)]
+pub struct GettextCatalog {
+    locales: HashMap<String, HashMap<String, Vec<String>>>,
+}
+
+impl GettextCatalog {
+    /// Create an empty catalog.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the entries of `.po` source `po` to `locale`.
+    pub fn add_po(mut self, locale: &str, po: &str) -> Result<Self> {
+        let entries = parse_po(po)?;
+        self.locales
+            .entry(locale.to_string())
+            .or_default()
+            .extend(entries);
+        Ok(self)
+    }
+
+    /// Load `dir/<locale>.po` files and `.po` files in `dir/<locale>/`.
+    pub fn load_dir(dir: impl AsRef<Path>) -> Result<Self> {
+        let mut catalog = Self::new();
+        for (locale, path) in catalog_files(dir.as_ref(), "po")? {
+            let source = std::fs::read_to_string(&path)?;
+            catalog = catalog
+                .add_po(&locale, &source)
+                .map_err(|e| Error::internal(format!("{}: {}", path.display(), e)))?;
+        }
+        Ok(catalog)
+    }
+}
+
+impl Catalog for GettextCatalog {
+    fn locales(&self) -> Vec<String> {
+        self.locales.keys().cloned().collect()
+    }
+
+    fn translate(&self, locale: &str, key: &str, args: &[(&str, Arg)]) -> Option<String> {
+        let forms = self.locales.get(locale)?.get(key)?;
+        let count = args.iter().find_map(|(name, value)| match value {
+            Arg::Number(n) if *name == "count" => Some(*n),
+            _ => None,
+        });
+        let index = match count {
+            Some(n) if n != 1.0 => 1,
+            _ => 0,
+        };
+        let form = forms.get(index).or_else(|| forms.first())?;
+        Some(interpolate(form, args))
+    }
+}
+
+/// `(locale, path)` of every `.{ext}` file under `dir`.
+pub(crate) fn catalog_files(dir: &Path, ext: &str) -> Result<Vec<(String, std::path::PathBuf)>> {
+    let has_ext = |path: &Path| path.extension().is_some_and(|e| e == ext);
+    let stem = |path: &Path| path.file_stem().map(|s| s.to_string_lossy().into_owned());
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            let Some(locale) = path.file_name().map(|s| s.to_string_lossy().into_owned()) else {
+                continue;
+            };
+            for entry in std::fs::read_dir(&path)? {
+                let file = entry?.path();
+                if has_ext(&file) {
+                    files.push((locale.clone(), file));
+                }
+            }
+        } else if has_ext(&path) {
+            if let Some(locale) = stem(&path) {
+                files.push((locale, path));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Field of a `.po` entry being read.
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    Context,
+    Id,
+    Plural,
+    Str(usize),
+}
+
+/// Entry under construction.
+#[derive(Default)]
+struct Entry {
+    context: Option<String>,
+    id: Option<String>,
+    forms: Vec<String>,
+}
+
+impl Entry {
+    /// Key and translations, skipping the header and untranslated entries.
+    fn finish(self) -> Option<(String, Vec<String>)> {
+        let id = self.id.filter(|id| !id.is_empty())?;
+        if self.forms.iter().all(String::is_empty) {
+            return None;
+        }
+        let key = match self.context {
+            Some(context) => format!("{}\u{4}{}", context, id),
+            None => id,
+        };
+        Some((key, self.forms))
+    }
+}
+
+/// Parse `.po` source into `msgid -> msgstr` forms.
+fn parse_po(source: &str) -> Result<HashMap<String, Vec<String>>> {
+    let mut entries = HashMap::new();
+    let mut entry = Entry::default();
+    let mut field = None;
+    for (n, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = || Error::internal(format!("invalid .po syntax on line {}", n + 1));
+        let (keyword, rest) = match line.split_once(char::is_whitespace) {
+            Some((keyword, rest)) if !line.starts_with('"') => (keyword, rest.trim()),
+            _ => ("", line),
+        };
+        let text = unquote(rest).ok_or_else(error)?;
+        let next = match keyword {
+            "" => field.ok_or_else(error)?,
+            "msgctxt" => Field::Context,
+            "msgid" => Field::Id,
+            "msgid_plural" => Field::Plural,
+            "msgstr" => Field::Str(0),
+            _ => {
+                let index = keyword
+                    .strip_prefix("msgstr[")
+                    .and_then(|rest| rest.strip_suffix(']'))
+                    .and_then(|index| index.parse().ok())
+                    .ok_or_else(error)?;
+                Field::Str(index)
+            }
+        };
+        // A new msgctxt or msgid after a msgstr starts the next entry.
+        let starts_entry = matches!(next, Field::Context | Field::Id)
+            && !keyword.is_empty()
+            && matches!(field, Some(Field::Str(_)));
+        if starts_entry {
+            if let Some((key, forms)) = std::mem::take(&mut entry).finish() {
+                entries.insert(key, forms);
+            }
+        }
+        match next {
+            Field::Context => entry.context.get_or_insert_default().push_str(&text),
+            Field::Id => entry.id.get_or_insert_default().push_str(&text),
+            Field::Plural => {}
+            Field::Str(index) => {
+                if entry.forms.len() <= index {
+                    entry.forms.resize(index + 1, String::new());
+                }
+                entry.forms[index].push_str(&text);
+            }
+        }
+        field = Some(next);
+    }
+    if let Some((key, forms)) = entry.finish() {
+        entries.insert(key, forms);
+    }
+    Ok(entries)
+}
+
+/// Contents of a quoted `.po` string, with escapes resolved.
+fn unquote(quoted: &str) -> Option<String> {
+    let inner = quoted.strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => out.push('\n'),
+            't' => out.push('\t'),
+            'r' => out.push('\r'),
+            other => out.push(other),
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PO: &str = r#"
+msgid ""
+msgstr ""
+"Language: sw\n"
+
+# A comment
+msgid "Hello"
+msgstr "Habari"
+
+msgid "{count} file"
+msgid_plural "{count} files"
+msgstr[0] "faili {count}"
+msgstr[1] "mafaili "
+"{count}"
+
+msgid "Untranslated"
+msgstr ""
+"#;
+
+    #[test]
+    fn test_parse_po() {
+        let catalog = GettextCatalog::new().add_po("sw", PO).unwrap();
+        assert_eq!(catalog.translate("sw", "Hello", &[]).unwrap(), "Habari");
+        let one = [("count", Arg::from(1))];
+        let many = [("count", Arg::from(3))];
+        assert_eq!(
+            catalog.translate("sw", "{count} file", &one).unwrap(),
+            "faili 1"
+        );
+        assert_eq!(
+            catalog.translate("sw", "{count} file", &many).unwrap(),
+            "mafaili 3"
+        );
+        assert!(catalog.translate("sw", "Untranslated", &[]).is_none());
+        assert!(catalog.translate("sw", "", &[]).is_none());
+        assert!(GettextCatalog::new().add_po("sw", "msgid Hello").is_err());
+    }
+}
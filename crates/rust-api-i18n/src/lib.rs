@@ -0,0 +1,348 @@
+//! Localization for rust-api applications.
+//!
+//! [`I18n`] holds a translation [`Catalog`] — gettext `.po` files through
+//! [`GettextCatalog`], or Fluent `.ftl` files through `FluentCatalog`
+//! (`fluent` feature, on by default). Attached as middleware it picks a
+//! locale for each request from the `lang` query parameter, the `lang`
+//! cookie or `Accept-Language`, in that order, and handlers read it with
+//! the [`Locale`] extractor. [`t!`] translates with named arguments; a
+//! `Locale` is cheap to clone, so it can be handed to templates too.
+//!
+//! ```rust
+//! use rust_api::RustApi;
+//! use rust_api_i18n::{GettextCatalog, I18n, Locale, t};
+//!
+//! async fn hello(locale: Locale) -> String {
+//!     t!(locale, "Hello, {name}!", name = "Ada")
+//! }
+//!
+//! let catalog = GettextCatalog::new()
+//!     .add_po("sw", "msgid \"Hello, {name}!\"\nmsgstr \"Habari, {name}!\"\n")
+//!     .unwrap();
+//! let mut app = RustApi::new();
+//! app.attach(I18n::new(catalog, "en"));
+//! app.get("/", hello);
+//! ```
+
+use async_trait::async_trait;
+use rust_api::{Error, FromRequest, Middleware, Next, Req, Res, Result};
+use std::fmt;
+use std::sync::Arc;
+
+#[cfg(feature = "fluent")]
+mod fluent;
+mod gettext;
+
+#[cfg(feature = "fluent")]
+pub use fluent::FluentCatalog;
+pub use gettext::GettextCatalog;
+
+/// Translate `key` for a [`Locale`], with optional named arguments.
+///
+/// Missing translations fall back to the default locale, then to `key`.
+///
+/// ```rust
+/// # use rust_api_i18n::{GettextCatalog, I18n, t};
+/// # let locale = I18n::new(GettextCatalog::new(), "en").locale("en");
+/// let text = t!(locale, "{count} new messages", count = 3);
+/// assert_eq!(text, "3 new messages");
+/// ```
+#[macro_export]
+macro_rules! t {
+    ($locale:expr, $key:expr $(,)?) => {
+        $locale.translate($key, &[])
+    };
+    ($locale:expr, $key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $locale.translate($key, &[$((stringify!($name), $crate::Arg::from($value))),+])
+    };
+}
+
+/// Argument interpolated into a translation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    /// Text.
+    Str(String),
+    /// Number; also selects plural forms.
+    Number(f64),
+}
+
+impl From<&str> for Arg {
+    fn from(value: &str) -> Self {
+        Arg::Str(value.to_string())
+    }
+}
+
+impl From<String> for Arg {
+    fn from(value: String) -> Self {
+        Arg::Str(value)
+    }
+}
+
+impl From<&String> for Arg {
+    fn from(value: &String) -> Self {
+        Arg::Str(value.clone())
+    }
+}
+
+macro_rules! number_arg {
+    ($($t:ty),*) => {
+        $(impl From<$t> for Arg {
+            fn from(value: $t) -> Self {
+                Arg::Number(value as f64)
+            }
+        })*
+    };
+}
+
+number_arg!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64);
+
+impl fmt::Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arg::Str(s) => f.write_str(s),
+            Arg::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            Arg::Number(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+/// Source of translations.
+pub trait Catalog: Send + Sync + 'static {
+    /// Locales with translations.
+    fn locales(&self) -> Vec<String>;
+
+    /// Translate `key` for `locale`; `None` if there is no translation.
+    fn translate(&self, locale: &str, key: &str, args: &[(&str, Arg)]) -> Option<String>;
+}
+
+/// Catalog with its locales.
+struct Translations {
+    catalog: Box<dyn Catalog>,
+    default: String,
+    locales: Vec<String>,
+}
+
+impl Translations {
+    /// Available locale matching `tag` exactly or by primary language.
+    fn matching(&self, tag: &str) -> Option<&str> {
+        let tag = tag.trim();
+        let primary = |tag: &str| tag.split(['-', '_']).next().unwrap_or("").to_string();
+        self.locales
+            .iter()
+            .find(|l| l.eq_ignore_ascii_case(tag))
+            .or_else(|| {
+                let wanted = primary(tag);
+                self.locales
+                    .iter()
+                    .find(|l| !wanted.is_empty() && primary(l).eq_ignore_ascii_case(&wanted))
+            })
+            .map(String::as_str)
+    }
+}
+
+/// Translations plus locale negotiation; middleware setting [`Locale`].
+#[derive(Clone)]
+pub struct I18n {
+    translations: Arc<Translations>,
+    query: Option<String>,
+    cookie: Option<String>,
+}
+
+impl I18n {
+    /// Translate with `catalog`, falling back to `default_locale`.
+    pub fn new(catalog: impl Catalog, default_locale: &str) -> Self {
+        let mut locales = catalog.locales();
+        if !locales
+            .iter()
+            .any(|l| l.eq_ignore_ascii_case(default_locale))
+        {
+            locales.push(default_locale.to_string());
+        }
+        Self {
+            translations: Arc::new(Translations {
+                catalog: Box::new(catalog),
+                default: default_locale.to_string(),
+                locales,
+            }),
+            query: Some("lang".to_string()),
+            cookie: Some("lang".to_string()),
+        }
+    }
+
+    /// Query parameter overriding the locale (default `lang`); `None`
+    /// disables it.
+    pub fn query_param(mut self, name: Option<&str>) -> Self {
+        self.query = name.map(str::to_string);
+        self
+    }
+
+    /// Cookie holding the user's locale (default `lang`); `None` disables
+    /// it.
+    pub fn cookie(mut self, name: Option<&str>) -> Self {
+        self.cookie = name.map(str::to_string);
+        self
+    }
+
+    /// Locale for tag `tag`, e.g. for background jobs or emails. Unknown
+    /// tags get the default locale.
+    pub fn locale(&self, tag: &str) -> Locale {
+        let translations = &self.translations;
+        let tag = translations.matching(tag).unwrap_or(&translations.default);
+        Locale {
+            tag: tag.to_string(),
+            translations: Arc::clone(translations),
+        }
+    }
+
+    /// Locale requested by `req`.
+    fn negotiate(&self, req: &Req) -> Locale {
+        let from_query = self.query.as_deref().and_then(|name| {
+            req.query()?.split('&').find_map(|pair| {
+                let (key, value) = pair.split_once('=')?;
+                (key == name).then_some(value)
+            })
+        });
+        let from_cookie = self.cookie.as_deref().and_then(|name| req.cookie(name));
+        if let Some(tag) = from_query.or(from_cookie) {
+            if self.translations.matching(tag).is_some() {
+                return self.locale(tag);
+            }
+        }
+
+        let mut ranges: Vec<(&str, f32)> = req
+            .header("accept-language")
+            .unwrap_or("")
+            .split(',')
+            .filter_map(|item| {
+                let mut params = item.split(';');
+                let tag = params.next()?.trim();
+                let q = params
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .and_then(|q| q.trim().parse().ok())
+                    .unwrap_or(1.0);
+                (!tag.is_empty() && tag != "*" && q > 0.0).then_some((tag, q))
+            })
+            .collect();
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let tag = ranges
+            .iter()
+            .find(|(tag, _)| self.translations.matching(tag).is_some());
+        self.locale(tag.map_or("", |(tag, _)| tag))
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> Middleware<S> for I18n {
+    async fn handle(&self, mut req: Req, _state: Arc<S>, next: Next<S>) -> Res {
+        let locale = self.negotiate(&req);
+        let tag = locale.tag.clone();
+        req.extensions_mut().insert(locale);
+        let res = next.run(req).await;
+        let res = if res.headers().contains_key("content-language") {
+            res
+        } else {
+            res.header("content-language", &tag)
+        };
+        res.vary("accept-language")
+    }
+
+    fn name(&self) -> &'static str {
+        "I18n"
+    }
+}
+
+/// Locale of the current request, set by the [`I18n`] middleware.
+#[derive(Clone)]
+pub struct Locale {
+    tag: String,
+    translations: Arc<Translations>,
+}
+
+impl Locale {
+    /// Locale tag, e.g. `en` or `pt-BR`.
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// Translate `key`; [`t!`] is shorter.
+    pub fn translate(&self, key: &str, args: &[(&str, Arg)]) -> String {
+        let translations = &self.translations;
+        let catalog = &translations.catalog;
+        catalog
+            .translate(&self.tag, key, args)
+            .or_else(|| catalog.translate(&translations.default, key, args))
+            .unwrap_or_else(|| interpolate(key, args))
+    }
+}
+
+impl fmt::Debug for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Locale").field(&self.tag).finish()
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.tag)
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> FromRequest<S> for Locale {
+    async fn from_request(req: &mut Req, _state: &Arc<S>) -> Result<Self> {
+        req.extensions()
+            .get::<Locale>()
+            .cloned()
+            .ok_or_else(|| Error::internal("I18n middleware not attached"))
+    }
+}
+
+/// Replace `{name}` placeholders in `text` with `args`.
+pub(crate) fn interpolate(text: &str, args: &[(&str, Arg)]) -> String {
+    let mut out = text.to_string();
+    for (name, value) in args {
+        out = out.replace(&format!("{{{}}}", name), &value.to_string());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_api::RustApi;
+    use rust_api::test::{TestClient, assert_header, body_text};
+
+    const SW: &str = r#"
+msgid "Hello, {name}!"
+msgstr "Habari, {name}!"
+"#;
+
+    #[tokio::test]
+    async fn test_locale_negotiation() {
+        let catalog = GettextCatalog::new().add_po("sw", SW).unwrap();
+        let mut app = RustApi::new();
+        app.attach(I18n::new(catalog, "en"));
+        app.get("/", |locale: Locale| async move {
+            t!(locale, "Hello, {name}!", name = "Ada")
+        });
+        let client = TestClient::new(app);
+
+        let res = client
+            .get("/")
+            .header("accept-language", "fr;q=0.9, sw-TZ, en;q=0.5")
+            .send()
+            .await;
+        assert_header(&res, "content-language", "sw");
+        assert_eq!(body_text(res).await, "Habari, Ada!");
+        let res = client
+            .get("/?lang=en")
+            .header("cookie", "lang=sw")
+            .send()
+            .await;
+        assert_eq!(body_text(res).await, "Hello, Ada!");
+        let res = client.get("/").header("cookie", "lang=sw").send().await;
+        assert_eq!(body_text(res).await, "Habari, Ada!");
+        let res = client.get("/").header("accept-language", "de").send().await;
+        assert_header(&res, "content-language", "en");
+    }
+}
@@ -0,0 +1,223 @@
+//! sqlx integration for rust-api.
+//!
+//! Put a [`sqlx::Pool`] (or any state implementing [`HasPool`]) in the app
+//! state, then take a pooled connection with [`Db`] or a transaction with
+//! [`Tx`]. Transactions roll back when dropped, so a handler that returns
+//! early with `?` never leaves partial writes behind.
+//!
+//! ```rust,ignore
+//! use rust_api::{Result, RustApi};
+//! use rust_api_sqlx::{DbResultExt, Tx};
+//! use sqlx::{Postgres, PgPool};
+//!
+//! async fn create_user(mut tx: Tx<Postgres>) -> Result<&'static str> {
+//!     sqlx::query("INSERT INTO users (name) VALUES ('ada')")
+//!         .execute(&mut *tx)
+//!         .await
+//!         .db_err()?;
+//!     tx.commit().await?;
+//!     Ok("created")
+//! }
+//!
+//! # async fn run(pool: PgPool) {
+//! let mut app = RustApi::with_state(pool);
+//! app.post("/users", create_user);
+//! # }
+//! ```
+//!
+//! To run every request in a transaction instead, attach
+//! `Transactional::new(PoolTransactions(pool))` and take
+//! `RequestTransaction<Transaction<'static, DB>>` in handlers.
+
+use async_trait::async_trait;
+use rust_api::transaction::TransactionProvider;
+use rust_api::{Error, FromRequest, Req, Result};
+use sqlx::pool::PoolConnection;
+use sqlx::{Database, Pool, Transaction};
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+/// App state that provides a connection pool.
+pub trait HasPool<DB: Database>: Send + Sync + 'static {
+    /// The pool handlers draw connections from.
+    fn pool(&self) -> &Pool<DB>;
+}
+
+impl<DB: Database> HasPool<DB> for Pool<DB> {
+    fn pool(&self) -> &Pool<DB> {
+        self
+    }
+}
+
+/// Pooled connection extractor, returned to the pool on drop.
+pub struct Db<DB: Database>(pub PoolConnection<DB>);
+
+impl<DB: Database> Deref for Db<DB> {
+    type Target = DB::Connection;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<DB: Database> DerefMut for Db<DB> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[async_trait]
+impl<S, DB> FromRequest<S> for Db<DB>
+where
+    S: HasPool<DB>,
+    DB: Database,
+{
+    async fn from_request(_req: &mut Req, state: &Arc<S>) -> Result<Self> {
+        let conn = state.pool().acquire().await.db_err()?;
+        Ok(Db(conn))
+    }
+}
+
+/// Transaction extractor.
+///
+/// Call [`commit`](Tx::commit) on success; dropping without committing
+/// rolls the transaction back.
+pub struct Tx<DB: Database>(Transaction<'static, DB>);
+
+impl<DB: Database> Tx<DB> {
+    /// Commit the transaction.
+    pub async fn commit(self) -> Result<()> {
+        self.0.commit().await.db_err()
+    }
+
+    /// Roll the transaction back explicitly.
+    pub async fn rollback(self) -> Result<()> {
+        self.0.rollback().await.db_err()
+    }
+}
+
+impl<DB: Database> Deref for Tx<DB> {
+    type Target = DB::Connection;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<DB: Database> DerefMut for Tx<DB> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[async_trait]
+impl<S, DB> FromRequest<S> for Tx<DB>
+where
+    S: HasPool<DB>,
+    DB: Database,
+{
+    async fn from_request(_req: &mut Req, state: &Arc<S>) -> Result<Self> {
+        let tx = state.pool().begin().await.db_err()?;
+        Ok(Tx(tx))
+    }
+}
+
+/// [`TransactionProvider`] beginning transactions on a pool.
+pub struct PoolTransactions<DB: Database>(pub Pool<DB>);
+
+#[async_trait]
+impl<DB: Database> TransactionProvider for PoolTransactions<DB> {
+    type Tx = Transaction<'static, DB>;
+
+    async fn begin(&self) -> Result<Self::Tx> {
+        self.0.begin().await.db_err()
+    }
+
+    async fn commit(&self, tx: Self::Tx) -> Result<()> {
+        tx.commit().await.db_err()
+    }
+
+    async fn rollback(&self, tx: Self::Tx) -> Result<()> {
+        tx.rollback().await.db_err()
+    }
+}
+
+/// Map a sqlx error to a framework error.
+///
+/// Missing rows become 404, constraint violations 409/422, pool exhaustion
+/// 503; anything else is logged and reported as a generic 500 so driver
+/// details don't leak to clients.
+pub fn db_error(error: sqlx::Error) -> Error {
+    match &error {
+        sqlx::Error::RowNotFound => return Error::not_found("Record not found"),
+        sqlx::Error::PoolTimedOut => {
+            return Error::Status(503, Some("Database unavailable".into()));
+        }
+        sqlx::Error::Database(db) => {
+            if db.is_unique_violation() {
+                return Error::Status(409, Some("Record already exists".into()));
+            }
+            if db.is_foreign_key_violation() {
+                return Error::Status(409, Some("Referenced record conflict".into()));
+            }
+            if db.is_check_violation() {
+                return Error::unprocessable("Constraint check failed");
+            }
+        }
+        _ => {}
+    }
+    log::error!("database error: {}", error);
+    Error::internal("Database error")
+}
+
+/// Convert sqlx results into framework results.
+pub trait DbResultExt<T> {
+    /// Map the error with [`db_error`].
+    fn db_err(self) -> Result<T>;
+}
+
+impl<T> DbResultExt<T> for std::result::Result<T, sqlx::Error> {
+    fn db_err(self) -> Result<T> {
+        self.map_err(db_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn test_error_mapping_and_rollback() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::query("CREATE TABLE users (name TEXT UNIQUE)")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let mut tx = Tx(pool.begin().await.unwrap());
+        sqlx::query("INSERT INTO users VALUES ('ada')")
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        let duplicate = sqlx::query("INSERT INTO users VALUES ('ada')")
+            .execute(&mut *tx)
+            .await
+            .db_err()
+            .unwrap_err();
+        assert!(matches!(duplicate, Error::Status(409, _)));
+        drop(tx);
+
+        let missing = sqlx::query("SELECT name FROM users")
+            .fetch_one(&pool)
+            .await
+            .db_err()
+            .err()
+            .unwrap();
+        assert!(matches!(missing, Error::Status(404, _)));
+    }
+}
@@ -0,0 +1,106 @@
+//! Object storage for rust-api uploads.
+//!
+//! [`Storage`] abstracts where uploaded files end up. [`LocalStorage`]
+//! writes to a directory and [`S3Storage`] talks to any S3-compatible
+//! service (AWS S3, MinIO, R2, ...). Bodies are streamed, never buffered.
+//!
+//! Uploads are taken from the raw request body ([`store_body`]), a
+//! [`TempFileUpload`] ([`store_upload`]) or the file parts of a
+//! `multipart/form-data` body ([`store_multipart`]).
+//!
+//! ```rust,no_run
+//! use rust_api::{Req, Res, Result, RustApi};
+//! use rust_api_storage::{S3Storage, Storage, store_body};
+//! use std::sync::Arc;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let storage: Arc<dyn Storage> = Arc::new(S3Storage::new(
+//!         "http://localhost:9000",
+//!         "uploads",
+//!         "us-east-1",
+//!         "minioadmin",
+//!         "minioadmin",
+//!     ));
+//!
+//!     let mut app = RustApi::new();
+//!     app.put("/files/{name}", move |mut req: Req| {
+//!         let storage = Arc::clone(&storage);
+//!         async move {
+//!             let key = req.param("name").unwrap_or("upload").to_string();
+//!             let size = store_body(storage.as_ref(), &key, &mut req).await?;
+//!             Result::Ok(Res::text(format!("stored {} bytes", size)))
+//!         }
+//!     });
+//!     app.listen(([127, 0, 0, 1], 3000)).await.unwrap();
+//! }
+//! ```
+
+use async_trait::async_trait;
+use rust_api::{FromRequest, Req, Result, TempFileUpload};
+use std::sync::Arc;
+use tokio::io::AsyncRead;
+
+mod local;
+mod multipart;
+mod s3;
+
+pub use local::LocalStorage;
+pub use multipart::{StoredFile, StoredForm, store_multipart};
+pub use s3::S3Storage;
+
+/// Readable object body.
+pub type ObjectReader = Box<dyn AsyncRead + Send + Sync + Unpin>;
+
+/// Object store keyed by `/`-separated names.
+#[async_trait]
+pub trait Storage: Send + Sync + 'static {
+    /// Store `len` bytes read from `body` under `key`.
+    async fn put(
+        &self,
+        key: &str,
+        body: ObjectReader,
+        len: u64,
+        content_type: Option<&str>,
+    ) -> Result<()>;
+
+    /// Open an object for reading (pipe into `Res::from_reader`).
+    async fn get(&self, key: &str) -> Result<ObjectReader>;
+
+    /// Delete an object. Deleting a missing object is not an error.
+    async fn delete(&self, key: &str) -> Result<()>;
+}
+
+/// Store a temp-file upload under `key`.
+pub async fn store_upload(storage: &dyn Storage, key: &str, upload: &TempFileUpload) -> Result<()> {
+    let file = upload.open().await?;
+    storage
+        .put(key, Box::new(file), upload.len(), upload.content_type())
+        .await
+}
+
+/// Stream a request body straight to storage, returning its size.
+///
+/// Bodies without a `Content-Length` are spooled to a temp file first,
+/// since object stores need the length up front.
+pub async fn store_body(storage: &dyn Storage, key: &str, req: &mut Req) -> Result<u64> {
+    let length = req
+        .header("content-length")
+        .and_then(|v| v.parse::<u64>().ok());
+    let content_type = req.content_type().map(str::to_string);
+
+    match length {
+        Some(len) => {
+            let reader = req.body_reader()?;
+            storage
+                .put(key, Box::new(reader), len, content_type.as_deref())
+                .await?;
+            Ok(len)
+        }
+        None => {
+            let upload = TempFileUpload::from_request(req, &Arc::new(())).await?;
+            store_upload(storage, key, &upload).await?;
+            Ok(upload.len())
+        }
+    }
+}
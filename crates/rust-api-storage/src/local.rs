@@ -0,0 +1,113 @@
+//! Directory-backed storage.
+
+use async_trait::async_trait;
+use rust_api::{Error, Result};
+use std::path::{Component, Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+use crate::{ObjectReader, Storage};
+
+/// Stores objects as files under a root directory.
+#[derive(Debug, Clone)]
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    /// Create storage rooted at `root` (created on first write).
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        let relative = Path::new(key);
+        let valid = !key.is_empty()
+            && relative
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)));
+        if !valid {
+            return Err(Error::bad_request(format!("Invalid storage key: {}", key)));
+        }
+        Ok(self.root.join(relative))
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn put(
+        &self,
+        key: &str,
+        body: ObjectReader,
+        len: u64,
+        _content_type: Option<&str>,
+    ) -> Result<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let mut file = tokio::fs::File::create(&path).await?;
+        let mut body = tokio::io::AsyncReadExt::take(body, len);
+        let written = tokio::io::copy(&mut body, &mut file).await?;
+        file.flush().await?;
+
+        if written != len {
+            tokio::fs::remove_file(&path).await.ok();
+            return Err(Error::bad_request(format!(
+                "Body ended after {} of {} bytes",
+                written, len
+            )));
+        }
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<ObjectReader> {
+        match tokio::fs::File::open(self.path(key)?).await {
+            Ok(file) => Ok(Box::new(file)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(Error::not_found(format!("Object not found: {}", key)))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_roundtrip() {
+        let root = std::env::temp_dir().join(format!("rust-api-storage-{}", std::process::id()));
+        let storage = LocalStorage::new(&root);
+
+        storage
+            .put("a/b.txt", Box::new(&b"hello"[..]), 5, None)
+            .await
+            .unwrap();
+        let mut out = String::new();
+        storage
+            .get("a/b.txt")
+            .await
+            .unwrap()
+            .read_to_string(&mut out)
+            .await
+            .unwrap();
+        assert_eq!(out, "hello");
+
+        storage.delete("a/b.txt").await.unwrap();
+        assert!(storage.get("a/b.txt").await.is_err());
+        assert!(storage.path("../escape").is_err());
+        assert!(storage.path("/etc/passwd").is_err());
+
+        std::fs::remove_dir_all(root).ok();
+    }
+}
@@ -0,0 +1,412 @@
+//! Streaming `multipart/form-data` uploads.
+
+use bytes::{Buf, Bytes, BytesMut};
+use rust_api::{Error, Req, Result};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+
+use crate::Storage;
+
+/// Largest header block accepted for one part.
+const MAX_HEADER_SIZE: usize = 16 * 1024;
+
+/// Largest text field accepted.
+const MAX_FIELD_SIZE: usize = 64 * 1024;
+
+/// Form received by [`store_multipart`].
+#[derive(Debug, Clone, Default)]
+pub struct StoredForm {
+    /// Text fields as `(name, value)`, in order.
+    pub fields: Vec<(String, String)>,
+    /// Stored file parts, in order.
+    pub files: Vec<StoredFile>,
+}
+
+impl StoredForm {
+    /// First text field named `name`.
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// File part stored by [`store_multipart`].
+#[derive(Debug, Clone)]
+pub struct StoredFile {
+    /// Form field name.
+    pub field: String,
+    /// File name sent by the client.
+    pub file_name: String,
+    /// Key the file was stored under.
+    pub key: String,
+    /// Size in bytes.
+    pub size: u64,
+    /// Content-Type sent with the part.
+    pub content_type: Option<String>,
+}
+
+/// Store every file part of a `multipart/form-data` body.
+///
+/// `key` maps `(field, file_name)` to the storage key; the file name comes
+/// from the client, so do not use it unchecked. Parts are read as they
+/// arrive and each file is spooled to a temp file before `put`, since
+/// object stores need the length up front. Text fields are collected into
+/// [`StoredForm::fields`]. If the body is malformed or a `put` fails, files
+/// already stored by this call are deleted again.
+///
+/// ```rust,no_run
+/// use rust_api::{Req, Res, Result};
+/// use rust_api_storage::{LocalStorage, store_multipart};
+///
+/// async fn upload(mut req: Req) -> Result<Res> {
+///     let storage = LocalStorage::new("/var/uploads");
+///     let id = req.param("id").unwrap_or("anon").to_string();
+///     let form = store_multipart(&storage, &mut req, |field, _file_name| {
+///         format!("{}/{}", id, field)
+///     })
+///     .await?;
+///     Ok(Res::text(format!("stored {} files", form.files.len())))
+/// }
+/// ```
+pub async fn store_multipart<F>(storage: &dyn Storage, req: &mut Req, key: F) -> Result<StoredForm>
+where
+    F: FnMut(&str, &str) -> String + Send,
+{
+    let boundary = req
+        .content_type()
+        .and_then(boundary)
+        .ok_or_else(|| Error::Status(415, Some("Expected multipart/form-data".into())))?;
+    let mut parser = Parser::new(req.body_reader()?, boundary.as_bytes());
+    let mut form = StoredForm::default();
+    match read_form(storage, &mut parser, key, &mut form).await {
+        Ok(()) => Ok(form),
+        Err(e) => {
+            for file in &form.files {
+                storage.delete(&file.key).await.ok();
+            }
+            Err(e)
+        }
+    }
+}
+
+async fn read_form<R, F>(
+    storage: &dyn Storage,
+    parser: &mut Parser<R>,
+    mut key: F,
+    form: &mut StoredForm,
+) -> Result<()>
+where
+    R: AsyncRead + Unpin,
+    F: FnMut(&str, &str) -> String,
+{
+    while let Some(part) = parser.next_part().await? {
+        let Some(file_name) = part.file_name else {
+            let mut value = Vec::new();
+            while let Some(chunk) = parser.chunk().await? {
+                if value.len() + chunk.len() > MAX_FIELD_SIZE {
+                    return Err(Error::payload_too_large(format!(
+                        "Field {} is too large",
+                        part.name
+                    )));
+                }
+                value.extend_from_slice(&chunk);
+            }
+            let value = String::from_utf8(value)
+                .map_err(|_| Error::bad_request(format!("Field {} is not UTF-8", part.name)))?;
+            form.fields.push((part.name, value));
+            continue;
+        };
+
+        let (spool, mut file) = Spool::create().await?;
+        let mut size = 0;
+        while let Some(chunk) = parser.chunk().await? {
+            file.write_all(&chunk).await?;
+            size += chunk.len() as u64;
+        }
+        file.flush().await?;
+
+        let key = key(&part.name, &file_name);
+        let file = tokio::fs::File::open(&spool.0).await?;
+        storage
+            .put(&key, Box::new(file), size, part.content_type.as_deref())
+            .await?;
+        form.files.push(StoredFile {
+            field: part.name,
+            file_name,
+            key,
+            size,
+            content_type: part.content_type,
+        });
+    }
+    Ok(())
+}
+
+/// `boundary` parameter of a `multipart/form-data` content type.
+fn boundary(content_type: &str) -> Option<String> {
+    let mut params = content_type.split(';');
+    let essence = params.next()?.trim();
+    if !essence.eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    params
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("boundary"))
+        .map(|(_, value)| value.trim().trim_matches('"').to_string())
+        .filter(|boundary| !boundary.is_empty() && boundary.len() <= 70)
+}
+
+/// Headers of one part.
+struct Part {
+    name: String,
+    file_name: Option<String>,
+    content_type: Option<String>,
+}
+
+impl Part {
+    fn parse(head: &[u8]) -> Result<Self> {
+        let head = String::from_utf8_lossy(head);
+        let mut name = None;
+        let mut file_name = None;
+        let mut content_type = None;
+        for line in head.split("\r\n") {
+            let Some((header, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            if header.trim().eq_ignore_ascii_case("content-type") {
+                content_type = Some(value.to_string());
+            } else if header.trim().eq_ignore_ascii_case("content-disposition") {
+                for param in value.split(';').skip(1) {
+                    let Some((key, value)) = param.split_once('=') else {
+                        continue;
+                    };
+                    let value = value.trim().trim_matches('"').to_string();
+                    match key.trim().to_ascii_lowercase().as_str() {
+                        "name" => name = Some(value),
+                        "filename" => file_name = Some(value),
+                        _ => {}
+                    }
+                }
+            }
+        }
+        Ok(Self {
+            name: name.ok_or_else(|| Error::bad_request("Multipart part without a name"))?,
+            file_name,
+            content_type,
+        })
+    }
+}
+
+/// Incremental `multipart/form-data` reader.
+struct Parser<R> {
+    reader: R,
+    buf: BytesMut,
+    /// `\r\n--boundary`.
+    delimiter: Vec<u8>,
+    /// The current part is read up to the next delimiter.
+    at_delimiter: bool,
+    done: bool,
+}
+
+impl<R: AsyncRead + Unpin> Parser<R> {
+    fn new(reader: R, boundary: &[u8]) -> Self {
+        Self {
+            reader,
+            // The leading CRLF lets the first boundary match the delimiter.
+            buf: BytesMut::from(&b"\r\n"[..]),
+            delimiter: [b"\r\n--", boundary].concat(),
+            at_delimiter: false,
+            done: false,
+        }
+    }
+
+    async fn fill(&mut self) -> Result<()> {
+        self.buf.reserve(8 * 1024);
+        if self.reader.read_buf(&mut self.buf).await? == 0 {
+            return Err(Error::bad_request("Multipart body ended early"));
+        }
+        Ok(())
+    }
+
+    /// Next piece of the current part's body, or `None` at its end. Before
+    /// the first part this skips the preamble.
+    async fn chunk(&mut self) -> Result<Option<Bytes>> {
+        while !self.at_delimiter {
+            match memchr::memmem::find(&self.buf, &self.delimiter) {
+                Some(0) => self.at_delimiter = true,
+                Some(at) => return Ok(Some(self.buf.split_to(at).freeze())),
+                None => {
+                    // Hold back what could be the start of a delimiter.
+                    let keep = self.delimiter.len() - 1;
+                    if self.buf.len() > keep {
+                        let at = self.buf.len() - keep;
+                        return Ok(Some(self.buf.split_to(at).freeze()));
+                    }
+                    self.fill().await?;
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Skip the rest of the current part and read the next part's
+    /// headers, or `None` after the closing delimiter.
+    async fn next_part(&mut self) -> Result<Option<Part>> {
+        if self.done {
+            return Ok(None);
+        }
+        while self.chunk().await?.is_some() {}
+        while self.buf.len() < self.delimiter.len() + 2 {
+            self.fill().await?;
+        }
+        self.buf.advance(self.delimiter.len());
+        self.at_delimiter = false;
+        if self.buf.starts_with(b"--") {
+            self.done = true;
+            return Ok(None);
+        }
+        if !self.buf.starts_with(b"\r\n") {
+            return Err(Error::bad_request("Malformed multipart boundary"));
+        }
+        let end = loop {
+            if let Some(end) = memchr::memmem::find(&self.buf, b"\r\n\r\n") {
+                break end;
+            }
+            if self.buf.len() > MAX_HEADER_SIZE {
+                return Err(Error::bad_request("Multipart part headers too large"));
+            }
+            self.fill().await?;
+        };
+        let head = self.buf.split_to(end + 4);
+        Part::parse(head.get(2..end).unwrap_or_default()).map(Some)
+    }
+}
+
+/// Temp file removed on drop.
+struct Spool(PathBuf);
+
+impl Spool {
+    async fn create() -> Result<(Self, tokio::fs::File)> {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "rust-api-part-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = tokio::fs::File::options()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .await?;
+        Ok((Self(path), file))
+    }
+}
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LocalStorage;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_store_multipart() {
+        let body = concat!(
+            "preamble\r\n",
+            "--XyZ\r\n",
+            "Content-Disposition: form-data; name=\"title\"\r\n",
+            "\r\n",
+            "Holiday\r\n",
+            "--XyZ\r\n",
+            "Content-Disposition: form-data; name=\"photo\"; filename=\"beach.txt\"\r\n",
+            "Content-Type: text/plain\r\n",
+            "\r\n",
+            "sand\r\n--XY and sea\r\n",
+            "--XyZ--\r\n",
+        );
+        let mut req = Req::builder()
+            .uri("/upload")
+            .header("content-type", "multipart/form-data; boundary=\"XyZ\"")
+            .body(body)
+            .build();
+        let root = std::env::temp_dir().join(format!("rust-api-multipart-{}", std::process::id()));
+        let storage = LocalStorage::new(&root);
+
+        let form = store_multipart(&storage, &mut req, |field, file_name| {
+            format!("{}/{}", field, file_name)
+        })
+        .await
+        .unwrap();
+        assert_eq!(form.field("title"), Some("Holiday"));
+        assert_eq!(form.files.len(), 1);
+        let file = &form.files[0];
+        assert_eq!(file.key, "photo/beach.txt");
+        assert_eq!(file.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(file.size, 18);
+        let mut stored = String::new();
+        storage
+            .get(&file.key)
+            .await
+            .unwrap()
+            .read_to_string(&mut stored)
+            .await
+            .unwrap();
+        assert_eq!(stored, "sand\r\n--XY and sea");
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_parser_handles_split_delimiters() {
+        let body = b"--b\r\nContent-Disposition: form-data; name=\"x\"\r\n\r\nab\r\n-\r\n--b--";
+        let chunks = body
+            .chunks(3)
+            .map(|chunk| Ok::<_, std::io::Error>(Bytes::copy_from_slice(chunk)));
+        let reader = tokio_util::io::StreamReader::new(futures_util::stream::iter(chunks));
+        let mut parser = Parser::new(reader, b"b");
+
+        let part = parser.next_part().await.unwrap().unwrap();
+        assert_eq!(part.name, "x");
+        let mut value = Vec::new();
+        while let Some(chunk) = parser.chunk().await.unwrap() {
+            value.extend_from_slice(&chunk);
+        }
+        assert_eq!(value, b"ab\r\n-");
+        assert!(parser.next_part().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_truncated_body_removes_stored_files() {
+        let body = concat!(
+            "--b\r\n",
+            "Content-Disposition: form-data; name=\"a\"; filename=\"a.txt\"\r\n",
+            "\r\n",
+            "first\r\n",
+            "--b\r\n",
+            "Content-Disposition: form-data; name=\"b\"; filename=\"b.txt\"\r\n",
+            "\r\n",
+            "cut off",
+        );
+        let mut req = Req::builder()
+            .uri("/upload")
+            .header("content-type", "multipart/form-data; boundary=b")
+            .body(body)
+            .build();
+        let root = std::env::temp_dir().join(format!("rust-api-truncated-{}", std::process::id()));
+        let storage = LocalStorage::new(&root);
+
+        let err = store_multipart(&storage, &mut req, |field, _| field.to_string())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Status(400, _)));
+        assert!(storage.get("a").await.is_err());
+        std::fs::remove_dir_all(&root).ok();
+    }
+}
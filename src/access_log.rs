@@ -0,0 +1,445 @@
+//! Access logs written to rotating files.
+//!
+//! [`LogFile`] opens a [`LogWriter`]: lines are queued without blocking
+//! the request and a background task writes them in batches, rotating the
+//! file by size or age and keeping a fixed number of old files
+//! (`access.log.1` is the newest). [`AccessLog`] is middleware writing one
+//! line per request, in Combined Log Format or as JSON.
+//!
+//! ```rust,no_run
+//! use rust_api::{RustApi, access_log::{AccessLog, LogFile}};
+//! use std::time::Duration;
+//!
+//! #[tokio::main]
+//! async fn main() -> std::io::Result<()> {
+//!     let log = LogFile::new("/var/log/app/access.log")
+//!         .max_size(100 * 1024 * 1024)
+//!         .rotate_every(Duration::from_secs(24 * 60 * 60))
+//!         .keep(14)
+//!         .open()?;
+//!
+//!     let mut app = RustApi::new();
+//!     app.attach(AccessLog::new(log.clone()));
+//!     app.listen(([127, 0, 0, 1], 3000)).await.ok();
+//!     log.flush().await;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Other middleware (e.g. audit trails) can share a writer and call
+//! [`LogWriter::write`] directly.
+
+use async_trait::async_trait;
+use serde_json::json;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{Middleware, Next, Req, Res};
+
+/// Buffered bytes that trigger a write before the flush interval.
+const BATCH_BYTES: usize = 64 * 1024;
+
+/// Rotating log file configuration.
+#[derive(Debug, Clone)]
+pub struct LogFile {
+    path: PathBuf,
+    max_size: Option<u64>,
+    rotate_every: Option<Duration>,
+    keep: usize,
+    flush_interval: Duration,
+    capacity: usize,
+}
+
+impl LogFile {
+    /// Append to `path`, never rotating, flushing every second.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_size: None,
+            rotate_every: None,
+            keep: 7,
+            flush_interval: Duration::from_secs(1),
+            capacity: 8192,
+        }
+    }
+
+    /// Rotate before the file would grow past `bytes`.
+    pub fn max_size(mut self, bytes: u64) -> Self {
+        self.max_size = Some(bytes);
+        self
+    }
+
+    /// Rotate once the current file has been written for `period`.
+    pub fn rotate_every(mut self, period: Duration) -> Self {
+        self.rotate_every = Some(period);
+        self
+    }
+
+    /// Keep `files` rotated files (default 7); older ones are deleted.
+    pub fn keep(mut self, files: usize) -> Self {
+        self.keep = files;
+        self
+    }
+
+    /// Write queued lines at least every `interval` (default 1 second).
+    pub fn flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = interval;
+        self
+    }
+
+    /// Queue at most `lines` unwritten lines (default 8192); further lines
+    /// are dropped and counted in [`LogWriter::dropped`].
+    pub fn capacity(mut self, lines: usize) -> Self {
+        self.capacity = lines.max(1);
+        self
+    }
+
+    /// Open the file and start the writer task.
+    ///
+    /// Must be called inside a tokio runtime.
+    pub fn open(self) -> io::Result<LogWriter> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = open_append(&self.path)?;
+        let size = file.metadata()?.len();
+        let (tx, rx) = mpsc::channel(self.capacity);
+        tokio::spawn(run(self, File::from_std(file), size, rx));
+        Ok(LogWriter {
+            tx,
+            dropped: Arc::new(AtomicU64::new(0)),
+        })
+    }
+}
+
+enum Command {
+    Line(String),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Handle queueing lines for a [`LogFile`].
+///
+/// Clones share the file. The task finishes, writing what is queued, once
+/// every clone is dropped.
+#[derive(Debug, Clone)]
+pub struct LogWriter {
+    tx: mpsc::Sender<Command>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl LogWriter {
+    /// Queue `line`; a newline is appended.
+    pub fn write(&self, line: impl Into<String>) {
+        if self.tx.try_send(Command::Line(line.into())).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Wait until every line queued so far is written.
+    pub async fn flush(&self) {
+        let (ack, done) = oneshot::channel();
+        if self.tx.send(Command::Flush(ack)).await.is_ok() {
+            done.await.ok();
+        }
+    }
+
+    /// Lines dropped because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+async fn run(config: LogFile, file: File, size: u64, mut rx: mpsc::Receiver<Command>) {
+    let mut out = Output {
+        config,
+        file,
+        size,
+        opened: Instant::now(),
+        buf: Vec::new(),
+    };
+    let mut ticker = tokio::time::interval(out.config.flush_interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            command = rx.recv() => match command {
+                Some(Command::Line(line)) => {
+                    out.buf.extend_from_slice(line.as_bytes());
+                    out.buf.push(b'\n');
+                    if out.buf.len() >= BATCH_BYTES {
+                        out.write().await;
+                    }
+                }
+                Some(Command::Flush(ack)) => {
+                    out.write().await;
+                    ack.send(()).ok();
+                }
+                None => break,
+            },
+            _ = ticker.tick() => out.write().await,
+        }
+    }
+    out.write().await;
+}
+
+struct Output {
+    config: LogFile,
+    file: File,
+    size: u64,
+    opened: Instant,
+    buf: Vec<u8>,
+}
+
+impl Output {
+    /// Write the buffered lines, rotating first if due.
+    async fn write(&mut self) {
+        if self.buf.is_empty() {
+            return;
+        }
+        let too_big = self
+            .config
+            .max_size
+            .is_some_and(|max| self.size > 0 && self.size + self.buf.len() as u64 > max);
+        let too_old = self
+            .config
+            .rotate_every
+            .is_some_and(|period| self.opened.elapsed() >= period);
+        if too_big || too_old {
+            if let Err(e) = self.rotate().await {
+                log::error!("failed to rotate {}: {}", self.config.path.display(), e);
+            }
+        }
+        let written = async {
+            self.file.write_all(&self.buf).await?;
+            self.file.flush().await
+        }
+        .await;
+        match written {
+            Ok(()) => self.size += self.buf.len() as u64,
+            Err(e) => log::error!("failed to write {}: {}", self.config.path.display(), e),
+        }
+        self.buf.clear();
+    }
+
+    async fn rotate(&mut self) -> io::Result<()> {
+        let path = &self.config.path;
+        let keep = self.config.keep;
+        if keep == 0 {
+            tokio::fs::remove_file(path).await?;
+        } else {
+            tokio::fs::remove_file(rotated(path, keep)).await.ok();
+            for n in (1..keep).rev() {
+                tokio::fs::rename(rotated(path, n), rotated(path, n + 1))
+                    .await
+                    .ok();
+            }
+            tokio::fs::rename(path, rotated(path, 1)).await?;
+        }
+        self.file = File::from_std(open_append(path)?);
+        self.size = 0;
+        self.opened = Instant::now();
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<std::fs::File> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+}
+
+/// `access.log` rotated `n` times: `access.log.{n}`.
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+/// Access log line format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Apache/nginx Combined Log Format followed by the duration in
+    /// milliseconds.
+    Combined,
+    /// One JSON object per line.
+    Json,
+}
+
+/// Middleware writing one line per request to a [`LogWriter`].
+///
+/// Attach with `attach` (post-routing) to log the matched route in JSON
+/// lines; requests that match no route are not logged.
+#[derive(Debug, Clone)]
+pub struct AccessLog {
+    writer: LogWriter,
+    format: LogFormat,
+}
+
+impl AccessLog {
+    /// Log to `writer` in Combined Log Format.
+    pub fn new(writer: LogWriter) -> Self {
+        Self {
+            writer,
+            format: LogFormat::Combined,
+        }
+    }
+
+    /// Set the line format.
+    pub fn format(mut self, format: LogFormat) -> Self {
+        self.format = format;
+        self
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> Middleware<S> for AccessLog {
+    async fn handle(&self, req: Req, _state: Arc<S>, next: Next<S>) -> Res {
+        let time = SystemTime::now();
+        let started = Instant::now();
+        let method = req.method().clone();
+        let uri = req.uri().clone();
+        let version = req.version();
+        let route = req.shared_matched_route();
+        let peer = req.peer_addr().map(|addr| addr.ip().to_string());
+        let referer = req.header("referer").map(str::to_string);
+        let user_agent = req.header("user-agent").map(str::to_string);
+
+        let res = next.run(req).await;
+        let status = res.status_code().as_u16();
+        let bytes = res
+            .headers()
+            .get("content-length")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+        let millis = started.elapsed().as_secs_f64() * 1000.0;
+        let (secs, [year, month, day, hour, minute, second]) = civil_time(time);
+
+        let line = match self.format {
+            LogFormat::Combined => format!(
+                "{} - - [{:02}/{}/{}:{:02}:{:02}:{:02} +0000] \"{} {} {:?}\" {} {} \"{}\" \"{}\" {:.3}",
+                peer.as_deref().unwrap_or("-"),
+                day,
+                MONTHS[month as usize - 1],
+                year,
+                hour,
+                minute,
+                second,
+                method,
+                uri,
+                version,
+                status,
+                bytes.map_or_else(|| "-".to_string(), |b| b.to_string()),
+                referer.as_deref().unwrap_or("-").replace('"', "\\\""),
+                user_agent.as_deref().unwrap_or("-").replace('"', "\\\""),
+                millis,
+            ),
+            LogFormat::Json => json!({
+                "time": format!(
+                    "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+                    year, month, day, hour, minute, second
+                ),
+                "unix": secs,
+                "peer": peer,
+                "method": method.as_str(),
+                "uri": uri.to_string(),
+                "route": route.as_deref(),
+                "version": format!("{:?}", version),
+                "status": status,
+                "bytes": bytes,
+                "duration_ms": millis,
+                "referer": referer,
+                "user_agent": user_agent,
+            })
+            .to_string(),
+        };
+        self.writer.write(line);
+        res
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Unix seconds and UTC `[year, month, day, hour, minute, second]`.
+fn civil_time(time: SystemTime) -> (u64, [u64; 6]) {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    // IMF-fixdate is fixed width: "Sun, 06 Nov 1994 08:49:37 GMT".
+    let date = httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(secs));
+    let field = |at: usize, len: usize| date[at..at + len].parse().unwrap_or(0);
+    let month = MONTHS
+        .iter()
+        .position(|month| date[8..11] == **month)
+        .map_or(1, |index| index as u64 + 1);
+    (
+        secs,
+        [
+            field(12, 4),
+            month,
+            field(5, 2),
+            field(17, 2),
+            field(20, 2),
+            field(23, 2),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RustApi;
+    use crate::test::TestClient;
+
+    #[test]
+    fn test_civil_time() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_792_247_736);
+        assert_eq!(civil_time(time).1, [2026, 10, 17, 14, 35, 36]);
+        assert_eq!(
+            civil_time(UNIX_EPOCH + Duration::from_secs(951_782_400)).1[..3],
+            [2000, 2, 29]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_access_log_rotation() {
+        let dir = std::env::temp_dir().join(format!("rust-api-log-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("access.log");
+        let log = LogFile::new(&path).max_size(150).keep(2).open().unwrap();
+
+        let mut app = RustApi::new();
+        app.attach(AccessLog::new(log.clone()).format(LogFormat::Json));
+        app.get("/users/{id}", |_req: Req| async { "ok" });
+        let client = TestClient::new(app);
+
+        client.get("/users/7?x=1").send().await;
+        log.flush().await;
+        let line = std::fs::read_to_string(&path).unwrap();
+        let entry: serde_json::Value = serde_json::from_str(line.trim()).unwrap();
+        assert_eq!(entry["uri"], "/users/7?x=1");
+        assert_eq!(entry["route"], "/users/{id}");
+        assert_eq!(entry["status"], 200);
+
+        for n in 0..4 {
+            log.write(format!("{:0>99}", n));
+            log.flush().await;
+        }
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!("{:0>99}\n", 3)
+        );
+        assert_eq!(
+            std::fs::read_to_string(rotated(&path, 1)).unwrap(),
+            format!("{:0>99}\n", 2)
+        );
+        assert!(rotated(&path, 2).exists());
+        assert!(!rotated(&path, 3).exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
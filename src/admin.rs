@@ -0,0 +1,372 @@
+//! Runtime configuration.
+//!
+//! [`RuntimeConfig`] holds [`Settings`] that can change while the server
+//! runs: the log level, maintenance mode, feature flags, CORS origins and
+//! named rate limits. Middleware reads the current values on every request,
+//! and other code can [`subscribe`](RuntimeConfig::subscribe) to changes.
+//! [`patch`](RuntimeConfig::patch) applies a JSON merge patch (RFC 7386),
+//! so `null` removes a flag or limit; the `rust-api-admin` dashboard exposes
+//! it behind the admin credentials.
+//!
+//! ```rust
+//! use rust_api::{Req, RustApi, cors::CorsConfig, flags::FeatureFlags};
+//! use rust_api::admin::{RuntimeConfig, Settings};
+//!
+//! let mut app = RustApi::new();
+//! let config = RuntimeConfig::new(Settings::default())
+//!     .maintenance(app.maintenance_switch());
+//! app.attach(config.cors(CorsConfig::new()));
+//! app.attach(FeatureFlags::new(config.clone()));
+//! app.get("/search", |_req: Req| async { "results" })
+//!     .attach(config.rate_limit("search"));
+//!
+//! let patch = serde_json::json!({"rate_limits": {"search": {"requests": 5, "period_secs": 1}}});
+//! config.patch(&patch).unwrap();
+//! ```
+
+use async_trait::async_trait;
+use hyper::header::{self, HeaderValue};
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+use crate::cors::{self, CorsConfig};
+use crate::flags::FlagProvider;
+use crate::maintenance::MaintenanceSwitch;
+use crate::route_table::RateLimiter;
+use crate::{Error, IntoRes, Middleware, Next, RateLimit, Req, Res, Result};
+
+/// Settings adjustable at runtime.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// Maximum log level, applied with `log::set_max_level`.
+    #[serde(with = "level")]
+    pub log_level: LevelFilter,
+    /// Whether maintenance mode is on.
+    pub maintenance: bool,
+    /// Rate limits by name, see [`RuntimeConfig::rate_limit`].
+    #[serde(with = "rate_limits")]
+    pub rate_limits: BTreeMap<String, RateLimit>,
+    /// Feature flags; unknown flags are off.
+    pub flags: BTreeMap<String, bool>,
+    /// Allowed CORS origins, in [`CorsConfig::allow_origins`] syntax.
+    pub cors_origins: Vec<String>,
+}
+
+impl Default for Settings {
+    /// The current log level, no maintenance, limits, flags or origins.
+    fn default() -> Self {
+        Self {
+            log_level: log::max_level(),
+            maintenance: false,
+            rate_limits: BTreeMap::new(),
+            flags: BTreeMap::new(),
+            cors_origins: Vec::new(),
+        }
+    }
+}
+
+mod level {
+    use log::LevelFilter;
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub(super) fn serialize<S: Serializer>(level: &LevelFilter, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&level.as_str().to_ascii_lowercase())
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<LevelFilter, D::Error> {
+        let level = String::deserialize(d)?;
+        level
+            .parse()
+            .map_err(|_| D::Error::custom(format!("invalid log level {:?}", level)))
+    }
+}
+
+mod rate_limits {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    use crate::RateLimit;
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Limit {
+        requests: u32,
+        period_secs: f64,
+    }
+
+    pub(super) fn serialize<S: Serializer>(
+        limits: &BTreeMap<String, RateLimit>,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        let limits: BTreeMap<&str, Limit> = limits
+            .iter()
+            .map(|(name, limit)| {
+                let limit = Limit {
+                    requests: limit.requests,
+                    period_secs: limit.period.as_secs_f64(),
+                };
+                (name.as_str(), limit)
+            })
+            .collect();
+        limits.serialize(s)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<BTreeMap<String, RateLimit>, D::Error> {
+        let limits = BTreeMap::<String, Limit>::deserialize(d)?;
+        limits
+            .into_iter()
+            .map(|(name, limit)| {
+                let period = Duration::try_from_secs_f64(limit.period_secs).unwrap_or_default();
+                if limit.requests == 0 || period.is_zero() {
+                    return Err(D::Error::custom(format!(
+                        "rate limit {} must be non-zero",
+                        name
+                    )));
+                }
+                let limit = RateLimit {
+                    requests: limit.requests,
+                    period,
+                };
+                Ok((name, limit))
+            })
+            .collect()
+    }
+}
+
+/// Shared, watchable [`Settings`]; clones share the same values.
+#[derive(Clone)]
+pub struct RuntimeConfig {
+    settings: Arc<watch::Sender<Settings>>,
+    maintenance: Option<MaintenanceSwitch>,
+}
+
+impl RuntimeConfig {
+    /// Start from `settings`, applying their log level.
+    pub fn new(settings: Settings) -> Self {
+        log::set_max_level(settings.log_level);
+        Self {
+            settings: Arc::new(watch::Sender::new(settings)),
+            maintenance: None,
+        }
+    }
+
+    /// Drive `switch` (see `RustApi::maintenance_switch`) from the
+    /// `maintenance` setting.
+    pub fn maintenance(mut self, switch: MaintenanceSwitch) -> Self {
+        if self.settings.borrow().maintenance {
+            switch.enable();
+        }
+        self.maintenance = Some(switch);
+        self
+    }
+
+    /// Current settings.
+    pub fn current(&self) -> Settings {
+        let mut settings = self.settings.borrow().clone();
+        if let Some(switch) = &self.maintenance {
+            settings.maintenance = switch.is_enabled();
+        }
+        settings
+    }
+
+    /// Receiver notified whenever the settings change.
+    pub fn subscribe(&self) -> watch::Receiver<Settings> {
+        self.settings.subscribe()
+    }
+
+    /// Change the settings and apply the log level and maintenance mode.
+    pub fn update(&self, f: impl FnOnce(&mut Settings)) {
+        self.try_update(|settings| {
+            f(settings);
+            Ok(())
+        })
+        .ok();
+    }
+
+    /// Apply a JSON merge patch to the settings and return the result.
+    /// Invalid settings are rejected with 400 and leave them unchanged.
+    pub fn patch(&self, patch: &Value) -> Result<Settings> {
+        self.try_update(|settings| {
+            let mut merged =
+                serde_json::to_value(&*settings).map_err(|e| Error::Json(e.to_string()))?;
+            merge_patch(&mut merged, patch);
+            *settings = serde_json::from_value(merged)
+                .map_err(|e| Error::bad_request(format!("Invalid settings: {}", e)))?;
+            Ok(())
+        })?;
+        log::info!(target: "rust_api::admin", "runtime config changed: {}", patch);
+        Ok(self.current())
+    }
+
+    /// Run `f` on the settings under the sender's lock, so concurrent
+    /// updates never overwrite each other; an error discards the change.
+    fn try_update(&self, f: impl FnOnce(&mut Settings) -> Result<()>) -> Result<()> {
+        let mut result = Ok(());
+        self.settings.send_if_modified(|current| {
+            let mut settings = current.clone();
+            if let Some(switch) = &self.maintenance {
+                settings.maintenance = switch.is_enabled();
+            }
+            result = f(&mut settings);
+            if result.is_err() {
+                return false;
+            }
+            log::set_max_level(settings.log_level);
+            if let Some(switch) = &self.maintenance {
+                if settings.maintenance {
+                    switch.enable();
+                } else {
+                    switch.disable();
+                }
+            }
+            *current = settings;
+            true
+        });
+        result
+    }
+
+    /// Whether `origin` matches one of the `cors_origins`.
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        self.settings
+            .borrow()
+            .cors_origins
+            .iter()
+            .any(|pattern| cors::origin_matches(pattern, origin))
+    }
+
+    /// `cors` additionally allowing the `cors_origins`.
+    pub fn cors(&self, cors: CorsConfig) -> CorsConfig {
+        let config = self.clone();
+        cors.allow_origin_fn(move |origin| config.allows_origin(origin))
+    }
+
+    /// Middleware enforcing the rate limit named `name`, shared by every
+    /// route it is attached to. Requests pass while no such limit is set.
+    pub fn rate_limit(&self, name: impl Into<String>) -> RuntimeRateLimit {
+        RuntimeRateLimit {
+            config: self.clone(),
+            name: name.into(),
+            limiter: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+impl FlagProvider for RuntimeConfig {
+    fn is_enabled(&self, flag: &str) -> bool {
+        self.settings.borrow().flags.get(flag) == Some(&true)
+    }
+}
+
+/// Token bucket for a limit that may change; see
+/// [`RuntimeConfig::rate_limit`].
+#[derive(Clone)]
+pub struct RuntimeRateLimit {
+    config: RuntimeConfig,
+    name: String,
+    limiter: Arc<Mutex<Option<RateLimiter>>>,
+}
+
+impl RuntimeRateLimit {
+    /// Take a token, or return how long until one is available.
+    fn acquire(&self) -> std::result::Result<(), std::time::Duration> {
+        let Some(limit) = self
+            .config
+            .settings
+            .borrow()
+            .rate_limits
+            .get(&self.name)
+            .copied()
+        else {
+            return Ok(());
+        };
+        let mut limiter = self.limiter.lock().unwrap();
+        // A changed limit starts with a full bucket.
+        let limiter = match &mut *limiter {
+            Some(limiter) if limiter.limit() == limit => limiter,
+            slot => slot.insert(RateLimiter::new(limit)),
+        };
+        limiter.acquire()
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> Middleware<S> for RuntimeRateLimit {
+    async fn handle(&self, req: Req, _state: Arc<S>, next: Next<S>) -> Res {
+        if let Err(retry_after) = self.acquire() {
+            let mut res = Error::too_many_requests("Rate limit exceeded").into_res();
+            let secs = retry_after.as_secs_f64().ceil() as u64;
+            res.headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs.max(1)));
+            return res;
+        }
+        next.run(req).await
+    }
+
+    fn name(&self) -> &'static str {
+        "RuntimeRateLimit"
+    }
+}
+
+/// RFC 7386 JSON merge patch.
+fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    let target = target.as_object_mut().expect("object set above");
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RustApi;
+    use crate::test::{TestClient, assert_status};
+
+    #[tokio::test]
+    async fn test_runtime_patch() {
+        let mut app = RustApi::new();
+        let config = RuntimeConfig::new(Settings::default()).maintenance(app.maintenance_switch());
+        app.get("/search", |_req: Req| async { "results" })
+            .attach(config.rate_limit("search"));
+        let client = TestClient::new(app);
+        for _ in 0..3 {
+            assert_status(&client.get("/search").send().await, 200);
+        }
+
+        let patch = serde_json::json!({
+            "flags": {"beta": true},
+            "rate_limits": {"search": {"requests": 1, "period_secs": 60}},
+        });
+        config.patch(&patch).unwrap();
+        assert!(config.is_enabled("beta"));
+        assert_status(&client.get("/search").send().await, 200);
+        assert_status(&client.get("/search").send().await, 429);
+
+        let patch = serde_json::json!({"rate_limits": {"search": null}, "log_level": "loud"});
+        assert!(config.patch(&patch).is_err());
+        assert_status(&client.get("/search").send().await, 429);
+        config.update(|settings| {
+            settings.rate_limits.clear();
+            settings.maintenance = true;
+        });
+        assert_status(&client.get("/search").send().await, 503);
+    }
+}
@@ -1,66 +1,109 @@
 //! HTTP application.
 
-use std::collections::HashMap;
 use std::convert::Infallible;
 use std::net::SocketAddr;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::time::Duration;
-
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::compression::NoCompression;
+use crate::conn::{ConnInfo, ConnIo, ConnectionDrain, MinBodyRate};
+use crate::cors::CorsConfig;
+use crate::dev;
+use crate::diagnostics::{self, PanicContext, StartupDiagnostics};
+use crate::error_handler::{ErrorContext, RaisedError};
+use crate::extractors::LenientContentType;
+use crate::maintenance::{Maintenance, MaintenanceSwitch};
+use crate::metrics::{
+    self, CloseReason, ConnectionStats, Metrics, RequestTimings, RequestTrace, Sampler, TraceParent,
+};
+use crate::pool::BufferPool;
+use crate::profile::{Environment, Profile};
+use crate::proxy::TrustedProxies;
+use crate::redirect::{self, HttpsRedirect};
+use crate::report::{self, ErrorMessage, ErrorReporter, RequestContext};
 use crate::res::BoxBody;
-use hyper::body::Incoming;
+use crate::resource::{self, Resource};
+use crate::route_table::{RouteTable, RoutedMethods};
+use crate::signals::Signals;
+use crate::versioning::{ApiVersion, Versioning};
+use futures_util::FutureExt;
+use http_body_util::BodyExt;
+use hyper::body::{Body, Incoming};
+use hyper::header::{self, HeaderValue};
 use hyper::server::conn::{http1, http2};
 use hyper::service::service_fn;
-use hyper::{Method, Request, Response};
-use hyper_util::rt::TokioIo;
+use hyper::{Method, Request, Response, StatusCode, Version};
+use hyper_util::rt::{TokioIo, TokioTimer};
 use tokio::net::TcpListener;
-use tokio::signal;
 use tokio::sync::watch;
 
 use crate::{
-    Error, ErrorHandler, Handler, IntoRes, Middleware, Req, Res, Result, Router, ServerConfig,
+    EarlyHints, Error, ErrorHandler, IntoRes, Middleware, Req, Res, ResponseHeaders, Result, Route,
+    RouteInfo, Router, ServerConfig, guard,
     handler::IntoHandler,
+    middleware::{self, NextFn},
+    route,
 };
 
-type BoxedHandler<S> = Arc<dyn Handler<S>>;
 type BoxedMiddleware<S> = Arc<dyn Middleware<S>>;
-type SharedMiddlewares<S> = Arc<Vec<BoxedMiddleware<S>>>;
 type BoxedErrorHandler = Arc<dyn ErrorHandler>;
-type MethodHandlers<S> = HashMap<Method, (BoxedHandler<S>, SharedMiddlewares<S>)>;
 
 /// HTTP application.
 pub struct RustApi<S = ()> {
-    routes: Vec<(Method, String, BoxedHandler<S>, SharedMiddlewares<S>)>,
+    routes: Vec<Route<S>>,
     middlewares: Vec<BoxedMiddleware<S>>,
+    pre_routing: Vec<BoxedMiddleware<S>>,
     state: Option<Arc<S>>,
-    router: Option<matchit::Router<Arc<MethodHandlers<S>>>>,
+    table: Option<Arc<RouteTable<S>>>,
+    routed_methods: Option<RoutedMethods>,
     error_handler: Option<BoxedErrorHandler>,
+    error_reporter: Option<Arc<dyn ErrorReporter>>,
+    json_panic_log: bool,
+    metrics: Option<Arc<dyn Metrics>>,
+    sampler: Option<Sampler>,
 
     // Configuration
     body_limit: Option<usize>,
+    lenient_content_type: bool,
+    trusted_proxies: Option<Arc<TrustedProxies>>,
+    header_read_timeout: Option<Duration>,
+    min_body_rate: Option<MinBodyRate>,
     request_timeout: Option<Duration>,
     handler_timeout: Option<Duration>,
     http2_enabled: bool,
+    early_hints: bool,
     max_connections: Option<usize>,
     keep_alive: Option<Duration>,
+    max_requests_per_connection: Option<u64>,
+    idle_timeout: Option<Duration>,
+    drain: ConnectionDrain,
+    maintenance: Maintenance,
+    maintenance_switch: MaintenanceSwitch,
+    buffer_pool: Option<Arc<BufferPool>>,
+    runtime_metrics_interval: Option<Duration>,
+    tokio_console: bool,
+    #[cfg(feature = "websocket")]
+    websockets: Arc<crate::websocket::WebSocketRegistry>,
+    websocket_shutdown_grace: Duration,
+    response_body_limit: Option<usize>,
+    max_response_headers: Option<usize>,
+    max_response_header_bytes: Option<usize>,
+    https_redirect_port: Option<u16>,
+    https_port: u16,
+    acme_challenge_dir: Option<PathBuf>,
+    versioning: Option<Versioning>,
+    route_table_path: Option<String>,
+    profile: Option<Profile>,
+    signals: Option<Signals>,
 }
 
 impl RustApi<()> {
     /// Create a new application with default state.
     pub fn new() -> Self {
-        Self {
-            routes: Vec::new(),
-            middlewares: Vec::new(),
-            state: Some(Arc::new(())),
-            router: None,
-            error_handler: None,
-            body_limit: None,
-            request_timeout: None,
-            handler_timeout: None,
-            http2_enabled: false,
-            max_connections: None,
-            keep_alive: None,
-        }
+        Self::with_state(())
     }
 }
 
@@ -70,111 +113,204 @@ impl<S: Send + Sync + 'static> RustApi<S> {
     /// State is shared across handlers via `Arc<S>` and accessed using `State<S>` extractor.
     pub fn with_state(state: S) -> Self {
         Self {
-            routes: Vec::new(),
-            middlewares: Vec::new(),
             state: Some(Arc::new(state)),
-            router: None,
-            error_handler: None,
-            body_limit: None,
-            request_timeout: None,
-            handler_timeout: None,
-            http2_enabled: false,
-            max_connections: None,
-            keep_alive: None,
+            ..Self::default()
         }
     }
 
+    /// Replace the application state (e.g. with a mock in tests).
+    pub fn set_state(&mut self, state: S) {
+        self.state = Some(Arc::new(state));
+    }
+
     /// Set custom error handler.
     pub fn set_error_handler<H: ErrorHandler>(&mut self, handler: H) {
         self.error_handler = Some(Arc::new(handler));
     }
 
-    /// Attach global middleware.
+    /// Report 5xx responses and handler panics (see [`report`](crate::report)).
+    pub fn set_error_reporter<R: ErrorReporter>(&mut self, reporter: R) {
+        self.error_reporter = Some(Arc::new(reporter));
+    }
+
+    /// Log panics as JSON records tagged with the request being served.
+    ///
+    /// Installs a process-wide panic hook when listening; see
+    /// [`diagnostics`](crate::diagnostics).
+    pub fn set_json_panic_log(&mut self, enabled: bool) {
+        self.json_panic_log = enabled;
+    }
+
+    /// Switch framework defaults to `profile` (see [`profile`](crate::profile)).
+    ///
+    /// Overrides the profile read from `RUST_API_PROFILE`.
+    pub fn set_profile(&mut self, profile: Profile) {
+        self.profile = Some(profile);
+    }
+
+    /// Active profile, if any.
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Whether the active profile allows `setting`; true without a profile.
+    pub(crate) fn profile_allows(&self, setting: impl Fn(&Profile) -> bool) -> bool {
+        self.profile.as_ref().is_none_or(setting)
+    }
+
+    /// Report connection and request timings to a metrics sink.
+    pub fn set_metrics<M: Metrics>(&mut self, metrics: M) {
+        self.metrics = Some(Arc::new(metrics));
+    }
+
+    /// Mark which requests reported to the metrics sink are traced.
     ///
-    /// Middleware runs for all routes. Execution order matches registration order.
+    /// Without a sampler every request is marked sampled.
+    pub fn set_sampler(&mut self, sampler: Sampler) {
+        self.sampler = Some(sampler);
+    }
+
+    /// Attach global post-routing middleware.
+    ///
+    /// Middleware runs for all matched routes, after path params, the matched
+    /// route and its name are set on the request. Execution order matches
+    /// registration order.
     pub fn attach<M: Middleware<S>>(&mut self, middleware: M) {
         self.middlewares.push(Arc::new(middleware));
     }
 
+    /// Attach pre-routing middleware.
+    ///
+    /// Runs before route matching, for every request including 404 and 405
+    /// responses. Changes to the request method or path affect routing.
+    pub fn attach_pre_routing<M: Middleware<S>>(&mut self, middleware: M) {
+        self.pre_routing.push(Arc::new(middleware));
+    }
+
     /// Register a GET route.
-    pub fn get<H, T>(&mut self, path: &str, handler: H)
+    pub fn get<H, T>(&mut self, path: &str, handler: H) -> &mut Route<S>
     where
         H: IntoHandler<S, T>,
     {
-        self.routes.push((
-            Method::GET,
-            path.to_string(),
-            handler.into_handler(),
-            Arc::new(Vec::new()),
-        ));
+        self.push_route(Route::new(Method::GET, path.to_string(), handler))
     }
 
     /// Register a POST route.
-    pub fn post<H, T>(&mut self, path: &str, handler: H)
+    pub fn post<H, T>(&mut self, path: &str, handler: H) -> &mut Route<S>
     where
         H: IntoHandler<S, T>,
     {
-        self.routes.push((
-            Method::POST,
-            path.to_string(),
-            handler.into_handler(),
-            Arc::new(Vec::new()),
-        ));
+        self.push_route(Route::new(Method::POST, path.to_string(), handler))
+    }
+
+    /// Register a GET route for API version `version`.
+    pub fn get_v<H, T>(&mut self, version: u32, path: &str, handler: H) -> &mut Route<S>
+    where
+        H: IntoHandler<S, T>,
+    {
+        self.get(path, handler).version(version)
+    }
+
+    /// Register a POST route for API version `version`.
+    pub fn post_v<H, T>(&mut self, version: u32, path: &str, handler: H) -> &mut Route<S>
+    where
+        H: IntoHandler<S, T>,
+    {
+        self.post(path, handler).version(version)
+    }
+
+    /// Register a PUT route for API version `version`.
+    pub fn put_v<H, T>(&mut self, version: u32, path: &str, handler: H) -> &mut Route<S>
+    where
+        H: IntoHandler<S, T>,
+    {
+        self.put(path, handler).version(version)
+    }
+
+    /// Register a DELETE route for API version `version`.
+    pub fn delete_v<H, T>(&mut self, version: u32, path: &str, handler: H) -> &mut Route<S>
+    where
+        H: IntoHandler<S, T>,
+    {
+        self.delete(path, handler).version(version)
+    }
+
+    /// Register a PATCH route for API version `version`.
+    pub fn patch_v<H, T>(&mut self, version: u32, path: &str, handler: H) -> &mut Route<S>
+    where
+        H: IntoHandler<S, T>,
+    {
+        self.patch(path, handler).version(version)
     }
 
     /// Register a PUT route.
-    pub fn put<H, T>(&mut self, path: &str, handler: H)
+    pub fn put<H, T>(&mut self, path: &str, handler: H) -> &mut Route<S>
     where
         H: IntoHandler<S, T>,
     {
-        self.routes.push((
-            Method::PUT,
-            path.to_string(),
-            handler.into_handler(),
-            Arc::new(Vec::new()),
-        ));
+        self.push_route(Route::new(Method::PUT, path.to_string(), handler))
     }
 
     /// Register a DELETE route.
-    pub fn delete<H, T>(&mut self, path: &str, handler: H)
+    pub fn delete<H, T>(&mut self, path: &str, handler: H) -> &mut Route<S>
     where
         H: IntoHandler<S, T>,
     {
-        self.routes.push((
-            Method::DELETE,
-            path.to_string(),
-            handler.into_handler(),
-            Arc::new(Vec::new()),
-        ));
+        self.push_route(Route::new(Method::DELETE, path.to_string(), handler))
     }
 
     /// Register a PATCH route.
-    pub fn patch<H, T>(&mut self, path: &str, handler: H)
+    pub fn patch<H, T>(&mut self, path: &str, handler: H) -> &mut Route<S>
     where
         H: IntoHandler<S, T>,
     {
-        self.routes.push((
-            Method::PATCH,
-            path.to_string(),
-            handler.into_handler(),
-            Arc::new(Vec::new()),
-        ));
+        self.push_route(Route::new(Method::PATCH, path.to_string(), handler))
     }
 
     /// Register a route with per-route middleware.
-    pub fn route(&mut self, route: crate::Route<S>) {
-        self.routes
-            .push((route.method, route.path, route.handler, route.middlewares));
+    pub fn route(&mut self, route: Route<S>) -> &mut Route<S> {
+        self.push_route(route)
+    }
+
+    fn push_route(&mut self, route: Route<S>) -> &mut Route<S> {
+        let index = self.routes.len();
+        self.routes.push(route);
+        &mut self.routes[index]
+    }
+
+    /// Register the standard REST routes of `R` under `path`
+    /// (see [`resource`](crate::resource)).
+    pub fn resource<R: Resource<S>>(&mut self, path: &str) {
+        self.routes.extend(resource::routes::<R, S>(path));
     }
 
     /// Mount a router at a prefix.
     pub fn nest(&mut self, prefix: &str, router: Router<S>) {
-        let flattened = router.flatten(prefix);
-        for (method, path, handler, middlewares) in flattened {
-            self.routes.push((method, path, handler, middlewares));
+        self.routes.extend(router.flatten(prefix));
+    }
+
+    /// Mount a router for a single host (e.g. `api.example.com`).
+    ///
+    /// A leading `*.` matches any subdomain. Requests for other hosts fall
+    /// through to routes registered without a host, which act as the default
+    /// virtual host.
+    pub fn vhost(&mut self, host: &str, router: Router<S>) {
+        let host: Arc<str> = Arc::from(host);
+        for mut route in router.flatten("") {
+            let host = Arc::clone(&host);
+            route.guard(move |req: &Req| guard::host_matches(req, &host));
+            self.routes.push(route);
         }
     }
 
+    /// Serve the route table (with descriptions and tags) at `path`.
+    ///
+    /// Responds with HTML to browsers and JSON otherwise. Intended for
+    /// development; the table exposes handler and middleware type names.
+    pub fn serve_route_table(&mut self, path: &str) {
+        self.route_table_path = Some(path.to_string());
+    }
+
     /// Get the number of registered routes.
     pub fn route_count(&self) -> usize {
         self.routes.len()
@@ -182,7 +318,28 @@ impl<S: Send + Sync + 'static> RustApi<S> {
 
     /// Check if a route exists at the given path.
     pub fn has_route(&self, path: &str) -> bool {
-        self.routes.iter().any(|(_, p, _, _)| p == path)
+        self.routes.iter().any(|route| route.path == path)
+    }
+
+    /// Describe registered routes in registration order.
+    ///
+    /// Pre-routing and global middleware are listed before per-route middleware.
+    pub fn routes(&self) -> Vec<RouteInfo> {
+        self.routes
+            .iter()
+            .map(|route| {
+                let mut info = route.info();
+                let mut middleware: Vec<_> = self
+                    .pre_routing
+                    .iter()
+                    .chain(&self.middlewares)
+                    .map(|m| m.name())
+                    .collect();
+                middleware.append(&mut info.middleware);
+                info.middleware = middleware;
+                info
+            })
+            .collect()
     }
 
     /// Set maximum request body size in bytes.
@@ -190,6 +347,33 @@ impl<S: Send + Sync + 'static> RustApi<S> {
         self.body_limit = Some(limit);
     }
 
+    /// Let `Json` and `Form` parse bodies sent without `Content-Type`
+    /// instead of rejecting them with 400. A mismatched type is still
+    /// rejected. Strict by default; see also `Route::lenient_content_type`.
+    pub fn set_lenient_content_type(&mut self, enabled: bool) {
+        self.lenient_content_type = enabled;
+    }
+
+    /// Honor forwarding headers from `proxies` (see [`proxy`](crate::proxy)).
+    pub fn set_trusted_proxies(&mut self, proxies: TrustedProxies) {
+        self.trusted_proxies = Some(Arc::new(proxies));
+    }
+
+    /// Close HTTP/1 connections whose request headers take longer than
+    /// `timeout` to arrive, so slow clients cannot hold them open.
+    pub fn set_header_read_timeout(&mut self, timeout: Duration) {
+        self.header_read_timeout = Some(timeout);
+    }
+
+    /// Answer 408 and close the connection when a request body arrives
+    /// slower than `bytes_per_sec` on average, once `grace` has passed.
+    pub fn set_min_body_rate(&mut self, bytes_per_sec: u64, grace: Duration) {
+        self.min_body_rate = Some(MinBodyRate {
+            bytes_per_sec,
+            grace,
+        });
+    }
+
     /// Set request timeout duration.
     pub fn set_request_timeout(&mut self, timeout: Duration) {
         self.request_timeout = Some(timeout);
@@ -205,6 +389,14 @@ impl<S: Send + Sync + 'static> RustApi<S> {
         self.http2_enabled = enabled;
     }
 
+    /// Let [`EarlyHints`] send `103 Early Hints` (off by default).
+    ///
+    /// hyper has no server API for informational responses, so hints are
+    /// written to the socket outside hyper; see [`hints`](crate::hints).
+    pub fn set_early_hints(&mut self, enabled: bool) {
+        self.early_hints = enabled;
+    }
+
     /// Set maximum number of concurrent connections.
     pub fn set_max_connections(&mut self, max: usize) {
         self.max_connections = Some(max);
@@ -215,6 +407,154 @@ impl<S: Send + Sync + 'static> RustApi<S> {
         self.keep_alive = Some(duration);
     }
 
+    /// Close HTTP/1 connections after this many requests.
+    ///
+    /// The last response carries `Connection: close`, spreading long-lived
+    /// clients across instances behind a load balancer.
+    pub fn set_max_requests_per_connection(&mut self, max: u64) {
+        self.max_requests_per_connection = Some(max);
+    }
+
+    /// Close connections that have had no request in flight for this long.
+    pub fn set_idle_timeout(&mut self, timeout: Duration) {
+        self.idle_timeout = Some(timeout);
+    }
+
+    /// Handle for draining connections without stopping the listener.
+    pub fn connection_drain(&self) -> ConnectionDrain {
+        self.drain.clone()
+    }
+
+    /// Configure the response and allow-list used in maintenance mode.
+    pub fn set_maintenance(&mut self, maintenance: Maintenance) {
+        self.maintenance = maintenance;
+    }
+
+    /// Current maintenance mode configuration.
+    pub fn maintenance(&self) -> &Maintenance {
+        &self.maintenance
+    }
+
+    /// Handle for switching maintenance mode on and off at runtime.
+    pub fn maintenance_switch(&self) -> MaintenanceSwitch {
+        self.maintenance_switch.clone()
+    }
+
+    /// Signals `listen` shuts down on, with reload hooks; see
+    /// [`signals`](crate::signals).
+    pub fn set_signals(&mut self, signals: Signals) {
+        self.signals = Some(signals);
+    }
+
+    /// Reuse buffers for request bodies and JSON responses.
+    ///
+    /// Keep a clone of the pool to read its [`stats`](BufferPool::stats).
+    pub fn set_buffer_pool(&mut self, pool: Arc<BufferPool>) {
+        self.buffer_pool = Some(pool);
+    }
+
+    /// Sample tokio runtime metrics into the metrics sink every `interval`.
+    ///
+    /// Requires `set_metrics`. Blocking pool and poll time figures are only
+    /// reported when built with `RUSTFLAGS="--cfg tokio_unstable"`.
+    pub fn set_runtime_metrics_interval(&mut self, interval: Duration) {
+        self.runtime_metrics_interval = Some(interval);
+    }
+
+    /// Start the tokio-console instrumentation server when listening.
+    ///
+    /// Needs the `console` feature and `RUSTFLAGS="--cfg tokio_unstable"`;
+    /// the server binds `127.0.0.1:6669` unless `TOKIO_CONSOLE_BIND` is set.
+    /// It installs the global `tracing` subscriber, so it cannot be combined
+    /// with another one.
+    pub fn set_tokio_console(&mut self, enabled: bool) {
+        self.tokio_console = enabled;
+    }
+
+    /// Limit WebSocket connections and the messages they may send.
+    #[cfg(feature = "websocket")]
+    pub fn set_websocket_limits(&mut self, limits: crate::websocket::WebSocketLimits) {
+        self.websockets = Arc::new(crate::websocket::WebSocketRegistry::new(limits));
+    }
+
+    /// How long shutdown waits for WebSocket handlers to return after
+    /// sending them `Close(1001 Going Away)` (default 10 seconds).
+    pub fn set_websocket_shutdown_grace(&mut self, grace: Duration) {
+        self.websocket_shutdown_grace = grace;
+    }
+
+    /// Set maximum response body size in bytes.
+    ///
+    /// Oversized buffered bodies are replaced with a 500 response; streamed
+    /// bodies are cut off once they exceed the limit.
+    pub fn set_response_body_limit(&mut self, limit: usize) {
+        self.response_body_limit = Some(limit);
+    }
+
+    /// Set maximum number of response headers.
+    pub fn set_max_response_headers(&mut self, max: usize) {
+        self.max_response_headers = Some(max);
+    }
+
+    /// Set maximum total size of response headers in bytes.
+    pub fn set_max_response_header_bytes(&mut self, max: usize) {
+        self.max_response_header_bytes = Some(max);
+    }
+
+    /// Answer plain HTTP on `port` with 301 redirects to the HTTPS origin.
+    ///
+    /// The companion listener binds the same address as `listen`; redirects
+    /// target the port set with [`set_https_port`](Self::set_https_port).
+    pub fn redirect_http_to_https(&mut self, port: u16) {
+        self.https_redirect_port = Some(port);
+    }
+
+    /// Set the public HTTPS port redirects point to (default 443, which is
+    /// omitted from the URL).
+    ///
+    /// This is the port clients reach, e.g. on a TLS-terminating proxy, not
+    /// necessarily the one passed to `listen`.
+    pub fn set_https_port(&mut self, port: u16) {
+        self.https_port = port;
+    }
+
+    /// Serve ACME HTTP-01 challenge tokens from a directory on the redirect listener.
+    ///
+    /// Files are looked up by token under `/.well-known/acme-challenge/`,
+    /// e.g. as written by `certbot certonly --webroot`.
+    pub fn set_acme_challenge_dir(&mut self, dir: impl Into<PathBuf>) {
+        self.acme_challenge_dir = Some(dir.into());
+    }
+
+    /// Resolve API versions for versioned routes (`get_v` and friends).
+    pub fn set_versioning(&mut self, versioning: Versioning) {
+        self.versioning = Some(versioning);
+    }
+
+    /// Current server configuration.
+    pub fn config(&self) -> ServerConfig {
+        ServerConfig {
+            body_limit: self.body_limit,
+            request_timeout: self.request_timeout,
+            handler_timeout: self.handler_timeout,
+            http2: self.http2_enabled,
+            max_connections: self.max_connections,
+            keep_alive: self.keep_alive,
+            max_requests_per_connection: self.max_requests_per_connection,
+            idle_timeout: self.idle_timeout,
+            header_read_timeout: self.header_read_timeout,
+            min_body_rate: self.min_body_rate.map(|rate| rate.bytes_per_sec),
+            min_body_rate_grace: self.min_body_rate.map(|rate| rate.grace),
+            buffer_pool_size: self.buffer_pool.as_ref().map(|pool| pool.max_buffers()),
+            runtime_metrics_interval: self.runtime_metrics_interval,
+            tokio_console: self.tokio_console,
+            websocket_shutdown_grace: Some(self.websocket_shutdown_grace),
+            response_body_limit: self.response_body_limit,
+            max_response_headers: self.max_response_headers,
+            max_response_header_bytes: self.max_response_header_bytes,
+        }
+    }
+
     /// Apply configuration from a config struct.
     pub fn apply_config(&mut self, config: ServerConfig) {
         if let Some(limit) = config.body_limit {
@@ -231,133 +571,240 @@ impl<S: Send + Sync + 'static> RustApi<S> {
             self.max_connections = Some(max);
         }
         self.keep_alive = config.keep_alive;
+        if let Some(max) = config.max_requests_per_connection {
+            self.max_requests_per_connection = Some(max);
+        }
+        if let Some(timeout) = config.idle_timeout {
+            self.idle_timeout = Some(timeout);
+        }
+        if let Some(timeout) = config.header_read_timeout {
+            self.header_read_timeout = Some(timeout);
+        }
+        if let Some(bytes_per_sec) = config.min_body_rate {
+            let grace = config.min_body_rate_grace.unwrap_or(Duration::from_secs(5));
+            self.set_min_body_rate(bytes_per_sec, grace);
+        }
+        if let Some(size) = config.buffer_pool_size {
+            self.buffer_pool = Some(Arc::new(BufferPool::new(size)));
+        }
+        if let Some(interval) = config.runtime_metrics_interval {
+            self.runtime_metrics_interval = Some(interval);
+        }
+        self.tokio_console = config.tokio_console;
+        if let Some(grace) = config.websocket_shutdown_grace {
+            self.websocket_shutdown_grace = grace;
+        }
+        if let Some(limit) = config.response_body_limit {
+            self.response_body_limit = Some(limit);
+        }
+        if let Some(max) = config.max_response_headers {
+            self.max_response_headers = Some(max);
+        }
+        if let Some(max) = config.max_response_header_bytes {
+            self.max_response_header_bytes = Some(max);
+        }
     }
 
-    fn build_router(&mut self) {
-        let mut router = matchit::Router::new();
-        let mut path_methods: HashMap<String, MethodHandlers<S>> = HashMap::new();
-
-        let global_middlewares = Arc::new(self.middlewares.clone());
-
-        for (method, path, handler, route_middlewares) in self.routes.drain(..) {
-            let combined_middlewares: SharedMiddlewares<S> = if route_middlewares.is_empty() {
-                Arc::clone(&global_middlewares)
-            } else if global_middlewares.is_empty() {
-                route_middlewares
-            } else {
-                let mut combined =
-                    Vec::with_capacity(global_middlewares.len() + route_middlewares.len());
-                combined.extend_from_slice(&global_middlewares);
-                combined.extend_from_slice(&route_middlewares);
-                Arc::new(combined)
-            };
-
-            path_methods
-                .entry(path.clone())
-                .or_insert_with(HashMap::new)
-                .insert(method, (handler, combined_middlewares));
+    /// Compile the route table, returning the number of routes.
+    fn build_router(&mut self) -> usize {
+        if let Some(path) = self
+            .route_table_path
+            .clone()
+            .filter(|_| self.profile_allows(|p| p.debug_endpoints))
+        {
+            // Filled after registration so the table lists its own route.
+            let table = Arc::new(OnceLock::<Vec<RouteInfo>>::new());
+            let handler_table = Arc::clone(&table);
+            self.get(&path, move |req: Req| {
+                let table = Arc::clone(&handler_table);
+                async move { route::render_table(table.get().map_or(&[], Vec::as_slice), &req) }
+            })
+            .describe("Route table")
+            .tag("debug");
+            table.set(self.routes()).ok();
         }
 
-        for (path, methods) in path_methods {
-            router.insert(&path, Arc::new(methods)).ok();
+        if self.profile.is_some_and(|p| p.permissive_cors) {
+            self.pre_routing
+                .insert(0, Arc::new(CorsConfig::permissive()));
         }
 
-        self.router = Some(router);
+        let count = self.routes.len();
+        let global_middlewares = Arc::new(self.middlewares.clone());
+        let table = Arc::new(RouteTable::build(self.routes.drain(..), global_middlewares));
+        self.routed_methods = Some(RoutedMethods::new(Arc::clone(&table)));
+        self.table = Some(table);
+        count
     }
 
     /// Start the HTTP server.
     ///
-    /// Implements graceful shutdown on SIGTERM/SIGINT signals.
-    /// In-flight requests complete before the server terminates.
+    /// Implements graceful shutdown on SIGTERM/SIGINT signals (see
+    /// `set_signals`). In-flight requests complete before the server
+    /// terminates.
     pub async fn listen(mut self, addr: impl Into<SocketAddr>) -> Result<()> {
         let addr = addr.into();
-        self.build_router();
-        let app = Arc::new(self);
+        let routes = self.build_router();
         let listener = TcpListener::bind(addr).await?;
+        let mut diagnostics = StartupDiagnostics {
+            version: env!("CARGO_PKG_VERSION"),
+            addresses: vec![listener.local_addr()?],
+            https_redirect: None,
+            tls: false,
+            routes,
+            features: diagnostics::enabled_features(),
+            pid: std::process::id(),
+            config: self.config(),
+        };
+        if self.json_panic_log {
+            diagnostics::install_panic_hook();
+        }
 
         let active_connections = Arc::new(AtomicUsize::new(0));
 
         let (shutdown_tx, mut shutdown_rx) = watch::channel(false);
 
+        if let Some(port) = self.https_redirect_port {
+            let redirect_listener = TcpListener::bind(SocketAddr::new(addr.ip(), port)).await?;
+            diagnostics.https_redirect = Some(redirect_listener.local_addr()?);
+            let config = HttpsRedirect {
+                https_port: self.https_port,
+                acme_dir: self.acme_challenge_dir.take(),
+            };
+            tokio::spawn(redirect::serve(
+                redirect_listener,
+                config,
+                shutdown_rx.clone(),
+            ));
+        }
+
+        if self.tokio_console {
+            start_tokio_console();
+        }
+        if let (Some(metrics), Some(interval)) = (&self.metrics, self.runtime_metrics_interval) {
+            tokio::spawn(metrics::sample_runtime(
+                Arc::clone(metrics),
+                interval,
+                shutdown_rx.clone(),
+            ));
+        }
+
+        diagnostics.log();
+        let mut signals = self.signals.take().unwrap_or_default();
+        // Install before accepting so an early signal is not missed.
+        let installed = signals.install();
+        let app = Arc::new(self);
+
         tokio::spawn(async move {
-            let _ = shutdown_signal().await;
+            let result = match installed {
+                Ok(()) => signals.shutdown().await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                log::error!("failed to listen for signals: {}", e);
+            }
             let _ = shutdown_tx.send(true);
         });
 
         loop {
             tokio::select! {
                 result = listener.accept() => {
-                    match result {
-                        Ok((stream, _)) => {
-                            // Check max connections limit
-                            if let Some(max) = app.max_connections {
-                                let current = active_connections.load(Ordering::Relaxed);
-                                if current >= max {
-                                    drop(stream);
-                                    continue;
+                    if let Ok((stream, peer)) = result {
+                        // Check max connections limit
+                        if let Some(max) = app.max_connections {
+                            let current = active_connections.load(Ordering::Relaxed);
+                            if current >= max {
+                                if let Some(metrics) = &app.metrics {
+                                    metrics.connection_rejected(peer);
                                 }
+                                drop(stream);
+                                continue;
                             }
+                        }
 
-                            // Increment active connections
-                            active_connections.fetch_add(1, Ordering::Relaxed);
+                        if let Some(metrics) = &app.metrics {
+                            metrics.connection_accepted(peer);
+                        }
 
-                            let io = TokioIo::new(stream);
+                        // Increment active connections
+                        active_connections.fetch_add(1, Ordering::Relaxed);
+
+                        let stream = Arc::new(stream);
+                        let io = TokioIo::new(ConnIo::new(Arc::clone(&stream)));
+                        let app = Arc::clone(&app);
+                        let mut shutdown_rx = shutdown_rx.clone();
+                        let active_connections = Arc::clone(&active_connections);
+                        let http2_enabled = app.http2_enabled;
+                        let header_read_timeout = app.header_read_timeout;
+                        let conn_info = Arc::new(ConnInfo::new(stream, peer, http2_enabled));
+                        let closed_info = Arc::clone(&conn_info);
+                        let closed_app = Arc::clone(&app);
+                        let service = service_fn(move |req| {
                             let app = Arc::clone(&app);
-                            let mut shutdown_rx = shutdown_rx.clone();
-                            let active_connections = Arc::clone(&active_connections);
-                            let http2_enabled = app.http2_enabled;
+                            let conn_info = Arc::clone(&conn_info);
+                            async move { app.handle_request(req, conn_info).await }
+                        });
 
-                            tokio::task::spawn(async move {
-                                if http2_enabled {
-                                    let conn = http2::Builder::new(hyper_util::rt::TokioExecutor::new())
-                                        .serve_connection(
-                                            io,
-                                            service_fn(move |req| {
-                                                let app = Arc::clone(&app);
-                                                async move { app.handle_request(req).await }
-                                            }),
-                                        );
+                        tokio::task::spawn(async move {
+                            let close = closed_app.close_signal(&closed_info, &mut shutdown_rx);
+                            if http2_enabled {
+                                let conn = http2::Builder::new(hyper_util::rt::TokioExecutor::new())
+                                    .serve_connection(io, service);
 
-                                    let mut conn = std::pin::pin!(conn);
+                                let mut conn = std::pin::pin!(conn);
 
-                                    tokio::select! {
-                                        result = conn.as_mut() => {
-                                            let _ = result;
-                                        }
-                                        _ = shutdown_rx.changed() => {
-                                            conn.as_mut().graceful_shutdown();
-                                            let _ = conn.await;
-                                        }
+                                tokio::select! {
+                                    result = conn.as_mut() => {
+                                        let _ = result;
                                     }
-                                } else {
-                                    let conn = http1::Builder::new()
-                                        .serve_connection(
-                                            io,
-                                            service_fn(move |req| {
-                                                let app = Arc::clone(&app);
-                                                async move { app.handle_request(req).await }
-                                            }),
-                                        )
-                                        .with_upgrades();
-
-                                    let mut conn = std::pin::pin!(conn);
-
-                                    tokio::select! {
-                                        result = conn.as_mut() => {
-                                            let _ = result;
-                                        }
-                                        _ = shutdown_rx.changed() => {
-                                            conn.as_mut().graceful_shutdown();
-                                            let _ = conn.await;
+                                    reason = close => {
+                                        closed_info.close_reason.set(reason).ok();
+                                        conn.as_mut().graceful_shutdown();
+                                        let _ = conn.await;
+                                    }
+                                }
+                            } else {
+                                let mut builder = http1::Builder::new();
+                                if let Some(timeout) = header_read_timeout {
+                                    builder.timer(TokioTimer::new()).header_read_timeout(timeout);
+                                }
+                                let conn = builder.serve_connection(io, service).with_upgrades();
+
+                                let mut conn = std::pin::pin!(conn);
+
+                                tokio::select! {
+                                    result = conn.as_mut() => {
+                                        if result.is_err_and(|e| e.is_timeout()) {
+                                            closed_info
+                                                .close_reason
+                                                .set(CloseReason::SlowClient)
+                                                .ok();
                                         }
                                     }
+                                    reason = close => {
+                                        closed_info.close_reason.set(reason).ok();
+                                        conn.as_mut().graceful_shutdown();
+                                        let _ = conn.await;
+                                    }
                                 }
+                            }
 
-                                // Decrement active connections when done
-                                active_connections.fetch_sub(1, Ordering::Relaxed);
-                            });
-                        }
-                        Err(_) => {}
+                            // Decrement active connections when done
+                            active_connections.fetch_sub(1, Ordering::Relaxed);
+
+                            if let Some(metrics) = &closed_app.metrics {
+                                metrics.connection_closed(&ConnectionStats {
+                                    peer: closed_info.peer,
+                                    requests: closed_info.requests.load(Ordering::Relaxed),
+                                    duration: closed_info.opened.elapsed(),
+                                    close_reason: closed_info
+                                        .close_reason
+                                        .get()
+                                        .copied()
+                                        .unwrap_or(CloseReason::Peer),
+                                });
+                            }
+                        });
                     }
                 }
                 _ = shutdown_rx.changed() => {
@@ -366,166 +813,514 @@ impl<S: Send + Sync + 'static> RustApi<S> {
             }
         }
 
+        #[cfg(feature = "websocket")]
+        app.websockets.shutdown(app.websocket_shutdown_grace).await;
+
         Ok(())
     }
 
     async fn handle_request(
-        &self,
+        self: Arc<Self>,
         req: Request<Incoming>,
+        conn: Arc<ConnInfo>,
     ) -> std::result::Result<Response<BoxBody>, Infallible> {
-        let path = req.uri().path().to_string();
+        let received = Instant::now();
+        let connection_request = conn.request_started();
+
         let method = req.method().clone();
+        let hints_stream = (self.early_hints
+            && !conn.http2
+            && req.version() == Version::HTTP_11
+            && !req.headers().contains_key(header::EXPECT))
+        .then(|| Arc::downgrade(&conn.stream));
         let mut rust_req = Req::from_hyper(req);
+        rust_req.set_peer_addr(conn.peer);
+        rust_req
+            .extensions_mut()
+            .insert(EarlyHints::new(hints_stream));
+        let queued = ResponseHeaders::default();
+        rust_req.extensions_mut().insert(queued.clone());
 
         // Set body limit if configured
         rust_req.set_body_limit(self.body_limit);
+        let slow_body = self.min_body_rate.map(|rate| {
+            let tripped = Arc::new(AtomicBool::new(false));
+            rust_req.watch_body_rate(rate, Arc::clone(&tripped));
+            tripped
+        });
+
+        let trace = self.metrics.as_ref().map(|_| {
+            let trace = Arc::new(RequestTrace::default());
+            rust_req.set_trace(Arc::clone(&trace));
+            trace
+        });
+        let parent = self
+            .sampler
+            .as_ref()
+            .and_then(|_| TraceParent::from_headers(rust_req.headers()));
 
         // Extract upgrade future before rust_req is moved
         #[cfg(feature = "websocket")]
         let on_upgrade = rust_req.take_upgrade();
 
-        let response = match &self.router {
-            Some(router) => match router.at(&path) {
-                Ok(matched) => {
-                    let mut params = HashMap::new();
-                    for (key, value) in matched.params.iter() {
-                        params.insert(key.to_string(), value.to_string());
-                    }
-                    rust_req.set_path_params(params);
+        let response = self.route_request(rust_req).await;
 
-                    if let Some(ref error_handler) = self.error_handler {
-                        rust_req.extensions_mut().insert(Arc::clone(error_handler));
+        // Check for WebSocket upgrade
+        #[cfg(feature = "websocket")]
+        let response = {
+            let mut response_mut = response;
+            if let Some(ws_callback) = response_mut.take_ws_callback() {
+                if let Some(upgrade_future) = on_upgrade {
+                    match self.websockets.register(conn.peer.ip()) {
+                        Ok(registration) => {
+                            let metrics = self.metrics.clone();
+                            tokio::task::spawn(async move {
+                                match upgrade_future.await {
+                                    Ok(upgraded) => {
+                                        let ws = crate::websocket::WebSocket::new(
+                                            upgraded,
+                                            registration,
+                                        );
+                                        if let Some(metrics) = &metrics {
+                                            metrics.websocket_opened();
+                                        }
+                                        ws_callback(ws).await;
+                                        if let Some(metrics) = &metrics {
+                                            metrics.websocket_closed();
+                                        }
+                                    }
+                                    Err(_e) => {
+                                        // Upgrade failed
+                                    }
+                                }
+                            });
+                        }
+                        // Refused: answer with the error instead of 101
+                        Err(e) => response_mut = e.into_res(),
                     }
+                }
+            }
+            response_mut
+        };
 
-                    let method_handlers = matched.value;
+        if let (Some(metrics), Some(trace)) = (&self.metrics, trace) {
+            let total = received.elapsed();
+            let queued = trace
+                .handler_started
+                .get()
+                .map_or(total, |started| started.saturating_duration_since(received));
+            let body_read = Duration::from_micros(trace.body_read_micros.load(Ordering::Relaxed));
+            let status = response.status_code().as_u16();
+            let sampled = self.sampler.as_ref().is_none_or(|sampler| {
+                sampler.sample(parent, trace.sample_rate.get().copied(), status, total)
+            });
+            metrics.request_completed(&RequestTimings {
+                method,
+                route: trace.route.get().cloned(),
+                status,
+                queued,
+                body_read,
+                handler: total.saturating_sub(queued).saturating_sub(body_read),
+                total,
+                connection_request,
+                variant: trace.variant.get().cloned(),
+                sampled,
+            });
+        }
 
-                    match method_handlers.get(&method) {
-                        Some((handler, middlewares)) => {
-                            let state = match &self.state {
-                                Some(s) => Arc::clone(s),
-                                None => {
-                                    return Ok(Error::internal("State not initialized")
-                                        .into_res()
-                                        .into_hyper());
-                                }
-                            };
+        let mut response = self.finalize_response(response.into_hyper(), &queued);
+        if !conn.http2 && response.status() != StatusCode::SWITCHING_PROTOCOLS {
+            let slow = slow_body.is_some_and(|tripped| tripped.load(Ordering::Relaxed));
+            let reason = if slow {
+                Some(CloseReason::SlowClient)
+            } else {
+                self.close_after(connection_request)
+            };
+            if let Some(reason) = reason {
+                response
+                    .headers_mut()
+                    .insert(header::CONNECTION, HeaderValue::from_static("close"));
+                conn.close_reason.set(reason).ok();
+            }
+        }
+        conn.request_finished();
+        Ok(response)
+    }
 
-                            // Execute handler with optional timeout
-                            let handler_future = if middlewares.is_empty() {
-                                Box::pin(handler.call(rust_req, state))
-                            } else {
-                                let handler_clone = Arc::clone(handler);
-                                let mut next_fn: Arc<
-                                    dyn Fn(
-                                            Req,
-                                            Arc<S>,
-                                        )
-                                            -> std::pin::Pin<
-                                            Box<dyn std::future::Future<Output = Res> + Send>,
-                                        > + Send
-                                        + Sync,
-                                > = Arc::new(move |req, state| {
-                                    let handler = Arc::clone(&handler_clone);
-                                    Box::pin(async move { handler.call(req, state).await })
-                                });
+    /// Whether an HTTP/1 response should close its connection.
+    fn close_after(&self, connection_request: u64) -> Option<CloseReason> {
+        if self.drain.is_draining() {
+            Some(CloseReason::Drain)
+        } else if self
+            .max_requests_per_connection
+            .is_some_and(|max| connection_request >= max)
+        {
+            Some(CloseReason::MaxRequests)
+        } else {
+            None
+        }
+    }
 
-                                for middleware in middlewares.iter().rev() {
-                                    let middleware_clone = Arc::clone(middleware);
-                                    let inner = Arc::clone(&next_fn);
-                                    let state_for_middleware = Arc::clone(&state);
-
-                                    next_fn = Arc::new(move |req, _state| {
-                                        let mw = Arc::clone(&middleware_clone);
-                                        let inner_clone = Arc::clone(&inner);
-                                        let state_clone = Arc::clone(&state_for_middleware);
-
-                                        Box::pin(async move {
-                                            let next = crate::Next::new(
-                                                inner_clone,
-                                                Arc::clone(&state_clone),
-                                            );
-                                            mw.handle(req, state_clone, next).await
-                                        })
-                                    });
-                                }
+    /// Resolve when a connection should be closed gracefully.
+    ///
+    /// Connections opened while draining are closed per response instead,
+    /// so the drain branch only fires when draining starts later.
+    async fn close_signal(
+        &self,
+        conn: &ConnInfo,
+        shutdown_rx: &mut watch::Receiver<bool>,
+    ) -> CloseReason {
+        let drain_at_open = self.drain.is_draining();
+        let idle = async {
+            match self.idle_timeout {
+                Some(timeout) => conn.idle(timeout).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            _ = shutdown_rx.changed() => CloseReason::Shutdown,
+            _ = self.drain.started(), if !drain_at_open => CloseReason::Drain,
+            _ = idle => CloseReason::IdleTimeout,
+        }
+    }
 
-                                Box::pin(next_fn(rust_req, state))
-                            };
-
-                            // Apply handler timeout if configured
-                            if let Some(timeout) = self.handler_timeout {
-                                match tokio::time::timeout(timeout, handler_future).await {
-                                    Ok(res) => res,
-                                    Err(_) => {
-                                        use crate::IntoRes;
-                                        Error::Custom(format!(
-                                            "Handler timeout after {:?}",
-                                            timeout
-                                        ))
-                                        .into_res()
-                                    }
-                                }
-                            } else {
-                                handler_future.await
-                            }
-                        }
-                        None => {
-                            use crate::IntoRes;
-                            let allowed_methods: Vec<String> = method_handlers
-                                .keys()
-                                .map(|m| m.as_str().to_string())
-                                .collect();
-
-                            let mut response = Error::method_not_allowed(&format!(
-                                "Method {} not allowed. Allowed methods: {}",
-                                method,
-                                allowed_methods.join(", ")
-                            ))
-                            .into_res();
-
-                            response
-                                .headers_mut()
-                                .insert("Allow", allowed_methods.join(", ").parse().unwrap());
-
-                            response
-                        }
-                    }
+    /// Run the request pipeline, with the buffer pool in scope if set.
+    async fn route_request(self: &Arc<Self>, mut req: Req) -> Res {
+        if let Some(proxies) = &self.trusted_proxies {
+            req.set_via_trusted_proxy(proxies.trusts(req.peer_addr().map(|addr| addr.ip())));
+            req.extensions_mut().insert(Arc::clone(proxies));
+        }
+        let context = self.json_panic_log.then(|| PanicContext::from_req(&req));
+        let pipeline = async {
+            match &self.buffer_pool {
+                Some(pool) => Arc::clone(pool).scope(self.pipeline(req)).await,
+                None => self.pipeline(req).await,
+            }
+        };
+        // Handler panics are caught in `dispatch`; this covers pre-routing.
+        let pipeline = AssertUnwindSafe(pipeline).catch_unwind();
+        let outcome = match context {
+            Some(context) => diagnostics::with_request(context, pipeline).await,
+            None => pipeline.await,
+        };
+        outcome.unwrap_or_else(|_| Error::status(500).into_res())
+    }
+
+    /// Run pre-routing middleware, then dispatch.
+    async fn pipeline(self: &Arc<Self>, mut req: Req) -> Res {
+        if self.maintenance_switch.is_enabled() && !self.maintenance.allows(req.path()) {
+            return self.maintenance.response();
+        }
+        match (&self.state, self.pre_routing.is_empty()) {
+            (_, true) => self.dispatch(req).await,
+            (Some(state), false) => {
+                if let Some(routed_methods) = &self.routed_methods {
+                    req.extensions_mut().insert(routed_methods.clone());
+                }
+                let app = Arc::clone(self);
+                let terminal: NextFn<S> = Arc::new(move |req, _state| {
+                    let app = Arc::clone(&app);
+                    Box::pin(async move { app.dispatch(req).await })
+                });
+                middleware::chain(&self.pre_routing, terminal, state)(req, Arc::clone(state)).await
+            }
+            (None, false) => Error::internal("State not initialized").into_res(),
+        }
+    }
+
+    /// Prepare the app for in-process requests (see `TestClient`).
+    pub(crate) fn into_in_process(mut self) -> Arc<Self> {
+        self.build_router();
+        Arc::new(self)
+    }
+
+    /// Handle an in-memory request without a connection.
+    pub(crate) async fn handle_in_process(self: &Arc<Self>, mut req: Req) -> Res {
+        req.extensions_mut().insert(EarlyHints::new(None));
+        let queued = ResponseHeaders::default();
+        req.extensions_mut().insert(queued.clone());
+        req.set_body_limit(self.body_limit);
+        let response = self.route_request(req).await;
+        Res::from_hyper(self.finalize_response(response.into_hyper(), &queued))
+    }
+
+    /// Route a request and run its post-routing middleware and handler.
+    async fn dispatch(&self, mut req: Req) -> Res {
+        let Some(table) = &self.table else {
+            return Error::internal("Router not initialized").into_res();
+        };
+        let version = self.versioning.as_ref().and_then(|v| v.resolve(&mut req));
+        let Some(matched) = table.at(req.path()) else {
+            return Error::not_found("Route not found").into_res();
+        };
+
+        let routes = Arc::clone(matched.value);
+        let params = routes.capture_params(&matched.params);
+        req.set_path_params(params);
+
+        if let Some(ref error_handler) = self.error_handler {
+            req.extensions_mut().insert(Arc::clone(error_handler));
+        }
+
+        let Some((candidates, auto_head)) = routes.handlers(req.method()) else {
+            let allowed_methods = routes.allowed_methods();
+
+            let mut response = Error::method_not_allowed(format!(
+                "Method {} not allowed. Allowed methods: {}",
+                req.method(),
+                allowed_methods.join(", ")
+            ))
+            .into_res();
+
+            response
+                .headers_mut()
+                .insert("Allow", allowed_methods.join(", ").parse().unwrap());
+
+            return response;
+        };
+
+        // First route whose version and guards all match handles the request.
+        let Some(route) = candidates.iter().find(|route| route.accepts(version, &req)) else {
+            return Error::not_found("Route not found").into_res();
+        };
+
+        req.set_matched_route(Arc::clone(&routes.pattern), route.name.clone());
+        if let (None, Some(route_version)) = (version, route.version) {
+            req.extensions_mut().insert(ApiVersion(route_version));
+        }
+
+        let state = match &self.state {
+            Some(s) => Arc::clone(s),
+            None => return Error::internal("State not initialized").into_res(),
+        };
+
+        if let Some(trace) = req.trace() {
+            trace.route.set(Arc::clone(&routes.pattern)).ok();
+            trace.handler_started.set(Instant::now()).ok();
+            if let Some(rate) = route.sample_rate {
+                trace.sample_rate.set(rate).ok();
+            }
+        }
+
+        if let Some(limiter) = &route.limiter {
+            if let Err(retry_after) = limiter.acquire() {
+                let mut res = Error::too_many_requests("Rate limit exceeded").into_res();
+                let secs = retry_after.as_secs_f64().ceil() as u64;
+                res.headers_mut()
+                    .insert(header::RETRY_AFTER, HeaderValue::from(secs.max(1)));
+                return res;
+            }
+        }
+        if route.body_limit.is_some() {
+            req.set_body_limit(route.body_limit);
+        }
+        if self.lenient_content_type || route.lenient_content_type {
+            req.extensions_mut().insert(LenientContentType);
+        }
+        if route.no_compression {
+            req.extensions_mut().insert(NoCompression);
+        }
+        if let Some(types) = &route.json_content_types {
+            req.extensions_mut().insert(types.clone());
+        }
+
+        let needs_context =
+            self.error_reporter.is_some() || self.error_handler.is_some() || self.dev_error_pages();
+        let context =
+            needs_context.then(|| RequestContext::capture(&req, &routes.pattern, self.profile));
+        let user = context.as_ref().map(|context| Arc::clone(&context.user));
+
+        // Execute handler with optional timeout
+        let handler_future = if route.middlewares.is_empty() {
+            if let Some(user) = &user {
+                report::capture_user(&req, user);
+            }
+            route.handler.call(req, state)
+        } else {
+            let handler = Arc::clone(&route.handler);
+            let terminal: NextFn<S> = Arc::new(move |req, state| {
+                let handler = Arc::clone(&handler);
+                if let Some(user) = &user {
+                    report::capture_user(&req, user);
                 }
+                Box::pin(async move { handler.call(req, state).await })
+            });
+            middleware::chain(&route.middlewares, terminal, &state)(req, state)
+        };
+        // A panicking handler gets a 500 instead of dropping the connection.
+        let handler_future = AssertUnwindSafe(handler_future).catch_unwind();
+
+        // Apply handler timeout if configured
+        let outcome = match route.timeout.or(self.handler_timeout) {
+            Some(timeout) => match tokio::time::timeout(timeout, handler_future).await {
+                Ok(outcome) => outcome,
                 Err(_) => {
-                    use crate::IntoRes;
-                    Error::not_found("Route not found").into_res()
+                    Ok(Error::Custom(format!("Handler timeout after {:?}", timeout)).into_res())
                 }
             },
-            None => {
-                use crate::IntoRes;
-                Error::internal("Router not initialized").into_res()
+            None => handler_future.await,
+        };
+        let (mut res, panic) = match outcome {
+            Ok(res) => (res, None),
+            Err(payload) => {
+                let message = report::panic_message(&*payload);
+                let error = if self.profile.is_some_and(|p| p.error_details) {
+                    Error::Custom(format!("Handler panicked: {}", message))
+                } else {
+                    Error::status(500)
+                };
+                (error.into_res(), Some(message))
             }
         };
 
-        // Check for WebSocket upgrade
-        #[cfg(feature = "websocket")]
+        if let Some(context) = context {
+            let status = res.status_code();
+            let report = (self.error_reporter.is_some() && status.is_server_error()).then(|| {
+                let panicked = panic.is_some();
+                let message = panic
+                    .or_else(|| {
+                        res.extensions()
+                            .get::<ErrorMessage>()
+                            .map(|message| message.0.clone())
+                    })
+                    .unwrap_or_else(|| status.to_string());
+                (message, panicked)
+            });
+            res = self.handle_error(res, &context.request);
+            if let (Some(reporter), Some((message, panicked))) = (&self.error_reporter, report) {
+                reporter.report(&context.into_report(status.as_u16(), message, panicked));
+            }
+        }
+
+        if let (Some(versioning), Some(version)) = (&self.versioning, route.version) {
+            versioning.annotate(version, res.headers_mut());
+        }
+        if auto_head {
+            res = res.strip_body();
+        }
+        res
+    }
+
+    /// Whether 5xx errors render the development error page.
+    fn dev_error_pages(&self) -> bool {
+        self.profile
+            .is_some_and(|p| p.environment == Environment::Development && p.error_details)
+    }
+
+    /// Pass the error behind `res`, if any, to the error handler, or render
+    /// the development error page for it.
+    fn handle_error(&self, res: Res, context: &ErrorContext) -> Res {
+        let Some(error) = res
+            .extensions()
+            .get::<RaisedError>()
+            .and_then(RaisedError::take)
+        else {
+            return res;
+        };
+        if let Some(handler) = &self.error_handler {
+            return handler.handle_request(error, context);
+        }
+        let wants_html = context
+            .headers
+            .get(header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .is_some_and(|accept| accept.contains("text/html"));
+        if self.dev_error_pages() && res.status_code().is_server_error() && wants_html {
+            let route_table = self
+                .route_table_path
+                .as_deref()
+                .filter(|_| self.profile_allows(|p| p.debug_endpoints));
+            return dev::error_page(res.status_code(), &error, context, route_table);
+        }
+        res
+    }
+
+    /// Merge queued headers and headers middleware appended to, then
+    /// enforce limits.
+    fn finalize_response(
+        &self,
+        mut response: Response<BoxBody>,
+        queued: &ResponseHeaders,
+    ) -> Response<BoxBody> {
+        if !self.profile_allows(|p| p.error_details)
+            && response.extensions().get::<ErrorMessage>().is_some()
         {
-            let mut response_mut = response;
-            if let Some(ws_callback) = response_mut.take_ws_callback() {
-                if let Some(upgrade_future) = on_upgrade {
-                    tokio::task::spawn(async move {
-                        match upgrade_future.await {
-                            Ok(upgraded) => {
-                                let ws = crate::websocket::WebSocket::new(upgraded);
-                                ws_callback(ws).await;
-                            }
-                            Err(_e) => {
-                                // Upgrade failed
-                            }
-                        }
-                    });
-                }
+            // Terse JSON instead of the message; reporters already received
+            // it. Headers set along the way (CORS, Retry-After, ...) stay.
+            let status = response.status();
+            let (mut parts, _) = response.into_parts();
+            let body = Res::json(&serde_json::json!({
+                "error": status.canonical_reason().unwrap_or_default(),
+                "status": status.as_u16(),
+            }))
+            .into_hyper();
+            parts.headers.remove(header::CONTENT_LENGTH);
+            parts.headers.remove(header::CONTENT_ENCODING);
+            parts.headers.extend(body.headers().clone());
+            response = Response::from_parts(parts, body.into_body());
+        }
+        queued.apply(response.headers_mut());
+        crate::res::merge_vary(response.headers_mut());
+        self.limit_response(response)
+    }
+
+    /// Enforce configured response header and body limits.
+    fn limit_response(&self, response: Response<BoxBody>) -> Response<BoxBody> {
+        let headers = response.headers();
+
+        if let Some(max) = self.max_response_headers {
+            if headers.len() > max {
+                log::error!(
+                    "Response has {} headers, exceeding limit of {}",
+                    headers.len(),
+                    max
+                );
+                return Error::internal("Response exceeds header limit")
+                    .into_res()
+                    .into_hyper();
+            }
+        }
+
+        if let Some(max) = self.max_response_header_bytes {
+            let size: usize = headers
+                .iter()
+                .map(|(name, value)| name.as_str().len() + value.len())
+                .sum();
+            if size > max {
+                log::error!(
+                    "Response headers total {} bytes, exceeding limit of {}",
+                    size,
+                    max
+                );
+                return Error::internal("Response exceeds header limit")
+                    .into_res()
+                    .into_hyper();
             }
-            return Ok(response_mut.into_hyper());
         }
 
-        #[cfg(not(feature = "websocket"))]
-        Ok(response.into_hyper())
+        let Some(limit) = self.response_body_limit else {
+            return response;
+        };
+
+        if let Some(size) = response.body().size_hint().exact() {
+            if size > limit as u64 {
+                log::error!("Response body size {} exceeds limit of {}", size, limit);
+                return Error::internal("Response body exceeds size limit")
+                    .into_res()
+                    .into_hyper();
+            }
+            return response;
+        }
+
+        // Streamed body: headers are already committed, so abort the stream.
+        response.map(|body| {
+            http_body_util::Limited::new(body, limit)
+                .map_err(move |e| {
+                    log::error!("Response body stream aborted: {}", e);
+                    Error::Custom(format!("Response body exceeds limit of {}", limit))
+                })
+                .boxed()
+        })
     }
 }
 
@@ -537,15 +1332,47 @@ where
         Self {
             routes: Vec::new(),
             middlewares: Vec::new(),
+            pre_routing: Vec::new(),
             state: None,
-            router: None,
+            table: None,
+            routed_methods: None,
             error_handler: None,
+            error_reporter: None,
+            json_panic_log: false,
+            lenient_content_type: false,
+            trusted_proxies: None,
+            header_read_timeout: None,
+            min_body_rate: None,
+            metrics: None,
+            sampler: None,
             body_limit: None,
             request_timeout: None,
             handler_timeout: None,
             http2_enabled: false,
+            early_hints: false,
             max_connections: None,
             keep_alive: None,
+            max_requests_per_connection: None,
+            idle_timeout: None,
+            drain: ConnectionDrain::new(),
+            maintenance: Maintenance::default(),
+            maintenance_switch: MaintenanceSwitch::default(),
+            buffer_pool: None,
+            runtime_metrics_interval: None,
+            tokio_console: false,
+            #[cfg(feature = "websocket")]
+            websockets: Arc::default(),
+            websocket_shutdown_grace: Duration::from_secs(10),
+            response_body_limit: None,
+            max_response_headers: None,
+            max_response_header_bytes: None,
+            https_redirect_port: None,
+            https_port: 443,
+            acme_challenge_dir: None,
+            versioning: None,
+            route_table_path: None,
+            profile: Profile::from_env(),
+            signals: None,
         }
     }
 }
@@ -555,10 +1382,10 @@ where
 /// # Example
 ///
 /// ```rust
-/// use rust_api::app;
+/// use rust_api::{Req, app};
 ///
 /// let mut app = app();
-/// app.get("/", |_| async { "Hello" });
+/// app.get("/", |_: Req| async { "Hello" });
 /// ```
 pub fn app() -> RustApi {
     RustApi::new()
@@ -573,35 +1400,111 @@ pub fn app() -> RustApi {
 /// ```rust
 /// use rust_api::{app_with_state, State};
 ///
+/// #[derive(Clone)]
 /// struct AppState {
-///     db: Database,
+///     name: String,
 /// }
 ///
-/// let mut app = app_with_state(AppState { db });
+/// let mut app = app_with_state(AppState { name: "demo".into() });
 /// app.get("/", |State(state): State<AppState>| async move {
-///     // Access state.db
+///     state.name
 /// });
 /// ```
 pub fn app_with_state<S: Send + Sync + 'static>(state: S) -> RustApi<S> {
     RustApi::with_state(state)
 }
 
-async fn shutdown_signal() -> std::io::Result<()> {
-    #[cfg(unix)]
-    {
-        let mut sigterm = signal::unix::signal(signal::unix::SignalKind::terminate())?;
-        let mut sigint = signal::unix::signal(signal::unix::SignalKind::interrupt())?;
+#[cfg(feature = "console")]
+fn start_tokio_console() {
+    console_subscriber::init();
+    log::info!("tokio-console instrumentation enabled");
+}
 
-        tokio::select! {
-            _ = sigterm.recv() => {}
-            _ = sigint.recv() => {}
-        }
+#[cfg(not(feature = "console"))]
+fn start_tokio_console() {
+    log::warn!("tokio_console is set but rust-api was built without the `console` feature");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Next, Res, from_fn};
+
+    async fn list_users(_req: Req) -> Res {
+        Res::text("users")
     }
 
-    #[cfg(not(unix))]
-    {
-        signal::ctrl_c().await?;
+    #[test]
+    fn test_routes_introspection() {
+        let mut app = RustApi::new();
+        app.attach(from_fn(
+            |req: Req, _state: Arc<()>, next: Next<()>| async move { next.run(req).await },
+        ));
+        app.get("/users", list_users).name("users.index");
+
+        let mut api = Router::new();
+        api.post("/items/{id}", |_: Req| async { "ok" });
+        app.nest("/api", api);
+
+        let routes = app.routes();
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].method, Method::GET);
+        assert_eq!(routes[0].pattern, "/users");
+        assert_eq!(routes[0].name.as_deref(), Some("users.index"));
+        assert!(routes[0].handler.ends_with("list_users"));
+        assert_eq!(routes[0].middleware.len(), 1);
+        assert_eq!(routes[1].pattern, "/api/items/{id}");
+        assert_eq!(routes[1].name, None);
     }
 
-    Ok(())
+    #[test]
+    fn test_response_limits() {
+        let mut app = RustApi::new();
+        app.set_response_body_limit(4);
+        app.set_max_response_headers(2);
+
+        let ok = app.limit_response(Res::text("1234").into_hyper());
+        assert_eq!(ok.status(), 200);
+
+        let too_big = app.limit_response(Res::text("12345").into_hyper());
+        assert_eq!(too_big.status(), 500);
+
+        let too_many = app.limit_response(
+            Res::text("ok")
+                .header("x-a", "1")
+                .header("x-b", "2")
+                .into_hyper(),
+        );
+        assert_eq!(too_many.status(), 500);
+    }
+
+    #[tokio::test]
+    async fn test_route_options() {
+        use crate::route::RateLimit;
+        use crate::test::{TestClient, assert_header, assert_status};
+
+        let mut app = RustApi::new();
+        app.set_body_limit(1024);
+        app.get("/export", |_req: Req| async { "ok" })
+            .rate_limit(RateLimit::per_minute(1));
+        app.post("/small", |mut req: Req| async move {
+            req.bytes().await.map(|_| "ok")
+        })
+        .body_limit(4);
+        app.get("/slow", |_req: Req| async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            "done"
+        })
+        .timeout(Duration::from_millis(10));
+
+        let client = TestClient::new(app);
+        assert_status(&client.get("/export").send().await, 200);
+        let limited = client.get("/export").send().await;
+        assert_status(&limited, 429);
+        assert_header(&limited, "retry-after", "60");
+
+        assert_status(&client.post("/small").body("1234").send().await, 200);
+        assert_status(&client.post("/small").body("12345").send().await, 413);
+        assert_status(&client.get("/slow").send().await, 500);
+    }
 }
@@ -0,0 +1,277 @@
+//! Bot mitigation.
+//!
+//! [`BotFilter`] scores requests with simple heuristics: user agents of
+//! known scanners, missing headers that browsers always send, and trap
+//! paths no legitimate client requests. Suspects are tagged with a
+//! [`SuspectedBot`] extension, rejected, or tarpitted (delayed, then
+//! rejected) depending on the configured [`BotAction`]. Trap paths are
+//! always rejected.
+//!
+//! ```rust
+//! use rust_api::{RustApi, bot::{BotAction, BotFilter}};
+//! use std::time::Duration;
+//!
+//! let mut app = RustApi::new();
+//! let filter = BotFilter::new()
+//!     .require_header("accept")
+//!     .trap_path("/wp-login.php")
+//!     .action(BotAction::Tarpit(Duration::from_secs(5)));
+//! let stats = filter.stats();
+//! app.attach_pre_routing(filter);
+//! // later: stats.snapshot()
+//! ```
+
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::{Error, IntoRes, Middleware, Next, Req, Res};
+
+/// User-agent fragments of common scanners, matched case-insensitively.
+const SCANNER_AGENTS: [&str; 8] = [
+    "sqlmap",
+    "nikto",
+    "nmap",
+    "masscan",
+    "zgrab",
+    "nuclei",
+    "dirbuster",
+    "wpscan",
+];
+
+/// What to do with a suspected bot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BotAction {
+    /// Let the request through with a [`SuspectedBot`] extension.
+    Tag,
+    /// Answer 403.
+    Reject,
+    /// Wait, then answer 403, to slow scanners down.
+    Tarpit(Duration),
+}
+
+/// Why a request was taken for a bot, inserted for downstream handlers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuspectedBot {
+    /// Heuristics that matched, e.g. `user-agent:sqlmap`.
+    pub reasons: Vec<String>,
+}
+
+// Indexes into `BotStats`.
+const PASSED: usize = 0;
+const TAGGED: usize = 1;
+const REJECTED: usize = 2;
+const TARPITTED: usize = 3;
+
+/// Decision counters shared by a [`BotFilter`] and its clones.
+#[derive(Debug, Clone, Default)]
+pub struct BotStats(Arc<[AtomicU64; 4]>);
+
+/// Point-in-time copy of [`BotStats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct BotStatsSnapshot {
+    /// Requests let through unsuspected.
+    pub passed: u64,
+    /// Suspects let through with a tag.
+    pub tagged: u64,
+    /// Suspects answered 403 immediately.
+    pub rejected: u64,
+    /// Suspects delayed before 403.
+    pub tarpitted: u64,
+}
+
+impl BotStats {
+    fn record(&self, decision: usize) {
+        self.0[decision].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Read the current counts.
+    pub fn snapshot(&self) -> BotStatsSnapshot {
+        let [passed, tagged, rejected, tarpitted] =
+            std::array::from_fn(|i| self.0[i].load(Ordering::Relaxed));
+        BotStatsSnapshot {
+            passed,
+            tagged,
+            rejected,
+            tarpitted,
+        }
+    }
+}
+
+/// Pre-routing middleware detecting likely bots.
+#[derive(Debug, Clone)]
+pub struct BotFilter {
+    agents: Vec<String>,
+    block_empty_agent: bool,
+    required_headers: Vec<String>,
+    trap_paths: Vec<String>,
+    action: BotAction,
+    stats: BotStats,
+}
+
+impl BotFilter {
+    /// Flag known scanner user agents and requests without one; tag
+    /// suspects.
+    pub fn new() -> Self {
+        Self {
+            agents: SCANNER_AGENTS
+                .iter()
+                .map(|agent| agent.to_string())
+                .collect(),
+            block_empty_agent: true,
+            required_headers: Vec::new(),
+            trap_paths: Vec::new(),
+            action: BotAction::Tag,
+            stats: BotStats::default(),
+        }
+    }
+
+    /// Also flag user agents containing `fragment` (case-insensitive).
+    pub fn block_user_agent(mut self, fragment: &str) -> Self {
+        self.agents.push(fragment.to_ascii_lowercase());
+        self
+    }
+
+    /// Whether a missing or empty `User-Agent` is suspicious (default true).
+    pub fn block_empty_user_agent(mut self, enabled: bool) -> Self {
+        self.block_empty_agent = enabled;
+        self
+    }
+
+    /// Flag requests without header `name`.
+    pub fn require_header(mut self, name: &str) -> Self {
+        self.required_headers.push(name.to_ascii_lowercase());
+        self
+    }
+
+    /// Reject every request to `path`, e.g. `/wp-login.php` or `/.env`.
+    pub fn trap_path(mut self, path: &str) -> Self {
+        self.trap_paths.push(path.to_string());
+        self
+    }
+
+    /// What to do with suspects (default [`BotAction::Tag`]).
+    pub fn action(mut self, action: BotAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// Counters for this filter's decisions.
+    pub fn stats(&self) -> BotStats {
+        self.stats.clone()
+    }
+
+    /// Heuristics matching `req`.
+    fn reasons(&self, req: &Req) -> Vec<String> {
+        let mut reasons = Vec::new();
+        let agent = req.header("user-agent").unwrap_or("").to_ascii_lowercase();
+        if agent.trim().is_empty() {
+            if self.block_empty_agent {
+                reasons.push("user-agent:missing".to_string());
+            }
+        } else if let Some(fragment) = self.agents.iter().find(|f| agent.contains(f.as_str())) {
+            reasons.push(format!("user-agent:{}", fragment));
+        }
+        for name in &self.required_headers {
+            if req.header(name).is_none_or(|value| value.trim().is_empty()) {
+                reasons.push(format!("missing-header:{}", name));
+            }
+        }
+        reasons
+    }
+}
+
+impl Default for BotFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> Middleware<S> for BotFilter {
+    async fn handle(&self, mut req: Req, _state: Arc<S>, next: Next<S>) -> Res {
+        let trapped = self.trap_paths.iter().any(|path| path == req.path());
+        let reasons = self.reasons(&req);
+        if reasons.is_empty() && !trapped {
+            self.stats.record(PASSED);
+            return next.run(req).await;
+        }
+        match self.action {
+            BotAction::Tag if !trapped => {
+                self.stats.record(TAGGED);
+                req.extensions_mut().insert(SuspectedBot { reasons });
+                next.run(req).await
+            }
+            BotAction::Tarpit(delay) => {
+                self.stats.record(TARPITTED);
+                tokio::time::sleep(delay).await;
+                Error::forbidden("Forbidden").into_res()
+            }
+            _ => {
+                self.stats.record(REJECTED);
+                Error::forbidden("Forbidden").into_res()
+            }
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "BotFilter"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RustApi;
+    use crate::test::{TestClient, assert_status, body_text};
+
+    #[tokio::test]
+    async fn test_bot_filter() {
+        let mut app = RustApi::new();
+        let filter = BotFilter::new().require_header("accept").trap_path("/.env");
+        let stats = filter.stats();
+        app.attach_pre_routing(filter);
+        app.get("/", |req: Req| async move {
+            match req.extensions().get::<SuspectedBot>() {
+                Some(bot) => bot.reasons.join(","),
+                None => "human".to_string(),
+            }
+        });
+        let client = TestClient::new(app);
+
+        let res = client
+            .get("/")
+            .header("user-agent", "Mozilla/5.0")
+            .header("accept", "text/html")
+            .send()
+            .await;
+        assert_eq!(body_text(res).await, "human");
+        let res = client
+            .get("/")
+            .header("user-agent", "sqlmap/1.7")
+            .send()
+            .await;
+        assert_eq!(
+            body_text(res).await,
+            "user-agent:sqlmap,missing-header:accept"
+        );
+        let res = client
+            .get("/.env")
+            .header("user-agent", "Mozilla/5.0")
+            .header("accept", "*/*")
+            .send()
+            .await;
+        assert_status(&res, 403);
+
+        assert_eq!(
+            stats.snapshot(),
+            BotStatsSnapshot {
+                passed: 1,
+                tagged: 1,
+                rejected: 1,
+                tarpitted: 0,
+            }
+        );
+    }
+}
@@ -0,0 +1,445 @@
+//! Shared response cache.
+//!
+//! [`ResponseCache`] keeps `GET` responses in memory for as long as their
+//! `Cache-Control` allows (`s-maxage`, else `max-age`), and honours the
+//! RFC 5861 extensions:
+//!
+//! - `stale-while-revalidate`: a stale copy is served immediately while
+//!   one background request refreshes it.
+//! - `stale-if-error`: a stale copy is served when refreshing fails or
+//!   returns a 5xx.
+//!
+//! Defaults for both can be set for responses that do not say. Entries are
+//! keyed by scheme, host and path. Responses carry `Age` and `X-Cache`
+//! (`HIT`, `STALE` or `MISS`). Requests with `Authorization` and responses
+//! that are `private`, `no-store`, `no-cache`, set cookies or `Vary: *`
+//! are never cached; other `Vary` headers are honoured.
+//!
+//! ```rust
+//! use rust_api::{CacheControl, Req, Res, RustApi, cache::ResponseCache};
+//! use std::time::Duration;
+//!
+//! let mut app = RustApi::new();
+//! app.attach(ResponseCache::new().stale_if_error(Duration::from_secs(300)));
+//! app.get("/catalog", |_req: Req| async {
+//!     Res::text("catalog")
+//!         .cache_control(CacheControl::public().max_age(60).stale_while_revalidate(30))
+//! });
+//! ```
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Body;
+use hyper::header::{self, HeaderMap, HeaderName, HeaderValue};
+use hyper::{Method, StatusCode};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{IntoRes, Middleware, Next, Req, Res};
+
+/// Stored response.
+struct Entry {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+    stored: Instant,
+    fresh: Duration,
+    stale_while_revalidate: Duration,
+    stale_if_error: Duration,
+    /// Request header values the response varies on.
+    vary: Vec<(HeaderName, Option<HeaderValue>)>,
+}
+
+impl Entry {
+    fn matches(&self, req: &Req) -> bool {
+        self.vary
+            .iter()
+            .all(|(name, value)| req.headers().get(name) == value.as_ref())
+    }
+
+    /// Time past freshness, or `None` while fresh.
+    fn staleness(&self) -> Option<Duration> {
+        self.stored.elapsed().checked_sub(self.fresh)
+    }
+
+    fn to_res(&self, status: &'static str) -> Res {
+        let mut res =
+            hyper::Response::new(Full::new(self.body.clone()).map_err(|e| match e {}).boxed());
+        *res.status_mut() = self.status;
+        *res.headers_mut() = self.headers.clone();
+        let headers = res.headers_mut();
+        headers.insert(
+            header::AGE,
+            HeaderValue::from(self.stored.elapsed().as_secs()),
+        );
+        headers.insert(
+            HeaderName::from_static("x-cache"),
+            HeaderValue::from_static(status),
+        );
+        Res::from_hyper(res)
+    }
+}
+
+#[derive(Default)]
+struct Store {
+    entries: HashMap<String, Arc<Entry>>,
+    refreshing: HashSet<String>,
+}
+
+/// In-memory shared cache middleware.
+#[derive(Clone)]
+pub struct ResponseCache {
+    store: Arc<Mutex<Store>>,
+    max_entries: usize,
+    max_body_size: u64,
+    stale_while_revalidate: Duration,
+    stale_if_error: Duration,
+}
+
+impl ResponseCache {
+    /// Cache up to 1024 responses of up to 1 MiB each.
+    pub fn new() -> Self {
+        Self {
+            store: Arc::default(),
+            max_entries: 1024,
+            max_body_size: 1024 * 1024,
+            stale_while_revalidate: Duration::ZERO,
+            stale_if_error: Duration::ZERO,
+        }
+    }
+
+    /// Maximum number of stored responses; the oldest is evicted first.
+    pub fn max_entries(mut self, max: usize) -> Self {
+        self.max_entries = max;
+        self
+    }
+
+    /// Largest body stored, in bytes.
+    pub fn max_body_size(mut self, bytes: u64) -> Self {
+        self.max_body_size = bytes;
+        self
+    }
+
+    /// `stale-while-revalidate` for responses that do not set it.
+    pub fn stale_while_revalidate(mut self, window: Duration) -> Self {
+        self.stale_while_revalidate = window;
+        self
+    }
+
+    /// `stale-if-error` for responses that do not set it.
+    pub fn stale_if_error(mut self, window: Duration) -> Self {
+        self.stale_if_error = window;
+        self
+    }
+
+    /// Drop every stored response.
+    pub fn clear(&self) {
+        self.store.lock().unwrap().entries.clear();
+    }
+
+    fn lookup(&self, key: &str, req: &Req) -> Option<Arc<Entry>> {
+        let store = self.store.lock().unwrap();
+        store
+            .entries
+            .get(key)
+            .filter(|entry| entry.matches(req))
+            .cloned()
+    }
+
+    /// Buffer `res` and store it if cacheable, returning it either way.
+    async fn store(&self, key: String, req_headers: &HeaderMap, res: Res) -> Res {
+        let Some(policy) = self.policy(&res) else {
+            return res;
+        };
+        let Some(vary) = vary_headers(res.headers(), req_headers) else {
+            return res;
+        };
+        let (parts, body) = res.into_hyper().into_parts();
+        let size = body.size_hint().exact();
+        if size.is_none_or(|len| len > self.max_body_size) {
+            return Res::from_hyper(hyper::Response::from_parts(parts, body));
+        }
+        let body = match body.collect().await {
+            Ok(collected) => collected.to_bytes(),
+            Err(e) => return e.into_res(),
+        };
+        let entry = Entry {
+            status: parts.status,
+            headers: parts.headers.clone(),
+            body: body.clone(),
+            stored: Instant::now(),
+            fresh: policy.0,
+            stale_while_revalidate: policy.1,
+            stale_if_error: policy.2,
+            vary,
+        };
+        let mut store = self.store.lock().unwrap();
+        if !store.entries.contains_key(&key) && store.entries.len() >= self.max_entries {
+            let oldest = store
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.stored)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                store.entries.remove(&oldest);
+            }
+        }
+        store.entries.insert(key, Arc::new(entry));
+        drop(store);
+
+        let mut res =
+            hyper::Response::from_parts(parts, Full::new(body).map_err(|e| match e {}).boxed());
+        res.headers_mut().insert(
+            HeaderName::from_static("x-cache"),
+            HeaderValue::from_static("MISS"),
+        );
+        Res::from_hyper(res)
+    }
+
+    /// Freshness and stale windows of a cacheable response.
+    fn policy(&self, res: &Res) -> Option<(Duration, Duration, Duration)> {
+        if !matches!(res.status_code().as_u16(), 200 | 203 | 301 | 404 | 410)
+            || res.headers().contains_key(header::SET_COOKIE)
+        {
+            return None;
+        }
+        let directives = directives(res.headers());
+        if directives
+            .iter()
+            .any(|(name, _)| matches!(name.as_str(), "private" | "no-store" | "no-cache"))
+        {
+            return None;
+        }
+        let seconds = |name: &str| {
+            directives
+                .iter()
+                .find(|(directive, _)| directive == name)
+                .and_then(|(_, value)| value.as_deref()?.parse().ok())
+                .map(Duration::from_secs)
+        };
+        let fresh = seconds("s-maxage").or_else(|| seconds("max-age"))?;
+        Some((
+            fresh,
+            seconds("stale-while-revalidate").unwrap_or(self.stale_while_revalidate),
+            seconds("stale-if-error").unwrap_or(self.stale_if_error),
+        ))
+    }
+
+    /// Refresh `key` in the background unless a refresh is running.
+    fn revalidate<S: Send + Sync + 'static>(&self, key: String, req: Req, next: Next<S>) {
+        if !self.store.lock().unwrap().refreshing.insert(key.clone()) {
+            return;
+        }
+        let refreshing = Refreshing {
+            store: Arc::clone(&self.store),
+            key: key.clone(),
+        };
+        let cache = self.clone();
+        tokio::spawn(async move {
+            let _refreshing = refreshing;
+            let headers = req.headers().clone();
+            let res = next.run(req).await;
+            if !res.status_code().is_server_error() {
+                cache.store(key, &headers, res).await;
+            }
+        });
+    }
+}
+
+/// Marks a key as refreshing until dropped, even if the refresh panics.
+struct Refreshing {
+    store: Arc<Mutex<Store>>,
+    key: String,
+}
+
+impl Drop for Refreshing {
+    fn drop(&mut self) {
+        self.store.lock().unwrap().refreshing.remove(&self.key);
+    }
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `Cache-Control` directives as lowercase `(name, value)` pairs.
+fn directives(headers: &HeaderMap) -> Vec<(String, Option<String>)> {
+    headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|directive| match directive.split_once('=') {
+            Some((name, value)) => (
+                name.trim().to_ascii_lowercase(),
+                Some(value.trim().trim_matches('"').to_string()),
+            ),
+            None => (directive.trim().to_ascii_lowercase(), None),
+        })
+        .collect()
+}
+
+/// Request header values named by the response's `Vary`; `None` for
+/// `Vary: *`.
+fn vary_headers(
+    res_headers: &HeaderMap,
+    req_headers: &HeaderMap,
+) -> Option<Vec<(HeaderName, Option<HeaderValue>)>> {
+    let mut vary = Vec::new();
+    for value in res_headers.get_all(header::VARY) {
+        for name in value.to_str().unwrap_or("").split(',') {
+            let name = name.trim();
+            if name == "*" {
+                return None;
+            }
+            if let Ok(name) = HeaderName::from_bytes(name.as_bytes()) {
+                let value = req_headers.get(&name).cloned();
+                vary.push((name, value));
+            }
+        }
+    }
+    Some(vary)
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> Middleware<S> for ResponseCache {
+    async fn handle(&self, req: Req, _state: Arc<S>, next: Next<S>) -> Res {
+        if req.method() != Method::GET || req.headers().contains_key(header::AUTHORIZATION) {
+            return next.run(req).await;
+        }
+        let request_directives = directives(req.headers());
+        let no_cache = request_directives
+            .iter()
+            .any(|(name, _)| name == "no-cache" || name == "no-store");
+        let no_store = request_directives
+            .iter()
+            .any(|(name, _)| name == "no-store");
+        let key = req.full_url().unwrap_or_else(|| req.uri().to_string());
+        let entry = self.lookup(&key, &req).filter(|_| !no_cache);
+
+        if let Some(entry) = &entry {
+            match entry.staleness() {
+                None => return entry.to_res("HIT"),
+                Some(stale) if stale < entry.stale_while_revalidate => {
+                    self.revalidate(key, req.replay(), next);
+                    return entry.to_res("STALE");
+                }
+                Some(_) => {}
+            }
+        }
+
+        let headers = req.headers().clone();
+        let res = next.run(req).await;
+        if let Some(entry) = entry {
+            let usable = entry
+                .staleness()
+                .is_some_and(|stale| stale < entry.stale_if_error);
+            if usable && res.status_code().is_server_error() {
+                return entry.to_res("STALE");
+            }
+        }
+        if no_store {
+            return res;
+        }
+        self.store(key, &headers, res).await
+    }
+
+    fn name(&self) -> &'static str {
+        "ResponseCache"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{TestClient, assert_header, body_text};
+    use crate::{CacheControl, RustApi};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_stale_while_revalidate() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&hits);
+        let mut app = RustApi::new();
+        app.attach(ResponseCache::new());
+        app.get("/count", move |_req: Req| {
+            let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            async move {
+                Res::text(n.to_string())
+                    .cache_control(CacheControl::public().max_age(1).stale_while_revalidate(60))
+            }
+        });
+        let client = TestClient::new(app);
+
+        let res = client.get("/count").send().await;
+        assert_header(&res, "x-cache", "MISS");
+        let res = client.get("/count").send().await;
+        assert_header(&res, "x-cache", "HIT");
+        assert_eq!(body_text(res).await, "1");
+
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        let res = client.get("/count").send().await;
+        assert_header(&res, "x-cache", "STALE");
+        assert_eq!(body_text(res).await, "1");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let res = client.get("/count").send().await;
+        assert_header(&res, "x-cache", "HIT");
+        assert_eq!(body_text(res).await, "2");
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_stale_if_error() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&hits);
+        let mut app = RustApi::new();
+        app.attach(ResponseCache::new().stale_if_error(Duration::from_secs(60)));
+        app.get("/flaky", move |_req: Req| {
+            let first = counter.fetch_add(1, Ordering::SeqCst) == 0;
+            async move {
+                if first {
+                    Res::text("ok").cache_control(CacheControl::public().max_age(1))
+                } else {
+                    Res::status(503)
+                }
+            }
+        });
+        let client = TestClient::new(app);
+
+        client.get("/flaky").send().await;
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        let res = client.get("/flaky").send().await;
+        assert_header(&res, "x-cache", "STALE");
+        assert_eq!(body_text(res).await, "ok");
+        let res = client
+            .get("/flaky")
+            .header("cache-control", "no-cache")
+            .send()
+            .await;
+        assert_eq!(res.status_code().as_u16(), 503);
+    }
+
+    #[tokio::test]
+    async fn test_keyed_by_host() {
+        let mut app = RustApi::new();
+        app.attach(ResponseCache::new());
+        app.get("/", |req: Req| async move {
+            Res::text(req.host().unwrap_or_default().to_string())
+                .cache_control(CacheControl::public().max_age(60))
+        });
+        let client = TestClient::new(app);
+
+        let res = client.get("/").header("host", "a.example").send().await;
+        assert_eq!(body_text(res).await, "a.example");
+        let res = client.get("/").header("host", "b.example").send().await;
+        assert_header(&res, "x-cache", "MISS");
+        assert_eq!(body_text(res).await, "b.example");
+        let res = client.get("/").header("host", "a.example").send().await;
+        assert_header(&res, "x-cache", "HIT");
+        assert_eq!(body_text(res).await, "a.example");
+    }
+}
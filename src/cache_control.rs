@@ -0,0 +1,171 @@
+//! `Cache-Control` response directives.
+//!
+//! ```rust
+//! use rust_api::{CacheControl, Res};
+//!
+//! let res = Res::text("catalog").cache_control(
+//!     CacheControl::public().max_age(300).stale_while_revalidate(60),
+//! );
+//! assert_eq!(
+//!     res.headers()["cache-control"],
+//!     "public, max-age=300, stale-while-revalidate=60"
+//! );
+//! ```
+
+use std::fmt;
+
+/// Builder for a `Cache-Control` header value.
+///
+/// Durations are in seconds. Directives are written in a fixed order, so
+/// equal builders produce equal headers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheControl {
+    public: bool,
+    private: bool,
+    no_cache: bool,
+    no_store: bool,
+    max_age: Option<u64>,
+    s_maxage: Option<u64>,
+    stale_while_revalidate: Option<u64>,
+    stale_if_error: Option<u64>,
+    must_revalidate: bool,
+    proxy_revalidate: bool,
+    no_transform: bool,
+    immutable: bool,
+}
+
+impl CacheControl {
+    /// No directives.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cacheable by browsers and shared caches (`public`).
+    pub fn public() -> Self {
+        Self {
+            public: true,
+            ..Self::default()
+        }
+    }
+
+    /// Cacheable by the browser only (`private`).
+    pub fn private() -> Self {
+        Self {
+            private: true,
+            ..Self::default()
+        }
+    }
+
+    /// Never store the response (`no-store`).
+    pub fn no_store() -> Self {
+        Self {
+            no_store: true,
+            ..Self::default()
+        }
+    }
+
+    /// Store, but revalidate before every reuse (`no-cache`).
+    pub fn no_cache(mut self) -> Self {
+        self.no_cache = true;
+        self
+    }
+
+    /// Fresh for `seconds` (`max-age`).
+    pub fn max_age(mut self, seconds: u64) -> Self {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    /// Fresh in shared caches for `seconds`, overriding `max-age` there
+    /// (`s-maxage`).
+    pub fn s_maxage(mut self, seconds: u64) -> Self {
+        self.s_maxage = Some(seconds);
+        self
+    }
+
+    /// Serve stale for `seconds` while revalidating in the background
+    /// (`stale-while-revalidate`).
+    pub fn stale_while_revalidate(mut self, seconds: u64) -> Self {
+        self.stale_while_revalidate = Some(seconds);
+        self
+    }
+
+    /// Serve stale for `seconds` when the origin errors (`stale-if-error`).
+    pub fn stale_if_error(mut self, seconds: u64) -> Self {
+        self.stale_if_error = Some(seconds);
+        self
+    }
+
+    /// Never serve stale without revalidating (`must-revalidate`).
+    pub fn must_revalidate(mut self) -> Self {
+        self.must_revalidate = true;
+        self
+    }
+
+    /// Like `must-revalidate`, for shared caches only (`proxy-revalidate`).
+    pub fn proxy_revalidate(mut self) -> Self {
+        self.proxy_revalidate = true;
+        self
+    }
+
+    /// Forbid intermediaries from altering the body (`no-transform`).
+    pub fn no_transform(mut self) -> Self {
+        self.no_transform = true;
+        self
+    }
+
+    /// Never changes while fresh, e.g. fingerprinted assets (`immutable`).
+    pub fn immutable(mut self) -> Self {
+        self.immutable = true;
+        self
+    }
+}
+
+impl fmt::Display for CacheControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flag = |set: bool, name: &str| set.then(|| name.to_string());
+        let seconds = |value: Option<u64>, name: &str| value.map(|v| format!("{}={}", name, v));
+        let directives = [
+            flag(self.public, "public"),
+            flag(self.private, "private"),
+            flag(self.no_cache, "no-cache"),
+            flag(self.no_store, "no-store"),
+            seconds(self.max_age, "max-age"),
+            seconds(self.s_maxage, "s-maxage"),
+            seconds(self.stale_while_revalidate, "stale-while-revalidate"),
+            seconds(self.stale_if_error, "stale-if-error"),
+            flag(self.must_revalidate, "must-revalidate"),
+            flag(self.proxy_revalidate, "proxy-revalidate"),
+            flag(self.no_transform, "no-transform"),
+            flag(self.immutable, "immutable"),
+        ];
+        let directives: Vec<String> = directives.into_iter().flatten().collect();
+        f.write_str(&directives.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directive_order() {
+        let cc = CacheControl::public()
+            .immutable()
+            .max_age(31_536_000)
+            .no_transform();
+        assert_eq!(
+            cc.to_string(),
+            "public, max-age=31536000, no-transform, immutable"
+        );
+        assert_eq!(
+            CacheControl::private()
+                .no_cache()
+                .must_revalidate()
+                .to_string(),
+            "private, no-cache, must-revalidate"
+        );
+        assert_eq!(CacheControl::no_store().to_string(), "no-store");
+        assert_eq!(CacheControl::new().to_string(), "");
+    }
+}
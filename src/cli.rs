@@ -0,0 +1,280 @@
+//! Command-line runner.
+//!
+//! Parses the common server flags and runs the application, so binaries
+//! don't have to hand-roll argument handling.
+//!
+//! ```rust,no_run
+//! use rust_api::{Req, Res, RustApi, cli};
+//!
+//! fn main() -> rust_api::Result<()> {
+//!     let mut app = RustApi::new();
+//!     app.get("/", |_: Req| async { Res::text("Hello") });
+//!     cli::run(app)
+//! }
+//! ```
+//!
+//! Supported usage:
+//!
+//! ```text
+//! app [serve] [--host <ip>] [--port <port>] [--workers <n>] [--config <file>] [--log-level <level>]
+//! app routes
+//! ```
+//!
+//! `--log-level` sets `log`'s maximum level. Records go to the logger the
+//! application installed before calling [`run`]; without one, they are
+//! written to stderr.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use log::LevelFilter;
+
+use crate::{Error, Result, RustApi, ServerConfig};
+
+const USAGE: &str = "\
+Usage: <app> [COMMAND] [OPTIONS]
+
+Commands:
+  serve    Start the HTTP server (default)
+  routes   Print the registered route table
+
+Options:
+  --host <ip>          Address to bind (default: 127.0.0.1)
+  --port <port>        Port to bind (default: 3000)
+  --workers <n>        Number of runtime worker threads
+  --config <file>      Load server configuration from a TOML file
+  --log-level <level>  Maximum log level (off, error, warn, info, debug, trace)
+  -h, --help           Print this help
+";
+
+/// Fallback logger for `--log-level` when the application has none.
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        if self.enabled(record.metadata()) {
+            eprintln!(
+                "{:<5} {}: {}",
+                record.level(),
+                record.target(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Subcommand selected on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Start the HTTP server.
+    Serve,
+    /// Print the registered route table and exit.
+    Routes,
+    /// Print usage and exit.
+    Help,
+}
+
+/// Parsed command-line options.
+#[derive(Debug, Clone)]
+pub struct Cli {
+    /// Selected subcommand.
+    pub command: Command,
+    /// Address to bind.
+    pub host: IpAddr,
+    /// Port to bind.
+    pub port: u16,
+    /// Runtime worker threads (defaults to the tokio default).
+    pub workers: Option<usize>,
+    /// Path to a TOML configuration file.
+    pub config: Option<PathBuf>,
+    /// Maximum log level; installs a stderr logger if none is set.
+    pub log_level: Option<LevelFilter>,
+}
+
+impl Default for Cli {
+    fn default() -> Self {
+        Self {
+            command: Command::Serve,
+            host: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 3000,
+            workers: None,
+            config: None,
+            log_level: None,
+        }
+    }
+}
+
+impl Cli {
+    /// Parse options from the process arguments.
+    pub fn parse() -> Result<Self> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    /// Parse options from an argument list (excluding the program name).
+    pub fn parse_from<I, T>(args: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        let mut cli = Cli::default();
+        let mut args = args.into_iter().map(Into::into);
+
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => {
+                    (flag.to_string(), Some(value.to_string()))
+                }
+                _ => (arg, None),
+            };
+
+            let mut value = |name: &str| {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| Error::Custom(format!("Missing value for {}", name)))
+            };
+
+            match flag.as_str() {
+                "serve" => cli.command = Command::Serve,
+                "routes" => cli.command = Command::Routes,
+                "-h" | "--help" | "help" => cli.command = Command::Help,
+                "--host" => cli.host = parse_value("--host", &value("--host")?)?,
+                "--port" => cli.port = parse_value("--port", &value("--port")?)?,
+                "--workers" => {
+                    let workers: usize = parse_value("--workers", &value("--workers")?)?;
+                    if workers == 0 {
+                        return Err(Error::Custom("--workers must be at least 1".into()));
+                    }
+                    cli.workers = Some(workers);
+                }
+                "--config" => cli.config = Some(PathBuf::from(value("--config")?)),
+                "--log-level" => {
+                    cli.log_level = Some(parse_value("--log-level", &value("--log-level")?)?)
+                }
+                other => {
+                    return Err(Error::Custom(format!(
+                        "Unknown argument: {}\n\n{}",
+                        other, USAGE
+                    )));
+                }
+            }
+        }
+
+        Ok(cli)
+    }
+
+    /// Socket address built from `--host` and `--port`.
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::new(self.host, self.port)
+    }
+
+    /// Run the selected command against the application.
+    ///
+    /// Builds its own tokio runtime, so call this from a synchronous `main`.
+    pub fn run<S: Send + Sync + 'static>(self, mut app: RustApi<S>) -> Result<()> {
+        if let Some(level) = self.log_level {
+            // Fails when the application installed its own logger.
+            log::set_logger(&StderrLogger).ok();
+            log::set_max_level(level);
+        }
+
+        match self.command {
+            Command::Help => {
+                print!("{}", USAGE);
+                Ok(())
+            }
+            Command::Routes => {
+                print!("{}", format_routes(&app));
+                Ok(())
+            }
+            Command::Serve => {
+                if let Some(path) = &self.config {
+                    app.apply_config(ServerConfig::from_file(path)?);
+                }
+
+                let mut builder = tokio::runtime::Builder::new_multi_thread();
+                if let Some(workers) = self.workers {
+                    builder.worker_threads(workers);
+                }
+                let runtime = builder.enable_all().build()?;
+
+                let addr = self.addr();
+                log::info!("Listening on http://{}", addr);
+                runtime.block_on(app.listen(addr))
+            }
+        }
+    }
+}
+
+/// Parse process arguments and run the application.
+pub fn run<S: Send + Sync + 'static>(app: RustApi<S>) -> Result<()> {
+    Cli::parse()?.run(app)
+}
+
+fn parse_value<T: FromStr>(name: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| Error::Custom(format!("Invalid value for {}: {}", name, value)))
+}
+
+fn format_routes<S: Send + Sync + 'static>(app: &RustApi<S>) -> String {
+    let mut out = String::new();
+    for route in app.routes() {
+        out.push_str(&format!("{:<8}{}", route.method.as_str(), route.pattern));
+        if let Some(name) = &route.name {
+            out.push_str(&format!("  ({})", name));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults() {
+        let cli = Cli::parse_from(Vec::<String>::new()).unwrap();
+        assert_eq!(cli.command, Command::Serve);
+        assert_eq!(cli.addr(), SocketAddr::from(([127, 0, 0, 1], 3000)));
+    }
+
+    #[test]
+    fn test_flags() {
+        let cli = Cli::parse_from([
+            "--host",
+            "0.0.0.0",
+            "--port=8080",
+            "--workers",
+            "4",
+            "--log-level",
+            "debug",
+        ])
+        .unwrap();
+        assert_eq!(cli.addr(), SocketAddr::from(([0, 0, 0, 0], 8080)));
+        assert_eq!(cli.workers, Some(4));
+        assert_eq!(cli.log_level, Some(LevelFilter::Debug));
+    }
+
+    #[test]
+    fn test_routes_command() {
+        let cli = Cli::parse_from(["routes"]).unwrap();
+        assert_eq!(cli.command, Command::Routes);
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        assert!(Cli::parse_from(["--port", "http"]).is_err());
+        assert!(Cli::parse_from(["--workers", "0"]).is_err());
+        assert!(Cli::parse_from(["--port"]).is_err());
+        assert!(Cli::parse_from(["--verbose"]).is_err());
+    }
+}
@@ -0,0 +1,401 @@
+//! Response compression.
+//!
+//! [`Compression`] gzip- or deflate-encodes responses the client accepts.
+//! Buffered bodies are compressed in one piece; streamed bodies are
+//! compressed frame by frame, flushing after each frame so a chunk written
+//! by the handler reaches the client without waiting for more data.
+//!
+//! Server-sent events, WebSocket upgrades, already encoded bodies and
+//! `Cache-Control: no-transform` responses are passed through untouched,
+//! as are routes marked with `Route::no_compression`.
+//!
+//! ```rust
+//! use rust_api::{Req, RustApi, compression::Compression};
+//!
+//! let mut app = RustApi::new();
+//! app.attach(Compression::new().min_size(512));
+//! app.get("/download", |_req: Req| async { "already compressed" })
+//!     .no_compression();
+//! ```
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use flate2::write::{GzEncoder, ZlibEncoder};
+use futures_util::stream;
+use http_body_util::{BodyExt, Full, StreamBody};
+use hyper::body::{Body, Frame};
+use hyper::{StatusCode, header};
+use std::io::Write;
+use std::sync::Arc;
+
+use crate::res::BoxBody;
+use crate::{Error, IntoRes, Middleware, Next, Req, Res};
+
+/// Marks requests to routes opted out of compression.
+#[derive(Clone, Copy)]
+pub(crate) struct NoCompression;
+
+/// Content codings this middleware produces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Coding {
+    Gzip,
+    Deflate,
+}
+
+impl Coding {
+    fn name(self) -> &'static str {
+        match self {
+            Coding::Gzip => "gzip",
+            Coding::Deflate => "deflate",
+        }
+    }
+
+    /// Preferred coding accepted by `accept_encoding`.
+    fn negotiate(accept_encoding: &str) -> Option<Self> {
+        let mut best: Option<(Self, f32)> = None;
+        for item in accept_encoding.split(',') {
+            let mut params = item.split(';');
+            let name = params.next().unwrap_or("").trim().to_ascii_lowercase();
+            let q = params
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .and_then(|q| q.trim().parse().ok())
+                .unwrap_or(1.0);
+            let coding = match name.as_str() {
+                "gzip" | "x-gzip" | "*" => Coding::Gzip,
+                "deflate" => Coding::Deflate,
+                _ => continue,
+            };
+            if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+                best = Some((coding, q));
+            }
+        }
+        best.map(|(coding, _)| coding)
+    }
+}
+
+/// Incremental encoder writing into a buffer.
+enum Encoder {
+    Gzip(GzEncoder<Vec<u8>>),
+    Deflate(ZlibEncoder<Vec<u8>>),
+}
+
+impl Encoder {
+    fn new(coding: Coding, level: flate2::Compression) -> Self {
+        match coding {
+            Coding::Gzip => Encoder::Gzip(GzEncoder::new(Vec::new(), level)),
+            Coding::Deflate => Encoder::Deflate(ZlibEncoder::new(Vec::new(), level)),
+        }
+    }
+
+    /// Compress `data` and flush, returning the output produced so far.
+    fn write(&mut self, data: &[u8]) -> std::io::Result<Bytes> {
+        let buf = match self {
+            Encoder::Gzip(encoder) => {
+                encoder.write_all(data)?;
+                encoder.flush()?;
+                encoder.get_mut()
+            }
+            Encoder::Deflate(encoder) => {
+                encoder.write_all(data)?;
+                encoder.flush()?;
+                encoder.get_mut()
+            }
+        };
+        Ok(Bytes::from(std::mem::take(buf)))
+    }
+
+    /// Compress all of `data` at once.
+    fn encode(self, data: &[u8]) -> std::io::Result<Bytes> {
+        match self {
+            Encoder::Gzip(mut encoder) => {
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Encoder::Deflate(mut encoder) => {
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+        .map(Bytes::from)
+    }
+
+    /// Finish the stream, returning the remaining output.
+    fn finish(self) -> std::io::Result<Bytes> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.finish(),
+            Encoder::Deflate(encoder) => encoder.finish(),
+        }
+        .map(Bytes::from)
+    }
+}
+
+/// Compression middleware.
+#[derive(Debug, Clone)]
+pub struct Compression {
+    min_size: u64,
+    level: u32,
+    excluded: Vec<String>,
+}
+
+impl Compression {
+    /// Compress responses of 1 KiB or more at the default level.
+    pub fn new() -> Self {
+        Self {
+            min_size: 1024,
+            level: 6,
+            excluded: vec!["text/event-stream".to_string()],
+        }
+    }
+
+    /// Leave buffered bodies smaller than `bytes` uncompressed. Streamed
+    /// bodies are always compressed.
+    pub fn min_size(mut self, bytes: u64) -> Self {
+        self.min_size = bytes;
+        self
+    }
+
+    /// Compression level, 0 (none) to 9 (best).
+    pub fn level(mut self, level: u32) -> Self {
+        self.level = level.min(9);
+        self
+    }
+
+    /// Leave responses of `content_type` (e.g. `image/png`) uncompressed.
+    /// `text/event-stream` is excluded by default.
+    pub fn exclude(mut self, content_type: &str) -> Self {
+        self.excluded.push(content_type.to_ascii_lowercase());
+        self
+    }
+
+    /// Whether `res` may be encoded.
+    fn compressible(&self, res: &Res) -> bool {
+        let status = res.status_code();
+        if status == StatusCode::SWITCHING_PROTOCOLS
+            || status == StatusCode::NO_CONTENT
+            || status == StatusCode::NOT_MODIFIED
+        {
+            return false;
+        }
+        let headers = res.headers();
+        if headers.contains_key(header::CONTENT_ENCODING) {
+            return false;
+        }
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or("")
+                .to_ascii_lowercase()
+        };
+        if header(header::CACHE_CONTROL).contains("no-transform") {
+            return false;
+        }
+        let content_type = header(header::CONTENT_TYPE);
+        let media_type = content_type.split(';').next().unwrap_or("").trim();
+        !self.excluded.iter().any(|excluded| excluded == media_type)
+    }
+
+    async fn compress(&self, res: Res, coding: Coding) -> Res {
+        let level = flate2::Compression::new(self.level);
+        let (mut parts, body) = res.into_hyper().into_parts();
+        let body = match body.size_hint().exact() {
+            Some(len) if len < self.min_size => {
+                return Res::from_hyper(hyper::Response::from_parts(parts, body))
+                    .vary("accept-encoding");
+            }
+            Some(_) => {
+                let bytes = match body.collect().await {
+                    Ok(collected) => collected.to_bytes(),
+                    Err(e) => return e.into_res(),
+                };
+                match Encoder::new(coding, level).encode(&bytes) {
+                    Ok(compressed) => Full::new(compressed).map_err(|e| match e {}).boxed(),
+                    Err(e) => return Error::Io(e).into_res(),
+                }
+            }
+            None => compress_stream(body, Encoder::new(coding, level)),
+        };
+        parts.headers.remove(header::CONTENT_LENGTH);
+        parts.headers.insert(
+            header::CONTENT_ENCODING,
+            header::HeaderValue::from_static(coding.name()),
+        );
+        // A strong ETag promises identical bytes; the encoded body differs.
+        let weak = parts
+            .headers
+            .get(header::ETAG)
+            .filter(|etag| !etag.as_bytes().starts_with(b"W/"))
+            .and_then(|etag| {
+                header::HeaderValue::from_bytes(&[b"W/", etag.as_bytes()].concat()).ok()
+            });
+        if let Some(weak) = weak {
+            parts.headers.insert(header::ETAG, weak);
+        }
+        Res::from_hyper(hyper::Response::from_parts(parts, body)).vary("accept-encoding")
+    }
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Progress of a streamed body through the encoder.
+enum StreamState {
+    Body(BoxBody, Encoder),
+    Trailers(hyper::HeaderMap),
+    Done,
+}
+
+/// Encode `body` frame by frame, flushing after each data frame.
+fn compress_stream(body: BoxBody, encoder: Encoder) -> BoxBody {
+    let frames = stream::unfold(StreamState::Body(body, encoder), |state| async move {
+        match state {
+            StreamState::Body(mut body, mut encoder) => match body.frame().await {
+                Some(Ok(frame)) => match frame.into_data() {
+                    Ok(data) => {
+                        let frame = encoder.write(&data).map(Frame::data).map_err(Error::Io);
+                        Some((frame, StreamState::Body(body, encoder)))
+                    }
+                    // Trailers end the body; the final block goes first.
+                    Err(frame) => {
+                        let next = frame
+                            .into_trailers()
+                            .map_or(StreamState::Done, StreamState::Trailers);
+                        let tail = encoder.finish().map(Frame::data).map_err(Error::Io);
+                        Some((tail, next))
+                    }
+                },
+                Some(Err(e)) => Some((Err(e), StreamState::Done)),
+                None => {
+                    let tail = encoder.finish().map(Frame::data).map_err(Error::Io);
+                    Some((tail, StreamState::Done))
+                }
+            },
+            StreamState::Trailers(trailers) => {
+                Some((Ok(Frame::trailers(trailers)), StreamState::Done))
+            }
+            StreamState::Done => None,
+        }
+    });
+    StreamBody::new(frames).boxed()
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> Middleware<S> for Compression {
+    async fn handle(&self, req: Req, _state: Arc<S>, next: Next<S>) -> Res {
+        let coding = req
+            .header("accept-encoding")
+            .and_then(Coding::negotiate)
+            .filter(|_| req.extensions().get::<NoCompression>().is_none());
+        let res = next.run(req).await;
+        match coding {
+            Some(coding) if self.compressible(&res) => self.compress(res, coding).await,
+            _ => res,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "Compression"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RustApi;
+    use crate::test::{TestClient, assert_header};
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    fn gunzip(bytes: &[u8]) -> String {
+        let mut text = String::new();
+        GzDecoder::new(bytes).read_to_string(&mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn test_negotiate_coding() {
+        assert_eq!(Coding::negotiate("gzip, deflate"), Some(Coding::Gzip));
+        assert_eq!(
+            Coding::negotiate("gzip;q=0.5, deflate"),
+            Some(Coding::Deflate)
+        );
+        assert_eq!(Coding::negotiate("gzip;q=0, br"), None);
+        assert_eq!(Coding::negotiate("identity"), None);
+    }
+
+    #[tokio::test]
+    async fn test_compression_buffered_and_streamed() {
+        let mut app = RustApi::new();
+        app.attach(Compression::new().min_size(16));
+        app.get("/big", |_req: Req| async { "a".repeat(100) });
+        app.get("/small", |_req: Req| async { "tiny" });
+        app.get("/raw", |_req: Req| async { "b".repeat(100) })
+            .no_compression();
+        app.get("/events", |_req: Req| async {
+            Res::text("data: hi\n\n".repeat(10)).header("content-type", "text/event-stream")
+        });
+        app.get("/stream", |_req: Req| async {
+            Res::stream(|mut tx| async move {
+                tx.send("chunk one,").await.ok();
+                tx.send("chunk two").await.ok();
+            })
+        });
+        let client = TestClient::new(app);
+        let get = |path: &'static str| client.get(path).header("accept-encoding", "gzip").send();
+
+        let res = get("/big").await;
+        assert_header(&res, "content-encoding", "gzip");
+        assert_header(&res, "vary", "accept-encoding");
+        let body = res.into_hyper().collect().await.unwrap().to_bytes();
+        assert_eq!(gunzip(&body), "a".repeat(100));
+
+        for path in ["/small", "/raw", "/events"] {
+            let res = get(path).await;
+            assert!(res.headers().get("content-encoding").is_none(), "{}", path);
+        }
+
+        // Each chunk is flushed as its own frame.
+        let res = get("/stream").await;
+        assert_header(&res, "content-encoding", "gzip");
+        let mut body = res.into_hyper().into_body();
+        let mut frames = Vec::new();
+        while let Some(frame) = body.frame().await {
+            if let Ok(data) = frame.unwrap().into_data() {
+                frames.push(data);
+            }
+        }
+        assert!(frames.len() >= 3);
+        assert_eq!(gunzip(&frames.concat()), "chunk one,chunk two");
+    }
+
+    #[tokio::test]
+    async fn test_compressed_etag_is_weak() {
+        let mut app = RustApi::new();
+        app.attach(Compression::new().min_size(16));
+        app.get("/strong", |_req: Req| async {
+            Res::text("a".repeat(100)).header("etag", "\"v1\"")
+        });
+        app.get("/weak", |_req: Req| async {
+            Res::text("a".repeat(100)).header("etag", "W/\"v1\"")
+        });
+        let client = TestClient::new(app);
+
+        let res = client.get("/strong").send().await;
+        assert_header(&res, "etag", "\"v1\"");
+        let res = client
+            .get("/strong")
+            .header("accept-encoding", "gzip")
+            .send()
+            .await;
+        assert_header(&res, "etag", "W/\"v1\"");
+        let res = client
+            .get("/weak")
+            .header("accept-encoding", "gzip")
+            .send()
+            .await;
+        assert_header(&res, "etag", "W/\"v1\"");
+    }
+}
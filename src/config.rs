@@ -7,7 +7,7 @@ use std::time::Duration;
 use crate::{Error, Result};
 
 /// Server configuration.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ServerConfig {
     /// Maximum request body size in bytes.
     pub body_limit: Option<usize>,
@@ -30,19 +30,48 @@ pub struct ServerConfig {
     /// TCP keep-alive duration in seconds.
     #[serde(default, with = "opt_duration_serde")]
     pub keep_alive: Option<Duration>,
-}
 
-impl Default for ServerConfig {
-    fn default() -> Self {
-        Self {
-            body_limit: None,
-            request_timeout: None,
-            handler_timeout: None,
-            http2: false,
-            max_connections: None,
-            keep_alive: None,
-        }
-    }
+    /// Requests served per HTTP/1 connection before it is closed.
+    pub max_requests_per_connection: Option<u64>,
+
+    /// Idle connection timeout in seconds.
+    #[serde(default, with = "opt_duration_serde")]
+    pub idle_timeout: Option<Duration>,
+
+    /// Seconds an HTTP/1 client has to send request headers.
+    #[serde(default, with = "opt_duration_serde")]
+    pub header_read_timeout: Option<Duration>,
+
+    /// Minimum request body throughput in bytes per second.
+    pub min_body_rate: Option<u64>,
+
+    /// Seconds before `min_body_rate` applies (default 5).
+    #[serde(default, with = "opt_duration_serde")]
+    pub min_body_rate_grace: Option<Duration>,
+
+    /// Idle buffers kept for request and response bodies (enables pooling).
+    pub buffer_pool_size: Option<usize>,
+
+    /// Tokio runtime metrics sampling interval in seconds.
+    #[serde(default, with = "opt_duration_serde")]
+    pub runtime_metrics_interval: Option<Duration>,
+
+    /// Start the tokio-console instrumentation server (`console` feature).
+    #[serde(default)]
+    pub tokio_console: bool,
+
+    /// Seconds shutdown waits for WebSocket handlers to finish.
+    #[serde(default, with = "opt_duration_serde")]
+    pub websocket_shutdown_grace: Option<Duration>,
+
+    /// Maximum response body size in bytes.
+    pub response_body_limit: Option<usize>,
+
+    /// Maximum number of response headers.
+    pub max_response_headers: Option<usize>,
+
+    /// Maximum total size of response headers in bytes.
+    pub max_response_header_bytes: Option<usize>,
 }
 
 impl ServerConfig {
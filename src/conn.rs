@@ -0,0 +1,335 @@
+//! Connection IO shared between hyper and the request pipeline.
+
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::task::{Context, Poll, ready};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use hyper::body::{Body, Frame, SizeHint};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio::sync::watch;
+use tokio::time::Sleep;
+
+use crate::Error;
+use crate::metrics::CloseReason;
+
+/// Per-connection context shared by every request on the connection.
+pub(crate) struct ConnInfo {
+    pub(crate) stream: Arc<TcpStream>,
+    pub(crate) peer: SocketAddr,
+    pub(crate) http2: bool,
+    pub(crate) opened: Instant,
+    pub(crate) requests: AtomicU64,
+    pub(crate) in_flight: AtomicUsize,
+    /// Microseconds after `opened` when the last request finished.
+    pub(crate) last_active: AtomicU64,
+    pub(crate) close_reason: OnceLock<CloseReason>,
+}
+
+impl ConnInfo {
+    pub(crate) fn new(stream: Arc<TcpStream>, peer: SocketAddr, http2: bool) -> Self {
+        Self {
+            stream,
+            peer,
+            http2,
+            opened: Instant::now(),
+            requests: AtomicU64::new(0),
+            in_flight: AtomicUsize::new(0),
+            last_active: AtomicU64::new(0),
+            close_reason: OnceLock::new(),
+        }
+    }
+
+    /// Record a new request; returns its 1-based position on the connection.
+    pub(crate) fn request_started(&self) -> u64 {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        self.requests.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub(crate) fn request_finished(&self) {
+        let now = self.opened.elapsed().as_micros() as u64;
+        self.last_active.store(now, Ordering::Relaxed);
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Time since the last request finished, or `None` while one is running.
+    fn idle_for(&self) -> Option<Duration> {
+        if self.in_flight.load(Ordering::Relaxed) > 0 {
+            return None;
+        }
+        let last = Duration::from_micros(self.last_active.load(Ordering::Relaxed));
+        Some(self.opened.elapsed().saturating_sub(last))
+    }
+
+    /// Resolve once the connection has been idle for `timeout`.
+    pub(crate) async fn idle(&self, timeout: Duration) {
+        loop {
+            match self.idle_for() {
+                Some(idle) if idle >= timeout => return,
+                Some(idle) => tokio::time::sleep(timeout - idle).await,
+                None => tokio::time::sleep(timeout).await,
+            }
+        }
+    }
+}
+
+/// Switch that asks clients to move off this server's connections.
+///
+/// While draining, HTTP/1 responses carry `Connection: close`, idle
+/// connections are closed and HTTP/2 connections receive GOAWAY. The
+/// listener keeps accepting, so a load balancer can shift traffic before a
+/// restart.
+///
+/// ```rust,no_run
+/// use rust_api::RustApi;
+///
+/// let app = RustApi::new();
+/// let drain = app.connection_drain();
+/// // e.g. from an admin endpoint or signal handler:
+/// drain.start();
+/// ```
+#[derive(Clone)]
+pub struct ConnectionDrain(Arc<watch::Sender<bool>>);
+
+impl ConnectionDrain {
+    pub(crate) fn new() -> Self {
+        Self(Arc::new(watch::Sender::new(false)))
+    }
+
+    /// Start draining connections.
+    pub fn start(&self) {
+        self.0.send_replace(true);
+    }
+
+    /// Stop draining; new connections are kept alive again.
+    pub fn stop(&self) {
+        self.0.send_replace(false);
+    }
+
+    /// Whether connections are being drained.
+    pub fn is_draining(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Resolve once draining starts.
+    pub(crate) async fn started(&self) {
+        let mut rx = self.0.subscribe();
+        let _ = rx.wait_for(|draining| *draining).await;
+    }
+}
+
+/// Minimum request body throughput, after a grace period.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MinBodyRate {
+    pub(crate) bytes_per_sec: u64,
+    pub(crate) grace: Duration,
+}
+
+impl MinBodyRate {
+    /// Latest time by which `received` bytes keep the client above the rate.
+    fn deadline(&self, started: tokio::time::Instant, received: u64) -> tokio::time::Instant {
+        let earned = received as f64 / self.bytes_per_sec.max(1) as f64;
+        started + self.grace + Duration::from_secs_f64(earned)
+    }
+}
+
+/// Body failing with 408 once the client falls below a [`MinBodyRate`].
+///
+/// `tripped` is set so the connection can be closed after the response.
+pub(crate) struct RateWatched<B> {
+    inner: B,
+    rate: MinBodyRate,
+    started: tokio::time::Instant,
+    received: u64,
+    deadline: Pin<Box<Sleep>>,
+    tripped: Arc<AtomicBool>,
+}
+
+impl<B> RateWatched<B> {
+    pub(crate) fn new(inner: B, rate: MinBodyRate, tripped: Arc<AtomicBool>) -> Self {
+        let started = tokio::time::Instant::now();
+        Self {
+            inner,
+            rate,
+            started,
+            received: 0,
+            deadline: Box::pin(tokio::time::sleep_until(rate.deadline(started, 0))),
+            tripped,
+        }
+    }
+}
+
+impl<B> Body for RateWatched<B>
+where
+    B: Body<Data = Bytes> + Unpin,
+    B::Error: Into<Error>,
+{
+    type Data = Bytes;
+    type Error = Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Error>>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &poll {
+            if let Some(data) = frame.data_ref() {
+                this.received += data.len() as u64;
+                let deadline = this.rate.deadline(this.started, this.received);
+                this.deadline.as_mut().reset(deadline);
+            }
+        }
+        match poll {
+            Poll::Ready(frame) => Poll::Ready(frame.map(|frame| frame.map_err(Into::into))),
+            Poll::Pending => {
+                ready!(this.deadline.as_mut().poll(cx));
+                this.tripped.store(true, Ordering::Relaxed);
+                Poll::Ready(Some(Err(Error::Status(
+                    408,
+                    Some("Request body sent too slowly".into()),
+                ))))
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// TCP stream that can also be written outside hyper (e.g. for 1xx responses).
+pub(crate) struct ConnIo(Arc<TcpStream>);
+
+impl ConnIo {
+    pub(crate) fn new(stream: Arc<TcpStream>) -> Self {
+        Self(stream)
+    }
+}
+
+impl AsyncRead for ConnIo {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            ready!(self.0.poll_read_ready(cx))?;
+            match self.0.try_read(buf.initialize_unfilled()) {
+                Ok(n) => {
+                    buf.advance(n);
+                    return Poll::Ready(Ok(()));
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+    }
+}
+
+impl AsyncWrite for ConnIo {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            ready!(self.0.poll_write_ready(cx))?;
+            match self.0.try_write(buf) {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                result => return Poll::Ready(result),
+            }
+        }
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            ready!(self.0.poll_write_ready(cx))?;
+            match self.0.try_write_vectored(bufs) {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                result => return Poll::Ready(result),
+            }
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // Half-close like `TcpStream::poll_shutdown`; the fd itself closes
+        // once the last handle is dropped.
+        match socket2::SockRef::from(&*self.0).shutdown(std::net::Shutdown::Write) {
+            Err(e) if e.kind() != io::ErrorKind::NotConnected => Poll::Ready(Err(e)),
+            _ => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+/// Write all bytes to a shared stream.
+pub(crate) async fn write_all(stream: &TcpStream, mut buf: &[u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        stream.writable().await?;
+        match stream.try_write(buf) {
+            Ok(n) => buf = &buf[n..],
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::{BodyExt, StreamBody};
+    use tokio::sync::mpsc;
+    use tokio_stream::wrappers::ReceiverStream;
+
+    #[tokio::test]
+    async fn test_min_body_rate() {
+        let (tx, rx) = mpsc::channel::<Result<Frame<Bytes>, Error>>(1);
+        let rate = MinBodyRate {
+            bytes_per_sec: 1000,
+            grace: Duration::from_millis(20),
+        };
+        let tripped = Arc::new(AtomicBool::new(false));
+        let mut body = RateWatched::new(
+            StreamBody::new(ReceiverStream::new(rx)),
+            rate,
+            Arc::clone(&tripped),
+        );
+
+        tx.send(Ok(Frame::data(Bytes::from(vec![0; 50]))))
+            .await
+            .unwrap();
+        let frame = body.frame().await.unwrap().unwrap();
+        assert_eq!(frame.into_data().unwrap().len(), 50);
+        assert!(!tripped.load(Ordering::Relaxed));
+
+        // 50 bytes buy 50ms on top of the grace period; then nothing comes.
+        let started = Instant::now();
+        let error = body.frame().await.unwrap().unwrap_err();
+        assert!(matches!(error, Error::Status(408, _)));
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(tripped.load(Ordering::Relaxed));
+        drop(tx);
+    }
+}
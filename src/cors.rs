@@ -0,0 +1,468 @@
+//! Cross-origin resource sharing.
+//!
+//! Attach [`CorsConfig`] as pre-routing middleware so preflight `OPTIONS`
+//! requests are answered even where no `OPTIONS` route exists. Preflights
+//! for a routed path only allow the configured methods that path actually
+//! serves.
+//!
+//! ```rust
+//! use rust_api::{RustApi, cors::CorsConfig};
+//!
+//! let mut app = RustApi::new();
+//! app.attach_pre_routing(
+//!     CorsConfig::new()
+//!         .allow_origins(["https://app.example.com", "https://*.example.com"])
+//!         .allow_origin_fn(|origin| origin.ends_with(".tenant.test"))
+//!         .allow_credentials(true),
+//! );
+//! ```
+
+use async_trait::async_trait;
+use hyper::Method;
+use hyper::header::{self, HeaderMap, HeaderValue};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::res::append_vary;
+use crate::route_table::RoutedMethods;
+use crate::{Error, IntoRes, Middleware, Next, Req, Res};
+
+type OriginFn = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// CORS policy, usable directly as middleware.
+#[derive(Clone)]
+pub struct CorsConfig {
+    origins: Vec<OriginRule>,
+    origin_fn: Option<OriginFn>,
+    methods: Vec<Method>,
+    headers: Vec<String>,
+    expose_headers: Vec<String>,
+    credentials: bool,
+    max_age: Option<Duration>,
+    // Header values built once from the lists above.
+    methods_value: Option<HeaderValue>,
+    headers_value: Option<HeaderValue>,
+    expose_value: Option<HeaderValue>,
+}
+
+/// Join a list into a single comma-separated header value.
+fn list_value<T: AsRef<str>>(items: &[T]) -> Option<HeaderValue> {
+    if items.is_empty() {
+        return None;
+    }
+    let joined: Vec<&str> = items.iter().map(AsRef::as_ref).collect();
+    HeaderValue::from_str(&joined.join(", ")).ok()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum OriginRule {
+    Any,
+    Exact(String),
+    /// `scheme://*.suffix`, stored as (`scheme://`, `.suffix`).
+    Subdomain(String, String),
+}
+
+impl OriginRule {
+    fn parse(pattern: &str) -> Self {
+        let pattern = pattern.trim_end_matches('/').to_ascii_lowercase();
+        if pattern == "*" {
+            return Self::Any;
+        }
+        match pattern.split_once("://*.") {
+            Some((scheme, suffix)) => {
+                Self::Subdomain(format!("{}://", scheme), format!(".{}", suffix))
+            }
+            None => Self::Exact(pattern),
+        }
+    }
+
+    fn matches(&self, origin: &str) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Exact(exact) => origin.eq_ignore_ascii_case(exact),
+            Self::Subdomain(scheme, suffix) => {
+                let origin = origin.to_ascii_lowercase();
+                origin
+                    .strip_prefix(scheme.as_str())
+                    .and_then(|host| host.strip_suffix(suffix.as_str()))
+                    .is_some_and(|sub| {
+                        !sub.is_empty()
+                            && sub
+                                .bytes()
+                                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'.')
+                    })
+            }
+        }
+    }
+}
+
+/// Whether `origin` matches an [`CorsConfig::allow_origins`] pattern.
+pub(crate) fn origin_matches(pattern: &str, origin: &str) -> bool {
+    OriginRule::parse(pattern).matches(origin)
+}
+
+impl CorsConfig {
+    /// Create a policy allowing no origins, with GET/HEAD/POST and no credentials.
+    pub fn new() -> Self {
+        Self {
+            origins: Vec::new(),
+            origin_fn: None,
+            methods: Vec::new(),
+            headers: Vec::new(),
+            expose_headers: Vec::new(),
+            credentials: false,
+            max_age: None,
+            methods_value: None,
+            headers_value: None,
+            expose_value: None,
+        }
+        .allow_methods([Method::GET, Method::HEAD, Method::POST])
+    }
+
+    /// Create a policy allowing any origin, common methods and any
+    /// request header, as used by the development profile.
+    pub fn permissive() -> Self {
+        Self::new()
+            .allow_origins(["*"])
+            .allow_methods([
+                Method::GET,
+                Method::HEAD,
+                Method::POST,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+            ])
+            .allow_headers(["*"])
+    }
+
+    /// Allow origins: exact (`https://a.com`), subdomain wildcards
+    /// (`https://*.a.com`) or `*` for any origin.
+    pub fn allow_origins<I, T>(mut self, origins: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        self.origins
+            .extend(origins.into_iter().map(|o| OriginRule::parse(o.as_ref())));
+        self
+    }
+
+    /// Allow origins accepted by a callback (e.g. a tenant lookup).
+    pub fn allow_origin_fn<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.origin_fn = Some(Arc::new(f));
+        self
+    }
+
+    /// Set allowed methods.
+    ///
+    /// As pre-routing middleware, preflights narrow this list to the
+    /// methods routed for the requested path; paths without a route (e.g.
+    /// served by other middleware) get the full list.
+    pub fn allow_methods<I: IntoIterator<Item = Method>>(mut self, methods: I) -> Self {
+        self.methods = methods.into_iter().collect();
+        self.methods_value = list_value(&self.methods);
+        self
+    }
+
+    /// Set allowed request headers.
+    ///
+    /// `*` allows any header; with credentials enabled the requested headers
+    /// are reflected instead, since browsers reject a literal `*` there.
+    pub fn allow_headers<I, T>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.headers = headers.into_iter().map(Into::into).collect();
+        self.headers_value = list_value(&self.headers);
+        self
+    }
+
+    /// Set response headers exposed to scripts.
+    pub fn expose_headers<I, T>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.expose_headers = headers.into_iter().map(Into::into).collect();
+        self.expose_value = list_value(&self.expose_headers);
+        self
+    }
+
+    /// Allow cookies and credentials on cross-origin requests.
+    pub fn allow_credentials(mut self, allow: bool) -> Self {
+        self.credentials = allow;
+        self
+    }
+
+    /// Set how long browsers may cache preflight results.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Check whether an origin is allowed.
+    pub fn is_origin_allowed(&self, origin: &str) -> bool {
+        self.origins.iter().any(|rule| rule.matches(origin))
+            || self.origin_fn.as_ref().is_some_and(|f| f(origin))
+    }
+
+    /// Whether the allowed origin differs per request, requiring `Vary: Origin`.
+    fn varies_by_origin(&self) -> bool {
+        self.credentials || self.origin_fn.is_some() || !self.origins.contains(&OriginRule::Any)
+    }
+
+    /// Echo the request's origin (a cheap clone) or `*`.
+    fn allow_origin_value(&self, origin: &HeaderValue) -> HeaderValue {
+        if self.varies_by_origin() {
+            origin.clone()
+        } else {
+            HeaderValue::from_static("*")
+        }
+    }
+
+    fn apply_common(&self, headers: &mut HeaderMap, origin: &HeaderValue) {
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_ORIGIN,
+            self.allow_origin_value(origin),
+        );
+        if self.credentials {
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+    }
+
+    /// Answer a preflight, or deny it without CORS headers.
+    ///
+    /// `routed` lists the methods the requested path serves, when known.
+    fn preflight(
+        &self,
+        origin: &HeaderValue,
+        request_method: &str,
+        request_headers: Option<&HeaderValue>,
+        routed: Option<&[Method]>,
+    ) -> Res {
+        if !origin.to_str().is_ok_and(|o| self.is_origin_allowed(o)) {
+            return Error::forbidden("CORS origin not allowed").into_res();
+        }
+        let allowed = |method: &Method| routed.is_none_or(|routed| routed.contains(method));
+        if !self
+            .methods
+            .iter()
+            .any(|m| m.as_str() == request_method && allowed(m))
+        {
+            return Error::forbidden("CORS method not allowed").into_res();
+        }
+
+        let mut res = Res::status(204);
+        let headers = res.headers_mut();
+        self.apply_common(headers, origin);
+
+        let methods_value = match routed {
+            Some(_) => {
+                let methods: Vec<&Method> = self.methods.iter().filter(|m| allowed(m)).collect();
+                list_value(&methods)
+            }
+            None => self.methods_value.clone(),
+        };
+        if let Some(value) = methods_value {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, value);
+        }
+        let any_header = self.headers.iter().any(|h| h == "*");
+        let allow_headers = match request_headers {
+            Some(requested) if any_header && self.credentials => {
+                append_vary(headers, "Access-Control-Request-Headers");
+                Some(requested.clone())
+            }
+            _ => self.headers_value.clone(),
+        };
+        if let Some(value) = allow_headers {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, value);
+        }
+        if let Some(max_age) = self.max_age {
+            headers.insert(header::ACCESS_CONTROL_MAX_AGE, max_age.as_secs().into());
+        }
+        res
+    }
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> Middleware<S> for CorsConfig {
+    async fn handle(&self, req: Req, _state: Arc<S>, next: Next<S>) -> Res {
+        let Some(origin) = req.headers().get(header::ORIGIN).cloned() else {
+            return next.run(req).await;
+        };
+        let request_method = req
+            .header("access-control-request-method")
+            .filter(|_| req.method() == Method::OPTIONS);
+
+        let mut res = if let Some(request_method) = request_method {
+            let routed = req
+                .extensions()
+                .get::<RoutedMethods>()
+                .and_then(|routed| routed.lookup(req.path()));
+            self.preflight(
+                &origin,
+                request_method,
+                req.headers().get(header::ACCESS_CONTROL_REQUEST_HEADERS),
+                routed.as_deref(),
+            )
+        } else {
+            let mut res = next.run(req).await;
+            if origin.to_str().is_ok_and(|o| self.is_origin_allowed(o)) {
+                let headers = res.headers_mut();
+                self.apply_common(headers, &origin);
+                if let Some(value) = &self.expose_value {
+                    headers.insert(header::ACCESS_CONTROL_EXPOSE_HEADERS, value.clone());
+                }
+            }
+            res
+        };
+
+        if self.varies_by_origin() {
+            res = res.vary("Origin");
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_origin_rules() {
+        let cors =
+            CorsConfig::new().allow_origins(["https://app.example.com", "https://*.example.org"]);
+        assert!(cors.is_origin_allowed("https://app.example.com"));
+        assert!(cors.is_origin_allowed("https://a.b.EXAMPLE.org"));
+        assert!(!cors.is_origin_allowed("https://example.org"));
+        assert!(!cors.is_origin_allowed("http://a.example.org"));
+        assert!(!cors.is_origin_allowed("https://evil-example.org"));
+        assert!(!cors.is_origin_allowed("https://a.example.org.evil.com"));
+        assert!(!cors.is_origin_allowed("https://a.example.org:8443"));
+    }
+
+    #[test]
+    fn test_origin_fn_and_vary() {
+        let cors = CorsConfig::new().allow_origin_fn(|origin| origin.ends_with(".tenant.test"));
+        assert!(cors.is_origin_allowed("https://acme.tenant.test"));
+        assert!(cors.varies_by_origin());
+
+        let any = CorsConfig::new().allow_origins(["*"]);
+        assert!(!any.varies_by_origin());
+        assert!(any.allow_credentials(true).varies_by_origin());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::VARY, HeaderValue::from_static("Accept-Encoding"));
+        append_vary(&mut headers, "Origin");
+        append_vary(&mut headers, "origin");
+        assert_eq!(headers.get_all(header::VARY).iter().count(), 2);
+    }
+
+    #[test]
+    fn test_preflight() {
+        let cors = CorsConfig::new()
+            .allow_origins(["https://app.test"])
+            .allow_methods([Method::GET, Method::PUT])
+            .allow_headers(["*"]);
+
+        let origin = HeaderValue::from_static("https://app.test");
+        let ok = cors.preflight(
+            &origin,
+            "PUT",
+            Some(&HeaderValue::from_static("x-token")),
+            None,
+        );
+        assert_eq!(ok.status_code(), 204);
+        assert_eq!(
+            ok.headers()[header::ACCESS_CONTROL_ALLOW_METHODS],
+            HeaderValue::from_static("GET, PUT")
+        );
+        assert_eq!(
+            ok.headers()[header::ACCESS_CONTROL_ALLOW_HEADERS],
+            HeaderValue::from_static("*")
+        );
+
+        let bad_method = cors.preflight(&origin, "DELETE", None, None);
+        assert_eq!(bad_method.status_code(), 403);
+        assert!(
+            !bad_method
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        );
+
+        let bad_origin = cors.preflight(
+            &HeaderValue::from_static("https://evil.test"),
+            "GET",
+            None,
+            None,
+        );
+        assert_eq!(bad_origin.status_code(), 403);
+        assert!(
+            !bad_origin
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        );
+
+        let reflected = cors.allow_credentials(true).preflight(
+            &origin,
+            "GET",
+            Some(&HeaderValue::from_static("x-token, content-type")),
+            None,
+        );
+        assert_eq!(
+            reflected.headers()[header::ACCESS_CONTROL_ALLOW_HEADERS],
+            HeaderValue::from_static("x-token, content-type")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_preflight_uses_routed_methods() {
+        use crate::test::{TestClient, assert_status};
+        use crate::{Req, RustApi};
+
+        let mut app = RustApi::new();
+        app.attach_pre_routing(
+            CorsConfig::new()
+                .allow_origins(["https://app.test"])
+                .allow_methods([Method::GET, Method::PUT, Method::DELETE]),
+        );
+        app.get("/items/{id}", |_: Req| async { "item" });
+        app.put("/items/{id}", |_: Req| async { "updated" });
+        let client = TestClient::new(app);
+
+        let preflight = |method: &'static str, path: &'static str| {
+            client
+                .request(Method::OPTIONS, path)
+                .header("origin", "https://app.test")
+                .header("access-control-request-method", method)
+                .send()
+        };
+        let res = preflight("PUT", "/items/1").await;
+        assert_status(&res, 204);
+        assert_eq!(
+            res.headers()[header::ACCESS_CONTROL_ALLOW_METHODS],
+            "GET, PUT"
+        );
+        assert_status(&preflight("DELETE", "/items/1").await, 403);
+
+        let unrouted = preflight("DELETE", "/elsewhere").await;
+        assert_status(&unrouted, 204);
+        assert_eq!(
+            unrouted.headers()[header::ACCESS_CONTROL_ALLOW_METHODS],
+            "GET, PUT, DELETE"
+        );
+    }
+}
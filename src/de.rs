@@ -0,0 +1,426 @@
+//! Deserialization helpers for query, path and body fields.
+//!
+//! Use them with `#[serde(deserialize_with = "...")]`. They accept the
+//! strings query strings and paths produce as well as native JSON values,
+//! so one struct works for [`Query`](crate::Query) and
+//! [`Json`](crate::Json) alike.
+//!
+//! ```rust
+//! use rust_api::de;
+//! use serde::Deserialize;
+//! use std::time::SystemTime;
+//!
+//! #[derive(Deserialize, PartialEq, Debug)]
+//! #[serde(rename_all = "snake_case")]
+//! enum Sort {
+//!     Newest,
+//!     Oldest,
+//! }
+//!
+//! #[derive(Deserialize)]
+//! struct Search {
+//!     #[serde(default, deserialize_with = "de::comma_separated")]
+//!     ids: Vec<uuid::Uuid>,
+//!     #[serde(deserialize_with = "de::rfc3339")]
+//!     since: SystemTime,
+//!     #[serde(deserialize_with = "de::case_insensitive")]
+//!     sort: Sort,
+//!     #[serde(default, deserialize_with = "de::empty_as_none")]
+//!     cursor: Option<u64>,
+//!     #[serde(deserialize_with = "de::cents")]
+//!     max_price: i64,
+//! }
+//!
+//! let search: Search = serde_urlencoded::from_str(
+//!     "ids=&since=2024-05-01T12:00:00Z&sort=NEWEST&cursor=&max_price=19.99",
+//! )
+//! .unwrap();
+//! assert_eq!(search.sort, Sort::Newest);
+//! assert_eq!(search.cursor, None);
+//! assert_eq!(search.max_price, 1999);
+//! ```
+
+use serde::de::value::StrDeserializer;
+use serde::de::{self, Deserializer, IntoDeserializer, SeqAccess, Visitor};
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Scalar read as text, whatever its wire type.
+struct Text;
+
+impl<'de> Visitor<'de> for Text {
+    type Value = Option<String>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a string or number")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(Some(v.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+        Ok(Some(v))
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(Some(v.to_string()))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(Some(v.to_string()))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(Some(v.to_string()))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(Some(v.to_string()))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        d.deserialize_any(Text)
+    }
+}
+
+/// Read a scalar as text; `null` is an error.
+fn text<'de, D: Deserializer<'de>>(d: D) -> Result<String, D::Error> {
+    d.deserialize_any(Text)?
+        .ok_or_else(|| de::Error::custom("expected a value, found null"))
+}
+
+fn parse<T, E>(value: &str) -> Result<T, E>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+    E: de::Error,
+{
+    value
+        .parse()
+        .map_err(|e| E::custom(format!("invalid value {:?}: {}", value, e)))
+}
+
+/// `a,b,c` (or a JSON array) as a `Vec`, parsing each item with `FromStr`.
+/// Empty items are skipped.
+pub fn comma_separated<'de, D, T>(d: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    struct Items<T>(PhantomData<T>);
+
+    impl<'de, T> Visitor<'de> for Items<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        type Value = Vec<T>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a comma-separated string or a list")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+            v.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(parse)
+                .collect()
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut items = Vec::new();
+            while let Some(item) = seq.next_element::<TextElement>()? {
+                items.push(parse(&item.0)?);
+            }
+            Ok(items)
+        }
+    }
+
+    d.deserialize_any(Items(PhantomData))
+}
+
+/// Sequence element read as text.
+struct TextElement(String);
+
+impl<'de> de::Deserialize<'de> for TextElement {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        text(d).map(TextElement)
+    }
+}
+
+/// RFC 3339 timestamp such as `2024-05-01T12:00:00Z` or
+/// `2024-05-01T14:00:00.5+02:00`.
+pub fn rfc3339<'de, D: Deserializer<'de>>(d: D) -> Result<SystemTime, D::Error> {
+    let value = text(d)?;
+    parse_rfc3339(&value)
+        .ok_or_else(|| de::Error::custom(format!("invalid RFC 3339 timestamp {:?}", value)))
+}
+
+/// Seconds since the Unix epoch, as a number or string; fractions allowed.
+pub fn unix_timestamp<'de, D: Deserializer<'de>>(d: D) -> Result<SystemTime, D::Error> {
+    let value = text(d)?;
+    let secs: f64 = parse(&value)?;
+    let offset = Duration::try_from_secs_f64(secs.abs())
+        .map_err(|_| de::Error::custom(format!("invalid timestamp {:?}", value)))?;
+    if secs >= 0.0 {
+        Ok(UNIX_EPOCH + offset)
+    } else {
+        Ok(UNIX_EPOCH - offset)
+    }
+}
+
+/// Enum whose variant names are matched ignoring ASCII case.
+pub fn case_insensitive<'de, D, T>(d: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: de::Deserialize<'de>,
+{
+    let value = text(d)?;
+    T::deserialize(CaseInsensitive(&value)).map_err(|e: de::value::Error| de::Error::custom(e))
+}
+
+/// Empty strings (and `null`) as `None`; other values parsed with
+/// `FromStr`.
+pub fn empty_as_none<'de, D, T>(d: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    match d.deserialize_any(Text)? {
+        Some(value) if !value.trim().is_empty() => parse(value.trim()).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Decimal amount such as `19.99` as integer minor units (`1999`), without
+/// going through floating point. At most two decimals are accepted.
+pub fn cents<'de, D: Deserializer<'de>>(d: D) -> Result<i64, D::Error> {
+    let value = text(d)?;
+    parse_cents(&value).ok_or_else(|| de::Error::custom(format!("invalid amount {:?}", value)))
+}
+
+fn parse_cents(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value),
+    };
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if whole.is_empty() || fraction.len() > 2 || !all_digits(whole) || !all_digits(fraction) {
+        return None;
+    }
+    let fraction: i64 = format!("{:0<2}", fraction).parse().ok()?;
+    let cents = whole
+        .parse::<i64>()
+        .ok()?
+        .checked_mul(100)?
+        .checked_add(fraction)?;
+    Some(if negative { -cents } else { cents })
+}
+
+/// Deserializer for one string that matches enum variants ignoring case.
+struct CaseInsensitive<'a>(&'a str);
+
+impl<'de> Deserializer<'de> for CaseInsensitive<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_str(self.0)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let variant = variants
+            .iter()
+            .find(|variant| variant.eq_ignore_ascii_case(self.0))
+            .copied()
+            .unwrap_or(self.0);
+        let variant: StrDeserializer<'_, Self::Error> = variant.into_deserializer();
+        visitor.visit_enum(variant)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// Parse an RFC 3339 timestamp.
+fn parse_rfc3339(value: &str) -> Option<SystemTime> {
+    let value = value.trim();
+    let number = |range: std::ops::Range<usize>| -> Option<i64> {
+        let digits = value.get(range)?;
+        digits
+            .bytes()
+            .all(|b| b.is_ascii_digit())
+            .then(|| digits.parse().ok())?
+    };
+    let bytes = value.as_bytes();
+    if bytes.len() < 20
+        || bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || !matches!(bytes[10], b'T' | b't' | b' ')
+        || bytes[13] != b':'
+        || bytes[16] != b':'
+    {
+        return None;
+    }
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let mut rest = &value[19..];
+    let mut nanos = 0u32;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let end = fraction
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(fraction.len());
+        if end == 0 {
+            return None;
+        }
+        let digits = &fraction[..end.min(9)];
+        nanos = format!("{:0<9}", digits).parse().ok()?;
+        rest = &fraction[end..];
+    }
+    let offset = match rest {
+        "Z" | "z" => 0,
+        _ => {
+            let sign = match rest.as_bytes().first()? {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            let (h, m) = rest[1..].split_once(':')?;
+            if h.len() != 2 || m.len() != 2 {
+                return None;
+            }
+            let (h, m): (i64, i64) = (h.parse().ok()?, m.parse().ok()?);
+            if h > 23 || m > 59 {
+                return None;
+            }
+            sign * (h * 3600 + m * 60)
+        }
+    };
+
+    let days = days_from_civil(year, month, day);
+    let secs = days * 86_400 + hour * 3600 + minute * 60 + second - offset;
+    let time = if secs >= 0 {
+        UNIX_EPOCH + Duration::from_secs(secs as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs())
+    };
+    Some(time + Duration::from_nanos(u64::from(nanos)))
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum Color {
+        Red,
+        DarkBlue,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Fields {
+        #[serde(default, deserialize_with = "comma_separated")]
+        tags: Vec<u32>,
+        #[serde(deserialize_with = "unix_timestamp")]
+        at: SystemTime,
+        #[serde(deserialize_with = "case_insensitive")]
+        color: Color,
+        #[serde(default, deserialize_with = "empty_as_none")]
+        limit: Option<u32>,
+    }
+
+    #[test]
+    fn test_query_and_json_fields() {
+        let query: Fields =
+            serde_urlencoded::from_str("tags=1,2,,3&at=1700000000&color=darkblue&limit=").unwrap();
+        assert_eq!(query.tags, vec![1, 2, 3]);
+        assert_eq!(query.at, UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        assert_eq!(query.color, Color::DarkBlue);
+        assert_eq!(query.limit, None);
+
+        let json: Fields =
+            serde_json::from_str(r#"{"tags": [4, "5"], "at": 1.5, "color": "RED", "limit": 10}"#)
+                .unwrap();
+        assert_eq!(json.tags, vec![4, 5]);
+        assert_eq!(json.at, UNIX_EPOCH + Duration::from_millis(1500));
+        assert_eq!(json.color, Color::Red);
+        assert_eq!(json.limit, Some(10));
+
+        assert!(serde_urlencoded::from_str::<Fields>("at=1&color=green").is_err());
+        assert!(serde_urlencoded::from_str::<Fields>("tags=1,x&at=1&color=red").is_err());
+    }
+
+    #[test]
+    fn test_parse_rfc3339_and_cents() {
+        let at = |secs: u64| Some(UNIX_EPOCH + Duration::from_secs(secs));
+        assert_eq!(parse_rfc3339("1970-01-01T00:00:00Z"), at(0));
+        assert_eq!(parse_rfc3339("2024-02-29T12:00:00Z"), at(1_709_208_000));
+        assert_eq!(
+            parse_rfc3339("2024-02-29T14:30:00+02:30"),
+            at(1_709_208_000)
+        );
+        assert_eq!(
+            parse_rfc3339("2024-02-29T12:00:00.25Z"),
+            Some(UNIX_EPOCH + Duration::from_millis(1_709_208_000_250))
+        );
+        assert_eq!(parse_rfc3339("2023-02-29T12:00:00Z"), None);
+        assert_eq!(parse_rfc3339("2024-02-29 12:00:00"), None);
+
+        assert_eq!(parse_cents("19.99"), Some(1999));
+        assert_eq!(parse_cents("-0.5"), Some(-50));
+        assert_eq!(parse_cents("7"), Some(700));
+        assert_eq!(parse_cents("1.999"), None);
+        assert_eq!(parse_cents("1e3"), None);
+    }
+}
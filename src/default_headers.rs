@@ -0,0 +1,150 @@
+//! Default response headers.
+//!
+//! [`DefaultHeaders`] adds configured headers to every response that does
+//! not already carry them. Attached to a [`Router`](crate::Router) it
+//! overrides the app-wide defaults for that group, since the innermost
+//! layer sets a header first; [`omit`](DefaultHeaders::omit) keeps an
+//! outer layer from adding a header at all.
+//!
+//! ```rust
+//! use rust_api::{Req, Router, RustApi, default_headers::DefaultHeaders};
+//!
+//! let mut app = RustApi::new();
+//! app.attach_pre_routing(
+//!     DefaultHeaders::new()
+//!         .server("acme")
+//!         .app_version(env!("CARGO_PKG_VERSION"))
+//!         .header("x-frame-options", "DENY"),
+//! );
+//!
+//! let mut embeds = Router::new();
+//! embeds.attach(DefaultHeaders::new().omit("x-frame-options"));
+//! embeds.get("/widget", |_req: Req| async { "widget" });
+//! app.nest("/embed", embeds);
+//! ```
+
+use async_trait::async_trait;
+use hyper::header::{self, HeaderName, HeaderValue};
+use std::sync::Arc;
+
+use crate::{Middleware, Next, Req, Res};
+
+/// Headers an inner [`DefaultHeaders`] told outer ones not to add.
+#[derive(Clone, Default)]
+struct Omitted(Vec<HeaderName>);
+
+/// Middleware adding headers missing from responses.
+#[derive(Debug, Clone, Default)]
+pub struct DefaultHeaders {
+    headers: Vec<(HeaderName, HeaderValue)>,
+    omit: Vec<HeaderName>,
+}
+
+impl DefaultHeaders {
+    /// Add no headers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `name: value`. Repeating a name adds several values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` or `value` is not a valid header.
+    pub fn header(mut self, name: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        let name = HeaderName::from_bytes(name.as_ref().as_bytes()).expect("invalid header name");
+        let value = HeaderValue::from_str(value.as_ref()).expect("invalid header value");
+        self.headers.push((name, value));
+        self
+    }
+
+    /// Add `Server: product`.
+    pub fn server(self, product: impl AsRef<str>) -> Self {
+        self.header(header::SERVER, product)
+    }
+
+    /// Add `X-App-Version: version`.
+    pub fn app_version(self, version: impl AsRef<str>) -> Self {
+        self.header("x-app-version", version)
+    }
+
+    /// Keep `name` off responses, including defaults from outer layers.
+    /// Headers set by the handler are kept.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is not a valid header name.
+    pub fn omit(mut self, name: impl AsRef<str>) -> Self {
+        let name = HeaderName::from_bytes(name.as_ref().as_bytes()).expect("invalid header name");
+        self.omit.push(name);
+        self
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> Middleware<S> for DefaultHeaders {
+    async fn handle(&self, req: Req, _state: Arc<S>, next: Next<S>) -> Res {
+        let mut res = next.run(req).await;
+        let mut omitted = res.extensions_mut().remove::<Omitted>().unwrap_or_default();
+        omitted.0.extend(self.omit.iter().cloned());
+
+        // Names present before this layer, so repeated defaults all apply.
+        let present: Vec<bool> = self
+            .headers
+            .iter()
+            .map(|(name, _)| res.headers().contains_key(name) || omitted.0.contains(name))
+            .collect();
+        for ((name, value), present) in self.headers.iter().zip(present) {
+            if !present {
+                res.headers_mut().append(name.clone(), value.clone());
+            }
+        }
+        if !omitted.0.is_empty() {
+            res.extensions_mut().insert(omitted);
+        }
+        res
+    }
+
+    fn name(&self) -> &'static str {
+        "DefaultHeaders"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{TestClient, assert_header};
+    use crate::{Router, RustApi};
+
+    #[tokio::test]
+    async fn test_group_overrides() {
+        let mut app = RustApi::new();
+        app.attach(
+            DefaultHeaders::new()
+                .server("acme")
+                .app_version("1.0")
+                .header("x-frame-options", "DENY"),
+        );
+        app.get("/", |_req: Req| async {
+            Res::text("home").header("server", "custom")
+        });
+        let mut embeds = Router::new();
+        embeds.attach(
+            DefaultHeaders::new()
+                .app_version("2.0-beta")
+                .omit("x-frame-options"),
+        );
+        embeds.get("/widget", |_req: Req| async { "widget" });
+        app.nest("/embed", embeds);
+        let client = TestClient::new(app);
+
+        let res = client.get("/").send().await;
+        assert_header(&res, "server", "custom");
+        assert_header(&res, "x-app-version", "1.0");
+        assert_header(&res, "x-frame-options", "DENY");
+        let res = client.get("/embed/widget").send().await;
+        assert_header(&res, "server", "acme");
+        assert_header(&res, "x-app-version", "2.0-beta");
+        assert!(!res.headers().contains_key("x-frame-options"));
+    }
+}
@@ -0,0 +1,674 @@
+//! Development helpers: auto-reload, request recording and error pages.
+//!
+//! Pairs with `cargo watch -x run`: source changes restart the process
+//! (in-flight requests drain on SIGTERM as usual), while changes in watched
+//! template/static directories are pushed straight to open browser tabs.
+//!
+//! HTML responses get a small script that subscribes to the reload stream.
+//! While the server restarts the page shows a "Reloading…" overlay, and once
+//! a new process answers the page is reloaded. Only the page itself (a GET)
+//! is re-requested; replaying other requests after a restart, even safe
+//! ones, is out of scope. Streamed and already encoded HTML is passed
+//! through without the script.
+//!
+//! ```rust,no_run
+//! use rust_api::{Req, Res, RustApi, dev::DevReload};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let mut app = RustApi::new();
+//!     app.get("/", |_: Req| async { Res::html("<body>Hello</body>") });
+//!
+//!     DevReload::new().watch("static").watch("templates").install(&mut app);
+//!     app.listen(([127, 0, 0, 1], 3000)).await.unwrap();
+//! }
+//! ```
+//!
+//! [`RequestRecorder`] writes matching requests to JSON files that
+//! [`TestClient::replay`](crate::test::TestClient::replay) re-sends against
+//! the app in-process, to reproduce bugs locally:
+//!
+//! ```rust,no_run
+//! use rust_api::{Req, RustApi, dev::RequestRecorder};
+//!
+//! let mut app = RustApi::new();
+//! app.attach(RequestRecorder::new("recordings").filter(|req: &Req| req.path().starts_with("/orders")));
+//! ```
+//!
+//! Under the development [`profile`](crate::profile), 5xx errors requested
+//! from a browser render an HTML page with the error chain, the request,
+//! the latest log lines kept by [`LogTail`] and a link to the route table.
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http_body_util::BodyExt;
+use hyper::StatusCode;
+use hyper::body::Body;
+use hyper::header::{self, HeaderName};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Once, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+use crate::error_handler::ErrorContext;
+use crate::html::escape;
+use crate::{Error, IntoRes, Middleware, Next, Req, Res, Result, RustApi, StreamSender};
+
+/// Path of the server-sent events endpoint used by the reload script.
+pub const RELOAD_PATH: &str = "/__dev/reload";
+
+const RELOAD_SCRIPT: &str = r#"<script>(function(){var boot=null,overlay=null;function show(){if(overlay)return;overlay=document.createElement('div');overlay.textContent='Reloading…';overlay.style.cssText='position:fixed;inset:0;display:flex;align-items:center;justify-content:center;background:rgba(255,255,255,.85);font:16px sans-serif;z-index:2147483647';document.body.appendChild(overlay);}function connect(){var es=new EventSource('/__dev/reload');es.onmessage=function(e){if(e.data==='reload'||(boot&&e.data!==boot)){location.reload();return;}boot=e.data;if(overlay){overlay.remove();overlay=null;}};es.onerror=function(){es.close();show();setTimeout(connect,500);};}connect();})();</script>"#;
+
+/// Development auto-reload helper.
+pub struct DevReload {
+    dirs: Vec<PathBuf>,
+    interval: Duration,
+}
+
+impl DevReload {
+    /// Create with no watched directories and a 500ms poll interval.
+    pub fn new() -> Self {
+        Self {
+            dirs: Vec::new(),
+            interval: Duration::from_millis(500),
+        }
+    }
+
+    /// Watch a directory (recursively) for changes.
+    pub fn watch(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dirs.push(dir.into());
+        self
+    }
+
+    /// Set the polling interval.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Register the reload endpoint and script-injecting middleware.
+    ///
+    /// Does nothing when the app's profile disables hot reload.
+    pub fn install<S: Send + Sync + 'static>(self, app: &mut RustApi<S>) {
+        if !app.profile_allows(|p| p.hot_reload) {
+            log::warn!("Hot reload disabled by profile; not installing DevReload");
+            return;
+        }
+        let (tx, _) = broadcast::channel(16);
+        let shared = Arc::new(Shared {
+            boot_id: uuid::Uuid::new_v4().to_string(),
+            dirs: self.dirs,
+            interval: self.interval,
+            tx,
+            watcher: Once::new(),
+        });
+
+        let endpoint = Arc::clone(&shared);
+        app.get(RELOAD_PATH, move |_: Req| {
+            let shared = Arc::clone(&endpoint);
+            async move { shared.subscribe() }
+        });
+        app.attach(InjectScript);
+    }
+}
+
+impl Default for DevReload {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Shared {
+    boot_id: String,
+    dirs: Vec<PathBuf>,
+    interval: Duration,
+    tx: broadcast::Sender<()>,
+    watcher: Once,
+}
+
+impl Shared {
+    fn subscribe(self: Arc<Self>) -> Res {
+        // Spawned lazily so `install` can run before the runtime starts.
+        self.watcher.call_once(|| {
+            tokio::spawn(watch_dirs(
+                self.dirs.clone(),
+                self.interval,
+                self.tx.clone(),
+            ));
+        });
+
+        let mut rx = self.tx.subscribe();
+        let boot_id = self.boot_id.clone();
+
+        Res::stream(move |mut tx: StreamSender| async move {
+            if tx
+                .send_text(format!("data: {}\n\n", boot_id))
+                .await
+                .is_err()
+            {
+                return;
+            }
+            while rx.recv().await.is_ok() {
+                if tx.send_text("data: reload\n\n").await.is_err() {
+                    return;
+                }
+            }
+        })
+        .header("content-type", "text/event-stream")
+        .header("cache-control", "no-cache")
+    }
+}
+
+async fn watch_dirs(dirs: Vec<PathBuf>, interval: Duration, tx: broadcast::Sender<()>) {
+    let scan = |dirs: Vec<PathBuf>| async move {
+        tokio::task::spawn_blocking(move || snapshot(&dirs))
+            .await
+            .unwrap_or_default()
+    };
+
+    let mut last = scan(dirs.clone()).await;
+    loop {
+        tokio::time::sleep(interval).await;
+        let current = scan(dirs.clone()).await;
+        if current != last {
+            log::debug!("dev reload: watched files changed");
+            let _ = tx.send(());
+            last = current;
+        }
+    }
+}
+
+fn snapshot(dirs: &[PathBuf]) -> HashMap<PathBuf, SystemTime> {
+    let mut files = HashMap::new();
+    for dir in dirs {
+        collect_mtimes(dir, &mut files);
+    }
+    files
+}
+
+fn collect_mtimes(dir: &Path, files: &mut HashMap<PathBuf, SystemTime>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        match entry.metadata() {
+            Ok(meta) if meta.is_dir() => collect_mtimes(&path, files),
+            Ok(meta) => {
+                if let Ok(modified) = meta.modified() {
+                    files.insert(path, modified);
+                }
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+/// Middleware injecting the reload script into buffered, unencoded HTML
+/// responses.
+struct InjectScript;
+
+#[async_trait]
+impl<S: Send + Sync + 'static> Middleware<S> for InjectScript {
+    async fn handle(&self, req: Req, _state: Arc<S>, next: Next<S>) -> Res {
+        let res = next.run(req).await;
+
+        let is_html = res
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|ct| ct.starts_with("text/html"))
+            .unwrap_or(false);
+        if !is_html || res.headers().contains_key(header::CONTENT_ENCODING) {
+            return res;
+        }
+
+        let (mut parts, body) = res.into_hyper().into_parts();
+        // Buffering a stream would hold back every chunk until it ends.
+        if body.size_hint().exact().is_none() {
+            return Res::from_hyper(hyper::Response::from_parts(parts, body));
+        }
+        let html = match body.collect().await {
+            Ok(collected) => collected.to_bytes(),
+            Err(e) => return e.into_res(),
+        };
+
+        parts.headers.remove(header::CONTENT_LENGTH);
+        let body = http_body_util::Full::new(inject_script(&html))
+            .map_err(|e| match e {})
+            .boxed();
+        Res::from_hyper(hyper::Response::from_parts(parts, body))
+    }
+}
+
+fn inject_script(html: &[u8]) -> Bytes {
+    const CLOSE_BODY: &[u8] = b"</body>";
+
+    let position = html
+        .windows(CLOSE_BODY.len())
+        .rposition(|w| w.eq_ignore_ascii_case(CLOSE_BODY))
+        .unwrap_or(html.len());
+
+    let mut out = Vec::with_capacity(html.len() + RELOAD_SCRIPT.len());
+    out.extend_from_slice(&html[..position]);
+    out.extend_from_slice(RELOAD_SCRIPT.as_bytes());
+    out.extend_from_slice(&html[position..]);
+    Bytes::from(out)
+}
+
+/// Request captured by [`RequestRecorder`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    /// Request method.
+    pub method: String,
+    /// Request URI (path and query).
+    pub uri: String,
+    /// Headers in arrival order; redacted values are replaced.
+    pub headers: Vec<(String, String)>,
+    /// UTF-8 body.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    /// Hex-encoded body, used when the body is not UTF-8.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_hex: Option<String>,
+}
+
+impl Recording {
+    /// Load a recording from a JSON file.
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let data = tokio::fs::read(path.as_ref()).await?;
+        serde_json::from_slice(&data)
+            .map_err(|e| Error::bad_request(format!("Invalid recording: {}", e)))
+    }
+
+    /// Decoded request body.
+    pub fn body_bytes(&self) -> Result<Bytes> {
+        if let Some(hex) = &self.body_hex {
+            return decode_hex(hex)
+                .map(Bytes::from)
+                .ok_or_else(|| Error::bad_request("Invalid hex body in recording"));
+        }
+        Ok(self.body.clone().map(Bytes::from).unwrap_or_default())
+    }
+}
+
+type RequestFilter = Arc<dyn Fn(&Req) -> bool + Send + Sync>;
+
+/// Middleware writing matching requests to `{dir}/{millis}-{id}.json`.
+///
+/// `Authorization`, `Cookie` and `Proxy-Authorization` values are redacted
+/// by default. Meant for debugging; bodies are buffered in memory.
+pub struct RequestRecorder {
+    dir: PathBuf,
+    filter: Option<RequestFilter>,
+    redact: Vec<HeaderName>,
+}
+
+impl RequestRecorder {
+    /// Record every request into `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            filter: None,
+            redact: vec![
+                header::AUTHORIZATION,
+                header::COOKIE,
+                header::PROXY_AUTHORIZATION,
+            ],
+        }
+    }
+
+    /// Only record requests matching `filter`.
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&Req) -> bool + Send + Sync + 'static,
+    {
+        self.filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Replace the set of redacted headers.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a name is not a valid header name.
+    pub fn redact_headers<I, N>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = N>,
+        N: AsRef<str>,
+    {
+        self.redact = names
+            .into_iter()
+            .map(|name| {
+                HeaderName::from_bytes(name.as_ref().as_bytes()).expect("invalid header name")
+            })
+            .collect();
+        self
+    }
+
+    fn record(&self, req: &Req, body: &[u8]) -> Recording {
+        let headers = req
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                let value = if self.redact.contains(name) {
+                    "[redacted]".to_string()
+                } else {
+                    String::from_utf8_lossy(value.as_bytes()).into_owned()
+                };
+                (name.as_str().to_string(), value)
+            })
+            .collect();
+        let (text, hex) = match std::str::from_utf8(body) {
+            Ok("") => (None, None),
+            Ok(text) => (Some(text.to_string()), None),
+            Err(_) => (None, Some(encode_hex(body))),
+        };
+        Recording {
+            method: req.method().to_string(),
+            uri: req
+                .uri()
+                .path_and_query()
+                .map(|pq| pq.as_str())
+                .unwrap_or("/")
+                .to_string(),
+            headers,
+            body: text,
+            body_hex: hex,
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> Middleware<S> for RequestRecorder {
+    async fn handle(&self, mut req: Req, _state: Arc<S>, next: Next<S>) -> Res {
+        if self.filter.as_ref().is_some_and(|filter| !filter(&req)) {
+            return next.run(req).await;
+        }
+        let body = match req.bytes().await {
+            Ok(body) => body,
+            Err(e) => return e.into_res(),
+        };
+        let recording = self.record(&req, &body);
+
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = self
+            .dir
+            .join(format!("{}-{}.json", millis, uuid::Uuid::new_v4().simple()));
+        let written = match serde_json::to_vec_pretty(&recording) {
+            Ok(data) => match tokio::fs::create_dir_all(&self.dir).await {
+                Ok(()) => tokio::fs::write(&path, data).await,
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e.into()),
+        };
+        match written {
+            Ok(()) => log::debug!(
+                "recorded {} {} to {}",
+                recording.method,
+                recording.uri,
+                path.display()
+            ),
+            Err(e) => log::warn!("failed to record request to {}: {}", path.display(), e),
+        }
+
+        next.run(req).await
+    }
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Logger keeping the latest lines for development error pages.
+///
+/// Forwards every record to the wrapped logger, if any.
+///
+/// ```rust,no_run
+/// use rust_api::dev::LogTail;
+///
+/// LogTail::new(50).install(log::LevelFilter::Debug).unwrap();
+/// ```
+pub struct LogTail {
+    capacity: usize,
+    inner: Option<Box<dyn log::Log>>,
+    lines: Mutex<VecDeque<String>>,
+}
+
+static LOG_TAIL: OnceLock<&'static LogTail> = OnceLock::new();
+
+impl LogTail {
+    /// Keep the last `capacity` lines.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: None,
+            lines: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Also pass records to `logger`.
+    pub fn forward<L: log::Log + 'static>(mut self, logger: L) -> Self {
+        self.inner = Some(Box::new(logger));
+        self
+    }
+
+    /// Install as the global logger at `level`.
+    pub fn install(self, level: log::LevelFilter) -> std::result::Result<(), log::SetLoggerError> {
+        let tail: &'static LogTail = Box::leak(Box::new(self));
+        log::set_logger(tail)?;
+        log::set_max_level(level);
+        LOG_TAIL.set(tail).ok();
+        Ok(())
+    }
+
+    fn push(&self, line: String) {
+        let Ok(mut lines) = self.lines.lock() else {
+            return;
+        };
+        if lines.len() == self.capacity {
+            lines.pop_front();
+        }
+        if self.capacity > 0 {
+            lines.push_back(line);
+        }
+    }
+}
+
+impl log::Log for LogTail {
+    fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        self.push(format!(
+            "{:<5} {}: {}",
+            record.level(),
+            record.target(),
+            record.args()
+        ));
+        if let Some(inner) = &self.inner {
+            inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(inner) = &self.inner {
+            inner.flush();
+        }
+    }
+}
+
+/// Lines kept by the installed [`LogTail`], oldest first.
+fn recent_logs() -> Vec<String> {
+    LOG_TAIL
+        .get()
+        .and_then(|tail| {
+            tail.lines
+                .lock()
+                .ok()
+                .map(|lines| lines.iter().cloned().collect())
+        })
+        .unwrap_or_default()
+}
+
+/// Development error page: error chain, request, recent log lines.
+pub(crate) fn error_page(
+    status: StatusCode,
+    error: &Error,
+    context: &ErrorContext,
+    route_table: Option<&str>,
+) -> Res {
+    let mut chain = String::new();
+    let mut source: Option<&dyn std::error::Error> = Some(error);
+    while let Some(err) = source {
+        chain.push_str(&format!("<li>{}</li>", escape(&err.to_string())));
+        source = err.source();
+    }
+
+    let mut headers = String::new();
+    for (name, value) in &context.headers {
+        headers.push_str(&format!(
+            "<tr><th>{}</th><td>{}</td></tr>",
+            escape(name.as_str()),
+            escape(&String::from_utf8_lossy(value.as_bytes()))
+        ));
+    }
+
+    let logs = recent_logs();
+    let logs = if logs.is_empty() {
+        "<p>No log lines captured; install <code>dev::LogTail</code> to see them here.</p>"
+            .to_string()
+    } else {
+        format!("<pre>{}</pre>", escape(&logs.join("\n")))
+    };
+
+    let routes = route_table
+        .map(|path| format!("<p><a href=\"{}\">Route table</a></p>", escape(path)))
+        .unwrap_or_default();
+
+    let title = format!(
+        "{} {}",
+        status.as_u16(),
+        status.canonical_reason().unwrap_or_default()
+    );
+    let html = format!(
+        "<!doctype html><html><head><meta charset=\"utf-8\"><title>{title}</title>\
+         <style>body{{font:14px system-ui,sans-serif;margin:2em;color:#222}}\
+         h1{{color:#b00}}pre,td{{font-family:ui-monospace,monospace}}\
+         th{{text-align:left;padding-right:1em}}pre{{background:#f4f4f4;padding:1em;overflow:auto}}</style>\
+         </head><body><h1>{title}</h1><h2>Error</h2><ol>{chain}</ol>\
+         <h2>Request</h2><p><code>{method} {uri}</code> (route <code>{route}</code>)</p>\
+         <table>{headers}</table><h2>Recent logs</h2>{logs}{routes}</body></html>",
+        method = escape(context.method.as_str()),
+        uri = escape(&context.uri.to_string()),
+        route = escape(context.route.as_deref().unwrap_or("-")),
+    );
+    Res::builder().status(status.as_u16()).html(html).no_store()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inject_before_body_close() {
+        let out = inject_script(b"<html><BODY>hi</BODY></html>");
+        let out = std::str::from_utf8(&out).unwrap();
+        assert!(out.starts_with("<html><BODY>hi<script>"));
+        assert!(out.ends_with("</script></BODY></html>"));
+    }
+
+    #[test]
+    fn test_inject_without_body_tag() {
+        let out = inject_script(b"<p>fragment</p>");
+        let out = std::str::from_utf8(&out).unwrap();
+        assert!(out.starts_with("<p>fragment</p><script>"));
+    }
+
+    #[tokio::test]
+    async fn test_inject_skips_streamed_and_encoded() {
+        use crate::profile::Profile;
+        use crate::test::{TestClient, body_text};
+
+        let mut app = RustApi::new();
+        app.set_profile(Profile::development());
+        app.get("/page", |_req: Req| async { Res::html("<body>hi</body>") });
+        app.get("/encoded", |_req: Req| async {
+            Res::html("<body>hi</body>").header("content-encoding", "identity")
+        });
+        app.get("/stream", |_req: Req| async {
+            Res::stream(|mut tx| async move {
+                tx.send("<body>hi</body>").await.ok();
+            })
+            .header("content-type", "text/html")
+        });
+        DevReload::new().install(&mut app);
+        let client = TestClient::new(app);
+
+        assert!(
+            body_text(client.get("/page").send().await)
+                .await
+                .contains("<script>")
+        );
+        for path in ["/encoded", "/stream"] {
+            let html = body_text(client.get(path).send().await).await;
+            assert_eq!(html, "<body>hi</body>", "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_hex_roundtrip() {
+        let bytes = [0u8, 0x7f, 0xff, 0x10];
+        assert_eq!(encode_hex(&bytes), "007fff10");
+        assert_eq!(decode_hex("007fff10").unwrap(), bytes);
+        assert!(decode_hex("0g").is_none());
+        assert!(decode_hex("abc").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_dev_error_page() {
+        use crate::profile::Profile;
+        use crate::test::{TestClient, assert_status, body_text};
+
+        let mut app = RustApi::new();
+        app.set_profile(Profile::development());
+        app.serve_route_table("/_routes");
+        app.get("/fail", |_req: Req| async {
+            Result::<&str>::Err(Error::Io(std::io::Error::other("disk <full>")))
+        });
+        let client = TestClient::new(app);
+
+        let res = client
+            .get("/fail")
+            .header("accept", "text/html")
+            .send()
+            .await;
+        assert_status(&res, 500);
+        let html = body_text(res).await;
+        assert!(html.contains("<li>IO error: disk &lt;full&gt;</li><li>disk &lt;full&gt;</li>"));
+        assert!(html.contains("<code>GET /fail</code>"));
+        assert!(html.contains("href=\"/_routes\""));
+
+        let res = client.get("/fail").send().await;
+        assert_eq!(body_text(res).await, "IO error: disk <full>");
+    }
+}
@@ -0,0 +1,176 @@
+//! Machine-readable startup and panic records.
+//!
+//! `listen` logs a [`StartupDiagnostics`] record as one JSON line at `info`
+//! level (target `rust_api::startup`). With
+//! `RustApi::set_json_panic_log(true)`, panics are logged as JSON at
+//! `error` level (target `rust_api::panic`) instead of the default stderr
+//! dump, tagged with the request being served:
+//!
+//! ```text
+//! {"event":"panic","message":"boom","location":"src/main.rs:12:9","thread":"tokio-runtime-worker",
+//!  "request":{"method":"GET","path":"/orders/7","request_id":"4f1c...","peer":"10.0.0.5:51544"}}
+//! ```
+//!
+//! The request id is taken from `x-request-id`. Panics in tasks spawned by
+//! a handler are logged without request details.
+
+use serde::Serialize;
+use serde_json::json;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Once;
+
+use crate::{Req, ServerConfig};
+
+/// Summary of a starting server.
+#[derive(Debug, Clone, Serialize)]
+pub struct StartupDiagnostics {
+    /// rust-api version.
+    pub version: &'static str,
+    /// Addresses accepting requests.
+    pub addresses: Vec<SocketAddr>,
+    /// Address of the HTTP-to-HTTPS redirect listener, if any.
+    pub https_redirect: Option<SocketAddr>,
+    /// Whether connections are TLS-terminated in process (rust-api serves
+    /// plain HTTP; TLS terminates at a proxy).
+    pub tls: bool,
+    /// Registered routes.
+    pub routes: usize,
+    /// Enabled cargo features.
+    pub features: Vec<&'static str>,
+    /// Process id.
+    pub pid: u32,
+    /// Effective configuration.
+    pub config: ServerConfig,
+}
+
+impl StartupDiagnostics {
+    pub(crate) fn log(&self) {
+        let mut record = serde_json::to_value(self).unwrap_or_default();
+        record["event"] = json!("startup");
+        log::info!(target: "rust_api::startup", "{}", record);
+    }
+}
+
+/// Cargo features rust-api was built with.
+pub(crate) fn enabled_features() -> Vec<&'static str> {
+    [
+        ("websocket", cfg!(feature = "websocket")),
+        ("console", cfg!(feature = "console")),
+        ("embed", cfg!(feature = "embed")),
+        ("sentry", cfg!(feature = "sentry")),
+        ("sanitize", cfg!(feature = "sanitize")),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect()
+}
+
+/// Request details attached to panics on its task.
+#[derive(Clone)]
+pub(crate) struct PanicContext {
+    method: String,
+    path: String,
+    request_id: Option<String>,
+    peer: Option<SocketAddr>,
+}
+
+impl PanicContext {
+    pub(crate) fn from_req(req: &Req) -> Self {
+        Self {
+            method: req.method().to_string(),
+            path: req.path().to_string(),
+            request_id: req.header("x-request-id").map(str::to_string),
+            peer: req.peer_addr(),
+        }
+    }
+}
+
+tokio::task_local! {
+    static REQUEST: PanicContext;
+}
+
+/// Run `future` with `context` attached to panics it raises.
+pub(crate) async fn with_request<F: Future>(context: PanicContext, future: F) -> F::Output {
+    REQUEST.scope(context, future).await
+}
+
+/// Replace the panic hook with one logging JSON records, once per process.
+///
+/// Falls back to the previous hook when no logger takes `error` records
+/// for `rust_api::panic`, so panics are never silent.
+pub(crate) fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if !log::log_enabled!(target: "rust_api::panic", log::Level::Error) {
+                return previous(info);
+            }
+            let request = REQUEST
+                .try_with(|request| {
+                    json!({
+                        "method": request.method,
+                        "path": request.path,
+                        "request_id": request.request_id,
+                        "peer": request.peer,
+                    })
+                })
+                .ok();
+            let record = json!({
+                "event": "panic",
+                "message": crate::report::panic_message(info.payload()),
+                "location": info.location().map(ToString::to_string),
+                "thread": std::thread::current().name(),
+                "request": request,
+            });
+            log::error!(target: "rust_api::panic", "{}", record);
+        }));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_startup_record() {
+        let diagnostics = StartupDiagnostics {
+            version: env!("CARGO_PKG_VERSION"),
+            addresses: vec![([127, 0, 0, 1], 3000).into()],
+            https_redirect: None,
+            tls: false,
+            routes: 4,
+            features: enabled_features(),
+            pid: 1,
+            config: ServerConfig::default(),
+        };
+        let record = serde_json::to_value(&diagnostics).unwrap();
+        assert_eq!(record["addresses"], json!(["127.0.0.1:3000"]));
+        assert_eq!(record["routes"], 4);
+        assert!(record["config"].is_object());
+    }
+
+    #[tokio::test]
+    async fn test_pre_routing_panic_answers_500() {
+        use crate::test::{TestClient, assert_status};
+        use crate::{Next, Res, RustApi, from_fn};
+        use std::sync::Arc;
+
+        let mut app = RustApi::new();
+        app.set_json_panic_log(true);
+        app.attach_pre_routing(from_fn(
+            |req: Req, _state: Arc<()>, next: Next<()>| async move {
+                if req.path() == "/boom" {
+                    panic!("pre-routing failure");
+                }
+                next.run(req).await
+            },
+        ));
+        app.get("/ok", |_req: Req| async { Res::text("ok") });
+        let client = TestClient::new(app);
+
+        assert_status(&client.get("/boom").send().await, 500);
+        assert_status(&client.get("/ok").send().await, 200);
+    }
+}
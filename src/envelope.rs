@@ -0,0 +1,166 @@
+//! Uniform response envelope.
+//!
+//! [`Envelope`] wraps successful JSON bodies as `{"data": ..., "meta": {...}}`
+//! and error responses as `{"error": {"status": ..., "message": ...}}`.
+//! Attach it to the app or to a [`Router`](crate::Router) to scope it to a
+//! route group.
+//!
+//! ```rust
+//! use rust_api::{Router, envelope::Envelope};
+//! use serde_json::json;
+//!
+//! let mut api = Router::<()>::new();
+//! api.attach(Envelope::new().meta(json!({ "version": "v1" })));
+//! ```
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http_body_util::BodyExt;
+use hyper::{StatusCode, header};
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+use std::sync::Arc;
+
+use crate::{IntoRes, Middleware, Next, Req, Res};
+
+/// Envelope middleware.
+#[derive(Clone)]
+pub struct Envelope {
+    meta: Map<String, Value>,
+    wrap_errors: bool,
+}
+
+impl Envelope {
+    /// Envelope with empty meta that also wraps errors.
+    pub fn new() -> Self {
+        Self {
+            meta: Map::new(),
+            wrap_errors: true,
+        }
+    }
+
+    /// Merge fields into `meta` (must serialize to an object).
+    pub fn meta(mut self, meta: impl Serialize) -> Self {
+        if let Ok(Value::Object(fields)) = serde_json::to_value(meta) {
+            self.meta.extend(fields);
+        }
+        self
+    }
+
+    /// Whether to wrap 4xx/5xx responses in `{"error": ...}` (default true).
+    pub fn wrap_errors(mut self, wrap: bool) -> Self {
+        self.wrap_errors = wrap;
+        self
+    }
+
+    fn wrap_success(&self, body: &[u8]) -> Option<Bytes> {
+        let data: Value = serde_json::from_slice(body).ok()?;
+        let envelope = json!({ "data": data, "meta": self.meta });
+        serde_json::to_vec(&envelope).ok().map(Bytes::from)
+    }
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> Middleware<S> for Envelope {
+    async fn handle(&self, req: Req, _state: Arc<S>, next: Next<S>) -> Res {
+        let res = next.run(req).await;
+
+        let status = res.status_code();
+        let is_json = res
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|ct| ct.starts_with("application/json"))
+            .unwrap_or(false);
+        let wrap = if status.is_success() {
+            is_json && status != StatusCode::NO_CONTENT
+        } else {
+            self.wrap_errors && (status.is_client_error() || status.is_server_error())
+        };
+        if !wrap {
+            return res;
+        }
+
+        let (mut parts, body) = res.into_hyper().into_parts();
+        let body = match body.collect().await {
+            Ok(collected) => collected.to_bytes(),
+            Err(e) => return e.into_res(),
+        };
+
+        let wrapped = if status.is_success() {
+            match self.wrap_success(&body) {
+                Some(wrapped) => wrapped,
+                None => body,
+            }
+        } else {
+            wrap_error(status, is_json, &body)
+        };
+
+        parts.headers.remove(header::CONTENT_LENGTH);
+        parts.headers.insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("application/json"),
+        );
+        let body = http_body_util::Full::new(wrapped)
+            .map_err(|e| match e {})
+            .boxed();
+        Res::from_hyper(hyper::Response::from_parts(parts, body))
+    }
+
+    fn name(&self) -> &'static str {
+        "Envelope"
+    }
+}
+
+/// Build `{"error": ...}` from an error response body.
+fn wrap_error(status: StatusCode, is_json: bool, body: &[u8]) -> Bytes {
+    let error = match serde_json::from_slice::<Value>(body) {
+        Ok(value) if is_json => value,
+        _ => {
+            // Framework errors render as "<code> <message>".
+            let text = String::from_utf8_lossy(body);
+            let text = text.trim();
+            let message = text
+                .strip_prefix(status.as_str())
+                .map(str::trim_start)
+                .filter(|m| !m.is_empty())
+                .or_else(|| Some(text).filter(|t| !t.is_empty()))
+                .or(status.canonical_reason())
+                .unwrap_or_default();
+            json!({ "status": status.as_u16(), "message": message })
+        }
+    };
+    Bytes::from(json!({ "error": error }).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_success() {
+        let envelope = Envelope::new().meta(json!({ "version": 1 }));
+        let body = envelope.wrap_success(br#"[1,2]"#).unwrap();
+        assert_eq!(body, r#"{"data":[1,2],"meta":{"version":1}}"#);
+        assert!(envelope.wrap_success(b"not json").is_none());
+    }
+
+    #[test]
+    fn test_wrap_error() {
+        let text = wrap_error(StatusCode::NOT_FOUND, false, b"404 Route not found");
+        assert_eq!(
+            text,
+            r#"{"error":{"message":"Route not found","status":404}}"#
+        );
+        let empty = wrap_error(StatusCode::FORBIDDEN, false, b"");
+        assert_eq!(empty, r#"{"error":{"message":"Forbidden","status":403}}"#);
+        let json = wrap_error(StatusCode::CONFLICT, true, br#"{"code":"taken"}"#);
+        assert_eq!(json, r#"{"error":{"code":"taken"}}"#);
+    }
+}
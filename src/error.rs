@@ -56,6 +56,11 @@ impl Error {
         Self::Status(422, Some(msg.into()))
     }
 
+    /// Create 429 Too Many Requests.
+    pub fn too_many_requests(msg: impl Into<String>) -> Self {
+        Self::Status(429, Some(msg.into()))
+    }
+
     /// Create 500 Internal Server Error.
     pub fn internal(msg: impl Into<String>) -> Self {
         Self::Status(500, Some(msg.into()))
@@ -80,7 +85,15 @@ impl fmt::Display for Error {
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Hyper(e) => Some(e),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
 impl From<hyper::Error> for Error {
     fn from(err: hyper::Error) -> Self {
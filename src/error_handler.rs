@@ -1,9 +1,93 @@
 //! Error handler trait.
 
+use hyper::{HeaderMap, Method, Uri};
+use std::sync::{Arc, Mutex};
+
+use crate::profile::Profile;
 use crate::{Error, Res};
 
 /// Convert errors to HTTP responses.
+///
+/// Set with `RustApi::set_error_handler`; called for errors returned by
+/// handlers and route middleware.
 pub trait ErrorHandler: Send + Sync + 'static {
     /// Handle error and return response.
     fn handle(&self, error: Error) -> Res;
+
+    /// Handle an error raised while serving the request in `context`.
+    ///
+    /// Defaults to [`handle`](Self::handle).
+    fn handle_request(&self, error: Error, context: &ErrorContext) -> Res {
+        let _ = context;
+        self.handle(error)
+    }
+}
+
+/// Request an error was raised on.
+#[derive(Debug, Clone)]
+pub struct ErrorContext {
+    /// Request method.
+    pub method: Method,
+    /// Request URI.
+    pub uri: Uri,
+    /// Matched route pattern.
+    pub route: Option<Arc<str>>,
+    /// Request headers, with `Authorization`, `Cookie` and
+    /// `Proxy-Authorization` values redacted.
+    pub headers: HeaderMap,
+    /// Active profile, if any.
+    pub profile: Option<Profile>,
+}
+
+/// Error behind a response, set by `Error::into_res` for the error handler.
+#[derive(Clone)]
+pub(crate) struct RaisedError(Arc<Mutex<Option<Error>>>);
+
+impl RaisedError {
+    pub(crate) fn new(error: Error) -> Self {
+        Self(Arc::new(Mutex::new(Some(error))))
+    }
+
+    /// Take the error, once.
+    pub(crate) fn take(&self) -> Option<Error> {
+        self.0.lock().ok()?.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{TestClient, assert_status, body_text};
+    use crate::{Req, RustApi};
+
+    struct Labeled;
+
+    impl ErrorHandler for Labeled {
+        fn handle(&self, error: Error) -> Res {
+            Res::text(error.to_string())
+        }
+
+        fn handle_request(&self, error: Error, context: &ErrorContext) -> Res {
+            Res::builder().status(503).text(format!(
+                "{} {}: {}",
+                context.method,
+                context.route.as_deref().unwrap_or("-"),
+                error
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handler_receives_request_context() {
+        let mut app = RustApi::new();
+        app.set_error_handler(Labeled);
+        app.get("/orders/{id}", |_req: Req| async {
+            crate::Result::<&str>::Err(Error::not_found("no order"))
+        });
+        let client = TestClient::new(app);
+
+        let res = client.get("/orders/7").send().await;
+        assert_status(&res, 503);
+        assert_eq!(body_text(res).await, "GET /orders/{id}: HTTP 404: no order");
+    }
 }
@@ -1,12 +1,39 @@
 //! Type-safe request extractors.
 
-use crate::{Error, Req, Result};
+use crate::req::RequestBody;
+use crate::{Error, PathParams, Req, Result};
 use async_trait::async_trait;
+use bytes::Bytes;
+use futures_util::Stream;
+use hyper::HeaderMap;
+use hyper::body::Body;
 use serde::de::DeserializeOwned;
-use std::collections::HashMap;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::task::{Context, Poll, ready};
 
 /// Extract data from request.
+///
+/// Body extractors read through [`Req::bytes`], which caches the body so
+/// several extractors (and middleware) can share it.
+///
+/// ```rust
+/// use async_trait::async_trait;
+/// use rust_api::{Error, FromRequest, Req, Result};
+/// use std::sync::Arc;
+///
+/// struct Utf8Body(String);
+///
+/// #[async_trait]
+/// impl<S: Send + Sync + 'static> FromRequest<S> for Utf8Body {
+///     async fn from_request(req: &mut Req, _state: &Arc<S>) -> Result<Self> {
+///         let bytes = req.bytes().await?;
+///         String::from_utf8(bytes.to_vec())
+///             .map(Utf8Body)
+///             .map_err(|_| Error::bad_request("Body must be UTF-8"))
+///     }
+/// }
+/// ```
 #[async_trait]
 pub trait FromRequest<S = ()>: Sized {
     /// Extract from request.
@@ -27,7 +54,20 @@ where
     }
 }
 
+/// Extracts nothing, for extractor slots that are not needed.
+#[async_trait]
+impl<S> FromRequest<S> for ()
+where
+    S: Send + Sync + 'static,
+{
+    #[inline]
+    async fn from_request(_req: &mut Req, _state: &Arc<S>) -> Result<Self> {
+        Ok(())
+    }
+}
+
 /// Query parameters extractor.
+#[derive(Clone)]
 pub struct Query<T>(pub T);
 
 #[async_trait]
@@ -50,7 +90,17 @@ where
     }
 }
 
+/// Marks requests whose body extractors tolerate a missing `Content-Type`.
+#[derive(Clone, Copy)]
+pub(crate) struct LenientContentType;
+
+/// Whether an absent `content_type` is accepted for `req`.
+fn missing_allowed(req: &Req, content_type: &str) -> bool {
+    content_type.is_empty() && req.extensions().get::<LenientContentType>().is_some()
+}
+
 /// Form data extractor.
+#[derive(Clone)]
 pub struct Form<T>(pub T);
 
 #[async_trait]
@@ -66,25 +116,53 @@ where
             .and_then(|v| v.to_str().ok())
             .unwrap_or("");
 
-        if !content_type.starts_with("application/x-www-form-urlencoded") {
+        if !content_type.starts_with("application/x-www-form-urlencoded")
+            && !missing_allowed(req, content_type)
+        {
             return Err(Error::bad_request(
                 "Content-Type must be application/x-www-form-urlencoded",
             ));
         }
 
-        let body = req.body().await?;
-        let value = serde_urlencoded::from_bytes::<T>(body.as_ref())
+        let body = req.bytes().await?;
+        let value = serde_urlencoded::from_bytes::<T>(&body)
             .map_err(|e| Error::unprocessable(format!("Invalid form data: {}", e)))?;
 
         Ok(Form(value))
     }
 }
 
-/// JSON request body extractor.
-pub struct Json<T>(pub T);
+/// Form data extractor for bracketed nested fields.
+///
+/// Parses `items[0][name]=x&tags[]=a` into nested structs, sequences and
+/// maps, as emitted by classic HTML forms and libraries such as `qs` and
+/// jQuery. Percent-encoded brackets are accepted; nesting deeper than five
+/// levels is rejected.
+///
+/// ```rust
+/// use rust_api::FormQs;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Item {
+///     name: String,
+///     qty: u32,
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct Order {
+///     items: Vec<Item>,
+/// }
+///
+/// async fn create(FormQs(order): FormQs<Order>) -> String {
+///     format!("{} items", order.items.len())
+/// }
+/// ```
+#[derive(Clone)]
+pub struct FormQs<T>(pub T);
 
 #[async_trait]
-impl<T, S> FromRequest<S> for Json<T>
+impl<T, S> FromRequest<S> for FormQs<T>
 where
     T: DeserializeOwned,
     S: Send + Sync + 'static,
@@ -96,19 +174,290 @@ where
             .and_then(|v| v.to_str().ok())
             .unwrap_or("");
 
-        if !content_type.starts_with("application/json") {
-            return Err(Error::bad_request("Content-Type must be application/json"));
+        if !content_type.starts_with("application/x-www-form-urlencoded") {
+            return Err(Error::bad_request(
+                "Content-Type must be application/x-www-form-urlencoded",
+            ));
+        }
+
+        let body = req.bytes().await?;
+        let value = serde_qs::Config::new(5, false)
+            .deserialize_bytes::<T>(&body)
+            .map_err(|e| Error::unprocessable(format!("Invalid form data: {}", e)))?;
+
+        Ok(FormQs(value))
+    }
+}
+
+/// JSON request body extractor.
+///
+/// Requires `Content-Type: application/json`, plus any types the route
+/// adds with `Route::accept_json`.
+#[derive(Clone)]
+pub struct Json<T>(pub T);
+
+/// Extra content types [`Json`] accepts on the matched route.
+#[derive(Clone)]
+pub(crate) struct JsonContentTypes(pub(crate) Arc<[String]>);
+
+/// Check `req` carries a content type [`Json`] accepts.
+fn check_json_content_type(req: &Req) -> Result<()> {
+    let content_type = req
+        .headers()
+        .get(hyper::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+
+    if !content_type.starts_with("application/json") && !missing_allowed(req, content_type) {
+        let media_type = content_type
+            .split(';')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_ascii_lowercase();
+        let extra = req.extensions().get::<JsonContentTypes>();
+        if !extra.is_some_and(|types| types.0.contains(&media_type)) {
+            let mut allowed = vec!["application/json"];
+            allowed.extend(
+                extra
+                    .iter()
+                    .flat_map(|types| types.0.iter().map(String::as_str)),
+            );
+            return Err(Error::bad_request(format!(
+                "Content-Type must be {}",
+                allowed.join(" or ")
+            )));
         }
+    }
+    Ok(())
+}
 
-        let body = req.body().await?;
-        let value = serde_json::from_slice(body)
+#[async_trait]
+impl<T, S> FromRequest<S> for Json<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync + 'static,
+{
+    async fn from_request(req: &mut Req, _state: &Arc<S>) -> Result<Self> {
+        check_json_content_type(req)?;
+
+        let body = req.bytes().await?;
+        let value = serde_json::from_slice(&body)
             .map_err(|e| Error::bad_request(format!("Invalid JSON: {}", e)))?;
 
         Ok(Json(value))
     }
 }
 
+/// [`Json`] that rejects unknown and duplicate keys with 400, as if every
+/// type had `#[serde(deny_unknown_fields)]`.
+///
+/// The error lists offending keys by path, e.g.
+/// `Unknown fields: address.zip; duplicate fields: name`.
+#[derive(Clone)]
+pub struct StrictJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for StrictJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync + 'static,
+{
+    async fn from_request(req: &mut Req, _state: &Arc<S>) -> Result<Self> {
+        check_json_content_type(req)?;
+
+        let body = req.bytes().await?;
+        let invalid = |e: serde_json::Error| Error::bad_request(format!("Invalid JSON: {}", e));
+        let mut duplicates = Vec::new();
+        let mut de = serde_json::Deserializer::from_slice(&body);
+        serde::de::DeserializeSeed::deserialize(
+            strict::DuplicateKeys {
+                path: String::new(),
+                found: &mut duplicates,
+            },
+            &mut de,
+        )
+        .map_err(invalid)?;
+
+        let mut unknown = Vec::new();
+        let mut de = serde_json::Deserializer::from_slice(&body);
+        let value = serde_ignored::deserialize(&mut de, |path| {
+            unknown.push(strict::field_path(&path));
+        })
+        .map_err(invalid)?;
+        de.end().map_err(invalid)?;
+
+        strict::check(unknown, duplicates)?;
+        Ok(StrictJson(value))
+    }
+}
+
+/// [`Query`] that rejects unknown and repeated parameters with 400.
+#[derive(Clone)]
+pub struct StrictQuery<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for StrictQuery<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync + 'static,
+{
+    async fn from_request(req: &mut Req, _state: &Arc<S>) -> Result<Self> {
+        let query = req
+            .uri()
+            .query()
+            .ok_or_else(|| Error::bad_request("Missing query string"))?;
+
+        let mut seen = std::collections::HashSet::new();
+        let duplicates = form_urlencoded::parse(query.as_bytes())
+            .filter(|(key, _)| !seen.insert(key.clone()))
+            .map(|(key, _)| key.into_owned())
+            .collect();
+
+        let mut unknown = Vec::new();
+        let de = serde_urlencoded::Deserializer::new(form_urlencoded::parse(query.as_bytes()));
+        let value = serde_ignored::deserialize(de, |path| {
+            unknown.push(strict::field_path(&path));
+        })
+        .map_err(|e| Error::bad_request(format!("Invalid query parameters: {}", e)))?;
+
+        strict::check(unknown, duplicates)?;
+        Ok(StrictQuery(value))
+    }
+}
+
+mod strict {
+    use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+    use std::collections::HashSet;
+    use std::fmt;
+
+    use crate::{Error, Result};
+
+    /// Reject if any keys were unknown or repeated.
+    pub(super) fn check(mut unknown: Vec<String>, mut duplicates: Vec<String>) -> Result<()> {
+        let mut problems = Vec::new();
+        for (label, keys) in [("unknown", &mut unknown), ("duplicate", &mut duplicates)] {
+            keys.sort();
+            keys.dedup();
+            if !keys.is_empty() {
+                problems.push(format!("{} fields: {}", label, keys.join(", ")));
+            }
+        }
+        if problems.is_empty() {
+            return Ok(());
+        }
+        let mut message = problems.join("; ");
+        message[..1].make_ascii_uppercase();
+        Err(Error::bad_request(message))
+    }
+
+    /// Dotted path of an ignored value, e.g. `items.0.name`.
+    pub(super) fn field_path(path: &serde_ignored::Path<'_>) -> String {
+        use serde_ignored::Path;
+        let join = |parent: &Path<'_>, segment: &str| {
+            let parent = field_path(parent);
+            if parent.is_empty() {
+                segment.to_string()
+            } else {
+                format!("{}.{}", parent, segment)
+            }
+        };
+        match path {
+            Path::Root => String::new(),
+            Path::Seq { parent, index } => join(parent, &index.to_string()),
+            Path::Map { parent, key } => join(parent, key),
+            Path::Some { parent }
+            | Path::NewtypeStruct { parent }
+            | Path::NewtypeVariant { parent } => field_path(parent),
+        }
+    }
+
+    /// Walks a document collecting keys repeated within one object.
+    pub(super) struct DuplicateKeys<'a> {
+        pub(super) path: String,
+        pub(super) found: &'a mut Vec<String>,
+    }
+
+    impl DuplicateKeys<'_> {
+        fn child(&mut self, segment: &str) -> DuplicateKeys<'_> {
+            let path = if self.path.is_empty() {
+                segment.to_string()
+            } else {
+                format!("{}.{}", self.path, segment)
+            };
+            DuplicateKeys {
+                path,
+                found: self.found,
+            }
+        }
+    }
+
+    impl<'de> DeserializeSeed<'de> for DuplicateKeys<'_> {
+        type Value = ();
+
+        fn deserialize<D: Deserializer<'de>>(self, d: D) -> std::result::Result<(), D::Error> {
+            d.deserialize_any(self)
+        }
+    }
+
+    impl<'de> Visitor<'de> for DuplicateKeys<'_> {
+        type Value = ();
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a JSON value")
+        }
+
+        fn visit_bool<E: de::Error>(self, _: bool) -> std::result::Result<(), E> {
+            Ok(())
+        }
+
+        fn visit_i64<E: de::Error>(self, _: i64) -> std::result::Result<(), E> {
+            Ok(())
+        }
+
+        fn visit_u64<E: de::Error>(self, _: u64) -> std::result::Result<(), E> {
+            Ok(())
+        }
+
+        fn visit_f64<E: de::Error>(self, _: f64) -> std::result::Result<(), E> {
+            Ok(())
+        }
+
+        fn visit_str<E: de::Error>(self, _: &str) -> std::result::Result<(), E> {
+            Ok(())
+        }
+
+        fn visit_unit<E: de::Error>(self) -> std::result::Result<(), E> {
+            Ok(())
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> std::result::Result<(), A::Error> {
+            let mut index = 0usize;
+            while seq
+                .next_element_seed(self.child(&index.to_string()))?
+                .is_some()
+            {
+                index += 1;
+            }
+            Ok(())
+        }
+
+        fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> std::result::Result<(), A::Error> {
+            let mut seen = HashSet::new();
+            while let Some(key) = map.next_key::<String>()? {
+                let child = self.child(&key);
+                if !seen.insert(key) {
+                    child.found.push(child.path.clone());
+                }
+                map.next_value_seed(child)?;
+            }
+            Ok(())
+        }
+    }
+}
+
 /// Path parameters extractor (deserializes HashMap directly).
+#[derive(Clone)]
 pub struct Path<T>(pub T);
 
 #[async_trait]
@@ -131,17 +480,28 @@ where
     }
 }
 
-/// Deserialize HashMap<String, String> directly to T.
+/// Deserialize path parameters directly to T.
 fn deserialize_path_params<T: DeserializeOwned>(
-    params: &HashMap<String, String>,
+    params: &PathParams,
 ) -> std::result::Result<T, serde::de::value::Error> {
     use serde::de::value::MapDeserializer;
-    let deserializer = MapDeserializer::new(params.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+    let deserializer = MapDeserializer::new(params.iter());
     T::deserialize(deserializer)
 }
 
 /// Headers extractor.
-pub struct Headers(pub hyper::HeaderMap);
+///
+/// Shares the request's header map instead of copying it.
+#[derive(Clone)]
+pub struct Headers(pub Arc<hyper::HeaderMap>);
+
+impl std::ops::Deref for Headers {
+    type Target = hyper::HeaderMap;
+
+    fn deref(&self) -> &hyper::HeaderMap {
+        &self.0
+    }
+}
 
 #[async_trait]
 impl<S> FromRequest<S> for Headers
@@ -150,11 +510,59 @@ where
 {
     #[inline]
     async fn from_request(req: &mut Req, _state: &Arc<S>) -> Result<Self> {
-        Ok(Headers(req.headers().clone()))
+        Ok(Headers(req.shared_headers()))
+    }
+}
+
+/// Host the client addressed, honoring `X-Forwarded-Host` from trusted
+/// proxies (see [`Req::host`]). Rejects requests without one with 400.
+#[derive(Debug, Clone)]
+pub struct Host(pub String);
+
+#[async_trait]
+impl<S> FromRequest<S> for Host
+where
+    S: Send + Sync + 'static,
+{
+    async fn from_request(req: &mut Req, _state: &Arc<S>) -> Result<Self> {
+        req.host()
+            .map(|host| Host(host.to_string()))
+            .ok_or_else(|| Error::bad_request("Missing Host header"))
+    }
+}
+
+/// Whether the request is a `HEAD` request.
+///
+/// `HEAD` requests are answered by the `GET` handler when no `HEAD` route
+/// exists, and the body is discarded. Handlers producing expensive bodies
+/// can skip them and set only `Content-Length`:
+///
+/// ```rust
+/// use rust_api::{IsHead, Res};
+///
+/// async fn report(IsHead(head): IsHead) -> Res {
+///     if head {
+///         return Res::new().content_length(1024);
+///     }
+///     Res::text("x".repeat(1024))
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct IsHead(pub bool);
+
+#[async_trait]
+impl<S> FromRequest<S> for IsHead
+where
+    S: Send + Sync + 'static,
+{
+    #[inline]
+    async fn from_request(req: &mut Req, _state: &Arc<S>) -> Result<Self> {
+        Ok(IsHead(req.method() == hyper::Method::HEAD))
     }
 }
 
 /// Raw body bytes extractor.
+#[derive(Clone)]
 pub struct BodyBytes(pub bytes::Bytes);
 
 #[async_trait]
@@ -164,8 +572,432 @@ where
 {
     #[inline]
     async fn from_request(req: &mut Req, _state: &Arc<S>) -> Result<Self> {
-        let body = req.body().await?;
-        Ok(BodyBytes(body.clone()))
+        Ok(BodyBytes(req.bytes().await?))
+    }
+}
+
+/// Text body extractor honoring the `Content-Type` charset.
+///
+/// Decodes UTF-8 (the default), UTF-16 (`utf-16`, `utf-16le`, `utf-16be`;
+/// a byte order mark takes precedence) and Latin-1 (`iso-8859-1`,
+/// `latin1`, `us-ascii`). Invalid bodies and unknown charsets are rejected
+/// with 400.
+#[derive(Debug, Clone)]
+pub struct Text(pub String);
+
+#[async_trait]
+impl<S> FromRequest<S> for Text
+where
+    S: Send + Sync + 'static,
+{
+    async fn from_request(req: &mut Req, _state: &Arc<S>) -> Result<Self> {
+        let charset = req
+            .header("content-type")
+            .and_then(|content_type| {
+                content_type.split(';').skip(1).find_map(|param| {
+                    let (name, value) = param.split_once('=')?;
+                    name.trim()
+                        .eq_ignore_ascii_case("charset")
+                        .then(|| value.trim().trim_matches('"').to_ascii_lowercase())
+                })
+            })
+            .unwrap_or_else(|| "utf-8".to_string());
+        let body = req.bytes().await?;
+        decode_text(&body, &charset).map(Text)
+    }
+}
+
+/// Decode `bytes` in `charset` (lowercase).
+fn decode_text(bytes: &[u8], charset: &str) -> Result<String> {
+    let invalid = || Error::bad_request(format!("Body is not valid {}", charset));
+    match charset {
+        "utf-8" | "utf8" => {
+            let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+            String::from_utf8(bytes.to_vec()).map_err(|_| invalid())
+        }
+        "utf-16" | "utf-16le" | "utf-16be" => {
+            let (big_endian, bytes) = match bytes {
+                [0xFE, 0xFF, rest @ ..] => (true, rest),
+                [0xFF, 0xFE, rest @ ..] => (false, rest),
+                _ => (charset != "utf-16le", bytes),
+            };
+            if bytes.len() % 2 != 0 {
+                return Err(invalid());
+            }
+            let units = bytes.chunks_exact(2).map(|pair| {
+                let pair = [pair[0], pair[1]];
+                if big_endian {
+                    u16::from_be_bytes(pair)
+                } else {
+                    u16::from_le_bytes(pair)
+                }
+            });
+            char::decode_utf16(units)
+                .collect::<std::result::Result<String, _>>()
+                .map_err(|_| invalid())
+        }
+        "iso-8859-1" | "latin1" | "latin-1" => Ok(bytes.iter().map(|&b| char::from(b)).collect()),
+        "us-ascii" | "ascii" if bytes.is_ascii() => {
+            Ok(bytes.iter().map(|&b| char::from(b)).collect())
+        }
+        "us-ascii" | "ascii" => Err(invalid()),
+        _ => Err(Error::bad_request(format!(
+            "Unsupported charset: {}",
+            charset
+        ))),
+    }
+}
+
+/// Runs `T`'s extractor at most once per request and shares the result.
+///
+/// The first successful extraction, in middleware or the handler, stores a
+/// clone in the request extensions; later `Cached<T>` extractions return it
+/// without running `T` again. Failures are not cached. Wrap large values in
+/// an `Arc` to keep the clones cheap.
+///
+/// ```rust
+/// use rust_api::{Cached, Json, Res};
+/// use serde::Deserialize;
+///
+/// #[derive(Clone, Deserialize)]
+/// struct Order {
+///     id: u64,
+/// }
+///
+/// // Deserialized once even if middleware already extracted `Cached<Json<Order>>`.
+/// async fn create(Cached(Json(order)): Cached<Json<Order>>) -> Res {
+///     Res::text(format!("order {}", order.id))
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Cached<T>(pub T);
+
+impl<T> std::ops::Deref for Cached<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+/// Extension key for cached extractor results.
+struct CachedValue<T>(T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for Cached<T>
+where
+    T: FromRequest<S> + Clone + Send + Sync + 'static,
+    S: Send + Sync + 'static,
+{
+    async fn from_request(req: &mut Req, state: &Arc<S>) -> Result<Self> {
+        if let Some(CachedValue(value)) = req.extensions().get::<CachedValue<T>>() {
+            return Ok(Cached(value.clone()));
+        }
+        let value = T::from_request(req, state).await?;
+        req.extensions_mut().insert(CachedValue(value.clone()));
+        Ok(Cached(value))
+    }
+}
+
+/// Streaming body extractor yielding chunks as they arrive.
+///
+/// ```rust,no_run
+/// use rust_api::{BodyStream, Res};
+///
+/// async fn upload(mut body: BodyStream) -> rust_api::Result<Res> {
+///     let mut total = 0;
+///     while let Some(chunk) = body.next().await {
+///         total += chunk?.len();
+///     }
+///     Ok(Res::text(format!("received {} bytes", total)))
+/// }
+/// ```
+pub struct BodyStream {
+    body: RequestBody,
+    limit: Option<usize>,
+    read: usize,
+    trailers: Option<HeaderMap>,
+}
+
+impl BodyStream {
+    pub(crate) fn new(body: RequestBody, limit: Option<usize>) -> Self {
+        Self {
+            body,
+            limit,
+            read: 0,
+            trailers: None,
+        }
+    }
+
+    /// Receive the next chunk, or `None` at end of body.
+    pub async fn next(&mut self) -> Option<Result<Bytes>> {
+        futures_util::StreamExt::next(self).await
+    }
+
+    /// Trailer headers, available once the stream has ended.
+    pub fn trailers(&self) -> Option<&HeaderMap> {
+        self.trailers.as_ref()
+    }
+}
+
+impl Stream for BodyStream {
+    type Item = Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let frame = match ready!(Pin::new(&mut self.body).poll_frame(cx)) {
+                Some(Ok(frame)) => frame,
+                Some(Err(e)) => {
+                    return Poll::Ready(Some(Err(Error::Custom(format!(
+                        "Failed to read body: {}",
+                        e
+                    )))));
+                }
+                None => return Poll::Ready(None),
+            };
+
+            match frame.into_data() {
+                Ok(data) => {
+                    self.read += data.len();
+                    if let Some(limit) = self.limit.filter(|&limit| self.read > limit) {
+                        return Poll::Ready(Some(Err(Error::payload_too_large(format!(
+                            "Request body exceeds limit of {}",
+                            limit
+                        )))));
+                    }
+                    return Poll::Ready(Some(Ok(data)));
+                }
+                Err(frame) => {
+                    if let Ok(trailers) = frame.into_trailers() {
+                        self.trailers = Some(trailers);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequest<S> for BodyStream
+where
+    S: Send + Sync + 'static,
+{
+    #[inline]
+    async fn from_request(req: &mut Req, _state: &Arc<S>) -> Result<Self> {
+        req.body_stream()
+    }
+}
+
+/// Largest array element [`JsonStream`] buffers by default.
+const DEFAULT_MAX_ELEMENT_SIZE: usize = 1024 * 1024;
+
+/// Progress of a [`JsonStream`] through the body.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ArrayState {
+    /// Before the opening `[`.
+    Start,
+    /// Inside the array, after `count` elements.
+    Elements { count: usize },
+    /// After the closing `]`, or after an error.
+    Done,
+}
+
+/// Streaming JSON array extractor yielding elements as they arrive.
+///
+/// The body must be a JSON array; only one element is buffered at a time,
+/// so arbitrarily long arrays are read in bounded memory. Elements larger
+/// than 1 MiB (see [`max_element_size`](Self::max_element_size)) are
+/// rejected with 413.
+///
+/// ```rust,no_run
+/// use rust_api::JsonStream;
+///
+/// #[derive(serde::Deserialize)]
+/// struct Row {
+///     id: u64,
+/// }
+///
+/// async fn ingest(mut rows: JsonStream<Row>) -> rust_api::Result<String> {
+///     let mut count = 0;
+///     while let Some(row) = rows.next().await {
+///         let _id = row?.id;
+///         count += 1;
+///     }
+///     Ok(format!("ingested {} rows", count))
+/// }
+/// ```
+pub struct JsonStream<T> {
+    body: BodyStream,
+    buf: Vec<u8>,
+    start: usize,
+    scanned: usize,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    state: ArrayState,
+    max_element_size: usize,
+    _element: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> JsonStream<T> {
+    fn new(body: BodyStream) -> Self {
+        Self {
+            body,
+            buf: Vec::new(),
+            start: 0,
+            scanned: 0,
+            depth: 0,
+            in_string: false,
+            escaped: false,
+            state: ArrayState::Start,
+            max_element_size: DEFAULT_MAX_ELEMENT_SIZE,
+            _element: std::marker::PhantomData,
+        }
+    }
+
+    /// Reject elements larger than `bytes`.
+    pub fn max_element_size(mut self, bytes: usize) -> Self {
+        self.max_element_size = bytes;
+        self
+    }
+
+    /// Receive the next element, or `None` after the last one. The stream
+    /// ends after the first error.
+    pub async fn next(&mut self) -> Option<Result<T>> {
+        loop {
+            match self.scan() {
+                Ok(Some(element)) => return Some(Ok(element)),
+                Ok(None) if self.state == ArrayState::Done => return None,
+                Ok(None) => {}
+                Err(e) => {
+                    self.state = ArrayState::Done;
+                    return Some(Err(e));
+                }
+            }
+            // Only an incomplete element is left in the buffer.
+            let error = if self.buf.len() - self.start > self.max_element_size {
+                self.too_large()
+            } else {
+                match self.body.next().await {
+                    Some(Ok(chunk)) => {
+                        self.compact();
+                        self.buf.extend_from_slice(&chunk);
+                        continue;
+                    }
+                    Some(Err(e)) => e,
+                    None => Error::bad_request("Invalid JSON: unexpected end of array"),
+                }
+            };
+            self.state = ArrayState::Done;
+            return Some(Err(error));
+        }
+    }
+
+    /// Adapt into a `Stream` of elements.
+    pub fn into_stream(self) -> impl Stream<Item = Result<T>> + Send
+    where
+        T: Send,
+    {
+        futures_util::stream::unfold(self, |mut elements| async move {
+            let element = elements.next().await?;
+            Some((element, elements))
+        })
+    }
+
+    fn too_large(&self) -> Error {
+        Error::payload_too_large(format!(
+            "JSON array element exceeds limit of {}",
+            self.max_element_size
+        ))
+    }
+
+    /// Skip the next `len` buffered bytes and reset the scanner.
+    fn consume(&mut self, len: usize) {
+        self.start += len;
+        self.scanned = self.start;
+    }
+
+    /// Drop consumed bytes, once per chunk rather than per element.
+    fn compact(&mut self) {
+        self.buf.drain(..self.start);
+        self.scanned -= self.start;
+        self.start = 0;
+    }
+
+    /// Parse the next complete element from the buffer, if there is one.
+    fn scan(&mut self) -> Result<Option<T>> {
+        let invalid = |msg: &str| Error::bad_request(format!("Invalid JSON: {}", msg));
+        if self.state == ArrayState::Start {
+            let buffered = &self.buf[self.start..];
+            let Some(start) = buffered.iter().position(|b| !b.is_ascii_whitespace()) else {
+                self.consume(buffered.len());
+                return Ok(None);
+            };
+            if buffered[start] != b'[' {
+                return Err(invalid("expected an array"));
+            }
+            self.consume(start + 1);
+            self.state = ArrayState::Elements { count: 0 };
+        }
+        let ArrayState::Elements { count } = self.state else {
+            return Ok(None);
+        };
+
+        while self.scanned < self.buf.len() {
+            let i = self.scanned;
+            self.scanned += 1;
+            let b = self.buf[i];
+            if self.in_string {
+                match b {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => self.in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match b {
+                b'"' => self.in_string = true,
+                b'{' | b'[' => self.depth += 1,
+                b'}' | b']' if self.depth > 0 => self.depth -= 1,
+                b',' | b']' if self.depth == 0 => {
+                    let element = self.buf[self.start..i].trim_ascii();
+                    if element.is_empty() {
+                        if b == b']' && count == 0 {
+                            self.state = ArrayState::Done;
+                            return Ok(None);
+                        }
+                        return Err(invalid("expected an array element"));
+                    }
+                    if element.len() > self.max_element_size {
+                        return Err(self.too_large());
+                    }
+                    let element =
+                        serde_json::from_slice(element).map_err(|e| invalid(&e.to_string()))?;
+                    self.consume(i + 1 - self.start);
+                    self.state = match b {
+                        b']' => ArrayState::Done,
+                        _ => ArrayState::Elements { count: count + 1 },
+                    };
+                    return Ok(Some(element));
+                }
+                b'}' => return Err(invalid("unbalanced brackets")),
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[async_trait]
+impl<T, S> FromRequest<S> for JsonStream<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync + 'static,
+{
+    async fn from_request(req: &mut Req, _state: &Arc<S>) -> Result<Self> {
+        check_json_content_type(req)?;
+        Ok(JsonStream::new(req.body_stream()?))
     }
 }
 
@@ -182,9 +1014,9 @@ mod tests {
             name: String,
         }
 
-        let mut map = HashMap::new();
-        map.insert("id".to_string(), "123".to_string());
-        map.insert("name".to_string(), "alice".to_string());
+        let mut map = PathParams::new();
+        map.insert("id", "123");
+        map.insert("name", "alice");
 
         let result: Params = deserialize_path_params(&map).unwrap();
         assert_eq!(result.id, "123");
@@ -198,10 +1030,389 @@ mod tests {
             id: String,
         }
 
-        let mut map = HashMap::new();
-        map.insert("id".to_string(), "456".to_string());
+        let mut map = PathParams::new();
+        map.insert("id", "456");
 
         let result: Params = deserialize_path_params(&map).unwrap();
         assert_eq!(result.id, "456");
     }
+
+    #[tokio::test]
+    async fn test_extract_from_built_request() {
+        #[derive(serde::Deserialize)]
+        struct Params {
+            id: String,
+        }
+
+        #[derive(serde::Deserialize)]
+        struct Body {
+            name: String,
+        }
+
+        let state = Arc::new(());
+        let mut req = Req::builder()
+            .method(hyper::Method::POST)
+            .uri("/users/7?page=2")
+            .param("id", "7")
+            .json(&serde_json::json!({"name": "alice"}))
+            .extension(42u32)
+            .build();
+
+        let Path(params) = Path::<Params>::from_request(&mut req, &state)
+            .await
+            .unwrap();
+        let Query(query) = Query::<HashMap<String, String>>::from_request(&mut req, &state)
+            .await
+            .unwrap();
+        let Json(body) = Json::<Body>::from_request(&mut req, &state).await.unwrap();
+        assert_eq!(params.id, "7");
+        assert_eq!(query["page"], "2");
+        assert_eq!(body.name, "alice");
+        assert_eq!(req.extensions().get::<u32>(), Some(&42));
+    }
+
+    #[tokio::test]
+    async fn test_body_shared_between_extractors() {
+        #[derive(serde::Deserialize)]
+        struct Body {
+            name: String,
+        }
+
+        let state = Arc::new(());
+        let mut req = Req::builder()
+            .method(hyper::Method::POST)
+            .json(&serde_json::json!({"name": "alice"}))
+            .build();
+
+        let peeked = req.peek_body().await.unwrap();
+        let Json(body) = Json::<Body>::from_request(&mut req, &state).await.unwrap();
+        let BodyBytes(bytes) = BodyBytes::from_request(&mut req, &state).await.unwrap();
+        let mut stream = BodyStream::from_request(&mut req, &state).await.unwrap();
+
+        assert_eq!(body.name, "alice");
+        assert_eq!(bytes, peeked);
+        assert_eq!(stream.next().await.unwrap().unwrap(), peeked);
+    }
+
+    #[tokio::test]
+    async fn test_cached_runs_once() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Clone)]
+        struct Expensive(usize);
+
+        #[async_trait]
+        impl FromRequest for Expensive {
+            async fn from_request(_req: &mut Req, _state: &Arc<()>) -> Result<Self> {
+                Ok(Expensive(RUNS.fetch_add(1, Ordering::SeqCst)))
+            }
+        }
+
+        let state = Arc::new(());
+        let mut req = Req::builder().build();
+        let Cached(first) = Cached::<Expensive>::from_request(&mut req, &state)
+            .await
+            .unwrap();
+        let Cached(second) = Cached::<Expensive>::from_request(&mut req, &state)
+            .await
+            .unwrap();
+
+        assert_eq!(RUNS.load(Ordering::SeqCst), 1);
+        assert_eq!(first.0, second.0);
+    }
+
+    #[tokio::test]
+    async fn test_buffer_after_stream_fails() {
+        let mut req = Req::builder().body("data").build();
+        let _stream = req.body_stream().unwrap();
+        let err = req.bytes().await.unwrap_err();
+        assert!(err.to_string().contains("peek_body"));
+    }
+
+    #[tokio::test]
+    async fn test_form_qs_nested() {
+        #[derive(serde::Deserialize)]
+        struct Item {
+            name: String,
+            qty: u32,
+        }
+
+        #[derive(serde::Deserialize)]
+        struct Order {
+            items: Vec<Item>,
+            tags: Vec<String>,
+        }
+
+        let state = Arc::new(());
+        let mut req = Req::builder()
+            .method(hyper::Method::POST)
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body("items[0][name]=tea&items[0][qty]=2&items%5B1%5D%5Bname%5D=milk&items%5B1%5D%5Bqty%5D=1&tags[]=a&tags[]=b")
+            .build();
+
+        let FormQs(order) = FormQs::<Order>::from_request(&mut req, &state)
+            .await
+            .unwrap();
+        assert_eq!(order.items.len(), 2);
+        assert_eq!(order.items[1].name, "milk");
+        assert_eq!(order.items[0].qty, 2);
+        assert_eq!(order.tags, ["a", "b"]);
+
+        let mut req = Req::builder()
+            .method(hyper::Method::POST)
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body("items[0][qty]=many")
+            .build();
+        let err = FormQs::<Order>::from_request(&mut req, &state)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::Status(422, _)));
+    }
+
+    #[test]
+    fn test_decode_text_charsets() {
+        assert_eq!(
+            decode_text(b"\xEF\xBB\xBFcaf\xC3\xA9", "utf-8").unwrap(),
+            "café"
+        );
+        assert_eq!(decode_text(b"caf\xE9", "iso-8859-1").unwrap(), "café");
+        assert_eq!(decode_text(b"\xFF\xFEh\x00i\x00", "utf-16").unwrap(), "hi");
+        assert_eq!(decode_text(b"\x00h\x00i", "utf-16be").unwrap(), "hi");
+        assert!(decode_text(b"caf\xE9", "utf-8").is_err());
+        assert!(decode_text(b"\x00h\x00", "utf-16").is_err());
+        assert!(decode_text(b"\xE9", "us-ascii").is_err());
+        assert!(decode_text(b"hi", "koi8-r").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_text_uses_content_type_charset() {
+        let state = Arc::new(());
+        let mut req = Req::builder()
+            .method(hyper::Method::POST)
+            .header("content-type", "text/plain; charset=\"UTF-16LE\"")
+            .body(&b"o\x00k\x00"[..])
+            .build();
+        let Text(text) = Text::from_request(&mut req, &state).await.unwrap();
+        assert_eq!(text, "ok");
+    }
+
+    #[tokio::test]
+    async fn test_auto_head_skips_body() {
+        use crate::Res;
+        use crate::test::{TestClient, assert_header, assert_status, body_text};
+
+        let mut app = crate::RustApi::new();
+        app.get("/report", |IsHead(head): IsHead| async move {
+            if head {
+                return Res::new().content_length(5);
+            }
+            Res::text("hello")
+        });
+        app.get("/plain", |_req: Req| async { "plain" });
+        app.post("/submit", |_req: Req| async { "ok" });
+        let client = TestClient::new(app);
+
+        let res = client.request(hyper::Method::HEAD, "/report").send().await;
+        assert_status(&res, 200);
+        assert_header(&res, "content-length", "5");
+        assert_eq!(body_text(res).await, "");
+        let res = client.request(hyper::Method::HEAD, "/plain").send().await;
+        assert_header(&res, "content-length", "5");
+        assert_eq!(body_text(res).await, "");
+        assert_eq!(body_text(client.get("/report").send().await).await, "hello");
+        let res = client.request(hyper::Method::HEAD, "/submit").send().await;
+        assert_status(&res, 405);
+    }
+
+    #[tokio::test]
+    async fn test_json_extra_content_types() {
+        use crate::test::{TestClient, assert_status, body_text};
+
+        let mut app = crate::RustApi::new();
+        app.post("/hook", |Json(value): Json<serde_json::Value>| async move {
+            value["event"].as_str().unwrap_or("-").to_string()
+        })
+        .accept_json(["text/plain"]);
+        app.post(
+            "/strict",
+            |Json(value): Json<serde_json::Value>| async move { value.to_string() },
+        );
+        let client = TestClient::new(app);
+
+        let res = client
+            .post("/hook")
+            .header("content-type", "text/plain; charset=utf-8")
+            .body(r#"{"event":"push"}"#)
+            .send()
+            .await;
+        assert_eq!(body_text(res).await, "push");
+        let res = client
+            .post("/hook")
+            .header("content-type", "text/html")
+            .body("{}")
+            .send()
+            .await;
+        assert_status(&res, 400);
+        assert!(
+            body_text(res)
+                .await
+                .contains("application/json or text/plain")
+        );
+        let res = client
+            .post("/strict")
+            .header("content-type", "text/plain")
+            .body("{}")
+            .send()
+            .await;
+        assert_status(&res, 400);
+    }
+
+    #[tokio::test]
+    async fn test_lenient_missing_content_type() {
+        #[derive(serde::Deserialize)]
+        struct Login {
+            user: String,
+        }
+
+        let state = Arc::new(());
+        let mut req = Req::builder()
+            .method(hyper::Method::POST)
+            .body(r#"{"user":"ann"}"#)
+            .build();
+        assert!(Json::<Login>::from_request(&mut req, &state).await.is_err());
+        req.extensions_mut().insert(LenientContentType);
+        let Json(login) = Json::<Login>::from_request(&mut req, &state).await.unwrap();
+        assert_eq!(login.user, "ann");
+
+        let mut req = Req::builder()
+            .method(hyper::Method::POST)
+            .header("content-type", "text/plain")
+            .body("user=bob")
+            .build();
+        req.extensions_mut().insert(LenientContentType);
+        assert!(Form::<Login>::from_request(&mut req, &state).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_strict_extractors() {
+        #[derive(Debug, serde::Deserialize)]
+        struct Item {
+            name: String,
+            #[serde(default)]
+            tags: Vec<Tag>,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        struct Tag {
+            #[allow(dead_code)]
+            label: String,
+        }
+
+        let state = Arc::new(());
+        let json = |body: &str| {
+            Req::builder()
+                .header("content-type", "application/json")
+                .body(body.to_string())
+                .build()
+        };
+        let error = |e: Error| e.to_string();
+
+        let mut req = json(r#"{"name": "a", "tags": [{"label": "x"}]}"#);
+        let StrictJson(item) = StrictJson::<Item>::from_request(&mut req, &state)
+            .await
+            .unwrap();
+        assert_eq!((item.name.as_str(), item.tags.len()), ("a", 1));
+
+        let mut req = json(r#"{"name": "a", "extra": 1, "tags": [{"label": "x", "color": 2}]}"#);
+        let err = StrictJson::<Item>::from_request(&mut req, &state).await;
+        assert_eq!(
+            error(err.err().unwrap()),
+            "HTTP 400: Unknown fields: extra, tags.0.color"
+        );
+        let mut req = json(r#"{"name": "a", "name": "b"}"#);
+        let err = StrictJson::<serde_json::Value>::from_request(&mut req, &state).await;
+        assert_eq!(
+            error(err.err().unwrap()),
+            "HTTP 400: Duplicate fields: name"
+        );
+
+        let mut req = Req::builder().uri("/?name=a&page=2&name=b").build();
+        let err = StrictQuery::<HashMap<String, String>>::from_request(&mut req, &state).await;
+        assert_eq!(
+            error(err.err().unwrap()),
+            "HTTP 400: Duplicate fields: name"
+        );
+        let mut req = Req::builder().uri("/?name=a&page=2").build();
+        let err = StrictQuery::<Item>::from_request(&mut req, &state).await;
+        assert_eq!(error(err.err().unwrap()), "HTTP 400: Unknown fields: page");
+    }
+
+    #[tokio::test]
+    async fn test_json_stream_elements() {
+        async fn stream(body: &str) -> Vec<std::result::Result<serde_json::Value, String>> {
+            let mut req = Req::builder()
+                .header("content-type", "application/json")
+                .body(body.to_string())
+                .build();
+            let mut stream = JsonStream::from_request(&mut req, &Arc::new(()))
+                .await
+                .unwrap()
+                .max_element_size(64);
+            let mut items = Vec::new();
+            while let Some(item) = stream.next().await {
+                items.push(item.map_err(|e| e.to_string()));
+            }
+            items
+        }
+
+        let items = stream(r#" [1, "a,]\"", {"b": [2, 3]}, [] ] "#).await;
+        assert_eq!(
+            items,
+            vec![
+                Ok(serde_json::json!(1)),
+                Ok(serde_json::json!("a,]\"")),
+                Ok(serde_json::json!({"b": [2, 3]})),
+                Ok(serde_json::json!([])),
+            ]
+        );
+        assert!(stream("[]").await.is_empty());
+
+        let items = stream("[1, 2").await;
+        assert_eq!(items.len(), 2);
+        assert_eq!(
+            items[1],
+            Err("HTTP 400: Invalid JSON: unexpected end of array".to_string())
+        );
+        let items = stream("[1,,2]").await;
+        assert_eq!(items.len(), 2);
+        assert!(items[1].is_err());
+        let items = stream(&format!("[\"{}\"]", "x".repeat(100))).await;
+        assert!(items[0].as_ref().unwrap_err().starts_with("HTTP 413"));
+        assert!(stream("{}").await[0].is_err());
+    }
+
+    #[test]
+    fn test_json_stream_split_chunks() {
+        let body = br#"[1, "a,]", {"b": [2, 3]}, []]"#;
+        for split in 0..body.len() {
+            let mut stream = JsonStream::<serde_json::Value>::new(BodyStream::new(
+                RequestBody::Full(None),
+                None,
+            ));
+            let mut items = Vec::new();
+            for chunk in [&body[..split], &body[split..]] {
+                stream.compact();
+                stream.buf.extend_from_slice(chunk);
+                while let Some(item) = stream.scan().unwrap() {
+                    items.push(item);
+                }
+            }
+            assert_eq!(items.len(), 4, "split at {}", split);
+            assert_eq!(items[2], serde_json::json!({"b": [2, 3]}));
+            assert!(stream.state == ArrayState::Done);
+        }
+    }
 }
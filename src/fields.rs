@@ -0,0 +1,152 @@
+//! Sparse fieldsets.
+
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::{Error, FromRequest, Req, Result};
+
+/// Requested fields from `?fields=id,name,author.name`.
+///
+/// Without a `fields` parameter every field is kept. Pass to
+/// [`Res::json_filtered`](crate::Res::json_filtered) to prune a response.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fields(Option<FieldTree>);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct FieldTree {
+    children: BTreeMap<String, FieldTree>,
+}
+
+impl FieldTree {
+    /// Empty subtree means "keep the whole value".
+    fn keeps_all(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    fn insert(&mut self, path: &str) {
+        let mut node = self;
+        let mut parts = path.split('.').filter(|p| !p.is_empty()).peekable();
+        while let Some(part) = parts.next() {
+            let existed = node.children.contains_key(part);
+            let child = node.children.entry(part.to_string()).or_default();
+            if existed && child.keeps_all() {
+                // A shorter path already selected this whole value.
+                return;
+            }
+            if parts.peek().is_none() {
+                child.children.clear();
+                return;
+            }
+            node = child;
+        }
+    }
+
+    fn prune(&self, value: Value) -> Value {
+        if self.keeps_all() {
+            return value;
+        }
+        match value {
+            Value::Object(map) => Value::Object(
+                map.into_iter()
+                    .filter_map(|(key, value)| {
+                        let child = self.children.get(&key)?;
+                        Some((key, child.prune(value)))
+                    })
+                    .collect(),
+            ),
+            Value::Array(items) => {
+                Value::Array(items.into_iter().map(|item| self.prune(item)).collect())
+            }
+            other => other,
+        }
+    }
+}
+
+impl Fields {
+    /// Keep every field.
+    pub fn all() -> Self {
+        Self(None)
+    }
+
+    /// Parse a comma-separated list of dotted paths.
+    pub fn parse(list: &str) -> Self {
+        let mut fields = Self::all();
+        fields.extend(list);
+        fields
+    }
+
+    fn extend(&mut self, list: &str) {
+        let tree = self.0.get_or_insert_with(FieldTree::default);
+        for path in list.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            tree.insert(path);
+        }
+    }
+
+    /// Whether no selection was made.
+    pub fn is_all(&self) -> bool {
+        self.0.is_none()
+    }
+
+    /// Drop unselected fields from `value` (arrays are pruned per element).
+    pub fn prune(&self, value: Value) -> Value {
+        match &self.0 {
+            Some(tree) => tree.prune(value),
+            None => value,
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> FromRequest<S> for Fields {
+    async fn from_request(req: &mut Req, _state: &Arc<S>) -> Result<Self> {
+        let Some(query) = req.query() else {
+            return Ok(Fields::all());
+        };
+        let params: Vec<(String, String)> = serde_urlencoded::from_str(query)
+            .map_err(|e| Error::bad_request(format!("Invalid query parameters: {}", e)))?;
+
+        let mut fields = Fields::all();
+        for (_, list) in params.iter().filter(|(k, _)| k == "fields") {
+            fields.extend(list);
+        }
+        Ok(fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_prune() {
+        let value = json!([
+            { "id": 1, "name": "a", "secret": "x", "author": { "name": "ada", "email": "e" } },
+            { "id": 2, "name": "b", "author": null }
+        ]);
+        let fields = Fields::parse("id, author.name");
+        assert_eq!(
+            fields.prune(value.clone()),
+            json!([
+                { "id": 1, "author": { "name": "ada" } },
+                { "id": 2, "author": null }
+            ])
+        );
+        assert_eq!(Fields::all().prune(value.clone()), value);
+    }
+
+    #[test]
+    fn test_shorter_path_wins() {
+        let value = json!({ "author": { "name": "ada", "email": "e" } });
+        assert_eq!(
+            Fields::parse("author,author.name").prune(value.clone()),
+            value
+        );
+        assert_eq!(
+            Fields::parse("author.name,author").prune(value.clone()),
+            value
+        );
+    }
+}
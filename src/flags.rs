@@ -0,0 +1,337 @@
+//! Feature flags.
+//!
+//! Attach [`FeatureFlags`] with a [`FlagProvider`] to make flags available
+//! to every request. Handlers read them with the [`Flag`] or
+//! [`FeatureFlags`] extractors, and [`RequireFlag`] hides whole routes
+//! while a flag is off.
+//!
+//! ```rust
+//! use rust_api::{Req, RustApi};
+//! use rust_api::flags::{FeatureFlags, Flag, FlagName, RequireFlag, StaticFlags};
+//!
+//! struct NewCheckout;
+//!
+//! impl FlagName for NewCheckout {
+//!     const NAME: &'static str = "new_checkout";
+//! }
+//!
+//! async fn checkout(flag: Flag<NewCheckout>) -> &'static str {
+//!     if flag.is_enabled() { "new checkout" } else { "old checkout" }
+//! }
+//!
+//! let mut app = RustApi::new();
+//! app.attach(FeatureFlags::new(StaticFlags::new().enable("new_checkout")));
+//! app.get("/checkout", checkout);
+//! app.get("/beta", |_req: Req| async { "beta" })
+//!     .attach(RequireFlag::new("beta"));
+//! ```
+
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::{Error, FromRequest, IntoRes, Middleware, Next, Req, Res, Result};
+
+/// Source of flag values.
+pub trait FlagProvider: Send + Sync + 'static {
+    /// Whether `flag` is on; unknown flags are off.
+    fn is_enabled(&self, flag: &str) -> bool;
+}
+
+impl<P: FlagProvider> FlagProvider for Arc<P> {
+    fn is_enabled(&self, flag: &str) -> bool {
+        (**self).is_enabled(flag)
+    }
+}
+
+/// Fixed set of flags.
+#[derive(Debug, Clone, Default)]
+pub struct StaticFlags(HashMap<String, bool>);
+
+impl StaticFlags {
+    /// Create a set with every flag off.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Turn `flag` on.
+    pub fn enable(self, flag: impl Into<String>) -> Self {
+        self.set(flag, true)
+    }
+
+    /// Turn `flag` off.
+    pub fn disable(self, flag: impl Into<String>) -> Self {
+        self.set(flag, false)
+    }
+
+    /// Set `flag` to `enabled`.
+    pub fn set(mut self, flag: impl Into<String>, enabled: bool) -> Self {
+        self.0.insert(flag.into(), enabled);
+        self
+    }
+}
+
+impl<K: Into<String>> FromIterator<(K, bool)> for StaticFlags {
+    fn from_iter<I: IntoIterator<Item = (K, bool)>>(iter: I) -> Self {
+        Self(iter.into_iter().map(|(k, v)| (k.into(), v)).collect())
+    }
+}
+
+impl FlagProvider for StaticFlags {
+    fn is_enabled(&self, flag: &str) -> bool {
+        self.0.get(flag).copied().unwrap_or(false)
+    }
+}
+
+/// Flags read from environment variables once, at construction.
+///
+/// With prefix `FEATURE_`, `FEATURE_NEW_CHECKOUT=1` turns on
+/// `new_checkout`. `1`, `true`, `on` and `yes` (any case) mean on.
+#[derive(Debug, Clone)]
+pub struct EnvFlags(StaticFlags);
+
+impl EnvFlags {
+    /// Read variables starting with `prefix`.
+    pub fn new(prefix: &str) -> Self {
+        Self::from_vars(prefix, std::env::vars())
+    }
+
+    fn from_vars(prefix: &str, vars: impl IntoIterator<Item = (String, String)>) -> Self {
+        Self(
+            vars.into_iter()
+                .filter_map(|(key, value)| {
+                    let name = key.strip_prefix(prefix)?.to_ascii_lowercase();
+                    let enabled = matches!(
+                        value.trim().to_ascii_lowercase().as_str(),
+                        "1" | "true" | "on" | "yes"
+                    );
+                    Some((name, enabled))
+                })
+                .collect(),
+        )
+    }
+}
+
+impl FlagProvider for EnvFlags {
+    fn is_enabled(&self, flag: &str) -> bool {
+        self.0.is_enabled(flag)
+    }
+}
+
+/// Flags reloaded periodically, e.g. from a remote flag service.
+///
+/// Clones share the same values. If a reload fails the previous values are
+/// kept. The refresh task stops once every clone is dropped.
+#[derive(Debug, Clone)]
+pub struct RefreshingFlags {
+    flags: Arc<RwLock<HashMap<String, bool>>>,
+}
+
+impl RefreshingFlags {
+    /// Load flags with `load`, then reload them every `interval`.
+    ///
+    /// Fails if the first load fails.
+    pub async fn start<F, Fut>(interval: Duration, load: F) -> Result<Self>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<HashMap<String, bool>>> + Send + 'static,
+    {
+        let flags = Arc::new(RwLock::new(load().await?));
+        let weak = Arc::downgrade(&flags);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let result = load().await;
+                let Some(flags) = weak.upgrade() else {
+                    break;
+                };
+                match result {
+                    Ok(loaded) => *flags.write().unwrap() = loaded,
+                    Err(e) => log::warn!("feature flag refresh failed: {}", e),
+                }
+            }
+        });
+        Ok(Self { flags })
+    }
+}
+
+impl FlagProvider for RefreshingFlags {
+    fn is_enabled(&self, flag: &str) -> bool {
+        self.flags
+            .read()
+            .unwrap()
+            .get(flag)
+            .copied()
+            .unwrap_or(false)
+    }
+}
+
+/// Middleware making a flag provider available to requests.
+///
+/// Also an extractor for checking flags by name.
+#[derive(Clone)]
+pub struct FeatureFlags(Arc<dyn FlagProvider>);
+
+impl FeatureFlags {
+    /// Serve flags from `provider`.
+    pub fn new<P: FlagProvider>(provider: P) -> Self {
+        Self(Arc::new(provider))
+    }
+
+    /// Whether `flag` is on.
+    pub fn is_enabled(&self, flag: &str) -> bool {
+        self.0.is_enabled(flag)
+    }
+}
+
+fn flags_of(req: &Req) -> Result<&FeatureFlags> {
+    req.extensions()
+        .get::<FeatureFlags>()
+        .ok_or_else(|| Error::internal("FeatureFlags middleware not attached"))
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> Middleware<S> for FeatureFlags {
+    async fn handle(&self, mut req: Req, _state: Arc<S>, next: Next<S>) -> Res {
+        req.extensions_mut().insert(self.clone());
+        next.run(req).await
+    }
+
+    fn name(&self) -> &'static str {
+        "FeatureFlags"
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> FromRequest<S> for FeatureFlags {
+    async fn from_request(req: &mut Req, _state: &Arc<S>) -> Result<Self> {
+        flags_of(req).cloned()
+    }
+}
+
+/// Name of a flag read with the [`Flag`] extractor.
+pub trait FlagName: Send + Sync + 'static {
+    /// Flag name, e.g. `new_checkout`.
+    const NAME: &'static str;
+}
+
+/// Extractor for whether flag `F` is on; derefs to `bool`.
+pub struct Flag<F> {
+    enabled: bool,
+    _name: PhantomData<fn() -> F>,
+}
+
+impl<F: FlagName> Flag<F> {
+    /// Whether the flag is on.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+impl<F> Deref for Flag<F> {
+    type Target = bool;
+
+    fn deref(&self) -> &bool {
+        &self.enabled
+    }
+}
+
+#[async_trait]
+impl<F: FlagName, S: Send + Sync + 'static> FromRequest<S> for Flag<F> {
+    async fn from_request(req: &mut Req, _state: &Arc<S>) -> Result<Self> {
+        Ok(Flag {
+            enabled: flags_of(req)?.is_enabled(F::NAME),
+            _name: PhantomData,
+        })
+    }
+}
+
+/// Route middleware answering 404 while a flag is off.
+#[derive(Debug, Clone)]
+pub struct RequireFlag {
+    flag: String,
+    status: u16,
+}
+
+impl RequireFlag {
+    /// Require `flag` to be on.
+    pub fn new(flag: impl Into<String>) -> Self {
+        Self {
+            flag: flag.into(),
+            status: 404,
+        }
+    }
+
+    /// Respond with `status` (e.g. 403) instead of 404 while off.
+    pub fn status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> Middleware<S> for RequireFlag {
+    async fn handle(&self, req: Req, _state: Arc<S>, next: Next<S>) -> Res {
+        match flags_of(&req) {
+            Ok(flags) if flags.is_enabled(&self.flag) => next.run(req).await,
+            Ok(_) => Error::status(self.status).into_res(),
+            Err(e) => e.into_res(),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "RequireFlag"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RustApi;
+    use crate::test::{TestClient, assert_status, body_text};
+
+    #[test]
+    fn test_env_flags() {
+        let vars = [
+            ("FEATURE_NEW_CHECKOUT", "true"),
+            ("FEATURE_BETA", "0"),
+            ("OTHER_FLAG", "1"),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()));
+        let flags = EnvFlags::from_vars("FEATURE_", vars);
+        assert!(flags.is_enabled("new_checkout"));
+        assert!(!flags.is_enabled("beta"));
+        assert!(!flags.is_enabled("other_flag"));
+    }
+
+    #[tokio::test]
+    async fn test_extractor_and_route_gate() {
+        struct NewCheckout;
+        impl FlagName for NewCheckout {
+            const NAME: &'static str = "new_checkout";
+        }
+
+        let mut app = RustApi::new();
+        app.attach(FeatureFlags::new(
+            StaticFlags::new().enable("new_checkout").disable("beta"),
+        ));
+        app.get("/checkout", |flag: Flag<NewCheckout>| async move {
+            if *flag { "new" } else { "old" }
+        });
+        app.get("/beta", |_req: Req| async { "beta" })
+            .attach(RequireFlag::new("beta"));
+        app.get("/admin", |_req: Req| async { "admin" })
+            .attach(RequireFlag::new("admin").status(403));
+
+        let client = TestClient::new(app);
+        assert_eq!(body_text(client.get("/checkout").send().await).await, "new");
+        assert_status(&client.get("/beta").send().await, 404);
+        assert_status(&client.get("/admin").send().await, 403);
+    }
+}
@@ -0,0 +1,119 @@
+//! Route guards.
+//!
+//! Guards are predicates checked after a route pattern matches. When a guard
+//! rejects the request, matching falls through to the next route registered
+//! for the same pattern and method, so one path can have header- or
+//! host-based variants. Guarded routes are tried in registration order before
+//! any unguarded route, which serves as the fallback.
+//!
+//! ```rust
+//! use rust_api::{Req, RustApi, guard};
+//!
+//! let mut app = RustApi::new();
+//! app.get("/", |_: Req| async { "api" })
+//!     .guard(guard::host("api.example.com"));
+//! app.post("/items", |_: Req| async { "json" })
+//!     .guard(guard::content_type("application/json"));
+//! app.post("/items", |_: Req| async { "form" });
+//! ```
+
+use crate::Req;
+
+/// Predicate deciding whether a route handles a request.
+pub trait Guard: Send + Sync + 'static {
+    /// Return `true` if the route should handle the request.
+    fn check(&self, req: &Req) -> bool;
+}
+
+impl<F> Guard for F
+where
+    F: Fn(&Req) -> bool + Send + Sync + 'static,
+{
+    fn check(&self, req: &Req) -> bool {
+        self(req)
+    }
+}
+
+/// Require a header to be present.
+pub fn header(name: &'static str) -> impl Guard {
+    move |req: &Req| req.headers().contains_key(name)
+}
+
+/// Require a header to have an exact value.
+pub fn header_value(name: &'static str, value: &'static str) -> impl Guard {
+    move |req: &Req| req.header(name) == Some(value)
+}
+
+/// Require a media type (parameters such as `charset` are ignored).
+pub fn content_type(mime: &'static str) -> impl Guard {
+    move |req: &Req| {
+        req.content_type()
+            .and_then(|ct| ct.split(';').next())
+            .is_some_and(|ct| ct.trim().eq_ignore_ascii_case(mime))
+    }
+}
+
+/// Require a host name (port ignored, case-insensitive).
+///
+/// A leading `*.` matches any subdomain, e.g. `*.example.com`.
+pub fn host(host: &'static str) -> impl Guard {
+    move |req: &Req| host_matches(req, host)
+}
+
+pub(crate) fn host_matches(req: &Req, pattern: &str) -> bool {
+    request_host(req).is_some_and(|host| host_pattern_matches(pattern, host))
+}
+
+fn host_pattern_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => host
+            .len()
+            .checked_sub(suffix.len() + 1)
+            .filter(|&dot| host.as_bytes()[dot] == b'.')
+            .is_some_and(|dot| host[dot + 1..].eq_ignore_ascii_case(suffix)),
+        None => host.eq_ignore_ascii_case(pattern),
+    }
+}
+
+/// Host name of a request, from the URI authority or the `Host` header.
+fn request_host(req: &Req) -> Option<&str> {
+    let authority = match req.uri().host() {
+        Some(host) => host,
+        None => req.header("host")?,
+    };
+    Some(strip_port(authority))
+}
+
+fn strip_port(authority: &str) -> &str {
+    if authority.starts_with('[') {
+        // IPv6 literal: keep the brackets, drop a trailing port.
+        return match authority.find(']') {
+            Some(end) => &authority[..=end],
+            None => authority,
+        };
+    }
+    authority
+        .rsplit_once(':')
+        .map_or(authority, |(host, _port)| host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_port() {
+        assert_eq!(strip_port("example.com:8080"), "example.com");
+        assert_eq!(strip_port("example.com"), "example.com");
+        assert_eq!(strip_port("[::1]:3000"), "[::1]");
+    }
+
+    #[test]
+    fn test_host_patterns() {
+        assert!(host_pattern_matches("api.example.com", "API.example.com"));
+        assert!(host_pattern_matches("*.example.com", "a.example.com"));
+        assert!(host_pattern_matches("*.example.com", "a.b.example.com"));
+        assert!(!host_pattern_matches("*.example.com", "example.com"));
+        assert!(!host_pattern_matches("*.example.com", "badexample.com"));
+    }
+}
@@ -139,3 +139,16 @@ impl_handler!(5, E1, E2, E3, E4, E5);
 impl_handler!(6, E1, E2, E3, E4, E5, E6);
 impl_handler!(7, E1, E2, E3, E4, E5, E6, E7);
 impl_handler!(8, E1, E2, E3, E4, E5, E6, E7, E8);
+
+/// Marker for types implementing [`Handler`] directly.
+pub struct HandlerType;
+
+impl<H, S> IntoHandler<S, HandlerType> for H
+where
+    H: Handler<S>,
+{
+    #[inline]
+    fn into_handler(self) -> Arc<dyn Handler<S>> {
+        Arc::new(self)
+    }
+}
@@ -0,0 +1,246 @@
+//! Request header hardening.
+//!
+//! [`RequestHardening`] checks requests before routing and rejects the
+//! ambiguities request-smuggling attacks rely on, in case a proxy in front
+//! of the app interprets a request differently than hyper:
+//!
+//! - more headers, or longer header values, than configured (431)
+//! - NUL and other control characters in header values (400)
+//! - `Content-Length` values that disagree or are not numbers (400);
+//!   identical duplicates are collapsed into one
+//! - `Transfer-Encoding` combined with `Content-Length`, on HTTP/1.0, or
+//!   not ending in `chunked` (400)
+//!
+//! Rejections carry `Connection: close`: the request body's framing is not
+//! trusted, so the rest of the connection is not read as further requests.
+//!
+//! ```rust
+//! use rust_api::{RustApi, hardening::RequestHardening};
+//!
+//! let mut app = RustApi::new();
+//! app.attach_pre_routing(RequestHardening::new().max_headers(64));
+//! ```
+
+use async_trait::async_trait;
+use hyper::header::{self, HeaderValue};
+use hyper::{StatusCode, Version};
+use std::sync::Arc;
+
+use crate::{Error, IntoRes, Middleware, Next, Req, Res};
+
+/// Pre-routing middleware enforcing header limits and framing rules.
+#[derive(Debug, Clone)]
+pub struct RequestHardening {
+    max_headers: usize,
+    max_value_len: usize,
+}
+
+impl RequestHardening {
+    /// Allow up to 100 headers of up to 8 KiB each.
+    pub fn new() -> Self {
+        Self {
+            max_headers: 100,
+            max_value_len: 8 * 1024,
+        }
+    }
+
+    /// Maximum number of header fields.
+    pub fn max_headers(mut self, max: usize) -> Self {
+        self.max_headers = max;
+        self
+    }
+
+    /// Maximum length of one header value in bytes.
+    pub fn max_header_value_len(mut self, max: usize) -> Self {
+        self.max_value_len = max;
+        self
+    }
+
+    /// Check `req`, collapsing duplicate `Content-Length` headers.
+    fn check(&self, req: &mut Req) -> Result<(), Error> {
+        let too_large = |msg: &str| {
+            Error::Status(
+                StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE.as_u16(),
+                Some(msg.to_string()),
+            )
+        };
+        let headers = req.headers();
+        if headers.len() > self.max_headers {
+            return Err(too_large("Too many headers"));
+        }
+        for (name, value) in headers {
+            if value.len() > self.max_value_len {
+                return Err(too_large(&format!("Header {} too long", name)));
+            }
+            if value
+                .as_bytes()
+                .iter()
+                .any(|&b| (b < 0x20 && b != b'\t') || b == 0x7f)
+            {
+                return Err(Error::bad_request(format!(
+                    "Control character in header {}",
+                    name
+                )));
+            }
+        }
+
+        let lengths: Vec<&HeaderValue> = headers.get_all(header::CONTENT_LENGTH).iter().collect();
+        let length = match lengths.split_first() {
+            Some((first, rest)) => {
+                let parse = |value: &HeaderValue| {
+                    let value = value.to_str().ok()?.trim();
+                    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+                        return None;
+                    }
+                    value.parse::<u64>().ok()
+                };
+                let length =
+                    parse(first).ok_or_else(|| Error::bad_request("Invalid Content-Length"))?;
+                if rest.iter().any(|value| parse(value) != Some(length)) {
+                    return Err(Error::bad_request("Conflicting Content-Length headers"));
+                }
+                Some((length, !rest.is_empty()))
+            }
+            None => None,
+        };
+
+        let codings: Vec<String> = headers
+            .get_all(header::TRANSFER_ENCODING)
+            .iter()
+            .flat_map(|value| value.to_str().unwrap_or("").split(','))
+            .map(|coding| coding.trim().to_ascii_lowercase())
+            .collect();
+        if !codings.is_empty() {
+            if length.is_some() {
+                return Err(Error::bad_request(
+                    "Transfer-Encoding and Content-Length both set",
+                ));
+            }
+            if req.version() == Version::HTTP_10 {
+                return Err(Error::bad_request("Transfer-Encoding on HTTP/1.0"));
+            }
+            if codings.last().map(String::as_str) != Some("chunked") {
+                return Err(Error::bad_request(
+                    "Transfer-Encoding must end with chunked",
+                ));
+            }
+        }
+
+        if let Some((length, true)) = length {
+            req.headers_mut()
+                .insert(header::CONTENT_LENGTH, HeaderValue::from(length));
+        }
+        Ok(())
+    }
+}
+
+impl Default for RequestHardening {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> Middleware<S> for RequestHardening {
+    async fn handle(&self, mut req: Req, _state: Arc<S>, next: Next<S>) -> Res {
+        match self.check(&mut req) {
+            Ok(()) => next.run(req).await,
+            Err(e) => {
+                let mut res = e.into_res();
+                res.headers_mut()
+                    .insert(header::CONNECTION, HeaderValue::from_static("close"));
+                res
+            }
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "RequestHardening"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(headers: &[(&str, &[u8])]) -> Result<Req, u16> {
+        let mut req = Req::builder().method(hyper::Method::POST).build();
+        for (name, value) in headers {
+            let name = header::HeaderName::from_bytes(name.as_bytes()).unwrap();
+            let value = HeaderValue::from_bytes(value).unwrap();
+            req.headers_mut().append(name, value);
+        }
+        RequestHardening::new()
+            .max_headers(4)
+            .check(&mut req)
+            .map(|()| req)
+            .map_err(|e| e.into_res().status_code().as_u16())
+    }
+
+    #[test]
+    fn test_request_hardening() {
+        let req = check(&[("content-length", b"5"), ("content-length", b"5")]).unwrap();
+        assert_eq!(req.headers().get_all("content-length").iter().count(), 1);
+        assert_eq!(
+            check(&[("content-length", b"5"), ("content-length", b"6")]).err(),
+            Some(400)
+        );
+        assert_eq!(check(&[("content-length", b"+5")]).err(), Some(400));
+        assert_eq!(
+            check(&[("transfer-encoding", b"chunked"), ("content-length", b"5")]).err(),
+            Some(400)
+        );
+        assert_eq!(
+            check(&[("transfer-encoding", b"chunked, gzip")]).err(),
+            Some(400)
+        );
+        assert!(check(&[("transfer-encoding", b"gzip, chunked")]).is_ok());
+        let many = [("x-a", &b"1"[..]); 5];
+        assert_eq!(check(&many).err(), Some(431));
+    }
+
+    #[tokio::test]
+    async fn test_rejection_closes_connection() {
+        use hyper::server::conn::http1;
+        use hyper::service::service_fn;
+        use hyper_util::rt::TokioIo;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let mut app = crate::RustApi::new();
+        app.attach_pre_routing(RequestHardening::new().max_headers(2));
+        app.post("/", |_req: Req| async { "ok" });
+        let app = app.into_in_process();
+        let (client, server) = tokio::io::duplex(4096);
+        tokio::spawn(async move {
+            let service = service_fn(move |req| {
+                let app = Arc::clone(&app);
+                async move {
+                    let res = app.handle_in_process(Req::from_hyper(req)).await;
+                    Ok::<_, std::convert::Infallible>(res.into_hyper())
+                }
+            });
+            http1::Builder::new()
+                .serve_connection(TokioIo::new(server), service)
+                .await
+        });
+
+        // The pipelined second request must not be answered.
+        let (mut read, mut write) = tokio::io::split(client);
+        write
+            .write_all(
+                b"POST / HTTP/1.1\r\nHost: x\r\nX-A: 1\r\nContent-Length: 5\r\n\r\n\
+                  hello\
+                  POST / HTTP/1.1\r\nHost: x\r\nContent-Length: 0\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut response = String::new();
+        let read_all = read.read_to_string(&mut response);
+        tokio::time::timeout(std::time::Duration::from_secs(5), read_all)
+            .await
+            .expect("connection left open")
+            .unwrap();
+        assert!(response.starts_with("HTTP/1.1 431"), "{}", response);
+        assert_eq!(response.matches("HTTP/1.").count(), 1, "{}", response);
+    }
+}
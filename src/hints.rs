@@ -0,0 +1,104 @@
+//! Early hints (`103 Early Hints`, RFC 8297).
+//!
+//! hyper cannot send informational responses on the server side, so hints
+//! are written to the socket directly. That is off unless enabled with
+//! `RustApi::set_early_hints(true)`, and even then only on HTTP/1.1
+//! requests without `Expect: 100-continue`, where hyper has nothing
+//! buffered for the connection while the handler runs.
+//!
+//! ```rust,no_run
+//! use rust_api::{EarlyHints, Res};
+//!
+//! async fn page(hints: EarlyHints) -> Res {
+//!     hints.preload(&[("/app.css", "style"), ("/app.js", "script")]).await.ok();
+//!     // ... render the page while the browser fetches assets ...
+//!     Res::html("<html>...</html>").preload("/app.css", "style")
+//! }
+//! ```
+
+use async_trait::async_trait;
+use hyper::header::{self, HeaderMap, HeaderValue};
+use std::sync::{Arc, Weak};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+use crate::extractors::FromRequest;
+use crate::{Error, Req, Result};
+
+/// Handle for sending informational `103 Early Hints` responses.
+///
+/// Hints are only sent when enabled and on HTTP/1.1 connections; elsewhere
+/// sending is a no-op, so handlers can use it unconditionally. The handle
+/// does not keep the connection open.
+#[derive(Clone)]
+pub struct EarlyHints {
+    stream: Option<Arc<Mutex<Weak<TcpStream>>>>,
+}
+
+impl EarlyHints {
+    pub(crate) fn new(stream: Option<Weak<TcpStream>>) -> Self {
+        Self {
+            stream: stream.map(|s| Arc::new(Mutex::new(s))),
+        }
+    }
+
+    /// Check if hints are delivered on this connection.
+    pub fn is_supported(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Send a `103 Early Hints` response with the given headers.
+    ///
+    /// Must be called before the final response is returned.
+    pub async fn send(&self, headers: &HeaderMap) -> Result<()> {
+        let Some(stream) = &self.stream else {
+            return Ok(());
+        };
+
+        let mut head = b"HTTP/1.1 103 Early Hints\r\n".to_vec();
+        for (name, value) in headers {
+            head.extend_from_slice(name.as_str().as_bytes());
+            head.extend_from_slice(b": ");
+            head.extend_from_slice(value.as_bytes());
+            head.extend_from_slice(b"\r\n");
+        }
+        head.extend_from_slice(b"\r\n");
+
+        let stream = stream.lock().await;
+        // The connection is gone; there is no one left to hint.
+        let Some(stream) = stream.upgrade() else {
+            return Ok(());
+        };
+        crate::conn::write_all(&stream, &head).await?;
+        Ok(())
+    }
+
+    /// Send `Link: <url>; rel=preload; as=<kind>` hints.
+    pub async fn preload(&self, assets: &[(&str, &str)]) -> Result<()> {
+        let mut headers = HeaderMap::with_capacity(assets.len());
+        for (url, kind) in assets {
+            headers.append(header::LINK, preload_link(url, kind)?);
+        }
+        self.send(&headers).await
+    }
+}
+
+/// Build a `Link` header value for preloading an asset.
+pub(crate) fn preload_link(url: &str, kind: &str) -> Result<HeaderValue> {
+    HeaderValue::from_str(&format!("<{}>; rel=preload; as={}", url, kind))
+        .map_err(|_| Error::internal(format!("Invalid preload link: {}", url)))
+}
+
+#[async_trait]
+impl<S> FromRequest<S> for EarlyHints
+where
+    S: Send + Sync + 'static,
+{
+    async fn from_request(req: &mut Req, _state: &Arc<S>) -> Result<Self> {
+        Ok(req
+            .extensions()
+            .get::<EarlyHints>()
+            .cloned()
+            .unwrap_or_else(|| EarlyHints::new(None)))
+    }
+}
@@ -0,0 +1,188 @@
+//! Escaping and sanitizing for HTML built from untrusted input.
+//!
+//! [`Markup`] assembles a page from trusted static snippets, escaped text
+//! and (with the `sanitize` feature) sanitized user HTML, so untrusted
+//! strings never reach `Res::html` unescaped:
+//!
+//! ```rust
+//! use rust_api::{Req, RustApi, html::Markup};
+//!
+//! let mut app = RustApi::new();
+//! app.get("/hello/{name}", |req: Req| async move {
+//!     Markup::new()
+//!         .raw("<h1>Hello, ")
+//!         .text(req.param("name").unwrap_or("stranger"))
+//!         .raw("</h1>")
+//! });
+//! ```
+//!
+//! [`Sanitizer`] keeps an allow-list of tags and attributes (by default the
+//! set of safe formatting tags, with links forced to `rel="noopener
+//! noreferrer"`) and drops everything else, including scripts, event
+//! handlers and `javascript:` URLs.
+
+use crate::{IntoRes, Res};
+
+/// Escape `&`, `<`, `>`, `"` and `'` for use in text and quoted attributes.
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#x27;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// HTML document built from trusted snippets and untrusted values.
+#[derive(Debug, Clone, Default)]
+pub struct Markup {
+    html: String,
+}
+
+impl Markup {
+    /// Create empty markup.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append trusted markup. Only string literals are accepted, so user
+    /// input cannot be passed here by accident.
+    pub fn raw(mut self, html: &'static str) -> Self {
+        self.html.push_str(html);
+        self
+    }
+
+    /// Append untrusted text, escaped.
+    pub fn text(mut self, text: &str) -> Self {
+        self.html.push_str(&escape(text));
+        self
+    }
+
+    /// Append untrusted HTML, cleaned by the default [`Sanitizer`].
+    #[cfg(feature = "sanitize")]
+    pub fn sanitized(self, html: &str) -> Self {
+        self.sanitized_with(&Sanitizer::new(), html)
+    }
+
+    /// Append untrusted HTML, cleaned by `sanitizer`.
+    #[cfg(feature = "sanitize")]
+    pub fn sanitized_with(mut self, sanitizer: &Sanitizer, html: &str) -> Self {
+        self.html.push_str(&sanitizer.clean(html));
+        self
+    }
+
+    /// The markup built so far.
+    pub fn as_str(&self) -> &str {
+        &self.html
+    }
+
+    /// Take the markup as a string.
+    pub fn into_string(self) -> String {
+        self.html
+    }
+}
+
+impl IntoRes for Markup {
+    fn into_res(self) -> Res {
+        Res::html(self.html)
+    }
+}
+
+/// Allow-list based HTML sanitizer.
+#[cfg(feature = "sanitize")]
+#[derive(Debug)]
+pub struct Sanitizer {
+    builder: ammonia::Builder<'static>,
+}
+
+#[cfg(feature = "sanitize")]
+impl Sanitizer {
+    /// Allow common formatting tags, links and images over http(s) and
+    /// mailto.
+    pub fn new() -> Self {
+        Self {
+            builder: ammonia::Builder::default(),
+        }
+    }
+
+    /// Allow only `tags`, with no attributes.
+    pub fn only_tags<I: IntoIterator<Item = &'static str>>(mut self, tags: I) -> Self {
+        self.builder
+            .tags(tags.into_iter().collect())
+            .tag_attributes(Default::default())
+            .generic_attributes(Default::default());
+        self
+    }
+
+    /// Additionally allow `tags`.
+    pub fn allow_tags<I: IntoIterator<Item = &'static str>>(mut self, tags: I) -> Self {
+        self.builder.add_tags(tags);
+        self
+    }
+
+    /// Drop `tags` (their text content is kept).
+    pub fn remove_tags<I: IntoIterator<Item = &'static str>>(mut self, tags: I) -> Self {
+        self.builder.rm_tags(tags);
+        self
+    }
+
+    /// Allow `attributes` on `tag`.
+    pub fn allow_attributes<I>(mut self, tag: &'static str, attributes: I) -> Self
+    where
+        I: IntoIterator<Item = &'static str>,
+    {
+        self.builder.add_tag_attributes(tag, attributes);
+        self
+    }
+
+    /// Clean `html`, returning markup safe to embed.
+    pub fn clean(&self, html: &str) -> String {
+        self.builder.clean(html).to_string()
+    }
+}
+
+#[cfg(feature = "sanitize")]
+impl Default for Sanitizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markup_escapes_text() {
+        let markup = Markup::new()
+            .raw("<p title=\"")
+            .text("\" onmouseover=\"x")
+            .raw("\">")
+            .text("<script>alert('hi')</script>")
+            .raw("</p>");
+        assert_eq!(
+            markup.as_str(),
+            "<p title=\"&quot; onmouseover=&quot;x\">&lt;script&gt;alert(&#x27;hi&#x27;)&lt;/script&gt;</p>"
+        );
+    }
+
+    #[cfg(feature = "sanitize")]
+    #[test]
+    fn test_sanitizer_allow_list() {
+        let dirty = r#"<b onclick="x()">bold</b><script>steal()</script><a href="javascript:x()">a</a><em>e</em>"#;
+        assert_eq!(
+            Sanitizer::new().clean(dirty),
+            r#"<b>bold</b><a rel="noopener noreferrer">a</a><em>e</em>"#
+        );
+        assert_eq!(
+            Sanitizer::new().only_tags(["b"]).clean(dirty),
+            "<b>bold</b>ae"
+        );
+    }
+}
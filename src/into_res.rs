@@ -1,5 +1,7 @@
 //! Response conversion trait.
 
+use crate::error_handler::RaisedError;
+use crate::report::ErrorMessage;
 use crate::{Error, Res};
 use std::borrow::Cow;
 
@@ -49,11 +51,17 @@ impl<T: IntoRes> IntoRes for Result<T, Error> {
 
 impl IntoRes for Error {
     fn into_res(self) -> Res {
-        match self {
+        let server_error = match &self {
+            Error::Status(code, _) => *code >= 500,
+            Error::Json(_) => false,
+            Error::Hyper(_) | Error::Io(_) | Error::Custom(_) => true,
+        };
+        let message = server_error.then(|| self.to_string());
+        let mut res = match &self {
             Error::Status(code, Some(msg)) => Res::builder()
-                .status(code)
+                .status(*code)
                 .text(format!("{} {}", code, msg)),
-            Error::Status(code, None) => Res::status(code),
+            Error::Status(code, None) => Res::status(*code),
             Error::Json(e) => Res::builder()
                 .status(400)
                 .text(format!("JSON error: {}", e)),
@@ -61,8 +69,13 @@ impl IntoRes for Error {
                 .status(500)
                 .text(format!("HTTP error: {}", e)),
             Error::Io(e) => Res::builder().status(500).text(format!("IO error: {}", e)),
-            Error::Custom(msg) => Res::builder().status(500).text(msg),
+            Error::Custom(msg) => Res::builder().status(500).text(msg.clone()),
+        };
+        if let Some(message) = message {
+            res.extensions_mut().insert(ErrorMessage(message));
         }
+        res.extensions_mut().insert(RaisedError::new(self));
+        res
     }
 }
 
@@ -0,0 +1,424 @@
+//! JSON:API documents.
+//!
+//! Builders for [JSON:API](https://jsonapi.org) documents and the
+//! [`JsonApi`] responder, which sends them as `application/vnd.api+json`.
+//!
+//! ```rust
+//! use rust_api::jsonapi::{Document, JsonApi, Relationship, Resource, ResourceIdentifier};
+//! use serde_json::json;
+//!
+//! async fn show_article() -> JsonApi<Document> {
+//!     let author = Resource::new("people", "9").attributes(json!({ "name": "Ada" }));
+//!     let article = Resource::new("articles", "1")
+//!         .attributes(json!({ "title": "Hello" }))
+//!         .relationship("author", Relationship::to_one(author.identifier()))
+//!         .link("self", "/articles/1");
+//!
+//!     JsonApi::new(Document::resource(article).include(author))
+//! }
+//! ```
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::{Error, IntoRes, Res};
+
+/// JSON:API media type.
+pub const MEDIA_TYPE: &str = "application/vnd.api+json";
+
+type Links = BTreeMap<String, String>;
+
+/// Top-level document.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Document {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<PrimaryData>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<ErrorObject>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    included: Vec<Resource>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    links: Links,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    meta: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+enum PrimaryData {
+    One(Box<Resource>),
+    Many(Vec<Resource>),
+    Null,
+}
+
+impl Document {
+    /// Document whose primary data is a single resource.
+    pub fn resource(resource: Resource) -> Self {
+        Self {
+            data: Some(PrimaryData::One(Box::new(resource))),
+            ..Self::default()
+        }
+    }
+
+    /// Document whose primary data is a collection.
+    pub fn collection(resources: Vec<Resource>) -> Self {
+        Self {
+            data: Some(PrimaryData::Many(resources)),
+            ..Self::default()
+        }
+    }
+
+    /// Document with `"data": null`.
+    pub fn empty() -> Self {
+        Self {
+            data: Some(PrimaryData::Null),
+            ..Self::default()
+        }
+    }
+
+    /// Error document.
+    pub fn errors(errors: Vec<ErrorObject>) -> Self {
+        Self {
+            errors,
+            ..Self::default()
+        }
+    }
+
+    /// Add a resource to `included`.
+    pub fn include(mut self, resource: Resource) -> Self {
+        self.included.push(resource);
+        self
+    }
+
+    /// Add a top-level link (`self`, `next`, ...).
+    pub fn link(mut self, name: impl Into<String>, href: impl Into<String>) -> Self {
+        self.links.insert(name.into(), href.into());
+        self
+    }
+
+    /// Set top-level meta.
+    pub fn meta(mut self, meta: impl Serialize) -> Self {
+        self.meta = serde_json::to_value(meta).ok();
+        self
+    }
+}
+
+/// Resource object.
+#[derive(Debug, Clone, Serialize)]
+pub struct Resource {
+    #[serde(rename = "type")]
+    kind: String,
+    id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attributes: Option<Value>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    relationships: BTreeMap<String, Relationship>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    links: Links,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    meta: Option<Value>,
+}
+
+impl Resource {
+    /// Resource of type `kind` with `id`.
+    pub fn new(kind: impl Into<String>, id: impl ToString) -> Self {
+        Self {
+            kind: kind.into(),
+            id: id.to_string(),
+            attributes: None,
+            relationships: BTreeMap::new(),
+            links: Links::new(),
+            meta: None,
+        }
+    }
+
+    /// Set attributes from any serializable value (should serialize to an object).
+    pub fn attributes(mut self, attributes: impl Serialize) -> Self {
+        self.attributes = serde_json::to_value(attributes).ok();
+        self
+    }
+
+    /// Add a relationship.
+    pub fn relationship(mut self, name: impl Into<String>, relationship: Relationship) -> Self {
+        self.relationships.insert(name.into(), relationship);
+        self
+    }
+
+    /// Add a resource link.
+    pub fn link(mut self, name: impl Into<String>, href: impl Into<String>) -> Self {
+        self.links.insert(name.into(), href.into());
+        self
+    }
+
+    /// Set resource meta.
+    pub fn meta(mut self, meta: impl Serialize) -> Self {
+        self.meta = serde_json::to_value(meta).ok();
+        self
+    }
+
+    /// Identifier (`type` + `id`) of this resource.
+    pub fn identifier(&self) -> ResourceIdentifier {
+        ResourceIdentifier::new(self.kind.clone(), self.id.clone())
+    }
+}
+
+/// Resource identifier object.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ResourceIdentifier {
+    #[serde(rename = "type")]
+    kind: String,
+    id: String,
+}
+
+impl ResourceIdentifier {
+    /// Identifier for resource `id` of type `kind`.
+    pub fn new(kind: impl Into<String>, id: impl ToString) -> Self {
+        Self {
+            kind: kind.into(),
+            id: id.to_string(),
+        }
+    }
+}
+
+/// Relationship object.
+#[derive(Debug, Clone, Serialize)]
+pub struct Relationship {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Linkage>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    links: Links,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+enum Linkage {
+    One(ResourceIdentifier),
+    Many(Vec<ResourceIdentifier>),
+    Null,
+}
+
+impl Relationship {
+    /// To-one relationship.
+    pub fn to_one(target: impl Into<Option<ResourceIdentifier>>) -> Self {
+        let data = match target.into() {
+            Some(id) => Linkage::One(id),
+            None => Linkage::Null,
+        };
+        Self {
+            data: Some(data),
+            links: Links::new(),
+        }
+    }
+
+    /// To-many relationship.
+    pub fn to_many(targets: Vec<ResourceIdentifier>) -> Self {
+        Self {
+            data: Some(Linkage::Many(targets)),
+            links: Links::new(),
+        }
+    }
+
+    /// Relationship described only by links.
+    pub fn links_only() -> Self {
+        Self {
+            data: None,
+            links: Links::new(),
+        }
+    }
+
+    /// Add a relationship link (`self`, `related`).
+    pub fn link(mut self, name: impl Into<String>, href: impl Into<String>) -> Self {
+        self.links.insert(name.into(), href.into());
+        self
+    }
+}
+
+/// Error object.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ErrorObject {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<ErrorSource>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+enum ErrorSource {
+    #[serde(rename = "pointer")]
+    Pointer(String),
+    #[serde(rename = "parameter")]
+    Parameter(String),
+}
+
+impl ErrorObject {
+    /// Error with HTTP `status`.
+    pub fn new(status: u16) -> Self {
+        Self {
+            status: Some(status.to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// Set the application error code.
+    pub fn code(mut self, code: impl Into<String>) -> Self {
+        self.code = Some(code.into());
+        self
+    }
+
+    /// Set the short summary.
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Set the occurrence-specific explanation.
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    /// Point at the offending document member (e.g. `/data/attributes/title`).
+    pub fn pointer(mut self, pointer: impl Into<String>) -> Self {
+        self.source = Some(ErrorSource::Pointer(pointer.into()));
+        self
+    }
+
+    /// Name the offending query parameter.
+    pub fn parameter(mut self, parameter: impl Into<String>) -> Self {
+        self.source = Some(ErrorSource::Parameter(parameter.into()));
+        self
+    }
+
+    fn status_code(&self) -> Option<u16> {
+        self.status.as_deref().and_then(|s| s.parse().ok())
+    }
+}
+
+impl From<&Error> for ErrorObject {
+    fn from(error: &Error) -> Self {
+        let (status, detail) = match error {
+            Error::Status(code, msg) => (*code, msg.clone()),
+            Error::Json(e) => (400, Some(e.clone())),
+            _ => (500, None),
+        };
+        let title = hyper::StatusCode::from_u16(status)
+            .ok()
+            .and_then(|s| s.canonical_reason())
+            .map(str::to_string);
+        Self {
+            status: Some(status.to_string()),
+            title,
+            detail,
+            ..Self::default()
+        }
+    }
+}
+
+/// Responder sending a body as `application/vnd.api+json`.
+pub struct JsonApi<T> {
+    status: u16,
+    body: T,
+}
+
+impl<T: Serialize> JsonApi<T> {
+    /// 200 response with `body`.
+    pub fn new(body: T) -> Self {
+        Self { status: 200, body }
+    }
+
+    /// Set the response status (e.g. 201 after creating a resource).
+    pub fn status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+}
+
+impl JsonApi<Document> {
+    /// Error document for a framework error, with the matching status.
+    pub fn error(error: &Error) -> Self {
+        Self::from_errors(vec![ErrorObject::from(error)])
+    }
+
+    /// Error document; the status is taken from the first error (default 500).
+    pub fn from_errors(errors: Vec<ErrorObject>) -> Self {
+        let status = errors
+            .first()
+            .and_then(ErrorObject::status_code)
+            .unwrap_or(500);
+        Self {
+            status,
+            body: Document::errors(errors),
+        }
+    }
+}
+
+impl<T: Serialize> IntoRes for JsonApi<T> {
+    fn into_res(self) -> Res {
+        Res::builder()
+            .status(self.status)
+            .header("content-type", MEDIA_TYPE)
+            .json(&self.body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_document_shape() {
+        let author = Resource::new("people", 9);
+        let doc = Document::collection(vec![
+            Resource::new("articles", 1)
+                .attributes(json!({ "title": "Hi" }))
+                .relationship("author", Relationship::to_one(author.identifier())),
+        ])
+        .link("self", "/articles");
+
+        assert_eq!(
+            serde_json::to_value(&doc).unwrap(),
+            json!({
+                "data": [{
+                    "type": "articles",
+                    "id": "1",
+                    "attributes": { "title": "Hi" },
+                    "relationships": { "author": { "data": { "type": "people", "id": "9" } } }
+                }],
+                "links": { "self": "/articles" }
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(Document::empty()).unwrap(),
+            json!({ "data": null })
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let res = JsonApi::from_errors(vec![
+            ErrorObject::new(422)
+                .title("Invalid title")
+                .pointer("/data/attributes/title"),
+        ]);
+        assert_eq!(res.status, 422);
+        assert_eq!(
+            serde_json::to_value(&res.body).unwrap(),
+            json!({ "errors": [{
+                "status": "422",
+                "title": "Invalid title",
+                "source": { "pointer": "/data/attributes/title" }
+            }] })
+        );
+
+        let res = JsonApi::error(&Error::not_found("No article"));
+        assert_eq!(res.status, 404);
+    }
+}
@@ -1,12 +1,12 @@
 //! Web framework for Rust.
 //!
 //! ```rust,no_run
-//! use rust_api::{RustApi, Res};
+//! use rust_api::{Req, Res, RustApi};
 //!
 //! #[tokio::main]
 //! async fn main() {
 //!     let mut app = RustApi::new();
-//!     app.get("/", |_| async { Res::text("Hello") });
+//!     app.get("/", |_: Req| async { Res::text("Hello") });
 //!     app.listen(([127, 0, 0, 1], 3000)).await.unwrap();
 //! }
 //! ```
@@ -14,45 +14,113 @@
 #![warn(missing_docs)]
 #![warn(rust_2018_idioms)]
 
+pub mod access_log;
+pub mod admin;
 mod api;
+pub mod bot;
+pub mod cache;
+mod cache_control;
+pub mod cli;
+pub mod compression;
 mod config;
+mod conn;
+pub mod cors;
+pub mod de;
+pub mod default_headers;
+pub mod dev;
+pub mod diagnostics;
+pub mod envelope;
 mod error;
 pub mod error_handler;
 pub mod extensions;
 pub mod extractors;
+mod fields;
+pub mod flags;
+pub mod guard;
 mod handler;
+pub mod hardening;
+mod hints;
+pub mod html;
 mod into_res;
+pub mod jsonapi;
+pub mod longpoll;
+pub mod maintenance;
+pub mod method_override;
+pub mod metrics;
 mod middleware;
+pub mod pagination;
+mod params;
+pub mod pool;
+pub mod profile;
+pub mod proxy;
+pub mod quota;
+mod redirect;
+pub mod report;
 mod req;
 mod res;
+pub mod resource;
+mod response_headers;
 pub mod route;
+mod route_table;
 mod router;
+pub mod signals;
+pub mod slow;
+pub mod split;
+pub mod static_files;
+pub mod tenant;
+pub mod test;
+pub mod transaction;
+pub mod tus;
+mod upload;
+pub mod versioning;
 
+#[cfg(feature = "websocket")]
+pub mod live;
 #[cfg(feature = "websocket")]
 pub mod websocket;
 
 pub use api::{RustApi, app, app_with_state};
+pub use cache_control::CacheControl;
+pub use cli::Cli;
 pub use config::ServerConfig;
+pub use conn::ConnectionDrain;
 pub use error::{Error, Result};
 pub use error_handler::ErrorHandler;
 pub use extensions::Extensions;
-pub use extractors::{BodyBytes, Form, FromRequest, Headers, Json, Path, Query, State};
+pub use extractors::{
+    BodyBytes, BodyStream, Cached, Form, FormQs, FromRequest, Headers, Host, IsHead, Json,
+    JsonStream, Path, Query, State, StrictJson, StrictQuery, Text,
+};
+pub use fields::Fields;
+pub use guard::Guard;
 pub use handler::{FnHandler, FnHandler1, FnHandler2, FnHandler3, Handler};
+pub use hints::EarlyHints;
+pub use hyper::StatusCode;
 pub use into_res::IntoRes;
 pub use middleware::{Middleware, Next, from_fn, middleware};
-pub use req::Req;
+pub use pagination::{Page, Pagination};
+pub use params::PathParams;
+pub use req::{Req, ReqBuilder};
 pub use res::{Res, ResBuilder, StreamSender};
-pub use route::Route;
+pub use response_headers::ResponseHeaders;
+pub use route::{RateLimit, Route, RouteInfo, RouteOptions};
 pub use router::Router;
+pub use upload::TempFileUpload;
 
 #[cfg(feature = "websocket")]
-pub use websocket::{CloseFrame, Message, WebSocket, WebSocketHandler, WebSocketUpgrade};
+pub use websocket::{
+    CloseFrame, Message, SubProtocol, SubProtocols, WebSocket, WebSocketHandler, WebSocketLimits,
+    WebSocketUpgrade,
+};
 
 /// Common types and traits.
 pub mod prelude {
-    pub use crate::extractors::{BodyBytes, Form, FromRequest, Headers, Json, Path, Query, State};
+    pub use crate::extractors::{
+        BodyBytes, Cached, Form, FormQs, FromRequest, Headers, Host, IsHead, Json, Path, Query,
+        State, StrictJson, StrictQuery, Text,
+    };
     pub use crate::{
         Error, ErrorHandler, Extensions, Handler, IntoRes, Middleware, Next, Req, Res, Result,
-        Route, Router, RustApi, app, app_with_state, from_fn, middleware,
+        Route, Router, RustApi, StatusCode, app, app_with_state, from_fn, middleware,
     };
 }
@@ -0,0 +1,320 @@
+//! Real-time connections with a server-sent events fallback.
+//!
+//! [`Live`] serves one endpoint that speaks WebSocket when the client can
+//! upgrade and falls back to server-sent events plus POST requests when a
+//! proxy blocks upgrades. Handlers get a [`LiveConnection`] with the same
+//! text `send`/`receive` API either way.
+//!
+//! ```rust
+//! use rust_api::{RustApi, live::Live};
+//!
+//! let mut app = RustApi::new();
+//! Live::new(|mut conn| async move {
+//!     while let Ok(Some(text)) = conn.receive().await {
+//!         if conn.send(format!("echo: {}", text)).await.is_err() {
+//!             break;
+//!         }
+//!     }
+//! })
+//! .install(&mut app, "/live");
+//! ```
+//!
+//! Browsers load the client from `{path}/client.js`, which tries WebSocket
+//! first:
+//!
+//! ```html
+//! <script src="/live/client.js"></script>
+//! <script>
+//!   const conn = liveConnect("/live", (text) => console.log(text));
+//!   conn.send("hello");
+//! </script>
+//! ```
+//!
+//! The fallback protocol: `GET {path}` without an upgrade opens an event
+//! stream whose first event is `event: session` carrying a session id;
+//! messages for the server are POSTed as text to `{path}/{session}`.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::websocket::{Message, WebSocket};
+use crate::{Error, IntoRes, Req, Res, Result, RustApi, StreamSender};
+
+type LiveHandler =
+    Arc<dyn Fn(LiveConnection) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+type Sessions = Mutex<HashMap<String, mpsc::Sender<String>>>;
+
+/// Messages buffered per fallback session before POSTs get 429.
+const INBOX_CAPACITY: usize = 64;
+
+const CLIENT_JS: &str = r#"window.liveConnect=function(path,onmessage){var base=new URL(path,location.href).href,queue=[],opened=false,send=function(t){queue.push(t)},close=function(){};function ready(s){send=s;queue.splice(0).forEach(s)}function sse(){var es=new EventSource(base),chain=Promise.resolve();close=function(){es.close()};es.addEventListener('session',function(e){var target=base+'/'+e.data;ready(function(t){chain=chain.then(function(){return fetch(target,{method:'POST',body:t})})})});es.onmessage=function(e){onmessage(e.data)}}try{var ws=new WebSocket(base.replace(/^http/,'ws'));close=function(){ws.close()};ws.onopen=function(){opened=true;ready(function(t){ws.send(t)})};ws.onmessage=function(e){onmessage(e.data)};ws.onclose=function(){if(!opened)sse()}}catch(e){sse()}return{send:function(t){send(t)},close:function(){close()}}};"#;
+
+/// Endpoint serving [`LiveConnection`]s over WebSocket or SSE.
+pub struct Live {
+    handler: LiveHandler,
+    heartbeat: Duration,
+}
+
+impl Live {
+    /// Run `handler` for every connection.
+    pub fn new<F, Fut>(handler: F) -> Self
+    where
+        F: Fn(LiveConnection) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        Self {
+            handler: Arc::new(move |conn| Box::pin(handler(conn))),
+            heartbeat: Duration::from_secs(15),
+        }
+    }
+
+    /// Send an SSE comment this often while idle (default 15 seconds).
+    ///
+    /// Keeps proxies from timing out the stream and detects clients that
+    /// went away.
+    pub fn heartbeat(mut self, interval: Duration) -> Self {
+        self.heartbeat = interval;
+        self
+    }
+
+    /// Register `GET {path}`, `POST {path}/{session}` and
+    /// `GET {path}/client.js`.
+    pub fn install<S: Send + Sync + 'static>(self, app: &mut RustApi<S>, path: &str) {
+        let path = path.trim_end_matches('/');
+        let sessions: Arc<Sessions> = Arc::default();
+
+        let handler = self.handler;
+        let heartbeat = self.heartbeat;
+        let open_sessions = Arc::clone(&sessions);
+        app.get(path, move |req: Req| {
+            let handler = Arc::clone(&handler);
+            let sessions = Arc::clone(&open_sessions);
+            async move { open(req, handler, sessions, heartbeat) }
+        });
+
+        app.post(&format!("{}/{{session}}", path), move |mut req: Req| {
+            let sessions = Arc::clone(&sessions);
+            async move { deliver(&mut req, &sessions).await.into_res() }
+        });
+
+        app.get(&format!("{}/client.js", path), |_req: Req| async {
+            Res::builder()
+                .header("content-type", "text/javascript; charset=utf-8")
+                .text(CLIENT_JS)
+        });
+    }
+}
+
+fn open(req: Req, handler: LiveHandler, sessions: Arc<Sessions>, heartbeat: Duration) -> Res {
+    if req.is_websocket_upgrade() {
+        if let Some(key) = req.websocket_key() {
+            return Res::websocket(key, move |ws| {
+                handler(LiveConnection {
+                    inner: Inner::WebSocket(Box::new(ws)),
+                })
+            });
+        }
+    }
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let (tx, inbox) = mpsc::channel(INBOX_CAPACITY);
+    sessions.lock().unwrap().insert(id.clone(), tx);
+
+    Res::stream(move |mut events: StreamSender| async move {
+        let session = Session {
+            id,
+            sessions,
+            inbox,
+        };
+        if events
+            .send_text(format!("event: session\ndata: {}\n\n", session.id))
+            .await
+            .is_ok()
+        {
+            handler(LiveConnection {
+                inner: Inner::Sse {
+                    events,
+                    session,
+                    heartbeat,
+                },
+            })
+            .await;
+        }
+    })
+    .header("content-type", "text/event-stream")
+    .header("cache-control", "no-cache")
+    .header("x-accel-buffering", "no")
+}
+
+async fn deliver(req: &mut Req, sessions: &Sessions) -> Result<Res> {
+    let session = req.param("session").unwrap_or_default().to_string();
+    let Some(inbox) = sessions.lock().unwrap().get(&session).cloned() else {
+        return Err(Error::not_found("Unknown live session"));
+    };
+    let body = req.bytes().await?;
+    let text = String::from_utf8(body.to_vec())
+        .map_err(|_| Error::bad_request("Message must be UTF-8 text"))?;
+    match inbox.try_send(text) {
+        Ok(()) => Ok(Res::builder().status(202).text("")),
+        Err(mpsc::error::TrySendError::Full(_)) => {
+            Err(Error::too_many_requests("Live session inbox full"))
+        }
+        Err(mpsc::error::TrySendError::Closed(_)) => Err(Error::not_found("Unknown live session")),
+    }
+}
+
+/// Fallback session, removed from the registry when dropped.
+struct Session {
+    id: String,
+    sessions: Arc<Sessions>,
+    inbox: mpsc::Receiver<String>,
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.sessions.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Transport a [`LiveConnection`] runs over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiveTransport {
+    /// Upgraded WebSocket connection.
+    WebSocket,
+    /// Server-sent events for server messages, POSTs for client messages.
+    Sse,
+}
+
+/// Text connection to a client over WebSocket or SSE.
+pub struct LiveConnection {
+    inner: Inner,
+}
+
+enum Inner {
+    WebSocket(Box<WebSocket>),
+    Sse {
+        events: StreamSender,
+        session: Session,
+        heartbeat: Duration,
+    },
+}
+
+impl LiveConnection {
+    /// Transport negotiated with the client.
+    pub fn transport(&self) -> LiveTransport {
+        match self.inner {
+            Inner::WebSocket(_) => LiveTransport::WebSocket,
+            Inner::Sse { .. } => LiveTransport::Sse,
+        }
+    }
+
+    /// Send a text message.
+    pub async fn send(&mut self, text: impl Into<String>) -> Result<()> {
+        match &mut self.inner {
+            Inner::WebSocket(ws) => ws.send_text(text).await,
+            Inner::Sse { events, .. } => events.send_text(sse_event(&text.into())).await,
+        }
+    }
+
+    /// Receive the next text message, or `None` once the client is gone.
+    ///
+    /// Binary WebSocket messages are skipped and pings are answered.
+    pub async fn receive(&mut self) -> Result<Option<String>> {
+        match &mut self.inner {
+            Inner::WebSocket(ws) => loop {
+                match ws.receive().await? {
+                    Some(Message::Text(text)) => return Ok(Some(text)),
+                    Some(Message::Ping(payload)) => ws.send(Message::Pong(payload)).await?,
+                    Some(Message::Binary(_) | Message::Pong(_)) => {}
+                    Some(Message::Close(_)) | None => return Ok(None),
+                }
+            },
+            Inner::Sse {
+                events,
+                session,
+                heartbeat,
+            } => loop {
+                tokio::select! {
+                    text = session.inbox.recv() => return Ok(text),
+                    _ = events.closed() => return Ok(None),
+                    _ = tokio::time::sleep(*heartbeat) => {
+                        if events.send_text(": keep-alive\n\n").await.is_err() {
+                            return Ok(None);
+                        }
+                    }
+                }
+            },
+        }
+    }
+
+    /// Close the connection.
+    pub async fn close(self) -> Result<()> {
+        match self.inner {
+            Inner::WebSocket(ws) => ws.close().await,
+            Inner::Sse { .. } => Ok(()),
+        }
+    }
+}
+
+/// Format `text` as one SSE event, one `data:` line per line.
+fn sse_event(text: &str) -> String {
+    let mut event = String::with_capacity(text.len() + 8);
+    for line in text.split('\n') {
+        event.push_str("data: ");
+        event.push_str(line.strip_suffix('\r').unwrap_or(line));
+        event.push('\n');
+    }
+    event.push('\n');
+    event
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{TestClient, assert_status};
+    use http_body_util::BodyExt;
+
+    #[test]
+    fn test_sse_event_splits_lines() {
+        assert_eq!(sse_event("hi"), "data: hi\n\n");
+        assert_eq!(sse_event("a\r\nb"), "data: a\ndata: b\n\n");
+    }
+
+    #[tokio::test]
+    async fn test_sse_fallback_round_trip() {
+        let mut app = RustApi::new();
+        Live::new(|mut conn| async move {
+            assert_eq!(conn.transport(), LiveTransport::Sse);
+            while let Ok(Some(text)) = conn.receive().await {
+                conn.send(format!("echo: {}", text)).await.unwrap();
+            }
+        })
+        .install(&mut app, "/live");
+        let client = TestClient::new(app);
+
+        let mut body = client.get("/live").send().await.into_hyper().into_body();
+        let mut next_event = async || {
+            let frame = body.frame().await.unwrap().unwrap();
+            String::from_utf8(frame.into_data().unwrap().to_vec()).unwrap()
+        };
+        let opened = next_event().await;
+        let session = opened
+            .strip_prefix("event: session\ndata: ")
+            .unwrap()
+            .trim_end();
+
+        let res = client
+            .post(&format!("/live/{}", session))
+            .body("hello")
+            .send()
+            .await;
+        assert_status(&res, 202);
+        assert_eq!(next_event().await, "data: echo: hello\n\n");
+
+        assert_status(&client.post("/live/unknown").body("x").send().await, 404);
+    }
+}
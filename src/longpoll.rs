@@ -0,0 +1,213 @@
+//! Long polling.
+//!
+//! [`LongPoll`] keeps the most recent events, each tagged with an
+//! increasing cursor. A poll answers with the first event after the
+//! client's cursor, parking until one is published, or with 204 No Content
+//! once the timeout passes. The cursor is sent as the `ETag`; clients
+//! resume by sending it back in `If-None-Match` (or as `?cursor=`).
+//!
+//! ```rust
+//! use rust_api::{Req, RustApi, longpoll::LongPoll};
+//! use std::time::Duration;
+//!
+//! let events = LongPoll::new().timeout(Duration::from_secs(25));
+//! let mut app = RustApi::new();
+//! let poll = events.clone();
+//! app.get("/events", move |req: Req| {
+//!     let poll = poll.clone();
+//!     async move { poll.poll(&req).await }
+//! });
+//! // wherever events happen:
+//! events.publish(r#"{"type":"order.created","id":7}"#);
+//! ```
+//!
+//! Without a cursor a poll waits for the next event published after it
+//! arrives. A cursor older than the kept history resumes at the oldest
+//! event still kept, with the number of missed events in
+//! `x-events-skipped`.
+
+use bytes::Bytes;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+
+use crate::{Req, Res};
+
+struct Shared {
+    events: Mutex<VecDeque<(u64, Bytes)>>,
+    latest: watch::Sender<u64>,
+}
+
+/// Event source answering long-poll requests.
+///
+/// Clones share the same events.
+#[derive(Clone)]
+pub struct LongPoll {
+    shared: Arc<Shared>,
+    timeout: Duration,
+    history: usize,
+    content_type: &'static str,
+}
+
+impl Default for LongPoll {
+    fn default() -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                events: Mutex::new(VecDeque::new()),
+                latest: watch::Sender::new(0),
+            }),
+            timeout: Duration::from_secs(30),
+            history: 64,
+            content_type: "application/json",
+        }
+    }
+}
+
+impl LongPoll {
+    /// JSON events, 30 second timeout, last 64 events kept.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer 204 when nothing arrives within `timeout` (default 30
+    /// seconds).
+    ///
+    /// Keep it below proxy and client idle timeouts.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Keep the last `events` events for clients catching up (default 64,
+    /// at least 1).
+    pub fn history(mut self, events: usize) -> Self {
+        self.history = events.max(1);
+        self
+    }
+
+    /// `Content-Type` of event responses (default `application/json`).
+    pub fn content_type(mut self, content_type: &'static str) -> Self {
+        self.content_type = content_type;
+        self
+    }
+
+    /// Publish an event, waking parked polls. Returns its cursor.
+    pub fn publish(&self, payload: impl Into<Bytes>) -> u64 {
+        let mut events = self.shared.events.lock().unwrap();
+        let cursor = *self.shared.latest.borrow() + 1;
+        events.push_back((cursor, payload.into()));
+        while events.len() > self.history {
+            events.pop_front();
+        }
+        // Sent under the lock so cursors reach the buffer in order.
+        self.shared.latest.send_replace(cursor);
+        cursor
+    }
+
+    /// Cursor of the latest event (0 before the first).
+    pub fn cursor(&self) -> u64 {
+        *self.shared.latest.borrow()
+    }
+
+    /// Answer a poll request.
+    ///
+    /// Returns the next event with its cursor as `ETag`, or 204 with the
+    /// unchanged cursor when the timeout passes first.
+    pub async fn poll(&self, req: &Req) -> Res {
+        let latest = self.cursor();
+        // Cursors from before a restart are ahead of ours; start over.
+        let cursor = request_cursor(req)
+            .filter(|&cursor| cursor <= latest)
+            .unwrap_or(latest);
+
+        let mut updates = self.shared.latest.subscribe();
+        let arrived = tokio::time::timeout(self.timeout, updates.wait_for(|&c| c > cursor)).await;
+        if !matches!(arrived, Ok(Ok(_))) {
+            return Res::status(204)
+                .header("etag", etag(cursor))
+                .header("cache-control", "no-store");
+        }
+
+        let events = self.shared.events.lock().unwrap();
+        let Some((next, payload)) = events.iter().find(|(c, _)| *c > cursor) else {
+            // Only reachable with an empty history, which `history` rules out.
+            return Res::status(204).header("etag", etag(cursor));
+        };
+        let mut res = Res::builder()
+            .header("content-type", self.content_type)
+            .header("etag", etag(*next))
+            .header("cache-control", "no-store");
+        if *next > cursor + 1 {
+            res = res.header("x-events-skipped", (*next - cursor - 1).to_string());
+        }
+        res.body(payload.clone())
+    }
+}
+
+fn etag(cursor: u64) -> String {
+    format!("\"{}\"", cursor)
+}
+
+/// Cursor from `If-None-Match` or the `cursor` query parameter.
+fn request_cursor(req: &Req) -> Option<u64> {
+    if let Some(tag) = req.header("if-none-match") {
+        let tag = tag.trim().trim_start_matches("W/").trim_matches('"');
+        if let Ok(cursor) = tag.parse() {
+            return Some(cursor);
+        }
+    }
+    let pairs: Vec<(String, String)> = serde_urlencoded::from_str(req.query()?).ok()?;
+    pairs
+        .into_iter()
+        .find(|(key, _)| key == "cursor")
+        .and_then(|(_, value)| value.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::BodyExt;
+
+    async fn body(res: Res) -> String {
+        let bytes = res.into_hyper().into_body().collect().await.unwrap();
+        String::from_utf8(bytes.to_bytes().to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_resumes_from_cursor() {
+        let events = LongPoll::new().history(2);
+        events.publish("a");
+        events.publish("b");
+        events.publish("c");
+
+        let req = Req::builder().header("if-none-match", "\"2\"").build();
+        let res = events.poll(&req).await;
+        assert_eq!(res.headers()["etag"], "\"3\"");
+        assert_eq!(body(res).await, "c");
+
+        let req = Req::builder().uri("/events?cursor=0").build();
+        let res = events.poll(&req).await;
+        assert_eq!(res.headers()["etag"], "\"2\"");
+        assert_eq!(res.headers()["x-events-skipped"], "1");
+        assert_eq!(body(res).await, "b");
+    }
+
+    #[tokio::test]
+    async fn test_parks_until_publish_or_timeout() {
+        let events = LongPoll::new().timeout(Duration::from_millis(20));
+        let res = events.poll(&Req::builder().build()).await;
+        assert_eq!(res.status_code(), 204);
+        assert_eq!(res.headers()["etag"], "\"0\"");
+
+        let events = events.timeout(Duration::from_secs(5));
+        let publisher = events.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            publisher.publish("ready");
+        });
+        let res = events.poll(&Req::builder().build()).await;
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(body(res).await, "ready");
+    }
+}
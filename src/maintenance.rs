@@ -0,0 +1,167 @@
+//! Maintenance mode.
+//!
+//! While the [`MaintenanceSwitch`] is on, requests are answered with
+//! 503 Service Unavailable before any middleware runs, except for paths
+//! under an allow-listed prefix (`/health` by default).
+//!
+//! ```rust
+//! use rust_api::{RustApi, maintenance::Maintenance};
+//! use std::time::Duration;
+//!
+//! let mut app = RustApi::new();
+//! app.set_maintenance(
+//!     Maintenance::new()
+//!         .retry_after(Duration::from_secs(120))
+//!         .allow("/status")
+//!         .html("<h1>Back soon</h1>"),
+//! );
+//! let switch = app.maintenance_switch();
+//! // during a deploy:
+//! switch.enable();
+//! ```
+
+use bytes::Bytes;
+use hyper::header::{self, HeaderValue};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::Res;
+
+const DEFAULT_BODY: &str = r#"{"error":{"message":"Service under maintenance","status":503}}"#;
+
+/// Response and allow-list used while in maintenance mode.
+#[derive(Debug, Clone)]
+pub struct Maintenance {
+    retry_after: Option<Duration>,
+    allow: Vec<String>,
+    content_type: &'static str,
+    body: Bytes,
+}
+
+impl Default for Maintenance {
+    fn default() -> Self {
+        Self {
+            retry_after: None,
+            allow: vec!["/health".to_string()],
+            content_type: "application/json",
+            body: Bytes::from_static(DEFAULT_BODY.as_bytes()),
+        }
+    }
+}
+
+impl Maintenance {
+    /// JSON error body, no `Retry-After`, `/health` allowed.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send `Retry-After` with this many seconds (rounded up).
+    pub fn retry_after(mut self, duration: Duration) -> Self {
+        self.retry_after = Some(duration);
+        self
+    }
+
+    /// Keep serving paths starting with `prefix`.
+    pub fn allow(mut self, prefix: impl Into<String>) -> Self {
+        self.allow.push(prefix.into());
+        self
+    }
+
+    /// Remove all allowed prefixes, including the default `/health`.
+    pub fn clear_allowed(mut self) -> Self {
+        self.allow.clear();
+        self
+    }
+
+    /// Respond with a JSON body.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` fails to serialize.
+    pub fn json<T: serde::Serialize>(mut self, value: &T) -> Self {
+        let body = serde_json::to_vec(value).expect("JSON serialization failed");
+        self.content_type = "application/json";
+        self.body = body.into();
+        self
+    }
+
+    /// Respond with an HTML page.
+    pub fn html(mut self, page: impl Into<String>) -> Self {
+        self.content_type = "text/html; charset=utf-8";
+        self.body = page.into().into();
+        self
+    }
+
+    /// Whether `path` stays reachable during maintenance.
+    pub(crate) fn allows(&self, path: &str) -> bool {
+        self.allow
+            .iter()
+            .any(|prefix| path.starts_with(prefix.as_str()))
+    }
+
+    pub(crate) fn response(&self) -> Res {
+        let mut res = Res::builder()
+            .status(503)
+            .header("content-type", self.content_type)
+            .body(self.body.clone());
+        if let Some(retry_after) = self.retry_after {
+            let secs = retry_after.as_secs_f64().ceil() as u64;
+            res.headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        res
+    }
+}
+
+/// Handle turning maintenance mode on and off at runtime.
+///
+/// Clones share the same switch; see `RustApi::maintenance_switch`.
+#[derive(Debug, Clone, Default)]
+pub struct MaintenanceSwitch(Arc<AtomicBool>);
+
+impl MaintenanceSwitch {
+    /// Start answering requests with 503.
+    pub fn enable(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Resume normal service.
+    pub fn disable(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+
+    /// Whether maintenance mode is on.
+    pub fn is_enabled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{TestClient, assert_header, assert_status, body_text};
+    use crate::{Req, RustApi};
+
+    #[tokio::test]
+    async fn test_switch_and_allow_list() {
+        let mut app = RustApi::new();
+        app.set_maintenance(Maintenance::new().retry_after(Duration::from_secs(30)));
+        app.get("/users", |_req: Req| async { "users" });
+        app.get("/health", |_req: Req| async { "ok" });
+        let switch = app.maintenance_switch();
+        let client = TestClient::new(app);
+
+        assert_status(&client.get("/users").send().await, 200);
+
+        switch.enable();
+        let res = client.get("/users").send().await;
+        assert_status(&res, 503);
+        assert_header(&res, "retry-after", "30");
+        assert_eq!(body_text(res).await, DEFAULT_BODY);
+        assert_status(&client.get("/health").send().await, 200);
+
+        switch.disable();
+        assert_status(&client.get("/users").send().await, 200);
+    }
+}
@@ -0,0 +1,146 @@
+//! HTTP method override for clients limited to GET and POST.
+//!
+//! Attach [`MethodOverride`] as pre-routing middleware so the rewritten
+//! method picks the route. Only `POST` requests are rewritten, and only to
+//! the configured target methods; other override values are rejected with
+//! 400.
+//!
+//! ```rust
+//! use hyper::Method;
+//! use rust_api::{RustApi, method_override::MethodOverride};
+//!
+//! let mut app = RustApi::new();
+//! app.attach_pre_routing(MethodOverride::new([Method::PUT, Method::PATCH, Method::DELETE]));
+//! ```
+
+use async_trait::async_trait;
+use hyper::Method;
+use std::sync::Arc;
+
+use crate::{Error, IntoRes, Middleware, Next, Req, Res};
+
+/// Header naming the intended method.
+pub const OVERRIDE_HEADER: &str = "x-http-method-override";
+
+/// Form field naming the intended method.
+pub const OVERRIDE_FIELD: &str = "_method";
+
+/// Middleware rewriting `POST` requests to the method named in
+/// `X-HTTP-Method-Override` or a `_method` form field.
+#[derive(Debug, Clone)]
+pub struct MethodOverride {
+    targets: Vec<Method>,
+    form_field: bool,
+}
+
+impl MethodOverride {
+    /// Allow overriding `POST` to `targets`; the `_method` field of
+    /// urlencoded forms is honored too.
+    pub fn new<I: IntoIterator<Item = Method>>(targets: I) -> Self {
+        Self {
+            targets: targets.into_iter().collect(),
+            form_field: true,
+        }
+    }
+
+    /// Whether to read the `_method` form field (buffers form bodies).
+    pub fn form_field(mut self, enabled: bool) -> Self {
+        self.form_field = enabled;
+        self
+    }
+
+    /// Method requested by `req`, if any.
+    async fn requested(&self, req: &mut Req) -> crate::Result<Option<String>> {
+        if let Some(method) = req.header(OVERRIDE_HEADER) {
+            return Ok(Some(method.to_string()));
+        }
+        let is_form = req
+            .header("content-type")
+            .is_some_and(|ct| ct.starts_with("application/x-www-form-urlencoded"));
+        if !self.form_field || !is_form {
+            return Ok(None);
+        }
+        let body = req.peek_body().await?;
+        let fields: Vec<(String, String)> = serde_urlencoded::from_bytes(&body).unwrap_or_default();
+        Ok(fields
+            .into_iter()
+            .find(|(name, _)| name == OVERRIDE_FIELD)
+            .map(|(_, value)| value))
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> Middleware<S> for MethodOverride {
+    async fn handle(&self, mut req: Req, _state: Arc<S>, next: Next<S>) -> Res {
+        if req.method() != Method::POST {
+            return next.run(req).await;
+        }
+        let requested = match self.requested(&mut req).await {
+            Ok(requested) => requested,
+            Err(e) => return e.into_res(),
+        };
+        if let Some(requested) = requested {
+            let method = requested.trim().to_ascii_uppercase();
+            match self.targets.iter().find(|target| target.as_str() == method) {
+                Some(target) => req.set_method(target.clone()),
+                None if method == "POST" => {}
+                None => {
+                    return Error::bad_request(format!(
+                        "Method override to {} not allowed",
+                        method
+                    ))
+                    .into_res();
+                }
+            }
+        }
+        next.run(req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RustApi;
+    use crate::test::{TestClient, assert_status, body_text};
+
+    #[tokio::test]
+    async fn test_method_override() {
+        let mut app = RustApi::new();
+        app.attach_pre_routing(MethodOverride::new([Method::DELETE, Method::PATCH]));
+        app.delete("/items/1", |_req: Req| async { "deleted" });
+        app.patch("/items/1", |req: Req| async move {
+            format!("patched {}", req.header("content-type").unwrap_or("-"))
+        });
+        let client = TestClient::new(app);
+
+        let res = client
+            .post("/items/1")
+            .header("x-http-method-override", "delete")
+            .send()
+            .await;
+        assert_eq!(body_text(res).await, "deleted");
+        let res = client
+            .post("/items/1")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body("name=x&_method=PATCH")
+            .send()
+            .await;
+        assert_eq!(
+            body_text(res).await,
+            "patched application/x-www-form-urlencoded"
+        );
+
+        let put = client
+            .post("/items/1")
+            .header("x-http-method-override", "PUT")
+            .send()
+            .await;
+        assert_status(&put, 400);
+        let get = client
+            .get("/items/1")
+            .header("x-http-method-override", "DELETE")
+            .send()
+            .await;
+        assert_status(&get, 405);
+    }
+}
@@ -0,0 +1,572 @@
+//! Server metrics hooks.
+//!
+//! Implement [`Metrics`] to forward connection and request timings to a
+//! metrics backend, or use [`InMemoryMetrics`] for simple counters.
+//!
+//! ```rust
+//! use rust_api::{RustApi, metrics::InMemoryMetrics};
+//! use std::sync::Arc;
+//!
+//! let metrics = Arc::new(InMemoryMetrics::new());
+//! let mut app = RustApi::new();
+//! app.set_metrics(Arc::clone(&metrics));
+//! // later: metrics.snapshot()
+//! ```
+//!
+//! Every request is reported; [`RequestTimings::sampled`] tells a tracing
+//! backend which ones to keep. Without a [`Sampler`] all are sampled.
+//!
+//! ```rust
+//! use rust_api::{Req, RustApi, metrics::Sampler};
+//! use std::time::Duration;
+//!
+//! let mut app = RustApi::new();
+//! app.set_sampler(Sampler::ratio(0.01).slow(Duration::from_secs(1)));
+//! app.get("/checkout", |_req: Req| async { "ok" }).sample_rate(0.5);
+//! app.get("/health", |_req: Req| async { "ok" }).sample_rate(0.0);
+//! ```
+
+use hyper::{Method, header};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::split::Variant;
+
+/// Receives server events. All methods default to no-ops.
+pub trait Metrics: Send + Sync + 'static {
+    /// A connection was accepted.
+    fn connection_accepted(&self, _peer: SocketAddr) {}
+
+    /// A connection was refused because the connection limit was reached.
+    fn connection_rejected(&self, _peer: SocketAddr) {}
+
+    /// A connection was closed.
+    fn connection_closed(&self, _stats: &ConnectionStats) {}
+
+    /// A request finished.
+    fn request_completed(&self, _timings: &RequestTimings) {}
+
+    /// A WebSocket connection was established.
+    fn websocket_opened(&self) {}
+
+    /// A WebSocket handler returned.
+    fn websocket_closed(&self) {}
+
+    /// Tokio runtime metrics were sampled (see `set_runtime_metrics_interval`).
+    fn runtime_sampled(&self, _stats: &RuntimeStats) {}
+}
+
+impl<M: Metrics> Metrics for Arc<M> {
+    fn connection_accepted(&self, peer: SocketAddr) {
+        (**self).connection_accepted(peer)
+    }
+
+    fn connection_rejected(&self, peer: SocketAddr) {
+        (**self).connection_rejected(peer)
+    }
+
+    fn connection_closed(&self, stats: &ConnectionStats) {
+        (**self).connection_closed(stats)
+    }
+
+    fn request_completed(&self, timings: &RequestTimings) {
+        (**self).request_completed(timings)
+    }
+
+    fn websocket_opened(&self) {
+        (**self).websocket_opened()
+    }
+
+    fn websocket_closed(&self) {
+        (**self).websocket_closed()
+    }
+
+    fn runtime_sampled(&self, stats: &RuntimeStats) {
+        (**self).runtime_sampled(stats)
+    }
+}
+
+/// Statistics for a closed connection.
+#[derive(Debug, Clone)]
+pub struct ConnectionStats {
+    /// Remote address.
+    pub peer: SocketAddr,
+    /// Requests served on the connection.
+    pub requests: u64,
+    /// Time the connection was open.
+    pub duration: Duration,
+    /// Why the connection closed.
+    pub close_reason: CloseReason,
+}
+
+/// Why a connection closed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    /// The client closed it, asked to, or the connection failed.
+    Peer,
+    /// No request arrived within the idle timeout.
+    IdleTimeout,
+    /// The per-connection request limit was reached.
+    MaxRequests,
+    /// Connections were being drained.
+    Drain,
+    /// The server shut down.
+    Shutdown,
+    /// The client sent headers or a body too slowly.
+    SlowClient,
+}
+
+/// Timings for a single request.
+///
+/// `queued` covers routing and time waiting before the handler pipeline
+/// starts, `body_read` the time spent receiving the request body, and
+/// `handler` the remaining middleware and handler time.
+#[derive(Debug, Clone)]
+pub struct RequestTimings {
+    /// HTTP method.
+    pub method: Method,
+    /// Matched route pattern (e.g. `/users/{id}`), if any.
+    pub route: Option<Arc<str>>,
+    /// Response status code.
+    pub status: u16,
+    /// Time before the handler pipeline started.
+    pub queued: Duration,
+    /// Time spent reading the request body.
+    pub body_read: Duration,
+    /// Middleware and handler time, excluding body reads.
+    pub handler: Duration,
+    /// Total time from receipt to response.
+    pub total: Duration,
+    /// Position of the request on its connection; above 1 means the
+    /// connection was reused.
+    pub connection_request: u64,
+    /// Experiment variant that served the request, if split.
+    pub variant: Option<Variant>,
+    /// Whether the request should be traced (see [`Sampler`]).
+    pub sampled: bool,
+}
+
+/// Decides which requests are traced.
+///
+/// A request is sampled when the upstream `traceparent` says so, or else
+/// with the route's [`sample_rate`](crate::Route::sample_rate) or the
+/// sampler's ratio. Server errors and slow requests are sampled regardless,
+/// so the requests worth investigating are never dropped.
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    ratio: f64,
+    errors: bool,
+    slow: Option<Duration>,
+    parent: bool,
+}
+
+impl Sampler {
+    /// Sample `ratio` (0.0 to 1.0) of requests, plus all 5xx responses.
+    pub fn ratio(ratio: f64) -> Self {
+        Self {
+            ratio: ratio.clamp(0.0, 1.0),
+            errors: true,
+            slow: None,
+            parent: true,
+        }
+    }
+
+    /// Also sample every request taking longer than `threshold`.
+    pub fn slow(mut self, threshold: Duration) -> Self {
+        self.slow = Some(threshold);
+        self
+    }
+
+    /// Do not force sampling of 5xx responses.
+    pub fn skip_errors(mut self) -> Self {
+        self.errors = false;
+        self
+    }
+
+    /// Ignore the sampled flag of incoming `traceparent` headers.
+    ///
+    /// Their trace id still drives the ratio, so services sharing a ratio
+    /// agree on each trace.
+    pub fn ignore_parent(mut self) -> Self {
+        self.parent = false;
+        self
+    }
+
+    /// Decide for a finished request.
+    pub(crate) fn sample(
+        &self,
+        parent: Option<TraceParent>,
+        route_rate: Option<f64>,
+        status: u16,
+        total: Duration,
+    ) -> bool {
+        if self.errors && status >= 500 || self.slow.is_some_and(|slow| total >= slow) {
+            return true;
+        }
+        if let Some(parent) = parent.filter(|_| self.parent) {
+            return parent.sampled;
+        }
+        let ratio = route_rate.map_or(self.ratio, |rate| rate.clamp(0.0, 1.0));
+        // Compare the low 64 bits of the trace id (random without one)
+        // against the ratio, as OpenTelemetry's ratio sampler does.
+        let bits = parent.map_or_else(|| uuid::Uuid::new_v4().as_u64_pair().1, |p| p.id_bits);
+        ratio >= 1.0 || ((bits >> 11) as f64 / (1u64 << 53) as f64) < ratio
+    }
+}
+
+/// Sampling-relevant parts of a W3C `traceparent` header.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TraceParent {
+    id_bits: u64,
+    sampled: bool,
+}
+
+impl TraceParent {
+    /// Parse `traceparent` (`00-{trace id}-{parent id}-{flags}`).
+    pub(crate) fn from_headers(headers: &header::HeaderMap) -> Option<Self> {
+        let value = headers.get("traceparent")?.to_str().ok()?;
+        let mut parts = value.split('-');
+        let (version, trace_id, parent_id, flags) =
+            (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+        if version.len() != 2
+            || version == "ff"
+            || trace_id.len() != 32
+            || parent_id.len() != 16
+            || flags.len() != 2
+        {
+            return None;
+        }
+        let trace_id = u128::from_str_radix(trace_id, 16)
+            .ok()
+            .filter(|&id| id != 0)?;
+        let flags = u8::from_str_radix(flags, 16).ok()?;
+        Some(Self {
+            id_bits: trace_id as u64,
+            sampled: flags & 1 == 1,
+        })
+    }
+}
+
+/// Tokio runtime metrics at one point in time.
+///
+/// Fields marked unstable are only filled when built with
+/// `RUSTFLAGS="--cfg tokio_unstable"`.
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeStats {
+    /// Worker threads.
+    pub workers: usize,
+    /// Tasks currently alive.
+    pub alive_tasks: usize,
+    /// Tasks waiting in the global queue.
+    pub global_queue_depth: usize,
+    /// Time workers spent busy since the runtime started, summed.
+    pub busy: Duration,
+    /// Times workers parked since the runtime started, summed.
+    pub parks: u64,
+    /// Threads in the blocking pool (unstable).
+    pub blocking_threads: Option<usize>,
+    /// Idle threads in the blocking pool (unstable).
+    pub idle_blocking_threads: Option<usize>,
+    /// Tasks waiting for a blocking thread (unstable).
+    pub blocking_queue_depth: Option<usize>,
+    /// Mean task poll time across workers (unstable).
+    pub mean_poll_time: Option<Duration>,
+}
+
+impl RuntimeStats {
+    /// Sample the current runtime.
+    pub fn sample() -> Self {
+        let metrics = tokio::runtime::Handle::current().metrics();
+        let workers = metrics.num_workers();
+        let mut stats = RuntimeStats {
+            workers,
+            alive_tasks: metrics.num_alive_tasks(),
+            global_queue_depth: metrics.global_queue_depth(),
+            ..Default::default()
+        };
+        for worker in 0..workers {
+            stats.busy += metrics.worker_total_busy_duration(worker);
+            stats.parks += metrics.worker_park_count(worker);
+        }
+        #[cfg(tokio_unstable)]
+        {
+            stats.blocking_threads = Some(metrics.num_blocking_threads());
+            stats.idle_blocking_threads = Some(metrics.num_idle_blocking_threads());
+            stats.blocking_queue_depth = Some(metrics.blocking_queue_depth());
+            if workers > 0 {
+                let total: Duration = (0..workers)
+                    .map(|worker| metrics.worker_mean_poll_time(worker))
+                    .sum();
+                stats.mean_poll_time = Some(total / workers as u32);
+            }
+        }
+        stats
+    }
+}
+
+/// Report runtime metrics every `interval` until shutdown.
+pub(crate) async fn sample_runtime(
+    metrics: Arc<dyn Metrics>,
+    interval: Duration,
+    mut shutdown_rx: tokio::sync::watch::Receiver<bool>,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => metrics.runtime_sampled(&RuntimeStats::sample()),
+            _ = shutdown_rx.changed() => break,
+        }
+    }
+}
+
+/// Lock-free counters implementing [`Metrics`].
+#[derive(Debug, Default)]
+pub struct InMemoryMetrics {
+    connections_accepted: AtomicU64,
+    connections_rejected: AtomicU64,
+    connections_closed: AtomicU64,
+    keep_alive_closes: AtomicU64,
+    slow_client_closes: AtomicU64,
+    requests: AtomicU64,
+    requests_reused: AtomicU64,
+    requests_sampled: AtomicU64,
+    server_errors: AtomicU64,
+    queued_micros: AtomicU64,
+    body_read_micros: AtomicU64,
+    handler_micros: AtomicU64,
+    websockets_opened: AtomicU64,
+    websockets_closed: AtomicU64,
+    runtime_alive_tasks: AtomicU64,
+    runtime_global_queue_depth: AtomicU64,
+}
+
+/// Point-in-time copy of [`InMemoryMetrics`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct MetricsSnapshot {
+    /// Connections accepted.
+    pub connections_accepted: u64,
+    /// Connections refused at the connection limit.
+    pub connections_rejected: u64,
+    /// Currently open connections.
+    pub connections_active: u64,
+    /// Connections closed by the idle timeout or request limit.
+    pub keep_alive_closes: u64,
+    /// Connections closed for sending headers or a body too slowly.
+    pub slow_client_closes: u64,
+    /// Requests completed.
+    pub requests: u64,
+    /// Requests served on a reused connection.
+    pub requests_reused: u64,
+    /// Requests sampled for tracing.
+    pub requests_sampled: u64,
+    /// Requests answered with a 5xx status.
+    pub server_errors: u64,
+    /// Total queueing time in microseconds.
+    pub queued_micros: u64,
+    /// Total body read time in microseconds.
+    pub body_read_micros: u64,
+    /// Total handler time in microseconds.
+    pub handler_micros: u64,
+    /// Currently open WebSocket connections.
+    pub websockets_active: u64,
+    /// Alive tokio tasks at the last runtime sample.
+    pub runtime_alive_tasks: u64,
+    /// Global queue depth at the last runtime sample.
+    pub runtime_global_queue_depth: u64,
+}
+
+impl InMemoryMetrics {
+    /// Create zeroed counters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read current counter values.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let accepted = self.connections_accepted.load(Ordering::Relaxed);
+        let closed = self.connections_closed.load(Ordering::Relaxed);
+        MetricsSnapshot {
+            connections_accepted: accepted,
+            connections_rejected: self.connections_rejected.load(Ordering::Relaxed),
+            connections_active: accepted.saturating_sub(closed),
+            keep_alive_closes: self.keep_alive_closes.load(Ordering::Relaxed),
+            slow_client_closes: self.slow_client_closes.load(Ordering::Relaxed),
+            requests: self.requests.load(Ordering::Relaxed),
+            requests_reused: self.requests_reused.load(Ordering::Relaxed),
+            requests_sampled: self.requests_sampled.load(Ordering::Relaxed),
+            server_errors: self.server_errors.load(Ordering::Relaxed),
+            queued_micros: self.queued_micros.load(Ordering::Relaxed),
+            body_read_micros: self.body_read_micros.load(Ordering::Relaxed),
+            handler_micros: self.handler_micros.load(Ordering::Relaxed),
+            websockets_active: self
+                .websockets_opened
+                .load(Ordering::Relaxed)
+                .saturating_sub(self.websockets_closed.load(Ordering::Relaxed)),
+            runtime_alive_tasks: self.runtime_alive_tasks.load(Ordering::Relaxed),
+            runtime_global_queue_depth: self.runtime_global_queue_depth.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Metrics for InMemoryMetrics {
+    fn connection_accepted(&self, _peer: SocketAddr) {
+        self.connections_accepted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn connection_rejected(&self, _peer: SocketAddr) {
+        self.connections_rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn connection_closed(&self, stats: &ConnectionStats) {
+        self.connections_closed.fetch_add(1, Ordering::Relaxed);
+        match stats.close_reason {
+            CloseReason::IdleTimeout | CloseReason::MaxRequests => {
+                self.keep_alive_closes.fetch_add(1, Ordering::Relaxed);
+            }
+            CloseReason::SlowClient => {
+                self.slow_client_closes.fetch_add(1, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+    }
+
+    fn request_completed(&self, timings: &RequestTimings) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        if timings.connection_request > 1 {
+            self.requests_reused.fetch_add(1, Ordering::Relaxed);
+        }
+        if timings.sampled {
+            self.requests_sampled.fetch_add(1, Ordering::Relaxed);
+        }
+        if timings.status >= 500 {
+            self.server_errors.fetch_add(1, Ordering::Relaxed);
+        }
+        self.queued_micros
+            .fetch_add(timings.queued.as_micros() as u64, Ordering::Relaxed);
+        self.body_read_micros
+            .fetch_add(timings.body_read.as_micros() as u64, Ordering::Relaxed);
+        self.handler_micros
+            .fetch_add(timings.handler.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn websocket_opened(&self) {
+        self.websockets_opened.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn websocket_closed(&self) {
+        self.websockets_closed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn runtime_sampled(&self, stats: &RuntimeStats) {
+        self.runtime_alive_tasks
+            .store(stats.alive_tasks as u64, Ordering::Relaxed);
+        self.runtime_global_queue_depth
+            .store(stats.global_queue_depth as u64, Ordering::Relaxed);
+    }
+}
+
+/// Per-request measurements gathered across the pipeline.
+#[derive(Default)]
+pub(crate) struct RequestTrace {
+    pub(crate) route: OnceLock<Arc<str>>,
+    pub(crate) handler_started: OnceLock<Instant>,
+    pub(crate) body_read_micros: AtomicU64,
+    pub(crate) variant: OnceLock<Variant>,
+    pub(crate) sample_rate: OnceLock<f64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_in_memory_counters() {
+        let metrics = InMemoryMetrics::new();
+        let peer: SocketAddr = ([127, 0, 0, 1], 9000).into();
+
+        metrics.connection_accepted(peer);
+        metrics.connection_accepted(peer);
+        metrics.connection_closed(&ConnectionStats {
+            peer,
+            requests: 1,
+            duration: Duration::from_secs(1),
+            close_reason: CloseReason::IdleTimeout,
+        });
+        metrics.request_completed(&RequestTimings {
+            method: Method::GET,
+            route: Some(Arc::from("/users/{id}")),
+            status: 503,
+            queued: Duration::from_micros(5),
+            body_read: Duration::from_micros(10),
+            handler: Duration::from_micros(20),
+            total: Duration::from_micros(35),
+            connection_request: 2,
+            variant: None,
+            sampled: true,
+        });
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.connections_accepted, 2);
+        assert_eq!(snapshot.connections_active, 1);
+        assert_eq!(snapshot.keep_alive_closes, 1);
+        assert_eq!(snapshot.requests, 1);
+        assert_eq!(snapshot.requests_reused, 1);
+        assert_eq!(snapshot.requests_sampled, 1);
+        assert_eq!(snapshot.server_errors, 1);
+        assert_eq!(snapshot.handler_micros, 20);
+    }
+
+    #[test]
+    fn test_sampler() {
+        let fast = Duration::from_millis(5);
+        let never = Sampler::ratio(0.0).slow(Duration::from_secs(1));
+        assert!(!never.sample(None, None, 200, fast));
+        assert!(never.sample(None, None, 503, fast));
+        assert!(never.sample(None, None, 200, Duration::from_secs(2)));
+        assert!(never.sample(None, Some(1.0), 200, fast));
+        assert!(!Sampler::ratio(1.0).sample(None, Some(0.0), 200, fast));
+        assert!(!never.clone().skip_errors().sample(None, None, 503, fast));
+
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            "traceparent",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+                .parse()
+                .unwrap(),
+        );
+        let parent = TraceParent::from_headers(&headers);
+        assert!(never.sample(parent, None, 200, fast));
+        assert!(
+            !never
+                .clone()
+                .ignore_parent()
+                .sample(parent, None, 200, fast)
+        );
+        // The trace id decides consistently: its low bits map to about 0.64.
+        let half = Sampler::ratio(0.5).ignore_parent();
+        assert!(!half.sample(parent, None, 200, fast));
+        assert!(half.sample(parent, Some(0.7), 200, fast));
+
+        headers.insert("traceparent", "00-abc-00f067aa0ba902b7-01".parse().unwrap());
+        assert!(TraceParent::from_headers(&headers).is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_runtime_sample() {
+        let stats = RuntimeStats::sample();
+        assert_eq!(stats.workers, 2);
+
+        let metrics = InMemoryMetrics::new();
+        metrics.runtime_sampled(&RuntimeStats {
+            alive_tasks: 3,
+            global_queue_depth: 1,
+            ..stats
+        });
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.runtime_alive_tasks, 3);
+        assert_eq!(snapshot.runtime_global_queue_depth, 1);
+    }
+}
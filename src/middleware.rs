@@ -11,15 +11,32 @@ use crate::{Req, Res};
 pub trait Middleware<S = ()>: Send + Sync + 'static {
     /// Handle request before passing to next middleware/handler.
     async fn handle(&self, req: Req, state: Arc<S>, next: Next<S>) -> Res;
+
+    /// Name reported by route introspection (defaults to the type name).
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
 }
 
 /// Next middleware/handler in chain.
 pub struct Next<S = ()> {
-    pub(crate) handler: Arc<dyn Fn(Req, Arc<S>) -> BoxFuture<Res> + Send + Sync>,
+    pub(crate) handler: NextFn<S>,
     pub(crate) state: Arc<S>,
 }
 
-type BoxFuture<T> = std::pin::Pin<Box<dyn Future<Output = T> + Send>>;
+pub(crate) type BoxFuture<T> = std::pin::Pin<Box<dyn Future<Output = T> + Send>>;
+pub(crate) type NextFn<S> = Arc<dyn Fn(Req, Arc<S>) -> BoxFuture<Res> + Send + Sync>;
+
+/// Cloning lets middleware run the rest of the chain more than once, e.g.
+/// to refresh a cached response in the background.
+impl<S> Clone for Next<S> {
+    fn clone(&self) -> Self {
+        Self {
+            handler: Arc::clone(&self.handler),
+            state: Arc::clone(&self.state),
+        }
+    }
+}
 
 impl<S: 'static> Next<S> {
     /// Create next handler.
@@ -38,6 +55,30 @@ impl<S: 'static> Next<S> {
     }
 }
 
+/// Compose middleware around a terminal handler (first middleware outermost).
+pub(crate) fn chain<S: Send + Sync + 'static>(
+    middlewares: &[Arc<dyn Middleware<S>>],
+    terminal: NextFn<S>,
+    state: &Arc<S>,
+) -> NextFn<S> {
+    let mut next_fn = terminal;
+
+    for middleware in middlewares.iter().rev() {
+        let middleware = Arc::clone(middleware);
+        let inner = Arc::clone(&next_fn);
+        let state = Arc::clone(state);
+
+        next_fn = Arc::new(move |req, _state| {
+            let mw = Arc::clone(&middleware);
+            let next = Next::new(Arc::clone(&inner), Arc::clone(&state));
+            let state = Arc::clone(&state);
+            Box::pin(async move { mw.handle(req, state, next).await })
+        });
+    }
+
+    next_fn
+}
+
 /// Function-based middleware wrapper.
 pub struct FnMiddleware<F>(pub F);
 
@@ -0,0 +1,215 @@
+//! List pagination.
+//!
+//! [`Pagination`] reads `page`/`per_page` (or `cursor`) from the query
+//! string; returning a [`Page`] writes the items as JSON with `Link` and
+//! `X-Total-Count` headers.
+//!
+//! ```rust
+//! use rust_api::{Page, Pagination};
+//!
+//! async fn list(pagination: Pagination) -> Page<u64> {
+//!     let total = 250;
+//!     let items = (pagination.offset()..total).take(pagination.limit() as usize).collect();
+//!     Page::new(items, &pagination).total(total)
+//! }
+//! ```
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::{Error, FromRequest, IntoRes, Req, Res, Result};
+
+/// Page size when `per_page` is absent.
+pub const DEFAULT_PER_PAGE: u64 = 20;
+
+/// Largest accepted `per_page`; larger values are clamped.
+pub const MAX_PER_PAGE: u64 = 100;
+
+#[derive(Deserialize)]
+struct PaginationQuery {
+    page: Option<u64>,
+    per_page: Option<u64>,
+    cursor: Option<String>,
+}
+
+/// Pagination parameters extractor.
+#[derive(Debug, Clone)]
+pub struct Pagination {
+    /// 1-based page number.
+    pub page: u64,
+    /// Items per page, clamped to `1..=MAX_PER_PAGE`.
+    pub per_page: u64,
+    /// Opaque cursor for cursor-based pagination.
+    pub cursor: Option<String>,
+    path: String,
+    params: Vec<(String, String)>,
+}
+
+impl Pagination {
+    fn from_query(path: &str, query: Option<&str>) -> Result<Self> {
+        let query = query.unwrap_or("");
+        let parsed: PaginationQuery = serde_urlencoded::from_str(query)
+            .map_err(|e| Error::bad_request(format!("Invalid pagination parameters: {}", e)))?;
+        let params: Vec<(String, String)> = serde_urlencoded::from_str(query)
+            .map_err(|e| Error::bad_request(format!("Invalid query parameters: {}", e)))?;
+
+        Ok(Self {
+            page: parsed.page.unwrap_or(1).max(1),
+            per_page: parsed
+                .per_page
+                .unwrap_or(DEFAULT_PER_PAGE)
+                .clamp(1, MAX_PER_PAGE),
+            cursor: parsed.cursor.filter(|c| !c.is_empty()),
+            path: path.to_string(),
+            params: params
+                .into_iter()
+                .filter(|(k, _)| !matches!(k.as_str(), "page" | "per_page" | "cursor"))
+                .collect(),
+        })
+    }
+
+    /// Rows to skip for offset-based queries.
+    pub fn offset(&self) -> u64 {
+        (self.page - 1).saturating_mul(self.per_page)
+    }
+
+    /// Rows to fetch (same as `per_page`).
+    pub fn limit(&self) -> u64 {
+        self.per_page
+    }
+
+    /// URL of the same request with different pagination parameters.
+    fn url(&self, extra: &[(&str, &str)]) -> String {
+        let mut pairs: Vec<(&str, &str)> = self
+            .params
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        pairs.extend_from_slice(extra);
+        let query = serde_urlencoded::to_string(&pairs).unwrap_or_default();
+        format!("{}?{}", self.path, query)
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> FromRequest<S> for Pagination {
+    async fn from_request(req: &mut Req, _state: &Arc<S>) -> Result<Self> {
+        Pagination::from_query(req.path(), req.query())
+    }
+}
+
+/// One page of results, rendered as a JSON array with pagination headers.
+pub struct Page<T> {
+    items: Vec<T>,
+    total: Option<u64>,
+    next_cursor: Option<String>,
+    pagination: Pagination,
+}
+
+impl<T> Page<T> {
+    /// Page of `items` for the request's `pagination`.
+    pub fn new(items: Vec<T>, pagination: &Pagination) -> Self {
+        Self {
+            items,
+            total: None,
+            next_cursor: None,
+            pagination: pagination.clone(),
+        }
+    }
+
+    /// Set the total item count (enables `last` and `X-Total-Count`).
+    pub fn total(mut self, total: u64) -> Self {
+        self.total = Some(total);
+        self
+    }
+
+    /// Set the cursor of the following page (cursor-based pagination).
+    pub fn next_cursor(mut self, cursor: impl Into<String>) -> Self {
+        self.next_cursor = Some(cursor.into());
+        self
+    }
+
+    /// Build the `Link` header value.
+    fn links(&self) -> String {
+        let p = &self.pagination;
+        let per_page = p.per_page.to_string();
+        let link = |rel: &str, extra: &[(&str, &str)]| {
+            let mut pairs = extra.to_vec();
+            pairs.push(("per_page", &per_page));
+            format!("<{}>; rel=\"{}\"", p.url(&pairs), rel)
+        };
+
+        if self.next_cursor.is_some() || p.cursor.is_some() {
+            let mut links = vec![link("first", &[])];
+            if let Some(cursor) = &self.next_cursor {
+                links.push(link("next", &[("cursor", cursor)]));
+            }
+            return links.join(", ");
+        }
+
+        let page_link = |rel: &str, page: u64| link(rel, &[("page", &page.to_string())]);
+        let last = self.total.map(|total| total.div_ceil(p.per_page).max(1));
+        let has_next = match last {
+            Some(last) => p.page < last,
+            None => self.items.len() as u64 >= p.per_page,
+        };
+
+        let mut links = vec![page_link("first", 1)];
+        if p.page > 1 {
+            links.push(page_link("prev", p.page - 1));
+        }
+        if has_next {
+            links.push(page_link("next", p.page + 1));
+        }
+        if let Some(last) = last {
+            links.push(page_link("last", last));
+        }
+        links.join(", ")
+    }
+}
+
+impl<T: Serialize> IntoRes for Page<T> {
+    fn into_res(self) -> Res {
+        let mut res = Res::json(&self.items).header("link", self.links());
+        if let Some(total) = self.total {
+            res = res.header("x-total-count", total.to_string());
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_clamp() {
+        let p = Pagination::from_query("/users", Some("page=0&per_page=500&q=a")).unwrap();
+        assert_eq!((p.page, p.per_page, p.offset()), (1, MAX_PER_PAGE, 0));
+        let p = Pagination::from_query("/users", None).unwrap();
+        assert_eq!((p.page, p.per_page), (1, DEFAULT_PER_PAGE));
+        assert!(Pagination::from_query("/users", Some("page=x")).is_err());
+    }
+
+    #[test]
+    fn test_links() {
+        let p = Pagination::from_query("/users", Some("q=a+b&page=2&per_page=10")).unwrap();
+        let page = Page::new(vec![0; 10], &p).total(35);
+        assert_eq!(
+            page.links(),
+            "</users?q=a+b&page=1&per_page=10>; rel=\"first\", \
+             </users?q=a+b&page=1&per_page=10>; rel=\"prev\", \
+             </users?q=a+b&page=3&per_page=10>; rel=\"next\", \
+             </users?q=a+b&page=4&per_page=10>; rel=\"last\""
+        );
+
+        let p = Pagination::from_query("/users", Some("cursor=abc")).unwrap();
+        let page = Page::new(vec![1], &p).next_cursor("d/e");
+        assert_eq!(
+            page.links(),
+            "</users?per_page=20>; rel=\"first\", \
+             </users?cursor=d%2Fe&per_page=20>; rel=\"next\""
+        );
+    }
+}
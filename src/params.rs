@@ -0,0 +1,151 @@
+//! Path parameters captured during routing.
+
+use smallvec::SmallVec;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Path parameters matched by the router (e.g. `id` in `/users/{id}`).
+///
+/// Names are shared with the route table and values are copied into a
+/// single buffer, so capturing parameters allocates at most once.
+#[derive(Clone, Default)]
+pub struct PathParams {
+    values: String,
+    entries: SmallVec<[(Arc<str>, Range<usize>); 4]>,
+}
+
+impl PathParams {
+    /// Create an empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Collect matched parameters; `name` maps a matched key to a shared name.
+    pub(crate) fn capture(
+        matched: &matchit::Params<'_, '_>,
+        name: impl Fn(&str) -> Arc<str>,
+    ) -> Self {
+        let len = matched.iter().map(|(_, value)| value.len()).sum();
+        let mut params = Self {
+            values: String::with_capacity(len),
+            entries: SmallVec::new(),
+        };
+        for (key, value) in matched.iter() {
+            params.push(name(key), value);
+        }
+        params
+    }
+
+    fn push(&mut self, name: Arc<str>, value: &str) {
+        let start = self.values.len();
+        self.values.push_str(value);
+        self.entries.push((name, start..self.values.len()));
+    }
+
+    /// Set a parameter, replacing any existing value.
+    pub fn insert(&mut self, name: impl Into<Arc<str>>, value: &str) {
+        let name = name.into();
+        self.entries.retain(|(existing, _)| *existing != name);
+        self.push(name, value);
+    }
+
+    /// Get a parameter by name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(key, _)| &**key == name)
+            .map(|(_, range)| &self.values[range.clone()])
+    }
+
+    /// Whether a parameter is present.
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Iterate over `(name, value)` pairs in pattern order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> + Clone {
+        self.entries
+            .iter()
+            .map(|(key, range)| (&**key, &self.values[range.clone()]))
+    }
+
+    /// Number of parameters.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether there are no parameters.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl fmt::Debug for PathParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Parameter names in a route pattern, e.g. `["id", "path"]` for
+/// `/users/{id}/files/{*path}`.
+pub(crate) fn pattern_names(pattern: &str) -> Vec<Arc<str>> {
+    let mut names = Vec::new();
+    let mut rest = pattern;
+    while let Some(open) = rest.find('{') {
+        if rest[open + 1..].starts_with('{') {
+            rest = &rest[open + 2..];
+            continue;
+        }
+        let Some(close) = rest[open..].find('}') else {
+            break;
+        };
+        let name = &rest[open + 1..open + close];
+        names.push(Arc::from(name.trim_start_matches('*')));
+        rest = &rest[open + close + 1..];
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_and_get() {
+        let pattern = "/users/{id}/files/{*path}";
+        let names = pattern_names(pattern);
+        let mut router = matchit::Router::new();
+        router.insert(pattern, ()).unwrap();
+        let matched = router.at("/users/42/files/a/b.txt").unwrap();
+        let params = PathParams::capture(&matched.params, |key| {
+            names.iter().find(|name| &***name == key).cloned().unwrap()
+        });
+
+        assert_eq!(params.get("id"), Some("42"));
+        assert_eq!(params.get("path"), Some("a/b.txt"));
+        assert_eq!(params.get("missing"), None);
+        assert_eq!(
+            params.iter().collect::<Vec<_>>(),
+            [("id", "42"), ("path", "a/b.txt")]
+        );
+    }
+
+    #[test]
+    fn test_insert_replaces() {
+        let mut params = PathParams::new();
+        params.insert("id", "1");
+        params.insert("id", "2");
+        assert_eq!(params.len(), 1);
+        assert_eq!(params.get("id"), Some("2"));
+    }
+
+    #[test]
+    fn test_pattern_names() {
+        assert_eq!(
+            pattern_names("/{{literal}}/{id}.json/{*rest}"),
+            [Arc::from("id"), Arc::from("rest")]
+        );
+        assert!(pattern_names("/static").is_empty());
+    }
+}
@@ -0,0 +1,199 @@
+//! Reusable byte buffers for request and response bodies.
+//!
+//! With a pool set, request bodies that arrive in several chunks and JSON
+//! responses are written into recycled buffers instead of fresh
+//! allocations. A buffer returns to the pool when the last `Bytes` handle
+//! to it is dropped.
+//!
+//! ```rust
+//! use rust_api::{RustApi, pool::BufferPool};
+//! use std::sync::Arc;
+//!
+//! let pool = Arc::new(BufferPool::new(256));
+//! let mut app = RustApi::new();
+//! app.set_buffer_pool(Arc::clone(&pool));
+//! // later: pool.stats().hit_rate()
+//! ```
+
+use bytes::Bytes;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+const DEFAULT_MAX_BUFFER_SIZE: usize = 1024 * 1024;
+
+tokio::task_local! {
+    static CURRENT: Arc<BufferPool>;
+}
+
+/// Pool of byte buffers shared by an app's connections.
+#[derive(Debug)]
+pub struct BufferPool {
+    buffers: Mutex<Vec<Vec<u8>>>,
+    max_buffers: usize,
+    max_buffer_size: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// Point-in-time pool counters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct PoolStats {
+    /// Buffers taken from the pool.
+    pub hits: u64,
+    /// Buffers allocated because the pool was empty.
+    pub misses: u64,
+    /// Buffers currently waiting in the pool.
+    pub available: usize,
+}
+
+impl PoolStats {
+    /// Fraction of requests served from the pool (0.0 when unused).
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+impl BufferPool {
+    /// Create a pool keeping at most `max_buffers` idle buffers.
+    pub fn new(max_buffers: usize) -> Self {
+        Self {
+            buffers: Mutex::new(Vec::with_capacity(max_buffers)),
+            max_buffers,
+            max_buffer_size: DEFAULT_MAX_BUFFER_SIZE,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Drop buffers that grew beyond `size` bytes instead of pooling them
+    /// (default 1 MiB).
+    pub fn max_buffer_size(mut self, size: usize) -> Self {
+        self.max_buffer_size = size;
+        self
+    }
+
+    /// Read current counters.
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            available: self.buffers.lock().unwrap().len(),
+        }
+    }
+
+    /// Take an empty buffer with `capacity` bytes reserved, up to the
+    /// maximum buffer size.
+    pub(crate) fn get(self: &Arc<Self>, capacity: usize) -> PooledBuf {
+        let capacity = capacity.min(self.max_buffer_size);
+        let pooled = self.buffers.lock().unwrap().pop();
+        let buf = match pooled {
+            Some(mut buf) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                buf.reserve(capacity);
+                buf
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                Vec::with_capacity(capacity)
+            }
+        };
+        PooledBuf {
+            buf,
+            pool: Arc::clone(self),
+        }
+    }
+
+    pub(crate) fn max_buffers(&self) -> usize {
+        self.max_buffers
+    }
+
+    fn put(&self, mut buf: Vec<u8>) {
+        if buf.capacity() == 0 || buf.capacity() > self.max_buffer_size {
+            return;
+        }
+        buf.clear();
+        let mut buffers = self.buffers.lock().unwrap();
+        if buffers.len() < self.max_buffers {
+            buffers.push(buf);
+        }
+    }
+
+    /// Run `future` with this pool available to [`current`].
+    pub(crate) async fn scope<F: Future>(self: Arc<Self>, future: F) -> F::Output {
+        CURRENT.scope(self, future).await
+    }
+}
+
+/// Pool of the request being handled on this task, if any.
+pub(crate) fn current() -> Option<Arc<BufferPool>> {
+    CURRENT.try_with(Arc::clone).ok()
+}
+
+/// Buffer that goes back to its pool when dropped.
+pub(crate) struct PooledBuf {
+    buf: Vec<u8>,
+    pool: Arc<BufferPool>,
+}
+
+impl PooledBuf {
+    pub(crate) fn as_mut_vec(&mut self) -> &mut Vec<u8> {
+        &mut self.buf
+    }
+
+    /// Freeze into `Bytes`; the buffer is recycled once all clones drop.
+    pub(crate) fn freeze(self) -> Bytes {
+        Bytes::from_owner(self)
+    }
+}
+
+impl AsRef<[u8]> for PooledBuf {
+    fn as_ref(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+impl Drop for PooledBuf {
+    fn drop(&mut self) {
+        self.pool.put(std::mem::take(&mut self.buf));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffers_are_recycled() {
+        let pool = Arc::new(BufferPool::new(2));
+
+        let mut buf = pool.get(64);
+        buf.as_mut_vec().extend_from_slice(b"hello");
+        let bytes = buf.freeze();
+        assert_eq!(&bytes[..], b"hello");
+        assert_eq!(pool.stats().available, 0);
+
+        drop(bytes);
+        assert_eq!(pool.stats().available, 1);
+
+        let buf = pool.get(16);
+        assert!(buf.as_ref().is_empty());
+        let stats = pool.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+        assert_eq!(stats.hit_rate(), 0.5);
+    }
+
+    #[test]
+    fn test_oversized_buffers_are_dropped() {
+        let pool = Arc::new(BufferPool::new(2).max_buffer_size(16));
+        let mut buf = pool.get(1024);
+        assert!(buf.as_mut_vec().capacity() <= 16);
+        buf.as_mut_vec().extend_from_slice(&[0; 1024]);
+        drop(buf);
+        assert_eq!(pool.stats().available, 0);
+    }
+}
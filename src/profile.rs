@@ -0,0 +1,214 @@
+//! Environment profiles.
+//!
+//! A [`Profile`] switches framework defaults between development, staging
+//! and production. `RustApi::new` reads it from `RUST_API_PROFILE`
+//! (`dev`, `staging` or `prod`); `RustApi::set_profile` overrides it.
+//! Without either, every feature behaves exactly as configured.
+//!
+//! | Setting           | dev | staging | prod |
+//! |-------------------|-----|---------|------|
+//! | `error_details`   | yes | no      | no   |
+//! | `hot_reload`      | yes | no      | no   |
+//! | `permissive_cors` | yes | no      | no   |
+//! | `debug_endpoints` | yes | yes     | no   |
+//!
+//! ```rust
+//! use rust_api::{RustApi, profile::Profile};
+//!
+//! let mut app = RustApi::new();
+//! // Production, but keep the route table reachable.
+//! app.set_profile(Profile {
+//!     debug_endpoints: true,
+//!     ..Profile::production()
+//! });
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::Error;
+
+/// Deployment environment a profile was derived from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Environment {
+    /// Local development.
+    Development,
+    /// Pre-production.
+    Staging,
+    /// Production.
+    Production,
+}
+
+impl FromStr for Environment {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "dev" | "development" => Ok(Self::Development),
+            "staging" | "stage" => Ok(Self::Staging),
+            "prod" | "production" => Ok(Self::Production),
+            other => Err(Error::Custom(format!("unknown profile `{}`", other))),
+        }
+    }
+}
+
+impl fmt::Display for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Development => "development",
+            Self::Staging => "staging",
+            Self::Production => "production",
+        })
+    }
+}
+
+/// Framework defaults for one environment.
+///
+/// Start from [`Profile::development`], [`Profile::staging`] or
+/// [`Profile::production`] and override single settings with struct
+/// update syntax.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profile {
+    /// Environment the defaults came from.
+    pub environment: Environment,
+    /// Show error messages in 5xx bodies, with HTML error pages in
+    /// development. When off, server errors answer with terse JSON;
+    /// reporters still see the message.
+    pub error_details: bool,
+    /// Allow `dev::DevReload` to install its reload endpoint.
+    pub hot_reload: bool,
+    /// Answer CORS for any origin, ahead of any configured policy.
+    pub permissive_cors: bool,
+    /// Allow debug endpoints such as `RustApi::serve_route_table`.
+    pub debug_endpoints: bool,
+}
+
+impl Profile {
+    /// Environment variable naming the active profile.
+    pub const ENV_VAR: &'static str = "RUST_API_PROFILE";
+
+    /// Defaults for `environment`.
+    pub fn new(environment: Environment) -> Self {
+        let dev = environment == Environment::Development;
+        Self {
+            environment,
+            error_details: dev,
+            hot_reload: dev,
+            permissive_cors: dev,
+            debug_endpoints: environment != Environment::Production,
+        }
+    }
+
+    /// Development defaults: everything on.
+    pub fn development() -> Self {
+        Self::new(Environment::Development)
+    }
+
+    /// Staging defaults: debug endpoints only.
+    pub fn staging() -> Self {
+        Self::new(Environment::Staging)
+    }
+
+    /// Production defaults: everything off.
+    pub fn production() -> Self {
+        Self::new(Environment::Production)
+    }
+
+    /// Read the profile from [`Profile::ENV_VAR`].
+    ///
+    /// Returns `None` when unset. An unrecognized value logs an error and
+    /// falls back to production, so a typo never enables dev behavior.
+    pub fn from_env() -> Option<Self> {
+        let value = std::env::var(Self::ENV_VAR).ok()?;
+        let environment = value.parse().unwrap_or_else(|e| {
+            log::error!("{}: {}; using production", Self::ENV_VAR, e);
+            Environment::Production
+        });
+        Some(Self::new(environment))
+    }
+
+    /// Whether this is the production environment.
+    pub fn is_production(&self) -> bool {
+        self.environment == Environment::Production
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cors::CorsConfig;
+    use crate::test::{TestClient, assert_header, assert_status, body_text};
+    use crate::{Req, RustApi};
+
+    #[test]
+    fn test_environment_defaults() {
+        assert_eq!(
+            "Prod".parse::<Environment>().unwrap(),
+            Environment::Production
+        );
+        assert!("qa".parse::<Environment>().is_err());
+        assert!(Profile::development().hot_reload);
+        assert!(Profile::staging().debug_endpoints);
+        assert!(!Profile::staging().error_details);
+        let prod = Profile::production();
+        assert!(!prod.debug_endpoints && !prod.permissive_cors && prod.is_production());
+    }
+
+    #[tokio::test]
+    async fn test_profile_switches_defaults() {
+        let mut app = RustApi::new();
+        app.set_profile(Profile::production());
+        app.serve_route_table("/_routes");
+        app.get("/fail", |_req: Req| async {
+            crate::Result::<&str>::Err(crate::Error::internal("db password wrong"))
+        });
+        let client = TestClient::new(app);
+        assert_status(&client.get("/_routes").send().await, 404);
+        let res = client.get("/fail").send().await;
+        assert_status(&res, 500);
+        assert_eq!(
+            body_text(res).await,
+            r#"{"error":"Internal Server Error","status":500}"#
+        );
+
+        let mut app = RustApi::new();
+        app.set_profile(Profile::development());
+        app.get("/fail", |_req: Req| async {
+            crate::Result::<&str>::Err(crate::Error::internal("db password wrong"))
+        });
+        let client = TestClient::new(app);
+        let res = client
+            .get("/fail")
+            .header("origin", "http://localhost:5173")
+            .send()
+            .await;
+        assert_header(&res, "access-control-allow-origin", "*");
+        assert_eq!(body_text(res).await, "500 db password wrong");
+    }
+
+    #[tokio::test]
+    async fn test_terse_errors_keep_headers() {
+        let mut app = RustApi::new();
+        app.set_profile(Profile::production());
+        app.attach_pre_routing(CorsConfig::new().allow_origins(["https://app.example"]));
+        app.get("/fail", |_req: Req| async {
+            crate::Result::<&str>::Err(crate::Error::internal("db password wrong"))
+        });
+        let client = TestClient::new(app);
+        let res = client
+            .get("/fail")
+            .header("origin", "https://app.example")
+            .send()
+            .await;
+        assert_status(&res, 500);
+        assert_header(&res, "access-control-allow-origin", "https://app.example");
+        assert_header(&res, "vary", "Origin");
+        assert_header(&res, "content-type", "application/json");
+        assert_eq!(
+            body_text(res).await,
+            r#"{"error":"Internal Server Error","status":500}"#
+        );
+    }
+}
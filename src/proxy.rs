@@ -0,0 +1,423 @@
+//! Trusted reverse proxies.
+//!
+//! Behind a load balancer the connection comes from the proxy, which
+//! reports the client's scheme and host in `X-Forwarded-Proto` and
+//! `X-Forwarded-Host`. Those headers are honored by [`Req::scheme`],
+//! [`Req::host`] and [`Req::full_url`] only when the peer is listed in the
+//! app's [`TrustedProxies`]; anyone else could forge them.
+//!
+//! ```rust
+//! use rust_api::{RustApi, proxy::TrustedProxies};
+//!
+//! let mut app = RustApi::new();
+//! app.set_trusted_proxies(TrustedProxies::new().allow("10.0.0.0/8")?);
+//! # Ok::<(), rust_api::Error>(())
+//! ```
+//!
+//! The [`Forwarded`] pre-routing middleware goes further: it folds RFC 7239
+//! `Forwarded` and the legacy `X-Forwarded-*` headers into one
+//! [`ForwardedInfo`] extension and removes those headers when the peer is
+//! not trusted, so handlers never see forged copies.
+//!
+//! [`Req::scheme`]: crate::Req::scheme
+//! [`Req::host`]: crate::Req::host
+//! [`Req::full_url`]: crate::Req::full_url
+
+use async_trait::async_trait;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use crate::{Error, Middleware, Next, Req, Res, Result};
+
+/// Headers describing the path through proxies.
+const FORWARDING_HEADERS: [&str; 4] = [
+    "forwarded",
+    "x-forwarded-for",
+    "x-forwarded-proto",
+    "x-forwarded-host",
+];
+
+/// Peers whose forwarding headers are believed.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    all: bool,
+    nets: Vec<(IpAddr, u8)>,
+}
+
+impl TrustedProxies {
+    /// Trust no peer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trust every peer, for apps only reachable through the proxy.
+    pub fn all() -> Self {
+        Self {
+            all: true,
+            nets: Vec::new(),
+        }
+    }
+
+    /// Also trust `cidr`, an address (`127.0.0.1`) or network
+    /// (`10.0.0.0/8`, `fd00::/8`).
+    pub fn allow(mut self, cidr: &str) -> Result<Self> {
+        let invalid = || Error::Custom(format!("Invalid proxy address: {}", cidr));
+        let (addr, prefix) = match cidr.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (cidr, None),
+        };
+        let addr: IpAddr = addr.trim().parse().map_err(|_| invalid())?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.trim().parse().map_err(|_| invalid())?,
+            None => max,
+        };
+        if prefix > max {
+            return Err(invalid());
+        }
+        self.nets.push((addr, prefix));
+        Ok(self)
+    }
+
+    /// Whether a request from `peer` comes through a trusted proxy.
+    ///
+    /// Requests without a peer address are only trusted by [`all`](Self::all).
+    pub fn trusts(&self, peer: Option<IpAddr>) -> bool {
+        if self.all {
+            return true;
+        }
+        let Some(peer) = peer.map(|peer| peer.to_canonical()) else {
+            return false;
+        };
+        self.nets.iter().any(|&(net, prefix)| match (net, peer) {
+            (IpAddr::V4(net), IpAddr::V4(peer)) => {
+                same_prefix(u32::from(net).into(), u32::from(peer).into(), prefix, 32)
+            }
+            (IpAddr::V6(net), IpAddr::V6(peer)) => {
+                same_prefix(u128::from(net), u128::from(peer), prefix, 128)
+            }
+            _ => false,
+        })
+    }
+}
+
+/// Whether the top `prefix` of `bits` bits agree.
+fn same_prefix(a: u128, b: u128, prefix: u8, bits: u32) -> bool {
+    let shift = bits - u32::from(prefix);
+    shift >= bits || (a ^ b) >> shift == 0
+}
+
+/// Client connection as reported through trusted proxies, inserted by
+/// [`Forwarded`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ForwardedInfo {
+    /// Originating client address; `None` if hidden or unknown.
+    pub client: Option<IpAddr>,
+    /// Scheme the client used (`http` or `https`).
+    pub proto: Option<String>,
+    /// Host the client addressed.
+    pub host: Option<String>,
+    /// Interface of the proxy that received the request (`by=`).
+    pub by: Option<String>,
+}
+
+/// Pre-routing middleware resolving [`ForwardedInfo`].
+///
+/// With a trusted peer, the `Forwarded` header is read (or, without one,
+/// `X-Forwarded-For`/`-Proto`/`-Host`), skipping hops that are themselves
+/// trusted proxies to find the client. Otherwise the forwarding headers are
+/// removed and the info describes the direct connection.
+#[derive(Debug, Clone, Default)]
+pub struct Forwarded;
+
+impl Forwarded {
+    /// Create the middleware; trust comes from `RustApi::set_trusted_proxies`.
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn resolve(req: &Req, proxies: &TrustedProxies) -> ForwardedInfo {
+        let mut hops = match req.header("forwarded") {
+            Some(header) => parse_forwarded(header),
+            None => legacy_hops(req),
+        };
+        if hops.is_empty() {
+            return direct(req);
+        }
+        // Each proxy appends a hop, so the client is the rightmost hop that
+        // is not itself a trusted proxy.
+        let index = hops
+            .iter()
+            .rposition(|hop| !proxies.trusts(hop.client))
+            .unwrap_or(0);
+        let mut info = hops.swap_remove(index);
+        info.proto = info.proto.or_else(|| direct_proto(req));
+        info.host = info.host.or_else(|| req.header("host").map(str::to_string));
+        info
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> Middleware<S> for Forwarded {
+    async fn handle(&self, mut req: Req, _state: Arc<S>, next: Next<S>) -> Res {
+        let proxies = req.extensions().get::<Arc<TrustedProxies>>().cloned();
+        let info = match proxies {
+            Some(proxies) if req.via_trusted_proxy() => Self::resolve(&req, &proxies),
+            _ => {
+                let headers = req.headers_mut();
+                for name in FORWARDING_HEADERS {
+                    headers.remove(name);
+                }
+                direct(&req)
+            }
+        };
+        req.extensions_mut().insert(info);
+        next.run(req).await
+    }
+}
+
+/// Info for a request taken at face value.
+fn direct(req: &Req) -> ForwardedInfo {
+    ForwardedInfo {
+        client: req.peer_addr().map(|addr| addr.ip()),
+        proto: direct_proto(req),
+        host: req.header("host").map(str::to_string),
+        by: None,
+    }
+}
+
+fn direct_proto(req: &Req) -> Option<String> {
+    req.uri().scheme_str().map(str::to_ascii_lowercase)
+}
+
+/// Hops from `X-Forwarded-For`; proto and host apply to the client.
+fn legacy_hops(req: &Req) -> Vec<ForwardedInfo> {
+    let first = |name: &str| {
+        req.header(name)
+            .and_then(|value| value.split(',').next())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+    };
+    let mut hops: Vec<ForwardedInfo> = req
+        .header("x-forwarded-for")
+        .into_iter()
+        .flat_map(|value| value.split(','))
+        .map(|node| ForwardedInfo {
+            client: parse_node(node),
+            ..ForwardedInfo::default()
+        })
+        .collect();
+    if hops.is_empty()
+        && (first("x-forwarded-proto").is_some() || first("x-forwarded-host").is_some())
+    {
+        hops.push(ForwardedInfo::default());
+    }
+    let proto = first("x-forwarded-proto").and_then(normalize_proto);
+    let host = first("x-forwarded-host").map(str::to_string);
+    for hop in &mut hops {
+        hop.proto.clone_from(&proto);
+        hop.host.clone_from(&host);
+    }
+    hops
+}
+
+/// Parse an RFC 7239 `Forwarded` header into its elements.
+fn parse_forwarded(header: &str) -> Vec<ForwardedInfo> {
+    split_unquoted(header, ',')
+        .into_iter()
+        .map(|element| {
+            let mut info = ForwardedInfo::default();
+            for pair in split_unquoted(element, ';') {
+                let Some((name, value)) = pair.split_once('=') else {
+                    continue;
+                };
+                let value = value.trim().trim_matches('"');
+                match name.trim().to_ascii_lowercase().as_str() {
+                    "for" => info.client = parse_node(value),
+                    "proto" => info.proto = normalize_proto(value),
+                    "host" => info.host = Some(value.to_string()),
+                    "by" => info.by = Some(value.to_string()),
+                    _ => {}
+                }
+            }
+            info
+        })
+        .collect()
+}
+
+/// Split `value` on `separator` outside double quotes.
+fn split_unquoted(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut start, mut quoted) = (0, false);
+    for (i, c) in value.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c == separator && !quoted => {
+                parts.push(value[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(value[start..].trim());
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+/// Address of a node such as `192.0.2.1:8080` or `[2001:db8::1]:443`;
+/// `None` for `unknown` and obfuscated identifiers.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Ok(addr) = node.parse::<IpAddr>() {
+        return Some(addr);
+    }
+    if let Ok(addr) = node.parse::<SocketAddr>() {
+        return Some(addr.ip());
+    }
+    node.strip_prefix('[')?.strip_suffix(']')?.parse().ok()
+}
+
+fn normalize_proto(proto: &str) -> Option<String> {
+    let proto = proto.trim().to_ascii_lowercase();
+    matches!(proto.as_str(), "http" | "https").then_some(proto)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{TestClient, body_text};
+    use crate::{Host, Req, Res, RustApi};
+
+    #[test]
+    fn test_trusted_proxies_match_networks() {
+        let proxies = TrustedProxies::new()
+            .allow("10.0.0.0/8")
+            .unwrap()
+            .allow("::1")
+            .unwrap();
+        assert!(proxies.trusts(Some("10.20.30.40".parse().unwrap())));
+        assert!(proxies.trusts(Some("::ffff:10.0.0.1".parse().unwrap())));
+        assert!(proxies.trusts(Some("::1".parse().unwrap())));
+        assert!(!proxies.trusts(Some("11.0.0.1".parse().unwrap())));
+        assert!(!proxies.trusts(None));
+        assert!(TrustedProxies::new().allow("10.0.0.0/33").is_err());
+        assert!(
+            TrustedProxies::new()
+                .allow("0.0.0.0/0")
+                .unwrap()
+                .trusts(Some("8.8.8.8".parse().unwrap()))
+        );
+    }
+
+    async fn send(client: &TestClient, path: &str) -> Res {
+        client
+            .get(path)
+            .header("host", "10.0.0.5:8080")
+            .header("x-forwarded-proto", "https, http")
+            .header("x-forwarded-host", "api.example.com")
+            .send()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_full_url_behind_proxy() {
+        let build = |proxies: Option<TrustedProxies>| {
+            let mut app = RustApi::new();
+            if let Some(proxies) = proxies {
+                app.set_trusted_proxies(proxies);
+            }
+            app.get("/callback", |req: Req| async move {
+                req.full_url().unwrap_or_default()
+            });
+            app.get("/host", |Host(host): Host| async move { host });
+            TestClient::new(app)
+        };
+        let trusted = build(Some(TrustedProxies::all()));
+        let res = send(&trusted, "/callback?code=1").await;
+        assert_eq!(
+            body_text(res).await,
+            "https://api.example.com/callback?code=1"
+        );
+        assert_eq!(
+            body_text(send(&trusted, "/host").await).await,
+            "api.example.com"
+        );
+
+        let direct = build(None);
+        let res = send(&direct, "/callback?code=1").await;
+        assert_eq!(body_text(res).await, "http://10.0.0.5:8080/callback?code=1");
+    }
+
+    #[tokio::test]
+    async fn test_forwarded_middleware() {
+        let build = |proxies: TrustedProxies| {
+            let mut app = RustApi::new();
+            app.set_trusted_proxies(proxies);
+            app.attach_pre_routing(Forwarded::new());
+            app.get("/", |req: Req| async move {
+                let info = req.extensions().get::<ForwardedInfo>().cloned().unwrap();
+                format!(
+                    "{:?} {} {} {}",
+                    info.client,
+                    req.scheme(),
+                    req.host().unwrap_or("-"),
+                    req.header("x-forwarded-for").unwrap_or("-")
+                )
+            });
+            TestClient::new(app)
+        };
+
+        let client = build(TrustedProxies::new().allow("10.0.0.0/8").unwrap());
+        let send = |peer: &str, headers: &[(&str, &str)]| {
+            let mut req = Req::builder().uri("/").peer_addr(peer.parse().unwrap());
+            for (name, value) in headers {
+                req = req.header(name, value);
+            }
+            client.send_req(req.build())
+        };
+
+        let res = send(
+            "10.0.0.2:5000",
+            &[
+                ("host", "internal:8080"),
+                (
+                    "forwarded",
+                    r#"for=198.51.100.7;proto=https;host="api.example.com", for="[2001:db8::1]:4711", for=10.0.0.9"#,
+                ),
+            ],
+        )
+        .await;
+        assert_eq!(
+            body_text(res).await,
+            "Some(2001:db8::1) http internal:8080 -"
+        );
+
+        let res = send(
+            "10.0.0.2:5000",
+            &[
+                ("x-forwarded-for", "203.0.113.5, 10.0.0.3"),
+                ("x-forwarded-proto", "https"),
+                ("x-forwarded-host", "shop.example.com"),
+            ],
+        )
+        .await;
+        assert_eq!(
+            body_text(res).await,
+            "Some(203.0.113.5) https shop.example.com 203.0.113.5, 10.0.0.3"
+        );
+
+        let res = send(
+            "192.0.2.1:5000",
+            &[
+                ("host", "api.example.com"),
+                ("x-forwarded-for", "1.2.3.4"),
+                ("x-forwarded-proto", "https"),
+            ],
+        )
+        .await;
+        assert_eq!(
+            body_text(res).await,
+            "Some(192.0.2.1) http api.example.com -"
+        );
+    }
+}
@@ -0,0 +1,320 @@
+//! Per-tenant quotas.
+//!
+//! [`Quota`] counts requests per tenant (or any other key, such as an API
+//! key) in fixed windows, with the limit chosen by the tenant's plan.
+//! Responses carry `X-Quota-Limit`, `X-Quota-Remaining` and
+//! `X-Quota-Reset` (seconds until the window ends); requests over the
+//! limit get 429 with `Retry-After` and are not counted.
+//!
+//! Counters live in a [`QuotaStore`]. [`InMemoryQuotaStore`] is the
+//! default; implement the trait over a database to share counts between
+//! instances or keep them for billing. If the store fails, requests are let
+//! through and the error is logged.
+//!
+//! ```rust
+//! use rust_api::{RustApi, quota::Quota, route::RateLimit};
+//! use rust_api::tenant::{StaticTenants, Tenancy, TenantContext};
+//!
+//! let tenants = StaticTenants::new().tenant("acme", "pro");
+//! let mut app = RustApi::new();
+//! app.attach_pre_routing(Tenancy::new(tenants).header("x-tenant"));
+//! app.attach(
+//!     Quota::new()
+//!         .plan("free", RateLimit::per_minute(60))
+//!         .plan("pro", RateLimit::per_minute(6000))
+//!         .plan_by(|req| {
+//!             let tenant = req.extensions().get::<TenantContext<&'static str>>()?;
+//!             Some(tenant.config.to_string())
+//!         }),
+//! );
+//! ```
+
+use async_trait::async_trait;
+use hyper::header::{self, HeaderName, HeaderValue};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::route::RateLimit;
+use crate::tenant::TenantId;
+use crate::{Error, IntoRes, Middleware, Next, Req, Res, Result};
+
+/// Persistent request counters.
+#[async_trait]
+pub trait QuotaStore: Send + Sync + 'static {
+    /// Count one request for `key` in the window starting at `window`
+    /// (seconds since the Unix epoch) and lasting `period` seconds, unless
+    /// `limit` requests were already counted there. Returns the new count,
+    /// or `None` when over the limit.
+    async fn try_acquire(
+        &self,
+        key: &str,
+        window: u64,
+        period: u64,
+        limit: u64,
+    ) -> Result<Option<u64>>;
+}
+
+/// Counters kept in memory, current window only.
+///
+/// Keys whose window has ended are swept out when new keys are added.
+#[derive(Debug, Default)]
+pub struct InMemoryQuotaStore {
+    counts: Mutex<Counts>,
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    /// Window start, window end and count by key.
+    keys: HashMap<String, (u64, u64, u64)>,
+    /// Number of keys at which the next new key triggers a sweep.
+    sweep_at: usize,
+}
+
+impl InMemoryQuotaStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests counted for `key` in its latest window.
+    pub fn usage(&self, key: &str) -> u64 {
+        let counts = self.counts.lock().unwrap();
+        counts.keys.get(key).map_or(0, |&(_, _, count)| count)
+    }
+}
+
+#[async_trait]
+impl QuotaStore for InMemoryQuotaStore {
+    async fn try_acquire(
+        &self,
+        key: &str,
+        window: u64,
+        period: u64,
+        limit: u64,
+    ) -> Result<Option<u64>> {
+        let mut counts = self.counts.lock().unwrap();
+        if !counts.keys.contains_key(key) && counts.keys.len() >= counts.sweep_at {
+            // It is at least `window` now, so windows ending by then are over.
+            counts.keys.retain(|_, &mut (_, end, _)| end > window);
+            counts.sweep_at = (counts.keys.len() * 2).max(64);
+        }
+        let end = window + period;
+        let entry = counts
+            .keys
+            .entry(key.to_string())
+            .or_insert((window, end, 0));
+        if entry.0 != window {
+            *entry = (window, end, 0);
+        }
+        if entry.2 >= limit {
+            return Ok(None);
+        }
+        entry.2 += 1;
+        Ok(Some(entry.2))
+    }
+}
+
+#[async_trait]
+impl<T: QuotaStore> QuotaStore for Arc<T> {
+    async fn try_acquire(
+        &self,
+        key: &str,
+        window: u64,
+        period: u64,
+        limit: u64,
+    ) -> Result<Option<u64>> {
+        (**self).try_acquire(key, window, period, limit).await
+    }
+}
+
+type KeyFn = dyn Fn(&Req) -> Option<String> + Send + Sync;
+
+/// Middleware enforcing per-plan request quotas.
+pub struct Quota {
+    store: Arc<dyn QuotaStore>,
+    plans: HashMap<String, RateLimit>,
+    default_plan: Option<String>,
+    key: Box<KeyFn>,
+    plan: Option<Box<KeyFn>>,
+}
+
+impl Quota {
+    /// Count requests per [`TenantId`] in memory. Requests without a
+    /// tenant are not limited.
+    pub fn new() -> Self {
+        Self {
+            store: Arc::new(InMemoryQuotaStore::new()),
+            plans: HashMap::new(),
+            default_plan: None,
+            key: Box::new(|req| req.extensions().get::<TenantId>().map(|id| id.0.clone())),
+            plan: None,
+        }
+    }
+
+    /// Keep counters in `store`.
+    pub fn store(mut self, store: impl QuotaStore) -> Self {
+        self.store = Arc::new(store);
+        self
+    }
+
+    /// Allow `limit.requests` per `limit.period` on plan `name`. The first
+    /// plan added is the default unless [`default_plan`](Self::default_plan)
+    /// says otherwise.
+    pub fn plan(mut self, name: &str, limit: RateLimit) -> Self {
+        self.default_plan.get_or_insert_with(|| name.to_string());
+        self.plans.insert(name.to_string(), limit);
+        self
+    }
+
+    /// Plan for keys whose plan is unknown.
+    pub fn default_plan(mut self, name: &str) -> Self {
+        self.default_plan = Some(name.to_string());
+        self
+    }
+
+    /// Count requests per key returned by `key`, e.g. a user or API key,
+    /// instead of per tenant. Requests without a key are not limited.
+    pub fn key_by<F>(mut self, key: F) -> Self
+    where
+        F: Fn(&Req) -> Option<String> + Send + Sync + 'static,
+    {
+        self.key = Box::new(key);
+        self
+    }
+
+    /// Read the plan name of a request with `plan`, e.g. from its
+    /// [`TenantContext`](crate::tenant::TenantContext).
+    pub fn plan_by<F>(mut self, plan: F) -> Self
+    where
+        F: Fn(&Req) -> Option<String> + Send + Sync + 'static,
+    {
+        self.plan = Some(Box::new(plan));
+        self
+    }
+
+    /// Limit applying to `req`.
+    fn limit(&self, req: &Req) -> Option<RateLimit> {
+        let plan = self.plan.as_ref().and_then(|plan| plan(req));
+        plan.and_then(|plan| self.plans.get(&plan))
+            .or_else(|| self.plans.get(self.default_plan.as_deref()?))
+            .copied()
+    }
+}
+
+impl Default for Quota {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn set_header(res: &mut Res, name: &'static str, value: u64) {
+    res.headers_mut()
+        .insert(HeaderName::from_static(name), HeaderValue::from(value));
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> Middleware<S> for Quota {
+    async fn handle(&self, req: Req, _state: Arc<S>, next: Next<S>) -> Res {
+        let (Some(key), Some(limit)) = ((self.key)(&req), self.limit(&req)) else {
+            return next.run(req).await;
+        };
+        let period = limit.period.as_secs().max(1);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let window = now - now % period;
+        let reset = window + period - now;
+        let requests = u64::from(limit.requests);
+
+        let used = match self.store.try_acquire(&key, window, period, requests).await {
+            Ok(used) => used,
+            Err(e) => {
+                log::error!("quota store failed: {}", e);
+                return next.run(req).await;
+            }
+        };
+        let mut res = match used {
+            Some(_) => next.run(req).await,
+            None => {
+                let mut res = Error::too_many_requests("Quota exceeded").into_res();
+                res.headers_mut()
+                    .insert(header::RETRY_AFTER, HeaderValue::from(reset));
+                res
+            }
+        };
+        set_header(&mut res, "x-quota-limit", requests);
+        set_header(
+            &mut res,
+            "x-quota-remaining",
+            requests - used.unwrap_or(requests),
+        );
+        set_header(&mut res, "x-quota-reset", reset);
+        res
+    }
+
+    fn name(&self) -> &'static str {
+        "Quota"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RustApi;
+    use crate::tenant::{StaticTenants, Tenancy, TenantContext};
+    use crate::test::{TestClient, assert_header, assert_status};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_quota_per_plan() {
+        let store = Arc::new(InMemoryQuotaStore::new());
+        let tenants = StaticTenants::new()
+            .tenant("acme", "pro")
+            .tenant("globex", "free");
+        let mut app = RustApi::new();
+        app.attach_pre_routing(Tenancy::new(tenants).header("x-tenant"));
+        app.attach(
+            Quota::new()
+                .store(Arc::clone(&store))
+                .plan("free", RateLimit::new(1, Duration::from_secs(3600)))
+                .plan("pro", RateLimit::new(3, Duration::from_secs(3600)))
+                .plan_by(|req| {
+                    let tenant = req.extensions().get::<TenantContext<&'static str>>()?;
+                    Some(tenant.config.to_string())
+                }),
+        );
+        app.get("/", |_req: Req| async { "ok" });
+        let client = TestClient::new(app);
+        let get = |tenant: &'static str| client.get("/").header("x-tenant", tenant).send();
+
+        let res = get("acme").await;
+        assert_status(&res, 200);
+        assert_header(&res, "x-quota-limit", "3");
+        assert_header(&res, "x-quota-remaining", "2");
+        get("acme").await;
+        assert_header(&get("acme").await, "x-quota-remaining", "0");
+        let res = get("acme").await;
+        assert_status(&res, 429);
+        assert!(res.headers().contains_key("retry-after"));
+        assert_eq!(store.usage("acme"), 3);
+
+        assert_status(&get("globex").await, 200);
+        assert_status(&get("globex").await, 429);
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_sweeps_ended_windows() {
+        let store = InMemoryQuotaStore::new();
+        store.try_acquire("daily", 0, 86_400, 10).await.unwrap();
+        store.try_acquire("old", 0, 60, 10).await.unwrap();
+        for i in 0..64 {
+            let key = format!("key-{}", i);
+            store.try_acquire(&key, 120, 60, 10).await.unwrap();
+        }
+        assert_eq!(store.usage("old"), 0);
+        assert_eq!(store.usage("daily"), 1);
+        assert_eq!(store.usage("key-0"), 1);
+    }
+}
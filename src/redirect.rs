@@ -0,0 +1,142 @@
+//! Companion listener redirecting plain HTTP to HTTPS.
+
+use std::convert::Infallible;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use hyper::body::Incoming;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Request, Response};
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+
+use crate::res::BoxBody;
+use crate::{Error, IntoRes, Res};
+
+const ACME_PREFIX: &str = "/.well-known/acme-challenge/";
+
+/// Settings for the HTTP → HTTPS redirect listener.
+pub(crate) struct HttpsRedirect {
+    /// Port the HTTPS origin is served on (omitted from URLs when 443).
+    pub(crate) https_port: u16,
+    /// Directory holding ACME HTTP-01 challenge tokens.
+    pub(crate) acme_dir: Option<PathBuf>,
+}
+
+/// Serve redirects until shutdown is signalled.
+pub(crate) async fn serve(
+    listener: TcpListener,
+    config: HttpsRedirect,
+    mut shutdown_rx: watch::Receiver<bool>,
+) {
+    let config = Arc::new(config);
+
+    loop {
+        tokio::select! {
+            result = listener.accept() => {
+                let Ok((stream, _peer)) = result else {
+                    continue;
+                };
+                let config = Arc::clone(&config);
+                tokio::spawn(async move {
+                    let service = service_fn(move |req| {
+                        let config = Arc::clone(&config);
+                        async move { Ok::<_, Infallible>(respond(&req, &config).await) }
+                    });
+                    let _ = http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+            _ = shutdown_rx.changed() => break,
+        }
+    }
+}
+
+async fn respond(req: &Request<Incoming>, config: &HttpsRedirect) -> Response<BoxBody> {
+    let path = req.uri().path();
+
+    if let (Some(token), Some(dir)) = (path.strip_prefix(ACME_PREFIX), &config.acme_dir) {
+        return acme_challenge(dir, token).await.into_hyper();
+    }
+
+    let host = req
+        .headers()
+        .get(hyper::header::HOST)
+        .and_then(|h| h.to_str().ok());
+    let Some(host) = host else {
+        return Error::bad_request("Missing Host header")
+            .into_res()
+            .into_hyper();
+    };
+
+    let path_and_query = req.uri().path_and_query().map_or("/", |pq| pq.as_str());
+    Res::status(301)
+        .header(
+            "location",
+            https_location(host, config.https_port, path_and_query),
+        )
+        .into_hyper()
+}
+
+async fn acme_challenge(dir: &Path, token: &str) -> Res {
+    let valid = !token.is_empty()
+        && token
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+    if !valid {
+        return Error::not_found("Unknown challenge").into_res();
+    }
+
+    match tokio::fs::read(dir.join(token)).await {
+        Ok(contents) => Res::builder()
+            .header("content-type", "application/octet-stream")
+            .body(contents),
+        Err(_) => Error::not_found("Unknown challenge").into_res(),
+    }
+}
+
+fn https_location(host: &str, https_port: u16, path_and_query: &str) -> String {
+    // Drop the plain-HTTP port; bare IPv6 literals contain colons too.
+    let host = match host.rsplit_once(':') {
+        Some((name, port))
+            if !port.is_empty()
+                && port.bytes().all(|b| b.is_ascii_digit())
+                && (!name.contains(':') || name.ends_with(']')) =>
+        {
+            name
+        }
+        _ => host,
+    };
+
+    if https_port == 443 {
+        format!("https://{}{}", host, path_and_query)
+    } else {
+        format!("https://{}:{}{}", host, https_port, path_and_query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_https_location() {
+        assert_eq!(
+            https_location("example.com:80", 443, "/a?b=1"),
+            "https://example.com/a?b=1"
+        );
+        assert_eq!(
+            https_location("example.com:8080", 443, "/"),
+            "https://example.com/"
+        );
+        assert_eq!(
+            https_location("example.com", 8443, "/"),
+            "https://example.com:8443/"
+        );
+        assert_eq!(https_location("[::1]:80", 443, "/"), "https://[::1]/");
+        assert_eq!(https_location("[::1]", 443, "/"), "https://[::1]/");
+    }
+}
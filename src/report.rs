@@ -0,0 +1,252 @@
+//! Error reporting hooks.
+//!
+//! An [`ErrorReporter`] set with `RustApi::set_error_reporter` receives
+//! every 5xx response and every handler panic (answered with 500), with
+//! the request it failed on. Middleware that authenticates requests can
+//! insert a [`ReportUser`] into the request extensions to say who was
+//! affected.
+//!
+//! ```rust
+//! use rust_api::{Req, RustApi, report::ErrorReport};
+//!
+//! let mut app = RustApi::new();
+//! app.set_error_reporter(|report: &ErrorReport| {
+//!     log::error!("{} {} failed: {}", report.method, report.uri, report.message);
+//! });
+//! ```
+//!
+//! With the `sentry` feature, [`SentryReporter`] forwards reports to the
+//! Sentry client set up with `sentry::init`.
+
+use hyper::{HeaderMap, Method, Uri, header};
+use std::sync::{Arc, OnceLock};
+
+use crate::Req;
+use crate::error_handler::ErrorContext;
+use crate::profile::Profile;
+
+/// Receives failed requests. Runs on the request task; queue slow work.
+pub trait ErrorReporter: Send + Sync + 'static {
+    /// Report one failure.
+    fn report(&self, report: &ErrorReport);
+}
+
+impl<F> ErrorReporter for F
+where
+    F: Fn(&ErrorReport) + Send + Sync + 'static,
+{
+    fn report(&self, report: &ErrorReport) {
+        self(report)
+    }
+}
+
+/// User affected by a failure, read from the request extensions.
+#[derive(Debug, Clone, Default)]
+pub struct ReportUser {
+    /// Stable user id.
+    pub id: Option<String>,
+    /// Email address.
+    pub email: Option<String>,
+    /// Display or login name.
+    pub username: Option<String>,
+}
+
+/// A failed request.
+#[derive(Debug, Clone)]
+pub struct ErrorReport {
+    /// Response status sent to the client.
+    pub status: u16,
+    /// Error or panic message.
+    pub message: String,
+    /// Whether the handler panicked.
+    pub panicked: bool,
+    /// Request method.
+    pub method: Method,
+    /// Request URI.
+    pub uri: Uri,
+    /// Matched route pattern.
+    pub route: Option<Arc<str>>,
+    /// Request headers, with `Authorization`, `Cookie` and
+    /// `Proxy-Authorization` values redacted.
+    pub headers: HeaderMap,
+    /// User set by middleware, if any.
+    pub user: Option<ReportUser>,
+}
+
+/// Message of the error behind a 5xx response, set by `Error::into_res`.
+#[derive(Clone)]
+pub(crate) struct ErrorMessage(pub(crate) String);
+
+/// Request details kept while the handler owns the request.
+pub(crate) struct RequestContext {
+    pub(crate) request: ErrorContext,
+    pub(crate) user: Arc<OnceLock<ReportUser>>,
+}
+
+impl RequestContext {
+    pub(crate) fn capture(req: &Req, route: &Arc<str>, profile: Option<Profile>) -> Self {
+        let mut headers = req.headers().clone();
+        for name in [
+            header::AUTHORIZATION,
+            header::COOKIE,
+            header::PROXY_AUTHORIZATION,
+        ] {
+            if headers.contains_key(&name) {
+                headers.insert(name, header::HeaderValue::from_static("[redacted]"));
+            }
+        }
+        Self {
+            request: ErrorContext {
+                method: req.method().clone(),
+                uri: req.uri().clone(),
+                route: Some(Arc::clone(route)),
+                headers,
+                profile,
+            },
+            user: Arc::new(OnceLock::new()),
+        }
+    }
+
+    pub(crate) fn into_report(self, status: u16, message: String, panicked: bool) -> ErrorReport {
+        ErrorReport {
+            status,
+            message,
+            panicked,
+            method: self.request.method,
+            uri: self.request.uri,
+            route: self.request.route,
+            headers: self.request.headers,
+            user: self.user.get().cloned(),
+        }
+    }
+}
+
+/// Remember the user the innermost middleware saw.
+pub(crate) fn capture_user(req: &Req, slot: &OnceLock<ReportUser>) {
+    if let Some(user) = req.extensions().get::<ReportUser>() {
+        slot.set(user.clone()).ok();
+    }
+}
+
+/// Message carried by a panic payload.
+pub(crate) fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "handler panicked".to_string())
+}
+
+/// [`ErrorReporter`] sending events to Sentry.
+///
+/// Uses the client bound by `sentry::init`; without one, reports are
+/// dropped.
+#[cfg(feature = "sentry")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SentryReporter;
+
+#[cfg(feature = "sentry")]
+impl ErrorReporter for SentryReporter {
+    fn report(&self, report: &ErrorReport) {
+        use sentry_core::protocol::{Event, Level, Map, Request, User};
+
+        let headers: Map<String, String> = report
+            .headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        let url = report
+            .headers
+            .get(header::HOST)
+            .and_then(|host| host.to_str().ok())
+            .and_then(|host| format!("http://{}{}", host, report.uri.path()).parse().ok());
+        let mut tags = Map::new();
+        tags.insert("http.status_code".to_string(), report.status.to_string());
+        tags.insert("panicked".to_string(), report.panicked.to_string());
+        sentry_core::capture_event(Event {
+            level: Level::Error,
+            message: Some(report.message.clone()),
+            transaction: report
+                .route
+                .as_ref()
+                .map(|route| format!("{} {}", report.method, route)),
+            request: Some(Request {
+                url,
+                method: Some(report.method.to_string()),
+                query_string: report.uri.query().map(str::to_string),
+                headers,
+                ..Default::default()
+            }),
+            user: report.user.as_ref().map(|user| User {
+                id: user.id.clone(),
+                email: user.email.clone(),
+                username: user.username.clone(),
+                ..Default::default()
+            }),
+            tags,
+            ..Default::default()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{TestClient, assert_status};
+    use crate::{Error, IntoRes, Next, Res, RustApi};
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn test_reports_errors_and_panics() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let mut app = RustApi::new();
+        let sink = Arc::clone(&reports);
+        app.set_error_reporter(move |report: &ErrorReport| {
+            sink.lock().unwrap().push(report.clone());
+        });
+        app.attach(crate::from_fn(
+            |mut req: Req, _state: Arc<()>, next: Next<()>| async move {
+                req.extensions_mut().insert(ReportUser {
+                    id: Some("42".into()),
+                    ..Default::default()
+                });
+                next.run(req).await
+            },
+        ));
+        app.get("/ok", |_req: Req| async { "ok" });
+        app.get("/missing", |_req: Req| async {
+            Error::not_found("no such thing").into_res()
+        });
+        app.get("/db", |_req: Req| async {
+            crate::Result::<Res>::Err(Error::internal("pool exhausted"))
+        });
+        app.get("/panic", |_req: Req| async {
+            if true {
+                panic!("boom");
+            }
+            "unreachable"
+        });
+        let client = TestClient::new(app);
+
+        assert_status(&client.get("/ok").send().await, 200);
+        assert_status(&client.get("/missing").send().await, 404);
+        let db = client
+            .get("/db?page=2")
+            .header("authorization", "Bearer secret")
+            .send()
+            .await;
+        assert_status(&db, 500);
+        assert_status(&client.get("/panic").send().await, 500);
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].message, "HTTP 500: pool exhausted");
+        assert_eq!(reports[0].uri, "/db?page=2");
+        assert_eq!(reports[0].route.as_deref(), Some("/db"));
+        assert_eq!(reports[0].headers["authorization"], "[redacted]");
+        assert_eq!(reports[0].user.as_ref().unwrap().id.as_deref(), Some("42"));
+        assert!(!reports[0].panicked);
+        assert_eq!(reports[1].message, "boom");
+        assert!(reports[1].panicked);
+    }
+}
@@ -1,27 +1,151 @@
 //! HTTP request with lock-free body consumption.
 
 use bytes::Bytes;
+use futures_util::TryStreamExt;
 use http_body_util::BodyExt;
-use hyper::{Method, Request, Uri, body::Incoming, header};
-use std::collections::HashMap;
+use hyper::body::{Body, Frame, Incoming, SizeHint};
+use hyper::{Method, Request, Uri, Version, header};
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tokio::io::AsyncRead;
 use tokio::sync::OnceCell;
+use tokio_util::io::StreamReader;
 
+use crate::conn::{MinBodyRate, RateWatched};
 use crate::extensions::Extensions;
-use crate::{Error, Result};
+use crate::extractors::BodyStream;
+use crate::metrics::RequestTrace;
+use crate::pool::{self, BufferPool, PooledBuf};
+use crate::proxy::ForwardedInfo;
+use crate::{Error, PathParams, Result};
 
 #[cfg(feature = "websocket")]
 use hyper::upgrade::OnUpgrade;
 
+/// Raw request body: from a connection or held in memory.
+pub(crate) enum RequestBody {
+    Incoming(Incoming),
+    Watched(Box<RateWatched<Incoming>>),
+    Full(Option<Bytes>),
+}
+
+impl Body for RequestBody {
+    type Data = Bytes;
+    type Error = Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<std::result::Result<Frame<Bytes>, Error>>> {
+        match self.get_mut() {
+            RequestBody::Incoming(body) => Pin::new(body)
+                .poll_frame(cx)
+                .map(|frame| frame.map(|r| r.map_err(Error::from))),
+            RequestBody::Watched(body) => Pin::new(&mut **body).poll_frame(cx),
+            RequestBody::Full(bytes) => Poll::Ready(
+                bytes
+                    .take()
+                    .filter(|b| !b.is_empty())
+                    .map(|b| Ok(Frame::data(b))),
+            ),
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        match self {
+            RequestBody::Incoming(body) => body.is_end_stream(),
+            RequestBody::Watched(body) => body.is_end_stream(),
+            RequestBody::Full(bytes) => bytes.as_ref().is_none_or(Bytes::is_empty),
+        }
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        match self {
+            RequestBody::Incoming(body) => body.size_hint(),
+            RequestBody::Watched(body) => body.size_hint(),
+            RequestBody::Full(bytes) => {
+                SizeHint::with_exact(bytes.as_ref().map_or(0, |b| b.len() as u64))
+            }
+        }
+    }
+}
+
+/// Request headers, moved behind an `Arc` once shared with an extractor.
+enum HeaderStore {
+    Owned(header::HeaderMap),
+    Shared(Arc<header::HeaderMap>),
+}
+
+impl HeaderStore {
+    fn share(&mut self) -> Arc<header::HeaderMap> {
+        let map = match std::mem::replace(self, HeaderStore::Owned(header::HeaderMap::new())) {
+            HeaderStore::Owned(map) => Arc::new(map),
+            HeaderStore::Shared(map) => map,
+        };
+        *self = HeaderStore::Shared(Arc::clone(&map));
+        map
+    }
+
+    fn to_mut(&mut self) -> &mut header::HeaderMap {
+        if let HeaderStore::Shared(_) = self {
+            if let HeaderStore::Shared(map) =
+                std::mem::replace(self, HeaderStore::Owned(header::HeaderMap::new()))
+            {
+                *self = HeaderStore::Owned(Arc::unwrap_or_clone(map));
+            }
+        }
+        match self {
+            HeaderStore::Owned(map) => map,
+            HeaderStore::Shared(_) => unreachable!("headers were just made owned"),
+        }
+    }
+}
+
+impl std::ops::Deref for HeaderStore {
+    type Target = header::HeaderMap;
+
+    #[inline]
+    fn deref(&self) -> &header::HeaderMap {
+        match self {
+            HeaderStore::Owned(map) => map,
+            HeaderStore::Shared(map) => map,
+        }
+    }
+}
+
 /// HTTP request.
+///
+/// The body can be read in one of these ways:
+///
+/// - [`bytes`](Self::bytes) buffers it (up to the body limit) and caches
+///   it, so later calls and other extractors get the same bytes.
+/// - [`body_stream`](Self::body_stream) / [`into_body_stream`](Self::into_body_stream)
+///   yield chunks as they arrive; [`body_reader`](Self::body_reader) wraps
+///   them in an `AsyncRead`.
+///
+/// Streaming takes the body, so buffering afterwards fails unless it was
+/// already buffered.
 pub struct Req {
     method: Method,
     uri: Uri,
-    headers: header::HeaderMap,
+    headers: HeaderStore,
     body_cell: OnceCell<Bytes>,
-    incoming: Option<Incoming>,
-    path_params: HashMap<String, String>,
+    incoming: Option<RequestBody>,
+    path_params: PathParams,
+    matched_route: Option<Arc<str>>,
+    route_name: Option<Arc<str>>,
     extensions: Extensions,
     body_limit: Option<usize>,
+    trailers: Option<header::HeaderMap>,
+    trace: Option<Arc<RequestTrace>>,
+    version: Version,
+    peer_addr: Option<SocketAddr>,
+    via_trusted_proxy: bool,
     #[cfg(feature = "websocket")]
     upgrade: Option<OnUpgrade>,
 }
@@ -39,17 +163,29 @@ impl Req {
         Self {
             method: parts.method,
             uri: parts.uri,
-            headers: parts.headers,
+            headers: HeaderStore::Owned(parts.headers),
             body_cell: OnceCell::new(),
-            incoming: Some(body),
-            path_params: HashMap::new(),
+            incoming: Some(RequestBody::Incoming(body)),
+            path_params: PathParams::new(),
+            matched_route: None,
+            route_name: None,
             extensions: Extensions::new(),
             body_limit: None,
+            trailers: None,
+            trace: None,
+            version: parts.version,
+            peer_addr: None,
+            via_trusted_proxy: false,
             #[cfg(feature = "websocket")]
             upgrade,
         }
     }
 
+    /// Build an in-memory request (tests, fuzzing, replay).
+    pub fn builder() -> ReqBuilder {
+        ReqBuilder::new()
+    }
+
     /// Take the upgrade future (for WebSocket).
     #[cfg(feature = "websocket")]
     pub(crate) fn take_upgrade(&mut self) -> Option<OnUpgrade> {
@@ -61,6 +197,123 @@ impl Req {
         self.body_limit = limit;
     }
 
+    /// Record pipeline measurements into the given trace.
+    pub(crate) fn set_trace(&mut self, trace: Arc<RequestTrace>) {
+        self.trace = Some(trace);
+    }
+
+    pub(crate) fn trace(&self) -> Option<&RequestTrace> {
+        self.trace.as_deref()
+    }
+
+    pub(crate) fn set_peer_addr(&mut self, addr: SocketAddr) {
+        self.peer_addr = Some(addr);
+    }
+
+    /// Fail body reads with 408 when the client sends slower than `rate`,
+    /// setting `tripped`.
+    pub(crate) fn watch_body_rate(&mut self, rate: MinBodyRate, tripped: Arc<AtomicBool>) {
+        self.incoming = match self.incoming.take() {
+            Some(RequestBody::Incoming(body)) => Some(RequestBody::Watched(Box::new(
+                RateWatched::new(body, rate, tripped),
+            ))),
+            other => other,
+        };
+    }
+
+    pub(crate) fn set_via_trusted_proxy(&mut self, trusted: bool) {
+        self.via_trusted_proxy = trusted;
+    }
+
+    /// Copy of this request without body or extensions, for running it
+    /// again in the background.
+    pub(crate) fn replay(&self) -> Req {
+        Req {
+            method: self.method.clone(),
+            uri: self.uri.clone(),
+            headers: HeaderStore::Owned(self.headers().clone()),
+            body_cell: OnceCell::new(),
+            incoming: Some(RequestBody::Full(Some(Bytes::new()))),
+            path_params: self.path_params.clone(),
+            matched_route: self.matched_route.clone(),
+            route_name: self.route_name.clone(),
+            extensions: Extensions::new(),
+            body_limit: self.body_limit,
+            trailers: None,
+            trace: None,
+            version: self.version,
+            peer_addr: self.peer_addr,
+            via_trusted_proxy: self.via_trusted_proxy,
+            #[cfg(feature = "websocket")]
+            upgrade: None,
+        }
+    }
+
+    /// Get HTTP version.
+    #[inline]
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Address of the connected client (a proxy, if behind one).
+    ///
+    /// `None` for requests built in memory without one.
+    #[inline]
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+
+    /// Whether the peer is a trusted proxy (see [`proxy`](crate::proxy)).
+    #[inline]
+    pub fn via_trusted_proxy(&self) -> bool {
+        self.via_trusted_proxy
+    }
+
+    /// First value of a forwarding header, if sent by a trusted proxy.
+    fn forwarded(&self, name: &str) -> Option<&str> {
+        if !self.via_trusted_proxy {
+            return None;
+        }
+        let value = self.header(name)?.split(',').next()?.trim();
+        (!value.is_empty()).then_some(value)
+    }
+
+    /// Scheme the client used: from [`ForwardedInfo`], else
+    /// `X-Forwarded-Proto` from a trusted proxy, else the URI's, else `http`.
+    ///
+    /// [`ForwardedInfo`]: crate::proxy::ForwardedInfo
+    pub fn scheme(&self) -> &str {
+        let info = self.extensions.get::<ForwardedInfo>();
+        if let Some(proto) = info.and_then(|info| info.proto.as_deref()) {
+            return proto;
+        }
+        match self.forwarded("x-forwarded-proto") {
+            Some(proto) if proto.eq_ignore_ascii_case("https") => "https",
+            Some(proto) if proto.eq_ignore_ascii_case("http") => "http",
+            _ => self.uri.scheme_str().unwrap_or("http"),
+        }
+    }
+
+    /// Host the client addressed, with any port: from [`ForwardedInfo`],
+    /// else `X-Forwarded-Host` from a trusted proxy, else `Host`, else the
+    /// URI authority.
+    ///
+    /// [`ForwardedInfo`]: crate::proxy::ForwardedInfo
+    pub fn host(&self) -> Option<&str> {
+        let info = self.extensions.get::<ForwardedInfo>();
+        info.and_then(|info| info.host.as_deref())
+            .or_else(|| self.forwarded("x-forwarded-host"))
+            .or_else(|| self.header("host"))
+            .or_else(|| self.uri.authority().map(|authority| authority.as_str()))
+    }
+
+    /// Absolute URL the client requested, e.g. for OAuth redirects and
+    /// webhook callbacks. `None` without a known host.
+    pub fn full_url(&self) -> Option<String> {
+        let path = self.uri.path_and_query().map_or("/", |path| path.as_str());
+        Some(format!("{}://{}{}", self.scheme(), self.host()?, path))
+    }
+
     /// Get HTTP method.
     #[inline]
     pub fn method(&self) -> &Method {
@@ -73,6 +326,18 @@ impl Req {
         &self.uri
     }
 
+    /// Replace the HTTP method (pre-routing middleware).
+    #[inline]
+    pub fn set_method(&mut self, method: Method) {
+        self.method = method;
+    }
+
+    /// Replace the request URI (pre-routing middleware).
+    #[inline]
+    pub fn set_uri(&mut self, uri: Uri) {
+        self.uri = uri;
+    }
+
     /// Get request path.
     #[inline]
     pub fn path(&self) -> &str {
@@ -91,72 +356,145 @@ impl Req {
         self.headers.get(name).and_then(|v| v.to_str().ok())
     }
 
+    /// Get a cookie value from the `Cookie` headers.
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(';'))
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+    }
+
     /// Get headers.
     #[inline]
     pub fn headers(&self) -> &header::HeaderMap {
         &self.headers
     }
 
+    /// Get headers as a shared map, without copying them.
+    ///
+    /// Later calls to [`headers_mut`](Self::headers_mut) copy the map only
+    /// while the shared handle is still alive.
+    pub fn shared_headers(&mut self) -> Arc<header::HeaderMap> {
+        self.headers.share()
+    }
+
     /// Get mutable headers.
     #[inline]
     pub fn headers_mut(&mut self) -> &mut header::HeaderMap {
-        &mut self.headers
+        self.headers.to_mut()
     }
 
     /// Get path parameter.
     #[inline]
     pub fn param(&self, name: &str) -> Option<&str> {
-        self.path_params.get(name).map(|s| s.as_str())
+        self.path_params.get(name)
     }
 
     /// Get all path parameters.
     #[inline]
-    pub fn params(&self) -> &HashMap<String, String> {
+    pub fn params(&self) -> &PathParams {
         &self.path_params
     }
 
     /// Get path parameters (for extractors).
     #[inline]
-    pub fn path_params(&self) -> &HashMap<String, String> {
+    pub fn path_params(&self) -> &PathParams {
         &self.path_params
     }
 
+    /// Get the matched route pattern (e.g. `/users/{id}`).
+    ///
+    /// Available to middleware and handlers once routing has completed.
+    /// Prefer this over `path()` for metrics and log labels.
+    #[inline]
+    pub fn matched_route(&self) -> Option<&str> {
+        self.matched_route.as_deref()
+    }
+
+    /// Get the matched route name, if the route was named.
+    ///
+    /// Lets post-routing middleware apply conditionally per route.
+    #[inline]
+    pub fn route_name(&self) -> Option<&str> {
+        self.route_name.as_deref()
+    }
+
+    /// Read the whole body, buffering it on first call.
+    ///
+    /// Returns a cheap clone of the cached bytes.
+    ///
+    /// ```rust
+    /// use rust_api::{Req, Res};
+    ///
+    /// async fn checksum(mut req: Req) -> rust_api::Result<Res> {
+    ///     let body = req.bytes().await?;
+    ///     Ok(Res::text(format!("{} bytes", body.len())))
+    /// }
+    /// ```
+    pub async fn bytes(&mut self) -> Result<Bytes> {
+        self.buffered().await.cloned()
+    }
+
+    /// Buffer the body for inspection, leaving it readable downstream.
+    ///
+    /// For middleware such as signature checks: later [`bytes`](Self::bytes)
+    /// calls, body extractors and even [`BodyStream`] see the same bytes.
+    ///
+    /// ```rust
+    /// use rust_api::{Error, IntoRes, Next, Req, Res};
+    /// use std::sync::Arc;
+    ///
+    /// async fn verify(mut req: Req, state: Arc<()>, next: Next<()>) -> Res {
+    ///     let body = match req.peek_body().await {
+    ///         Ok(body) => body,
+    ///         Err(e) => return e.into_res(),
+    ///     };
+    ///     if body.is_empty() {
+    ///         return Error::bad_request("Empty body").into_res();
+    ///     }
+    ///     next.run(req).await // `Json<T>` still works in the handler
+    /// }
+    /// ```
+    pub async fn peek_body(&mut self) -> Result<Bytes> {
+        self.bytes().await
+    }
+
     /// Consume body as bytes (cached on first call).
+    #[deprecated(since = "0.0.6", note = "use `Req::bytes`")]
     pub async fn body(&mut self) -> Result<&Bytes> {
+        self.buffered().await
+    }
+
+    async fn buffered(&mut self) -> Result<&Bytes> {
         self.body_cell
             .get_or_try_init(|| async {
-                let incoming = self
-                    .incoming
-                    .take()
-                    .ok_or_else(|| Error::internal("Request body already consumed"))?;
-
-                // Check Content-Length header against limit
-                if let Some(limit) = self.body_limit {
-                    if let Some(content_length) = self.headers.get(header::CONTENT_LENGTH) {
-                        if let Ok(length_str) = content_length.to_str() {
-                            if let Ok(length) = length_str.parse::<usize>() {
-                                if length > limit {
-                                    return Err(Error::payload_too_large(&format!(
-                                        "Request body size {} exceeds limit of {}",
-                                        length, limit
-                                    )));
-                                }
-                            }
-                        }
+                let incoming = take_incoming(&mut self.incoming, &self.headers, self.body_limit)?;
+
+                let read_started = Instant::now();
+                let (body_bytes, trailers) = match pool::current() {
+                    Some(pool) => collect_pooled(incoming, &pool, &self.headers).await?,
+                    None => {
+                        let collected = incoming.collect().await.map_err(read_error)?;
+                        let trailers = collected.trailers().cloned();
+                        (collected.to_bytes(), trailers)
                     }
+                };
+                if let Some(trace) = &self.trace {
+                    trace
+                        .body_read_micros
+                        .fetch_add(read_started.elapsed().as_micros() as u64, Ordering::Relaxed);
                 }
 
-                let collected = incoming
-                    .collect()
-                    .await
-                    .map_err(|e| Error::Custom(format!("Failed to read body: {}", e)))?;
-
-                let body_bytes = collected.to_bytes();
+                self.trailers = trailers;
 
                 // Check actual body size against limit
                 if let Some(limit) = self.body_limit {
                     if body_bytes.len() > limit {
-                        return Err(Error::payload_too_large(&format!(
+                        return Err(Error::payload_too_large(format!(
                             "Request body size {} exceeds limit of {}",
                             body_bytes.len(),
                             limit
@@ -169,6 +507,43 @@ impl Req {
             .await
     }
 
+    /// Take the body as an `AsyncRead`, for use with tokio IO utilities.
+    ///
+    /// Body errors (including an exceeded body limit) surface as
+    /// `io::Error`s wrapping the original [`Error`].
+    pub fn body_reader(&mut self) -> Result<impl AsyncRead + Send + Sync + Unpin + 'static> {
+        let stream = self.body_stream()?.map_err(io::Error::other);
+        Ok(StreamReader::new(stream))
+    }
+
+    /// Take the body as a stream of chunks, without collecting it.
+    ///
+    /// The configured body limit still applies to the total streamed size.
+    /// A body already read with [`bytes`](Self::bytes) is streamed from memory.
+    pub fn body_stream(&mut self) -> Result<BodyStream> {
+        if let (None, Some(cached)) = (&self.incoming, self.body_cell.get()) {
+            return Ok(BodyStream::new(
+                RequestBody::Full(Some(cached.clone())),
+                self.body_limit,
+            ));
+        }
+        let incoming = take_incoming(&mut self.incoming, &self.headers, self.body_limit)?;
+        Ok(BodyStream::new(incoming, self.body_limit))
+    }
+
+    /// Consume the request, streaming its body.
+    pub fn into_body_stream(mut self) -> Result<BodyStream> {
+        self.body_stream()
+    }
+
+    /// Get trailer headers sent after the body.
+    ///
+    /// Available once the body has been consumed.
+    #[inline]
+    pub fn trailers(&self) -> Option<&header::HeaderMap> {
+        self.trailers.as_ref()
+    }
+
     /// Get Content-Type header.
     #[inline]
     pub fn content_type(&self) -> Option<&str> {
@@ -196,10 +571,21 @@ impl Req {
     }
 
     #[inline]
-    pub(crate) fn set_path_params(&mut self, params: HashMap<String, String>) {
+    pub(crate) fn set_path_params(&mut self, params: PathParams) {
         self.path_params = params;
     }
 
+    #[inline]
+    pub(crate) fn shared_matched_route(&self) -> Option<Arc<str>> {
+        self.matched_route.clone()
+    }
+
+    #[inline]
+    pub(crate) fn set_matched_route(&mut self, pattern: Arc<str>, name: Option<Arc<str>>) {
+        self.matched_route = Some(pattern);
+        self.route_name = name;
+    }
+
     /// Check if request is WebSocket upgrade (GET with upgrade headers).
     #[cfg(feature = "websocket")]
     pub fn is_websocket_upgrade(&self) -> bool {
@@ -225,3 +611,243 @@ impl Req {
         self.header("sec-websocket-key")
     }
 }
+
+/// Builder for in-memory requests.
+///
+/// Lets extractor and middleware tests, property tests and fuzz targets
+/// construct requests without a connection.
+///
+/// ```rust
+/// use rust_api::Req;
+/// use hyper::Method;
+///
+/// let req = Req::builder()
+///     .method(Method::POST)
+///     .uri("/users/42?verbose=1")
+///     .header("x-request-id", "abc")
+///     .param("id", "42")
+///     .json(&serde_json::json!({"name": "alice"}))
+///     .build();
+/// assert_eq!(req.param("id"), Some("42"));
+/// assert!(req.is_json());
+/// ```
+pub struct ReqBuilder {
+    method: Method,
+    uri: Uri,
+    headers: header::HeaderMap,
+    body: Bytes,
+    path_params: PathParams,
+    extensions: Extensions,
+    peer_addr: Option<SocketAddr>,
+}
+
+impl ReqBuilder {
+    /// Create builder for `GET /`.
+    pub fn new() -> Self {
+        Self {
+            method: Method::GET,
+            uri: Uri::from_static("/"),
+            headers: header::HeaderMap::new(),
+            body: Bytes::new(),
+            path_params: PathParams::new(),
+            extensions: Extensions::new(),
+            peer_addr: None,
+        }
+    }
+
+    /// Set method.
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Set URI (invalid URIs are ignored).
+    pub fn uri(mut self, uri: impl AsRef<str>) -> Self {
+        if let Ok(uri) = uri.as_ref().parse() {
+            self.uri = uri;
+        }
+        self
+    }
+
+    /// Append header (invalid names or values are ignored).
+    pub fn header(mut self, name: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        if let (Ok(name), Ok(value)) = (
+            header::HeaderName::from_bytes(name.as_ref().as_bytes()),
+            header::HeaderValue::from_str(value.as_ref()),
+        ) {
+            self.headers.append(name, value);
+        }
+        self
+    }
+
+    /// Replace all headers.
+    pub fn headers(mut self, headers: header::HeaderMap) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Set raw body.
+    pub fn body(mut self, body: impl Into<Bytes>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Set JSON body and `Content-Type`.
+    pub fn json<T: serde::Serialize>(mut self, value: &T) -> Self {
+        if let Ok(bytes) = serde_json::to_vec(value) {
+            self.body = Bytes::from(bytes);
+            self.headers.insert(
+                header::CONTENT_TYPE,
+                header::HeaderValue::from_static("application/json"),
+            );
+        }
+        self
+    }
+
+    /// Set a path parameter, as if matched by the router.
+    pub fn param(mut self, name: impl Into<Arc<str>>, value: impl AsRef<str>) -> Self {
+        self.path_params.insert(name, value.as_ref());
+        self
+    }
+
+    /// Set the client address.
+    pub fn peer_addr(mut self, addr: SocketAddr) -> Self {
+        self.peer_addr = Some(addr);
+        self
+    }
+
+    /// Insert an extension.
+    pub fn extension<T: Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.extensions.insert(value);
+        self
+    }
+
+    /// Replace all extensions.
+    pub fn extensions(mut self, extensions: Extensions) -> Self {
+        self.extensions = extensions;
+        self
+    }
+
+    /// Build the request; `Content-Length` is set from the body if missing.
+    pub fn build(mut self) -> Req {
+        if !self.body.is_empty() && !self.headers.contains_key(header::CONTENT_LENGTH) {
+            self.headers.insert(
+                header::CONTENT_LENGTH,
+                header::HeaderValue::from(self.body.len()),
+            );
+        }
+        Req {
+            method: self.method,
+            uri: self.uri,
+            headers: HeaderStore::Owned(self.headers),
+            body_cell: OnceCell::new(),
+            incoming: Some(RequestBody::Full(Some(self.body))),
+            path_params: self.path_params,
+            matched_route: None,
+            route_name: None,
+            extensions: self.extensions,
+            body_limit: None,
+            trailers: None,
+            trace: None,
+            version: Version::HTTP_11,
+            peer_addr: self.peer_addr,
+            via_trusted_proxy: false,
+            #[cfg(feature = "websocket")]
+            upgrade: None,
+        }
+    }
+}
+
+impl Default for ReqBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Unwrap body errors (e.g. an exceeded limit) carried in an `io::Error`
+/// from [`Req::body_reader`].
+pub(crate) fn body_io_error(e: io::Error) -> Error {
+    if !e.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+        return Error::Io(e);
+    }
+    match e.into_inner().map(|inner| inner.downcast::<Error>()) {
+        Some(Ok(error)) => *error,
+        _ => Error::internal("Failed to read body"),
+    }
+}
+
+fn read_error(e: Error) -> Error {
+    Error::Custom(format!("Failed to read body: {}", e))
+}
+
+/// Collect a body, joining multiple chunks in a pooled buffer.
+///
+/// A body that arrives as a single chunk is returned without copying.
+async fn collect_pooled(
+    mut body: RequestBody,
+    pool: &Arc<BufferPool>,
+    headers: &header::HeaderMap,
+) -> Result<(Bytes, Option<header::HeaderMap>)> {
+    let mut first: Option<Bytes> = None;
+    let mut joined: Option<PooledBuf> = None;
+    let mut trailers = None;
+
+    while let Some(frame) = body.frame().await {
+        let frame = match frame.map_err(read_error)?.into_data() {
+            Ok(data) => data,
+            Err(frame) => {
+                trailers = frame.into_trailers().ok();
+                continue;
+            }
+        };
+        if let Some(buf) = &mut joined {
+            buf.as_mut_vec().extend_from_slice(&frame);
+        } else if let Some(head) = first.take() {
+            let declared = headers
+                .get(header::CONTENT_LENGTH)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<usize>().ok());
+            let mut buf = pool.get(declared.unwrap_or(head.len() + frame.len()));
+            buf.as_mut_vec().extend_from_slice(&head);
+            buf.as_mut_vec().extend_from_slice(&frame);
+            joined = Some(buf);
+        } else {
+            first = Some(frame);
+        }
+    }
+
+    let bytes = match joined {
+        Some(buf) => buf.freeze(),
+        None => first.unwrap_or_default(),
+    };
+    Ok((bytes, trailers))
+}
+
+/// Take the raw body, rejecting declared lengths over the body limit.
+fn take_incoming(
+    incoming: &mut Option<RequestBody>,
+    headers: &header::HeaderMap,
+    body_limit: Option<usize>,
+) -> Result<RequestBody> {
+    let incoming = incoming.take().ok_or_else(|| {
+        Error::internal(
+            "Request body already streamed; buffer it with `Req::peek_body` first to share it",
+        )
+    })?;
+
+    // Check Content-Length header against limit
+    if let Some(limit) = body_limit {
+        let length = headers
+            .get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<usize>().ok());
+        if let Some(length) = length.filter(|&length| length > limit) {
+            return Err(Error::payload_too_large(format!(
+                "Request body size {} exceeds limit of {}",
+                length, limit
+            )));
+        }
+    }
+
+    Ok(incoming)
+}
@@ -6,9 +6,11 @@ use http_body_util::{BodyExt, Full, StreamBody as HttpStreamBody};
 use hyper::body::Frame;
 use hyper::{Response, StatusCode, header};
 use serde::Serialize;
+use std::cell::Cell;
 use std::future::Future;
 use std::path::Path;
 use tokio::fs::File;
+use tokio::io::AsyncRead;
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
 use tokio_util::io::ReaderStream;
@@ -18,6 +20,7 @@ use base64::{Engine as _, engine::general_purpose};
 #[cfg(feature = "websocket")]
 use sha1::{Digest, Sha1};
 
+use crate::pool;
 use crate::{Error, Result};
 
 /// Boxed body type for responses.
@@ -30,24 +33,77 @@ static CONTENT_TYPE_HTML: header::HeaderValue =
 static CONTENT_TYPE_JSON: header::HeaderValue =
     header::HeaderValue::from_static("application/json");
 
+/// Bounds for the per-thread JSON buffer size hint.
+const JSON_HINT_MIN: usize = 128;
+const JSON_HINT_MAX: usize = 16 * 1024;
+const JSON_POOL_MIN: usize = 1024;
+
+thread_local! {
+    static JSON_SIZE_HINT: Cell<usize> = const { Cell::new(JSON_HINT_MIN) };
+}
+
+/// Serialize to JSON, pre-sizing the buffer from the last response on this thread.
+///
+/// Recent responses of at least `JSON_POOL_MIN` bytes use the app's buffer
+/// pool when one is configured; smaller ones are cheaper to allocate.
+fn encode_json<T: Serialize + ?Sized>(value: &T) -> serde_json::Result<Bytes> {
+    let hint = JSON_SIZE_HINT.get();
+    if let Some(pool) = (hint >= JSON_POOL_MIN).then(pool::current).flatten() {
+        let mut buf = pool.get(hint);
+        serde_json::to_writer(buf.as_mut_vec(), value)?;
+        JSON_SIZE_HINT.set(buf.as_ref().len().clamp(JSON_HINT_MIN, JSON_HINT_MAX));
+        return Ok(buf.freeze());
+    }
+    let mut buf = Vec::with_capacity(hint);
+    serde_json::to_writer(&mut buf, value)?;
+    JSON_SIZE_HINT.set(buf.len().clamp(JSON_HINT_MIN, JSON_HINT_MAX));
+    Ok(Bytes::from(buf))
+}
+
+/// Build a JSON response; hyper writes `Content-Length` from the exact body size.
+fn json_response(bytes: Bytes) -> Response<BoxBody> {
+    let mut res = Response::new(Full::new(bytes).map_err(|e| match e {}).boxed());
+    res.headers_mut()
+        .insert(header::CONTENT_TYPE, CONTENT_TYPE_JSON.clone());
+    res
+}
+
 /// Channel sender for streaming response chunks.
 pub struct StreamSender {
-    tx: mpsc::Sender<Result<Bytes>>,
+    tx: mpsc::Sender<Result<Frame<Bytes>>>,
 }
 
 impl StreamSender {
     /// Send a chunk of data.
     pub async fn send(&mut self, data: impl Into<Bytes>) -> Result<()> {
-        self.tx
-            .send(Ok(data.into()))
-            .await
-            .map_err(|_| Error::Custom("Stream channel closed".into()))
+        self.send_frame(Frame::data(data.into())).await
     }
 
     /// Send text chunk.
     pub async fn send_text(&mut self, text: impl Into<String>) -> Result<()> {
         self.send(Bytes::from(text.into())).await
     }
+
+    /// Finish the stream with trailer headers (e.g. a checksum).
+    ///
+    /// Declare trailer names up front with the `Trailer` response header.
+    /// HTTP/1.1 clients only receive trailers when they send `TE: trailers`.
+    pub async fn send_trailers(mut self, trailers: header::HeaderMap) -> Result<()> {
+        self.send_frame(Frame::trailers(trailers)).await
+    }
+
+    /// Resolve once the client stops reading the response (e.g. it
+    /// disconnected).
+    pub async fn closed(&self) {
+        self.tx.closed().await
+    }
+
+    async fn send_frame(&mut self, frame: Frame<Bytes>) -> Result<()> {
+        self.tx
+            .send(Ok(frame))
+            .await
+            .map_err(|_| Error::Custom("Stream channel closed".into()))
+    }
 }
 
 /// HTTP response.
@@ -84,6 +140,17 @@ impl Res {
         self.inner
     }
 
+    /// Response extensions, for data the framework passes along with it.
+    #[inline]
+    pub(crate) fn extensions(&self) -> &hyper::http::Extensions {
+        self.inner.extensions()
+    }
+
+    #[inline]
+    pub(crate) fn extensions_mut(&mut self) -> &mut hyper::http::Extensions {
+        self.inner.extensions_mut()
+    }
+
     /// Get WebSocket callback if present.
     #[cfg(feature = "websocket")]
     #[inline]
@@ -112,15 +179,14 @@ impl Res {
         F: FnOnce(StreamSender) -> Fut + Send + 'static,
         Fut: Future<Output = ()> + Send + 'static,
     {
-        let (tx, rx) = mpsc::channel::<Result<Bytes>>(100);
+        let (tx, rx) = mpsc::channel::<Result<Frame<Bytes>>>(100);
         let sender = StreamSender { tx };
 
         tokio::spawn(async move {
             handler(sender).await;
         });
 
-        let stream = ReceiverStream::new(rx).map_ok(Frame::data);
-        let body = HttpStreamBody::new(stream).boxed();
+        let body = HttpStreamBody::new(ReceiverStream::new(rx)).boxed();
 
         Self {
             inner: Response::new(body),
@@ -132,7 +198,11 @@ impl Res {
     /// Stream file from disk. Returns 404 if not found.
     ///
     /// ```rust,no_run
-    /// Res::file("index.html").await.header("content-type", "text/html")
+    /// use rust_api::Res;
+    ///
+    /// async fn index() -> Res {
+    ///     Res::file("index.html").await.header("content-type", "text/html")
+    /// }
     /// ```
     pub async fn file(path: impl AsRef<Path>) -> Self {
         let path = path.as_ref();
@@ -144,7 +214,15 @@ impl Res {
             }
         };
 
-        let reader_stream = ReaderStream::new(file);
+        Self::from_reader(file)
+    }
+
+    /// Stream a response body from an `AsyncRead`.
+    pub fn from_reader<R>(reader: R) -> Self
+    where
+        R: AsyncRead + Send + Sync + 'static,
+    {
+        let reader_stream = ReaderStream::new(reader);
         let stream_body =
             HttpStreamBody::new(reader_stream.map_ok(Frame::data).map_err(Error::from));
         let boxed_body = stream_body.boxed();
@@ -192,23 +270,14 @@ impl Res {
         }
     }
 
-    /// JSON response (serializes to Vec<u8> directly).
+    /// JSON response (buffer pre-sized from recent responses).
     pub fn json<T: Serialize>(value: &T) -> Self {
-        match serde_json::to_vec(value) {
-            Ok(bytes) => {
-                let mut res = Response::new(
-                    Full::new(Bytes::from(bytes))
-                        .map_err(|e| match e {})
-                        .boxed(),
-                );
-                res.headers_mut()
-                    .insert(header::CONTENT_TYPE, CONTENT_TYPE_JSON.clone());
-                Self {
-                    inner: res,
-                    #[cfg(feature = "websocket")]
-                    ws_callback: None,
-                }
-            }
+        match encode_json(value) {
+            Ok(bytes) => Self {
+                inner: json_response(bytes),
+                #[cfg(feature = "websocket")]
+                ws_callback: None,
+            },
             Err(e) => {
                 let error_msg = format!(r#"{{"error": "JSON serialization failed: {}"}}"#, e);
                 let mut res = Response::new(
@@ -228,10 +297,36 @@ impl Res {
         }
     }
 
-    /// Status-only response.
+    /// JSON response from an owned `serde_json::Value`.
+    ///
+    /// Serializing a `Value` cannot fail, so this skips the error response path.
+    pub fn json_value(value: serde_json::Value) -> Self {
+        let bytes = encode_json(&value).unwrap_or_else(|_| Bytes::from_static(b"null"));
+        Self {
+            inner: json_response(bytes),
+            #[cfg(feature = "websocket")]
+            ws_callback: None,
+        }
+    }
+
+    /// JSON response keeping only the requested `fields`.
+    pub fn json_filtered<T: Serialize>(value: &T, fields: &crate::Fields) -> Self {
+        match serde_json::to_value(value) {
+            Ok(value) => Self::json_value(fields.prune(value)),
+            Err(_) => Self::json(value),
+        }
+    }
+
+    /// Status-only response. Invalid codes become 500; prefer
+    /// [`Res::with_status`].
     pub fn status(code: u16) -> Self {
+        Self::with_status(StatusCode::from_u16(code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR))
+    }
+
+    /// Status-only response.
+    pub fn with_status(status: StatusCode) -> Self {
         let mut res = Response::new(Full::new(Bytes::new()).map_err(|e| match e {}).boxed());
-        *res.status_mut() = StatusCode::from_u16(code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        *res.status_mut() = status;
         Self {
             inner: res,
             #[cfg(feature = "websocket")]
@@ -244,6 +339,22 @@ impl Res {
         ResBuilder::new()
     }
 
+    /// Builder for 201 Created, usually with a
+    /// [`location`](ResBuilder::location).
+    pub fn created() -> ResBuilder {
+        ResBuilder::new().with_status(StatusCode::CREATED)
+    }
+
+    /// Builder for 202 Accepted.
+    pub fn accepted() -> ResBuilder {
+        ResBuilder::new().with_status(StatusCode::ACCEPTED)
+    }
+
+    /// 204 No Content.
+    pub fn no_content() -> Self {
+        Self::with_status(StatusCode::NO_CONTENT)
+    }
+
     /// Create WebSocket upgrade response with handler callback.
     ///
     /// Returns 101 Switching Protocols with proper Sec-WebSocket-Accept header.
@@ -263,7 +374,7 @@ impl Res {
         hasher.update(websocket_key.as_bytes());
         hasher.update(WEBSOCKET_GUID.as_bytes());
         let hash = hasher.finalize();
-        let accept_key = general_purpose::STANDARD.encode(&hash);
+        let accept_key = general_purpose::STANDARD.encode(hash);
 
         let mut res = Response::new(Full::new(Bytes::new()).map_err(|e| match e {}).boxed());
         *res.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
@@ -305,6 +416,55 @@ impl Res {
         self
     }
 
+    /// Set the `Cache-Control` header.
+    pub fn cache_control(self, cache_control: crate::CacheControl) -> Self {
+        self.header(header::CACHE_CONTROL, cache_control.to_string())
+    }
+
+    /// Set `Cache-Control: no-store`, for responses that must never be
+    /// cached.
+    pub fn no_store(self) -> Self {
+        self.cache_control(crate::CacheControl::no_store())
+    }
+
+    /// Add `name` to the `Vary` header, keeping values set by others.
+    ///
+    /// Middleware should use this instead of inserting `Vary`; names
+    /// already listed (or covered by `*`) are skipped, and all `Vary`
+    /// values are merged into one header before the response is sent.
+    pub fn vary(mut self, name: &str) -> Self {
+        append_vary(self.inner.headers_mut(), name);
+        self
+    }
+
+    /// Append a `Link: <url>; rel=preload; as=<kind>` header.
+    pub fn preload(mut self, url: &str, kind: &str) -> Self {
+        if let Ok(value) = crate::hints::preload_link(url, kind) {
+            self.inner.headers_mut().append(header::LINK, value);
+        }
+        self
+    }
+
+    /// Set `Content-Length`, e.g. for a `HEAD` response whose body was
+    /// never produced (see `IsHead`).
+    pub fn content_length(self, len: u64) -> Self {
+        self.header(header::CONTENT_LENGTH, len.to_string())
+    }
+
+    /// Drop the body of an answer to `HEAD`, keeping the length it would
+    /// have had.
+    pub(crate) fn strip_body(mut self) -> Self {
+        if !self.inner.headers().contains_key(header::CONTENT_LENGTH) {
+            if let Some(len) = hyper::body::Body::size_hint(self.inner.body()).exact() {
+                self.inner
+                    .headers_mut()
+                    .insert(header::CONTENT_LENGTH, header::HeaderValue::from(len));
+            }
+        }
+        *self.inner.body_mut() = Full::new(Bytes::new()).map_err(|e| match e {}).boxed();
+        self
+    }
+
     /// Get mutable headers.
     #[inline]
     pub fn headers_mut(&mut self) -> &mut header::HeaderMap {
@@ -339,12 +499,23 @@ impl ResBuilder {
         }
     }
 
-    /// Set status code.
-    pub fn status(mut self, code: u16) -> Self {
-        self.status = StatusCode::from_u16(code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    /// Set status code. Invalid codes become 500; prefer
+    /// [`with_status`](Self::with_status).
+    pub fn status(self, code: u16) -> Self {
+        self.with_status(StatusCode::from_u16(code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR))
+    }
+
+    /// Set status.
+    pub fn with_status(mut self, status: StatusCode) -> Self {
+        self.status = status;
         self
     }
 
+    /// Set the `Location` header.
+    pub fn location(self, url: impl AsRef<str>) -> Self {
+        self.header(header::LOCATION, url)
+    }
+
     /// Add header.
     pub fn header(mut self, name: impl AsRef<str>, value: impl AsRef<str>) -> Self {
         if let (Ok(name), Ok(value)) = (
@@ -356,6 +527,17 @@ impl ResBuilder {
         self
     }
 
+    /// Set the `Cache-Control` header.
+    pub fn cache_control(self, cache_control: crate::CacheControl) -> Self {
+        self.header(header::CACHE_CONTROL, cache_control.to_string())
+    }
+
+    /// Add `name` to the `Vary` header (see [`Res::vary`]).
+    pub fn vary(mut self, name: &str) -> Self {
+        append_vary(&mut self.headers, name);
+        self
+    }
+
     /// Build text response.
     pub fn text(mut self, body: impl Into<String>) -> Res {
         let body_str = body.into();
@@ -403,22 +585,13 @@ impl ResBuilder {
     }
 
     /// Build JSON response.
-    pub fn json<T: Serialize>(mut self, value: &T) -> Res {
-        match serde_json::to_vec(value) {
+    pub fn json<T: Serialize>(self, value: &T) -> Res {
+        match encode_json(value) {
             Ok(bytes) => {
-                let mut res = Response::new(
-                    Full::new(Bytes::from(bytes))
-                        .map_err(|e| match e {})
-                        .boxed(),
-                );
+                let mut res = json_response(bytes);
                 *res.status_mut() = self.status;
-
-                if !self.headers.contains_key(header::CONTENT_TYPE) {
-                    self.headers
-                        .insert(header::CONTENT_TYPE, CONTENT_TYPE_JSON.clone());
-                }
-
-                *res.headers_mut() = self.headers;
+                // Builder headers (including a custom Content-Type) win.
+                res.headers_mut().extend(self.headers);
                 Res {
                     inner: res,
                     #[cfg(feature = "websocket")]
@@ -447,3 +620,134 @@ impl Default for ResBuilder {
         Self::new()
     }
 }
+
+/// Names listed across all `Vary` values, in order, without duplicates.
+fn vary_names(headers: &header::HeaderMap) -> Vec<&str> {
+    let mut names: Vec<&str> = Vec::new();
+    let listed = headers
+        .get_all(header::VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|name| !name.is_empty());
+    for name in listed {
+        if !names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// Add `name` to the `Vary` header unless already listed.
+pub(crate) fn append_vary(headers: &mut header::HeaderMap, name: &str) {
+    let listed = vary_names(headers)
+        .iter()
+        .any(|n| *n == "*" || n.eq_ignore_ascii_case(name));
+    if listed {
+        return;
+    }
+    if let Ok(value) = header::HeaderValue::from_str(name) {
+        headers.append(header::VARY, value);
+    }
+}
+
+/// Merge all `Vary` values into one header without duplicates.
+pub(crate) fn merge_vary(headers: &mut header::HeaderMap) {
+    let mut values = headers.get_all(header::VARY).iter();
+    if values.next().is_none() || values.next().is_none() {
+        return;
+    }
+    let names = vary_names(headers);
+    let merged = if names.contains(&"*") {
+        "*".to_string()
+    } else {
+        names.join(", ")
+    };
+    if let Ok(value) = header::HeaderValue::from_str(&merged) {
+        headers.insert(header::VARY, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vary_merging() {
+        let mut res = Res::text("ok")
+            .vary("Origin")
+            .vary("Accept-Encoding")
+            .vary("origin");
+        res.headers_mut().append(
+            header::VARY,
+            header::HeaderValue::from_static("accept-encoding, Accept-Language"),
+        );
+        merge_vary(res.headers_mut());
+        assert_eq!(
+            res.headers()
+                .get_all(header::VARY)
+                .iter()
+                .collect::<Vec<_>>(),
+            ["Origin, Accept-Encoding, Accept-Language"]
+        );
+
+        let mut res = Res::text("ok").vary("*").vary("Origin");
+        res.headers_mut()
+            .append(header::VARY, header::HeaderValue::from_static("Cookie"));
+        merge_vary(res.headers_mut());
+        assert_eq!(res.headers()[header::VARY], "*");
+    }
+
+    #[test]
+    fn test_typed_status_builders() {
+        let res = Res::created().location("/orders/7").json(&"ok");
+        assert_eq!(res.status_code(), StatusCode::CREATED);
+        assert_eq!(res.headers()[header::LOCATION], "/orders/7");
+        assert_eq!(Res::accepted().text("queued").status_code(), 202);
+        assert_eq!(Res::no_content().status_code(), StatusCode::NO_CONTENT);
+        assert_eq!(Res::status(1000).status_code(), 500);
+    }
+
+    #[tokio::test]
+    async fn test_stream_trailers() {
+        let res = Res::stream(|mut tx: StreamSender| async move {
+            tx.send_text("data").await.ok();
+            let mut trailers = header::HeaderMap::new();
+            trailers.insert("x-checksum", header::HeaderValue::from_static("abc"));
+            tx.send_trailers(trailers).await.ok();
+        });
+
+        let collected = res.into_hyper().into_body().collect().await.unwrap();
+        assert_eq!(
+            collected.trailers().and_then(|t| t.get("x-checksum")),
+            Some(&header::HeaderValue::from_static("abc"))
+        );
+        assert_eq!(collected.to_bytes(), Bytes::from("data"));
+    }
+
+    async fn body(res: Res) -> Bytes {
+        res.into_hyper()
+            .into_body()
+            .collect()
+            .await
+            .unwrap()
+            .to_bytes()
+    }
+
+    #[tokio::test]
+    async fn test_json_exact_length() {
+        use hyper::body::Body as _;
+
+        let first = Res::json(&serde_json::json!({"n": 1}));
+        let second = Res::json_value(serde_json::json!([true, null]));
+        assert_eq!(first.inner.body().size_hint().exact(), Some(7));
+        assert_eq!(second.inner.body().size_hint().exact(), Some(11));
+        assert_eq!(body(second).await, Bytes::from(r#"[true,null]"#));
+        assert_eq!(body(first).await, Bytes::from(r#"{"n":1}"#));
+
+        let large = "x".repeat(200 * 1024);
+        assert_eq!(body(Res::json(&large)).await.len(), large.len() + 2);
+        assert_eq!(body(Res::json(&1)).await, Bytes::from("1"));
+    }
+}
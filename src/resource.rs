@@ -0,0 +1,266 @@
+//! REST resource scaffolding.
+//!
+//! `app.resource::<R>("/users")` registers the standard routes for a
+//! [`Resource`]:
+//!
+//! | Route                 | Action   |
+//! |-----------------------|----------|
+//! | `GET /users`          | `index`  |
+//! | `POST /users`         | `create` |
+//! | `GET /users/{id}`     | `show`   |
+//! | `PUT /users/{id}`     | `update` |
+//! | `PATCH /users/{id}`   | `update` |
+//! | `DELETE /users/{id}`  | `delete` |
+//!
+//! Actions that are not overridden answer via
+//! [`Resource::not_implemented`] (501 by default).
+//!
+//! ```rust
+//! use async_trait::async_trait;
+//! use rust_api::{Json, Query, Res, Result, RustApi, resource::Resource};
+//! use serde::Deserialize;
+//! use std::sync::Arc;
+//!
+//! #[derive(Deserialize)]
+//! struct ListParams {
+//!     page: Option<u32>,
+//! }
+//!
+//! #[derive(Deserialize)]
+//! struct NewUser {
+//!     name: String,
+//! }
+//!
+//! struct Users;
+//!
+//! #[async_trait]
+//! impl Resource for Users {
+//!     type Id = u64;
+//!     type Filter = Query<ListParams>;
+//!     type Create = Json<NewUser>;
+//!     type Update = ();
+//!
+//!     async fn index(Query(params): Query<ListParams>, _state: Arc<()>) -> Result<Res> {
+//!         Ok(Res::text(format!("page {}", params.page.unwrap_or(1))))
+//!     }
+//!
+//!     async fn create(Json(user): Json<NewUser>, _state: Arc<()>) -> Result<Res> {
+//!         Ok(Res::builder().status(201).text(user.name))
+//!     }
+//! }
+//!
+//! let mut app = RustApi::new();
+//! app.resource::<Users>("/users");
+//! ```
+
+use async_trait::async_trait;
+use hyper::Method;
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::extractors::FromRequest;
+use crate::{Error, Handler, IntoRes, Req, Res, Result, Route};
+
+/// One of the standard resource actions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// List items.
+    Index,
+    /// Fetch one item.
+    Show,
+    /// Create an item.
+    Create,
+    /// Replace or modify an item.
+    Update,
+    /// Remove an item.
+    Delete,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Action::Index => "index",
+            Action::Show => "show",
+            Action::Create => "create",
+            Action::Update => "update",
+            Action::Delete => "delete",
+        })
+    }
+}
+
+/// A REST resource; register with `RustApi::resource`.
+///
+/// Request parts are taken through extractors named by the associated
+/// types; use `()` for parts an action does not need.
+#[async_trait]
+pub trait Resource<S = ()>: Send + Sync + 'static
+where
+    S: Send + Sync + 'static,
+{
+    /// Item identifier, parsed from the `{id}` path segment.
+    type Id: FromStr + Send + 'static;
+    /// Listing parameters for `index`, e.g. `Query<ListParams>`.
+    type Filter: FromRequest<S> + Send + 'static;
+    /// Body accepted by `create`, e.g. `Json<NewUser>`.
+    type Create: FromRequest<S> + Send + 'static;
+    /// Body accepted by `update`.
+    type Update: FromRequest<S> + Send + 'static;
+
+    /// `GET /`.
+    async fn index(_filter: Self::Filter, _state: Arc<S>) -> Result<Res> {
+        Err(Self::not_implemented(Action::Index))
+    }
+
+    /// `GET /{id}`.
+    async fn show(_id: Self::Id, _state: Arc<S>) -> Result<Res> {
+        Err(Self::not_implemented(Action::Show))
+    }
+
+    /// `POST /`.
+    async fn create(_body: Self::Create, _state: Arc<S>) -> Result<Res> {
+        Err(Self::not_implemented(Action::Create))
+    }
+
+    /// `PUT /{id}` and `PATCH /{id}`.
+    async fn update(_id: Self::Id, _body: Self::Update, _state: Arc<S>) -> Result<Res> {
+        Err(Self::not_implemented(Action::Update))
+    }
+
+    /// `DELETE /{id}`.
+    async fn delete(_id: Self::Id, _state: Arc<S>) -> Result<Res> {
+        Err(Self::not_implemented(Action::Delete))
+    }
+
+    /// Error for actions left at their default.
+    fn not_implemented(action: Action) -> Error {
+        Error::Status(501, Some(format!("{} not implemented", action)))
+    }
+}
+
+/// Routes serving `R` under `path`.
+pub(crate) fn routes<R, S>(path: &str) -> Vec<Route<S>>
+where
+    R: Resource<S>,
+    S: Send + Sync + 'static,
+{
+    let base = path.trim_end_matches('/');
+    let collection = if base.is_empty() { "/" } else { base };
+    let item = format!("{}/{{id}}", base);
+    let route = |method: Method, path: &str, action: Action| {
+        let handler = ActionHandler::<R> {
+            action,
+            _resource: PhantomData,
+        };
+        Route::new(method, path.to_string(), handler)
+    };
+    vec![
+        route(Method::GET, collection, Action::Index),
+        route(Method::POST, collection, Action::Create),
+        route(Method::GET, &item, Action::Show),
+        route(Method::PUT, &item, Action::Update),
+        route(Method::PATCH, &item, Action::Update),
+        route(Method::DELETE, &item, Action::Delete),
+    ]
+}
+
+struct ActionHandler<R> {
+    action: Action,
+    _resource: PhantomData<fn() -> R>,
+}
+
+impl<R> ActionHandler<R> {
+    fn id<S>(req: &Req) -> Result<R::Id>
+    where
+        R: Resource<S>,
+        S: Send + Sync + 'static,
+    {
+        req.param("id")
+            .and_then(|id| id.parse().ok())
+            .ok_or_else(|| Error::bad_request("Invalid id"))
+    }
+
+    async fn run<S>(&self, mut req: Req, state: Arc<S>) -> Result<Res>
+    where
+        R: Resource<S>,
+        S: Send + Sync + 'static,
+    {
+        match self.action {
+            Action::Index => {
+                R::index(R::Filter::from_request(&mut req, &state).await?, state).await
+            }
+            Action::Show => R::show(Self::id(&req)?, state).await,
+            Action::Create => {
+                R::create(R::Create::from_request(&mut req, &state).await?, state).await
+            }
+            Action::Update => {
+                let id = Self::id(&req)?;
+                R::update(id, R::Update::from_request(&mut req, &state).await?, state).await
+            }
+            Action::Delete => R::delete(Self::id(&req)?, state).await,
+        }
+    }
+}
+
+#[async_trait]
+impl<R, S> Handler<S> for ActionHandler<R>
+where
+    R: Resource<S>,
+    S: Send + Sync + 'static,
+{
+    async fn call(&self, req: Req, state: Arc<S>) -> Res {
+        self.run(req, state).await.into_res()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{TestClient, assert_status, body_text};
+    use crate::{Json, RustApi};
+
+    struct Notes;
+
+    #[async_trait]
+    impl Resource for Notes {
+        type Id = u32;
+        type Filter = ();
+        type Create = Json<String>;
+        type Update = ();
+
+        async fn show(id: u32, _state: Arc<()>) -> Result<Res> {
+            Ok(Res::text(format!("note {}", id)))
+        }
+
+        async fn create(Json(text): Json<String>, _state: Arc<()>) -> Result<Res> {
+            Ok(Res::builder().status(201).text(text))
+        }
+
+        fn not_implemented(_action: Action) -> Error {
+            Error::status(405)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resource_routes() {
+        let mut app = RustApi::new();
+        app.resource::<Notes>("/notes/");
+        let client = TestClient::new(app);
+
+        assert_eq!(
+            body_text(client.get("/notes/7").send().await).await,
+            "note 7"
+        );
+        assert_status(&client.get("/notes/seven").send().await, 400);
+        let created = client
+            .post("/notes")
+            .header("content-type", "application/json")
+            .body("\"hello\"")
+            .send()
+            .await;
+        assert_status(&created, 201);
+        assert_status(&client.get("/notes").send().await, 405);
+        assert_status(&client.delete("/notes/7").send().await, 405);
+    }
+}
@@ -0,0 +1,138 @@
+//! Headers queued for the response while the request is handled.
+//!
+//! Middleware and handlers deep in the stack often learn about a header
+//! before the response exists: a session middleware renewing its cookie, a
+//! limiter reporting what is left. [`ResponseHeaders`] queues them in the
+//! request extensions, and the server merges them when finalizing the
+//! response, after every middleware has run and before the `Vary` merge
+//! and header limits. Queued operations apply in the order they were
+//! queued: [`append`](ResponseHeaders::append) adds a value,
+//! [`insert`](ResponseHeaders::insert) replaces all earlier values,
+//! including ones set on the response.
+//!
+//! ```rust
+//! use rust_api::{Next, Req, Res, ResponseHeaders, RustApi, from_fn};
+//! use std::sync::Arc;
+//!
+//! let mut app = RustApi::new();
+//! app.attach(from_fn(|mut req: Req, _state: Arc<()>, next: Next<()>| async move {
+//!     ResponseHeaders::from_req(&mut req).append("set-cookie", "seen=1").ok();
+//!     next.run(req).await
+//! }));
+//! app.get("/", |headers: ResponseHeaders| async move {
+//!     headers.insert("x-ratelimit-remaining", "41").ok();
+//!     Res::text("hello")
+//! });
+//! ```
+
+use async_trait::async_trait;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use std::sync::{Arc, Mutex};
+
+use crate::extractors::FromRequest;
+use crate::{Error, Req, Result};
+
+/// Whether a queued header adds to or replaces existing values.
+#[derive(Clone, Copy)]
+enum Op {
+    Append,
+    Insert,
+}
+
+/// Queue of headers for the eventual response; clones share the queue.
+#[derive(Clone, Default)]
+pub struct ResponseHeaders(Arc<Mutex<Vec<(Op, HeaderName, HeaderValue)>>>);
+
+impl ResponseHeaders {
+    /// Queue of the request, created if missing. Headers queued on a
+    /// request the server did not create are dropped.
+    pub fn from_req(req: &mut Req) -> Self {
+        if let Some(queue) = req.extensions().get::<ResponseHeaders>() {
+            return queue.clone();
+        }
+        let queue = Self::default();
+        req.extensions_mut().insert(queue.clone());
+        queue
+    }
+
+    /// Add a `name: value` header, keeping existing values.
+    pub fn append(&self, name: impl AsRef<str>, value: impl AsRef<str>) -> Result<()> {
+        self.push(Op::Append, name.as_ref(), value.as_ref())
+    }
+
+    /// Set `name: value`, replacing existing values.
+    pub fn insert(&self, name: impl AsRef<str>, value: impl AsRef<str>) -> Result<()> {
+        self.push(Op::Insert, name.as_ref(), value.as_ref())
+    }
+
+    fn push(&self, op: Op, name: &str, value: &str) -> Result<()> {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| Error::internal(format!("Invalid header name: {}", name)))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(|_| Error::internal(format!("Invalid value for header {}", name)))?;
+        self.0.lock().unwrap().push((op, name, value));
+        Ok(())
+    }
+
+    /// Apply and clear the queued headers.
+    pub(crate) fn apply(&self, headers: &mut HeaderMap) {
+        for (op, name, value) in self.0.lock().unwrap().drain(..) {
+            match op {
+                Op::Append => {
+                    headers.append(name, value);
+                }
+                Op::Insert => {
+                    headers.insert(name, value);
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequest<S> for ResponseHeaders
+where
+    S: Send + Sync + 'static,
+{
+    async fn from_request(req: &mut Req, _state: &Arc<S>) -> Result<Self> {
+        Ok(Self::from_req(req))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{TestClient, assert_header};
+    use crate::{Next, Res, RustApi, from_fn};
+
+    #[tokio::test]
+    async fn test_queued_headers_merge_in_order() {
+        let mut app = RustApi::new();
+        app.attach(from_fn(
+            |mut req: Req, _state: Arc<()>, next: Next<()>| async move {
+                let headers = ResponseHeaders::from_req(&mut req);
+                headers.append("set-cookie", "session=abc").unwrap();
+                headers.insert("x-ratelimit-remaining", "10").unwrap();
+                next.run(req).await
+            },
+        ));
+        app.get("/", |headers: ResponseHeaders| async move {
+            headers.append("set-cookie", "seen=1").unwrap();
+            headers.insert("x-ratelimit-remaining", "9").unwrap();
+            assert!(headers.insert("bad header", "x").is_err());
+            Res::text("ok").header("x-ratelimit-remaining", "99")
+        });
+        app.get("/fail", |headers: ResponseHeaders| async move {
+            headers.append("set-cookie", "seen=1").unwrap();
+            Err::<Res, _>(Error::internal("boom"))
+        });
+        let client = TestClient::new(app);
+
+        let res = client.get("/").send().await;
+        let cookies: Vec<_> = res.headers().get_all("set-cookie").iter().collect();
+        assert_eq!(cookies, ["session=abc", "seen=1"]);
+        assert_header(&res, "x-ratelimit-remaining", "9");
+        let res = client.get("/fail").send().await;
+        assert_header(&res, "set-cookie", "session=abc");
+    }
+}
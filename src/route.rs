@@ -2,34 +2,231 @@
 
 use hyper::Method;
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::{Handler, Middleware, handler::IntoHandler};
+use crate::html::escape;
+use crate::{Guard, Handler, Middleware, Req, Res, handler::IntoHandler};
 
 /// Route with per-route middleware.
 pub struct Route<S = ()> {
     pub(crate) method: Method,
     pub(crate) path: String,
     pub(crate) handler: Arc<dyn Handler<S>>,
+    pub(crate) handler_name: &'static str,
+    pub(crate) name: Option<String>,
     pub(crate) middlewares: Arc<Vec<Arc<dyn Middleware<S>>>>,
+    pub(crate) guards: Vec<Arc<dyn Guard>>,
+    pub(crate) version: Option<u32>,
+    pub(crate) description: Option<String>,
+    pub(crate) tags: Vec<String>,
+    pub(crate) options: RouteOptions,
+}
+
+/// Limits for one route, overriding the app-wide settings.
+///
+/// ```rust
+/// use rust_api::{Req, RustApi, route::RateLimit};
+/// use std::time::Duration;
+///
+/// let mut app = RustApi::new();
+/// app.post("/export", |_req: Req| async { "started" })
+///     .timeout(Duration::from_secs(60))
+///     .body_limit(50 * 1024 * 1024)
+///     .rate_limit(RateLimit::per_second(1));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RouteOptions {
+    /// Handler timeout (replaces `set_handler_timeout`).
+    pub timeout: Option<Duration>,
+    /// Maximum request body size in bytes (replaces `set_body_limit`).
+    pub body_limit: Option<usize>,
+    /// Requests admitted per period, shared by all clients of the route.
+    pub rate_limit: Option<RateLimit>,
+    /// Trace sampling ratio (replaces the [`Sampler`](crate::metrics::Sampler) ratio).
+    pub sample_rate: Option<f64>,
+    /// Content types besides `application/json` that [`Json`](crate::Json)
+    /// accepts, e.g. `text/plain`.
+    pub json_content_types: Vec<String>,
+    /// Parse `Json` and `Form` bodies sent without `Content-Type`.
+    pub lenient_content_type: bool,
+    /// Skip the [`Compression`](crate::compression::Compression) middleware.
+    pub no_compression: bool,
+}
+
+/// Token bucket rate: `requests` per `period`, bursting up to `requests`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Requests admitted per period.
+    pub requests: u32,
+    /// Period over which `requests` are admitted.
+    pub period: Duration,
+}
+
+impl RateLimit {
+    /// Admit `requests` per `period`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `requests` or `period` is zero.
+    pub fn new(requests: u32, period: Duration) -> Self {
+        assert!(
+            requests > 0 && !period.is_zero(),
+            "rate limit must be non-zero"
+        );
+        Self { requests, period }
+    }
+
+    /// Admit `requests` per second.
+    pub fn per_second(requests: u32) -> Self {
+        Self::new(requests, Duration::from_secs(1))
+    }
+
+    /// Admit `requests` per minute.
+    pub fn per_minute(requests: u32) -> Self {
+        Self::new(requests, Duration::from_secs(60))
+    }
 }
 
 impl<S: Send + Sync + 'static> Route<S> {
-    pub(crate) fn new(method: Method, path: String, handler: Arc<dyn Handler<S>>) -> Self {
+    pub(crate) fn new<H, T>(method: Method, path: String, handler: H) -> Self
+    where
+        H: IntoHandler<S, T>,
+    {
         Self {
             method,
             path,
-            handler,
+            handler: handler.into_handler(),
+            handler_name: std::any::type_name::<H>(),
+            name: None,
             middlewares: Arc::new(Vec::new()),
+            guards: Vec::new(),
+            version: None,
+            description: None,
+            tags: Vec::new(),
+            options: RouteOptions::default(),
         }
     }
 
     /// Attach middleware to this route.
     ///
     /// Middleware is executed in registration order.
-    pub fn attach<M: Middleware<S>>(&mut self, middleware: M) {
+    pub fn attach<M: Middleware<S>>(&mut self, middleware: M) -> &mut Self {
         let mut mw = (*self.middlewares).clone();
         mw.push(Arc::new(middleware));
         self.middlewares = Arc::new(mw);
+        self
+    }
+
+    /// Add a guard; the route only matches requests passing every guard.
+    ///
+    /// Rejected requests fall through to the next route registered for the
+    /// same pattern and method.
+    pub fn guard<G: Guard>(&mut self, guard: G) -> &mut Self {
+        self.guards.push(Arc::new(guard));
+        self
+    }
+
+    /// Serve this route only for API version `version`.
+    ///
+    /// See [`versioning`](crate::versioning) for how versions are resolved.
+    pub fn version(&mut self, version: u32) -> &mut Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// Set the route name (e.g. `users.show`).
+    pub fn name(&mut self, name: impl Into<String>) -> &mut Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Set a human-readable description.
+    pub fn describe(&mut self, description: impl Into<String>) -> &mut Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Add a documentation tag (e.g. `users`).
+    pub fn tag(&mut self, tag: impl Into<String>) -> &mut Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    /// Abort the handler with an error after `timeout`.
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.options.timeout = Some(timeout);
+        self
+    }
+
+    /// Limit the request body to `limit` bytes.
+    pub fn body_limit(&mut self, limit: usize) -> &mut Self {
+        self.options.body_limit = Some(limit);
+        self
+    }
+
+    /// Reject requests beyond `limit` with 429 Too Many Requests.
+    ///
+    /// The budget is shared by all clients of this route and method.
+    pub fn rate_limit(&mut self, limit: RateLimit) -> &mut Self {
+        self.options.rate_limit = Some(limit);
+        self
+    }
+
+    /// Sample `ratio` (0.0 to 1.0) of this route's requests for tracing.
+    ///
+    /// Upstream decisions, errors and slow requests still take precedence;
+    /// see [`Sampler`](crate::metrics::Sampler).
+    pub fn sample_rate(&mut self, ratio: f64) -> &mut Self {
+        self.options.sample_rate = Some(ratio);
+        self
+    }
+
+    /// Let [`Json`](crate::Json) also parse bodies sent as `content_types`,
+    /// for clients that label JSON as e.g. `text/plain`.
+    pub fn accept_json<I, T>(&mut self, content_types: I) -> &mut Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.options.json_content_types.extend(
+            content_types
+                .into_iter()
+                .map(|t| t.into().to_ascii_lowercase()),
+        );
+        self
+    }
+
+    /// Let `Json` and `Form` parse bodies sent without `Content-Type`.
+    pub fn lenient_content_type(&mut self) -> &mut Self {
+        self.options.lenient_content_type = true;
+        self
+    }
+
+    /// Serve this route's responses uncompressed, e.g. for pre-compressed
+    /// downloads or hand-rolled streams.
+    pub fn no_compression(&mut self) -> &mut Self {
+        self.options.no_compression = true;
+        self
+    }
+
+    /// Replace all route options at once, e.g. to share a preset.
+    pub fn options(&mut self, options: RouteOptions) -> &mut Self {
+        self.options = options;
+        self
+    }
+
+    /// Describe this route for introspection.
+    pub fn info(&self) -> RouteInfo {
+        RouteInfo {
+            method: self.method.clone(),
+            pattern: self.path.clone(),
+            name: self.name.clone(),
+            version: self.version,
+            description: self.description.clone(),
+            tags: self.tags.clone(),
+            middleware: self.middlewares.iter().map(|m| m.name()).collect(),
+            handler: self.handler_name,
+        }
     }
 
     /// Create a GET route.
@@ -37,7 +234,7 @@ impl<S: Send + Sync + 'static> Route<S> {
     where
         H: IntoHandler<S, T>,
     {
-        Self::new(Method::GET, path.into(), handler.into_handler())
+        Self::new(Method::GET, path.into(), handler)
     }
 
     /// Create a POST route.
@@ -45,7 +242,7 @@ impl<S: Send + Sync + 'static> Route<S> {
     where
         H: IntoHandler<S, T>,
     {
-        Self::new(Method::POST, path.into(), handler.into_handler())
+        Self::new(Method::POST, path.into(), handler)
     }
 
     /// Create a PUT route.
@@ -53,7 +250,7 @@ impl<S: Send + Sync + 'static> Route<S> {
     where
         H: IntoHandler<S, T>,
     {
-        Self::new(Method::PUT, path.into(), handler.into_handler())
+        Self::new(Method::PUT, path.into(), handler)
     }
 
     /// Create a DELETE route.
@@ -61,7 +258,7 @@ impl<S: Send + Sync + 'static> Route<S> {
     where
         H: IntoHandler<S, T>,
     {
-        Self::new(Method::DELETE, path.into(), handler.into_handler())
+        Self::new(Method::DELETE, path.into(), handler)
     }
 
     /// Create a PATCH route.
@@ -69,6 +266,80 @@ impl<S: Send + Sync + 'static> Route<S> {
     where
         H: IntoHandler<S, T>,
     {
-        Self::new(Method::PATCH, path.into(), handler.into_handler())
+        Self::new(Method::PATCH, path.into(), handler)
+    }
+}
+
+/// Route metadata returned by `RustApi::routes()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteInfo {
+    /// HTTP method.
+    pub method: Method,
+    /// Route pattern (e.g. `/users/{id}`).
+    pub pattern: String,
+    /// Route name, if set.
+    pub name: Option<String>,
+    /// API version, if the route is versioned.
+    pub version: Option<u32>,
+    /// Description, if set.
+    pub description: Option<String>,
+    /// Documentation tags.
+    pub tags: Vec<String>,
+    /// Type names of attached middleware, outermost first.
+    pub middleware: Vec<&'static str>,
+    /// Type name of the handler.
+    pub handler: &'static str,
+}
+
+impl RouteInfo {
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "method": self.method.as_str(),
+            "pattern": self.pattern,
+            "name": self.name,
+            "version": self.version,
+            "description": self.description,
+            "tags": self.tags,
+            "middleware": self.middleware,
+            "handler": self.handler,
+        })
+    }
+}
+
+/// Render the route table as HTML (when `Accept` prefers it) or JSON.
+pub(crate) fn render_table(routes: &[RouteInfo], req: &Req) -> Res {
+    let wants_html = req
+        .header("accept")
+        .is_some_and(|accept| accept.contains("text/html"));
+    if !wants_html {
+        let table: Vec<_> = routes.iter().map(RouteInfo::to_json).collect();
+        return Res::json(&table);
+    }
+
+    let mut html = String::from(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Routes</title></head><body>\
+         <table border=\"1\" cellpadding=\"4\"><tr><th>Method</th><th>Pattern</th><th>Name</th>\
+         <th>Version</th><th>Description</th><th>Tags</th><th>Middleware</th><th>Handler</th></tr>",
+    );
+    for route in routes {
+        let cells = [
+            route.method.to_string(),
+            route.pattern.clone(),
+            route.name.clone().unwrap_or_default(),
+            route.version.map(|v| v.to_string()).unwrap_or_default(),
+            route.description.clone().unwrap_or_default(),
+            route.tags.join(", "),
+            route.middleware.join(", "),
+            route.handler.to_string(),
+        ];
+        html.push_str("<tr>");
+        for cell in cells {
+            html.push_str("<td>");
+            html.push_str(&escape(&cell));
+            html.push_str("</td>");
+        }
+        html.push_str("</tr>");
     }
+    html.push_str("</table></body></html>");
+    Res::html(html)
 }
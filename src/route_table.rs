@@ -0,0 +1,257 @@
+//! Compiled routing table.
+//!
+//! Built once from the registered routes when the app starts, then shared
+//! immutably behind an `Arc` by every connection; lookups take no locks.
+
+use hyper::Method;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::extractors::JsonContentTypes;
+use crate::params::{self, PathParams};
+use crate::route::{RateLimit, RouteOptions};
+use crate::{Guard, Handler, Middleware, Req, Route};
+
+type BoxedHandler<S> = Arc<dyn Handler<S>>;
+type SharedMiddlewares<S> = Arc<Vec<Arc<dyn Middleware<S>>>>;
+type MethodHandlers<S> = HashMap<Method, Vec<MethodRoute<S>>>;
+type MethodLookup = dyn Fn(&str) -> Option<Vec<Method>> + Send + Sync;
+
+/// Handler, middleware, guards and name registered for one method.
+pub(crate) struct MethodRoute<S> {
+    pub(crate) handler: BoxedHandler<S>,
+    pub(crate) middlewares: SharedMiddlewares<S>,
+    pub(crate) name: Option<Arc<str>>,
+    pub(crate) guards: Vec<Arc<dyn Guard>>,
+    pub(crate) version: Option<u32>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) body_limit: Option<usize>,
+    pub(crate) limiter: Option<RateLimiter>,
+    pub(crate) sample_rate: Option<f64>,
+    pub(crate) json_content_types: Option<JsonContentTypes>,
+    pub(crate) lenient_content_type: bool,
+    pub(crate) no_compression: bool,
+}
+
+impl<S> MethodRoute<S> {
+    /// Whether this route serves `version` and all its guards pass.
+    pub(crate) fn accepts(&self, version: Option<u32>, req: &Req) -> bool {
+        (self.version.is_none() || version.is_none() || self.version == version)
+            && self.guards.iter().all(|guard| guard.check(req))
+    }
+}
+
+/// Token bucket shared by all requests to one route.
+pub(crate) struct RateLimiter {
+    limit: RateLimit,
+    bucket: Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            bucket: Mutex::new((f64::from(limit.requests), Instant::now())),
+        }
+    }
+
+    pub(crate) fn limit(&self) -> RateLimit {
+        self.limit
+    }
+
+    /// Take a token, or return how long until one is available.
+    pub(crate) fn acquire(&self) -> Result<(), Duration> {
+        let capacity = f64::from(self.limit.requests);
+        let rate = capacity / self.limit.period.as_secs_f64();
+        let now = Instant::now();
+        let mut bucket = self.bucket.lock().unwrap();
+        let (tokens, refilled) = &mut *bucket;
+        *tokens = (*tokens + now.duration_since(*refilled).as_secs_f64() * rate).min(capacity);
+        *refilled = now;
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - *tokens) / rate))
+        }
+    }
+}
+
+/// Handlers registered under one route pattern.
+pub(crate) struct PathRoutes<S> {
+    pub(crate) pattern: Arc<str>,
+    pub(crate) methods: MethodHandlers<S>,
+    param_names: Vec<Arc<str>>,
+}
+
+impl<S> PathRoutes<S> {
+    /// Methods registered for this pattern, for `Allow` headers. `HEAD` is
+    /// implied by `GET`.
+    pub(crate) fn allowed_methods(&self) -> Vec<&str> {
+        let mut methods: Vec<&str> = self.methods.keys().map(Method::as_str).collect();
+        if self.methods.contains_key(&Method::GET) && !self.methods.contains_key(&Method::HEAD) {
+            methods.push(Method::HEAD.as_str());
+        }
+        methods
+    }
+
+    /// Handlers for `method`; `HEAD` falls back to `GET`. The flag is set
+    /// when the fallback was taken.
+    pub(crate) fn handlers(&self, method: &Method) -> Option<(&[MethodRoute<S>], bool)> {
+        if let Some(routes) = self.methods.get(method) {
+            return Some((routes, false));
+        }
+        if *method == Method::HEAD {
+            return self
+                .methods
+                .get(&Method::GET)
+                .map(|routes| (&routes[..], true));
+        }
+        None
+    }
+
+    /// Copy matched parameters, reusing this pattern's parameter names.
+    pub(crate) fn capture_params(&self, matched: &matchit::Params<'_, '_>) -> PathParams {
+        PathParams::capture(matched, |key| {
+            self.param_names
+                .iter()
+                .find(|name| &***name == key)
+                .cloned()
+                .unwrap_or_else(|| Arc::from(key))
+        })
+    }
+}
+
+/// Immutable path router.
+pub(crate) struct RouteTable<S> {
+    router: matchit::Router<Arc<PathRoutes<S>>>,
+}
+
+impl<S: Send + Sync + 'static> RouteTable<S> {
+    /// Compile routes, prepending the global middleware to each route's own.
+    pub(crate) fn build(
+        routes: impl IntoIterator<Item = Route<S>>,
+        global_middlewares: SharedMiddlewares<S>,
+    ) -> Self {
+        let mut path_methods: HashMap<String, MethodHandlers<S>> = HashMap::new();
+
+        for Route {
+            method,
+            path,
+            handler,
+            name,
+            middlewares: route_middlewares,
+            guards,
+            version,
+            options:
+                RouteOptions {
+                    timeout,
+                    body_limit,
+                    rate_limit,
+                    sample_rate,
+                    json_content_types,
+                    lenient_content_type,
+                    no_compression,
+                },
+            ..
+        } in routes
+        {
+            let combined_middlewares: SharedMiddlewares<S> = if route_middlewares.is_empty() {
+                Arc::clone(&global_middlewares)
+            } else if global_middlewares.is_empty() {
+                route_middlewares
+            } else {
+                let mut combined =
+                    Vec::with_capacity(global_middlewares.len() + route_middlewares.len());
+                combined.extend_from_slice(&global_middlewares);
+                combined.extend_from_slice(&route_middlewares);
+                Arc::new(combined)
+            };
+
+            path_methods
+                .entry(path.clone())
+                .or_default()
+                .entry(method)
+                .or_default()
+                .push(MethodRoute {
+                    handler,
+                    middlewares: combined_middlewares,
+                    name: name.map(Arc::from),
+                    guards,
+                    version,
+                    timeout,
+                    body_limit,
+                    limiter: rate_limit.map(RateLimiter::new),
+                    sample_rate,
+                    json_content_types: (!json_content_types.is_empty())
+                        .then(|| JsonContentTypes(json_content_types.into())),
+                    lenient_content_type,
+                    no_compression,
+                });
+        }
+
+        // Guarded routes are tried before unguarded fallbacks, newest versions first.
+        for methods in path_methods.values_mut() {
+            for candidates in methods.values_mut() {
+                candidates.sort_by_key(|route| (route.guards.is_empty(), Reverse(route.version)));
+            }
+        }
+
+        let mut router = matchit::Router::new();
+        for (path, methods) in path_methods {
+            let routes = PathRoutes {
+                pattern: Arc::from(path.as_str()),
+                methods,
+                param_names: params::pattern_names(&path),
+            };
+            router.insert(&path, Arc::new(routes)).ok();
+        }
+        Self { router }
+    }
+
+    /// Match a path to its routes and captured parameters.
+    pub(crate) fn at<'t, 'p>(
+        &'t self,
+        path: &'p str,
+    ) -> Option<matchit::Match<'t, 'p, &'t Arc<PathRoutes<S>>>> {
+        self.router.at(path).ok()
+    }
+}
+
+/// Methods routed for a path, for pre-routing middleware such as CORS.
+///
+/// Inserted into request extensions before pre-routing middleware runs;
+/// type-erased so middleware need not know the app's state type.
+#[derive(Clone)]
+pub(crate) struct RoutedMethods(Arc<MethodLookup>);
+
+impl RoutedMethods {
+    pub(crate) fn new<S: Send + Sync + 'static>(table: Arc<RouteTable<S>>) -> Self {
+        Self(Arc::new(move |path| {
+            let matched = table.at(path)?;
+            Some(matched.value.methods.keys().cloned().collect())
+        }))
+    }
+
+    /// Methods registered for the pattern matching `path`, or `None` when
+    /// no route matches.
+    pub(crate) fn lookup(&self, path: &str) -> Option<Vec<Method>> {
+        (self.0)(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter_bursts_then_rejects() {
+        let limiter = RateLimiter::new(RateLimit::per_minute(2));
+        assert!(limiter.acquire().is_ok());
+        assert!(limiter.acquire().is_ok());
+        let retry_after = limiter.acquire().unwrap_err();
+        assert!(retry_after > Duration::from_secs(29) && retry_after <= Duration::from_secs(30));
+    }
+}
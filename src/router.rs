@@ -3,15 +3,15 @@
 use hyper::Method;
 use std::sync::Arc;
 
-use crate::{Handler, Middleware, handler::IntoHandler};
+use crate::resource::{self, Resource};
+use crate::{Middleware, Route, handler::IntoHandler};
 
-type BoxedHandler<S> = Arc<dyn Handler<S>>;
 type BoxedMiddleware<S> = Arc<dyn Middleware<S>>;
 type SharedMiddlewares<S> = Arc<Vec<BoxedMiddleware<S>>>;
 
 /// Router for grouping routes with shared middleware.
 pub struct Router<S = ()> {
-    routes: Vec<(Method, String, BoxedHandler<S>)>,
+    routes: Vec<Route<S>>,
     middlewares: Vec<BoxedMiddleware<S>>,
     nested: Vec<(String, Router<S>)>,
 }
@@ -32,48 +32,56 @@ impl<S: Send + Sync + 'static> Router<S> {
     }
 
     /// Register a GET route.
-    pub fn get<H, T>(&mut self, path: &str, handler: H)
+    pub fn get<H, T>(&mut self, path: &str, handler: H) -> &mut Route<S>
     where
         H: IntoHandler<S, T>,
     {
-        self.routes
-            .push((Method::GET, path.to_string(), handler.into_handler()));
+        self.route(Route::new(Method::GET, path.to_string(), handler))
     }
 
     /// Register a POST route.
-    pub fn post<H, T>(&mut self, path: &str, handler: H)
+    pub fn post<H, T>(&mut self, path: &str, handler: H) -> &mut Route<S>
     where
         H: IntoHandler<S, T>,
     {
-        self.routes
-            .push((Method::POST, path.to_string(), handler.into_handler()));
+        self.route(Route::new(Method::POST, path.to_string(), handler))
     }
 
     /// Register a PUT route.
-    pub fn put<H, T>(&mut self, path: &str, handler: H)
+    pub fn put<H, T>(&mut self, path: &str, handler: H) -> &mut Route<S>
     where
         H: IntoHandler<S, T>,
     {
-        self.routes
-            .push((Method::PUT, path.to_string(), handler.into_handler()));
+        self.route(Route::new(Method::PUT, path.to_string(), handler))
     }
 
     /// Register a DELETE route.
-    pub fn delete<H, T>(&mut self, path: &str, handler: H)
+    pub fn delete<H, T>(&mut self, path: &str, handler: H) -> &mut Route<S>
     where
         H: IntoHandler<S, T>,
     {
-        self.routes
-            .push((Method::DELETE, path.to_string(), handler.into_handler()));
+        self.route(Route::new(Method::DELETE, path.to_string(), handler))
     }
 
     /// Register a PATCH route.
-    pub fn patch<H, T>(&mut self, path: &str, handler: H)
+    pub fn patch<H, T>(&mut self, path: &str, handler: H) -> &mut Route<S>
     where
         H: IntoHandler<S, T>,
     {
-        self.routes
-            .push((Method::PATCH, path.to_string(), handler.into_handler()));
+        self.route(Route::new(Method::PATCH, path.to_string(), handler))
+    }
+
+    /// Register a route with per-route middleware.
+    pub fn route(&mut self, route: Route<S>) -> &mut Route<S> {
+        let index = self.routes.len();
+        self.routes.push(route);
+        &mut self.routes[index]
+    }
+
+    /// Register the standard REST routes of `R` under `path`
+    /// (see [`resource`](crate::resource)).
+    pub fn resource<R: Resource<S>>(&mut self, path: &str) {
+        self.routes.extend(resource::routes::<R, S>(path));
     }
 
     /// Attach middleware to this router.
@@ -95,19 +103,15 @@ impl<S: Send + Sync + 'static> Router<S> {
         self.routes.len()
     }
 
-    pub(crate) fn flatten(
-        self,
-        prefix: &str,
-    ) -> Vec<(Method, String, BoxedHandler<S>, SharedMiddlewares<S>)> {
-        self.flatten_with_shared("", prefix, None)
+    pub(crate) fn flatten(self, prefix: &str) -> Vec<Route<S>> {
+        self.flatten_with_shared(prefix, None)
     }
 
     fn flatten_with_shared(
         self,
-        base_prefix: &str,
         prefix: &str,
         parent_middlewares: Option<&SharedMiddlewares<S>>,
-    ) -> Vec<(Method, String, BoxedHandler<S>, SharedMiddlewares<S>)> {
+    ) -> Vec<Route<S>> {
         let estimated_size = self.routes.len()
             + self
                 .nested
@@ -129,19 +133,22 @@ impl<S: Send + Sync + 'static> Router<S> {
             Arc::new(self.middlewares.clone())
         };
 
-        for (method, path, handler) in self.routes {
-            let full_path = if prefix.is_empty() {
-                path.clone()
+        for mut route in self.routes {
+            if !prefix.is_empty() {
+                route.path = format!("{}{}", prefix, route.path);
+            }
+
+            route.middlewares = if route.middlewares.is_empty() {
+                Arc::clone(&combined_middlewares)
             } else {
-                format!("{}{}", prefix, path)
+                let mut combined =
+                    Vec::with_capacity(combined_middlewares.len() + route.middlewares.len());
+                combined.extend_from_slice(&combined_middlewares);
+                combined.extend_from_slice(&route.middlewares);
+                Arc::new(combined)
             };
 
-            flattened.push((
-                method.clone(),
-                full_path,
-                Arc::clone(&handler),
-                Arc::clone(&combined_middlewares),
-            ));
+            flattened.push(route);
         }
 
         for (nested_prefix, nested_router) in self.nested {
@@ -151,11 +158,8 @@ impl<S: Send + Sync + 'static> Router<S> {
                 format!("{}{}", prefix, nested_prefix)
             };
 
-            let nested_routes = nested_router.flatten_with_shared(
-                base_prefix,
-                &full_prefix,
-                Some(&combined_middlewares),
-            );
+            let nested_routes =
+                nested_router.flatten_with_shared(&full_prefix, Some(&combined_middlewares));
             flattened.extend(nested_routes);
         }
 
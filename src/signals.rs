@@ -0,0 +1,247 @@
+//! Process signals.
+//!
+//! [`Signals`] turns OS signals into shutdown and reload events:
+//!
+//! | Unix      | Windows                                | [`Signal`]               |
+//! |-----------|----------------------------------------|--------------------------|
+//! | `SIGTERM` | close, shutdown and logoff events      | [`Signal::Terminate`]    |
+//! | `SIGINT`  | Ctrl-C                                 | [`Signal::Interrupt`]    |
+//! | `SIGHUP`  | Ctrl-Break                             | [`Signal::Reload`]       |
+//!
+//! `RustApi::listen` waits on them to shut down gracefully; reload hooks
+//! registered with [`Signals::on_reload`] run on every reload signal, e.g.
+//! to re-read configuration or certificates. Without reload hooks the
+//! reload signal is not handled and keeps its default effect.
+//!
+//! ```rust,no_run
+//! use rust_api::{RustApi, signals::Signals};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let mut app = RustApi::new();
+//! app.set_signals(Signals::new().on_reload(|| async {
+//!     log::info!("reloading configuration");
+//!     Ok(())
+//! }));
+//! app.listen(([127, 0, 0, 1], 3000)).await.unwrap();
+//! # }
+//! ```
+//!
+//! Servers not run by `listen` can wait on [`Signals::shutdown`] directly.
+
+use futures_util::future::BoxFuture;
+use std::future::Future;
+use std::io;
+use std::sync::Arc;
+
+use crate::Result;
+
+type ReloadHook = Arc<dyn Fn() -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+/// Event produced by [`Signals`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// Terminate gracefully (`SIGTERM`).
+    Terminate,
+    /// Interrupted from the terminal (`SIGINT`, Ctrl-C).
+    Interrupt,
+    /// Reload configuration (`SIGHUP`, Ctrl-Break).
+    Reload,
+}
+
+impl Signal {
+    /// Whether the process should shut down.
+    pub fn is_shutdown(self) -> bool {
+        matches!(self, Signal::Terminate | Signal::Interrupt)
+    }
+}
+
+/// Signal streams, installed on first use.
+#[cfg(unix)]
+struct Listeners {
+    terminate: tokio::signal::unix::Signal,
+    interrupt: tokio::signal::unix::Signal,
+    hangup: Option<tokio::signal::unix::Signal>,
+}
+
+#[cfg(unix)]
+impl Listeners {
+    fn install(reload: bool) -> io::Result<Self> {
+        use tokio::signal::unix::{SignalKind, signal};
+        Ok(Self {
+            terminate: signal(SignalKind::terminate())?,
+            interrupt: signal(SignalKind::interrupt())?,
+            hangup: reload.then(|| signal(SignalKind::hangup())).transpose()?,
+        })
+    }
+
+    async fn recv(&mut self) -> Signal {
+        let hangup = async {
+            match &mut self.hangup {
+                Some(hangup) => hangup.recv().await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            _ = self.terminate.recv() => Signal::Terminate,
+            _ = self.interrupt.recv() => Signal::Interrupt,
+            _ = hangup => Signal::Reload,
+        }
+    }
+}
+
+/// Signal streams, installed on first use.
+#[cfg(windows)]
+struct Listeners {
+    ctrl_c: tokio::signal::windows::CtrlC,
+    ctrl_break: Option<tokio::signal::windows::CtrlBreak>,
+    ctrl_close: tokio::signal::windows::CtrlClose,
+    ctrl_shutdown: tokio::signal::windows::CtrlShutdown,
+    ctrl_logoff: tokio::signal::windows::CtrlLogoff,
+}
+
+#[cfg(windows)]
+impl Listeners {
+    fn install(reload: bool) -> io::Result<Self> {
+        use tokio::signal::windows;
+        Ok(Self {
+            ctrl_c: windows::ctrl_c()?,
+            ctrl_break: reload.then(windows::ctrl_break).transpose()?,
+            ctrl_close: windows::ctrl_close()?,
+            ctrl_shutdown: windows::ctrl_shutdown()?,
+            ctrl_logoff: windows::ctrl_logoff()?,
+        })
+    }
+
+    async fn recv(&mut self) -> Signal {
+        let ctrl_break = async {
+            match &mut self.ctrl_break {
+                Some(ctrl_break) => ctrl_break.recv().await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            _ = self.ctrl_c.recv() => Signal::Interrupt,
+            _ = ctrl_break => Signal::Reload,
+            _ = self.ctrl_close.recv() => Signal::Terminate,
+            _ = self.ctrl_shutdown.recv() => Signal::Terminate,
+            _ = self.ctrl_logoff.recv() => Signal::Terminate,
+        }
+    }
+}
+
+/// Signal streams, installed on first use.
+#[cfg(not(any(unix, windows)))]
+struct Listeners;
+
+#[cfg(not(any(unix, windows)))]
+impl Listeners {
+    fn install(_reload: bool) -> io::Result<Self> {
+        Ok(Self)
+    }
+
+    async fn recv(&mut self) -> Signal {
+        match tokio::signal::ctrl_c().await {
+            Ok(()) => Signal::Interrupt,
+            Err(_) => std::future::pending().await,
+        }
+    }
+}
+
+/// Shutdown and reload signals, with hooks run on reload.
+#[derive(Default)]
+pub struct Signals {
+    reload_hooks: Vec<ReloadHook>,
+    listeners: Option<Listeners>,
+}
+
+impl Signals {
+    /// Listen for the platform's shutdown and reload signals.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `hook` on every reload signal. Hooks run in the order added;
+    /// errors are logged and do not stop the server.
+    pub fn on_reload<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.reload_hooks
+            .push(Arc::new(move || Box::pin(hook()) as BoxFuture<'static, _>));
+        self
+    }
+
+    /// Wait for the next signal. Installs the signal handlers on first
+    /// call, the reload handler only if reload hooks are registered; must
+    /// run inside a tokio runtime.
+    pub async fn recv(&mut self) -> io::Result<Signal> {
+        Ok(self.listeners()?.recv().await)
+    }
+
+    /// Install the signal handlers now rather than on the first
+    /// [`recv`](Self::recv), so signals sent in between are not missed.
+    pub(crate) fn install(&mut self) -> io::Result<()> {
+        self.listeners().map(drop)
+    }
+
+    fn listeners(&mut self) -> io::Result<&mut Listeners> {
+        let listeners = match self.listeners.take() {
+            Some(listeners) => listeners,
+            None => Listeners::install(!self.reload_hooks.is_empty())?,
+        };
+        Ok(self.listeners.insert(listeners))
+    }
+
+    /// Run the reload hooks.
+    pub async fn reload(&self) {
+        for hook in &self.reload_hooks {
+            if let Err(e) = hook().await {
+                log::error!("reload hook failed: {}", e);
+            }
+        }
+    }
+
+    /// Wait for a shutdown signal, running the reload hooks on every
+    /// reload signal until then.
+    pub async fn shutdown(mut self) -> io::Result<Signal> {
+        loop {
+            let signal = self.recv().await?;
+            if signal.is_shutdown() {
+                return Ok(signal);
+            }
+            log::info!("reload signal received");
+            self.reload().await;
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reload_hooks_then_shutdown() {
+        let (reloaded, mut reloads) = tokio::sync::mpsc::unbounded_channel();
+        let mut signals = Signals::new().on_reload(move || {
+            let _ = reloaded.send(());
+            async { Err(crate::Error::internal("bad config")) }
+        });
+        signals.install().unwrap();
+        let shutdown = tokio::spawn(signals.shutdown());
+
+        let pid = std::process::id().to_string();
+        let kill = |signal: &str| {
+            std::process::Command::new("kill")
+                .args([signal, &pid])
+                .status()
+                .unwrap()
+        };
+        kill("-HUP");
+        reloads.recv().await.unwrap();
+        assert!(!shutdown.is_finished());
+        kill("-TERM");
+        assert_eq!(shutdown.await.unwrap().unwrap(), Signal::Terminate);
+    }
+}
@@ -0,0 +1,200 @@
+//! Slow request logging.
+//!
+//! [`SlowRequestLogger`] logs requests that take longer than a threshold,
+//! with the route template, status and selected request extensions.
+//!
+//! ```rust
+//! use rust_api::{RustApi, slow::SlowRequestLogger};
+//! use std::time::Duration;
+//!
+//! #[derive(Clone)]
+//! struct RequestId(String);
+//!
+//! impl std::fmt::Display for RequestId {
+//!     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+//!         f.write_str(&self.0)
+//!     }
+//! }
+//!
+//! let mut app = RustApi::new();
+//! app.attach(
+//!     SlowRequestLogger::new(Duration::from_millis(500)).field::<RequestId>("request_id"),
+//! );
+//! ```
+
+use async_trait::async_trait;
+use hyper::Method;
+use std::fmt::{self, Display};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{Extensions, Middleware, Next, Req, Res};
+
+type FieldFn = Arc<dyn Fn(&Extensions) -> Option<String> + Send + Sync>;
+type SlowHook = Arc<dyn Fn(&SlowRequest) + Send + Sync>;
+
+/// Details of a request that exceeded the threshold.
+#[derive(Debug, Clone)]
+pub struct SlowRequest {
+    /// HTTP method.
+    pub method: Method,
+    /// Matched route template (e.g. `/users/{id}`), if routed.
+    pub route: Option<Arc<str>>,
+    /// Request path.
+    pub path: String,
+    /// Response status code.
+    pub status: u16,
+    /// Time spent in the middleware and handlers below the logger.
+    pub duration: Duration,
+    /// Selected extension values, by field name.
+    pub fields: Vec<(&'static str, String)>,
+}
+
+impl Display for SlowRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "slow request: {} {} -> {} in {:?}",
+            self.method,
+            self.route.as_deref().unwrap_or(&self.path),
+            self.status,
+            self.duration
+        )?;
+        for (name, value) in &self.fields {
+            write!(f, " {}={}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Middleware logging requests slower than a threshold.
+///
+/// Attach with `attach` (post-routing) so the route template is known.
+/// Extension fields are read when the request reaches the logger, so
+/// values must be inserted by middleware attached before it.
+#[derive(Clone)]
+pub struct SlowRequestLogger {
+    threshold: Duration,
+    level: log::Level,
+    fields: Vec<(&'static str, FieldFn)>,
+    on_slow: Option<SlowHook>,
+}
+
+impl SlowRequestLogger {
+    /// Log requests taking longer than `threshold`, at `warn` level.
+    pub fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            level: log::Level::Warn,
+            fields: Vec::new(),
+            on_slow: None,
+        }
+    }
+
+    /// Set the log level.
+    pub fn level(mut self, level: log::Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Include the request extension `T` as `name=value`.
+    pub fn field<T: Display + Send + Sync + 'static>(mut self, name: &'static str) -> Self {
+        let read: FieldFn =
+            Arc::new(|extensions: &Extensions| extensions.get::<T>().map(ToString::to_string));
+        self.fields.push((name, read));
+        self
+    }
+
+    /// Call `hook` for each slow request, after logging it.
+    ///
+    /// Use it to export metrics or capture diagnostics such as a tokio
+    /// task dump.
+    pub fn on_slow<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&SlowRequest) + Send + Sync + 'static,
+    {
+        self.on_slow = Some(Arc::new(hook));
+        self
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> Middleware<S> for SlowRequestLogger {
+    async fn handle(&self, req: Req, _state: Arc<S>, next: Next<S>) -> Res {
+        let method = req.method().clone();
+        let route = req.shared_matched_route();
+        let uri = req.uri().clone();
+        let fields: Vec<_> = self
+            .fields
+            .iter()
+            .filter_map(|(name, read)| read(req.extensions()).map(|value| (*name, value)))
+            .collect();
+
+        let started = Instant::now();
+        let res = next.run(req).await;
+        let duration = started.elapsed();
+        if duration <= self.threshold {
+            return res;
+        }
+
+        let slow = SlowRequest {
+            method,
+            route,
+            path: uri.path().to_string(),
+            status: res.status_code().as_u16(),
+            duration,
+            fields,
+        };
+        log::log!(target: "rust_api::slow", self.level, "{}", slow);
+        if let Some(hook) = &self.on_slow {
+            hook(&slow);
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let slow = SlowRequest {
+            method: Method::GET,
+            route: Some("/users/{id}".into()),
+            path: "/users/7".into(),
+            status: 200,
+            duration: Duration::from_millis(1500),
+            fields: vec![("request_id", "abc".into()), ("user_id", "7".into())],
+        };
+        assert_eq!(
+            slow.to_string(),
+            "slow request: GET /users/{id} -> 200 in 1.5s request_id=abc user_id=7"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hook_receives_route_and_fields() {
+        use crate::RustApi;
+        use crate::test::TestClient;
+        use std::sync::Mutex;
+
+        let seen = Arc::new(Mutex::new(None));
+        let hook_seen = Arc::clone(&seen);
+        let mut app = RustApi::new();
+        app.attach(
+            SlowRequestLogger::new(Duration::ZERO)
+                .field::<u32>("user_id")
+                .on_slow(move |slow| *hook_seen.lock().unwrap() = Some(slow.clone())),
+        );
+        app.get("/users/{id}", |_req: Req| async { "ok" });
+
+        let client = TestClient::new(app).extension(7u32);
+        client.get("/users/7").send().await;
+
+        let slow = seen.lock().unwrap().take().unwrap();
+        assert_eq!(slow.route.as_deref(), Some("/users/{id}"));
+        assert_eq!(slow.path, "/users/7");
+        assert_eq!(slow.fields, [("user_id", "7".to_string())]);
+    }
+}
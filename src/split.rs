@@ -0,0 +1,194 @@
+//! Traffic splitting for experiments.
+//!
+//! [`Split`] is a handler sending a percentage of requests to a treatment
+//! handler and the rest to a control handler. The choice is a stable hash
+//! of the experiment name and a per-user key, so a user keeps seeing the
+//! same variant. The [`Variant`] that served a request is inserted into its
+//! extensions and reported to the metrics sink with the request timings.
+//!
+//! ```rust
+//! use rust_api::{Req, RustApi, split::Split};
+//!
+//! let mut app = RustApi::new();
+//! app.get(
+//!     "/checkout",
+//!     Split::new(
+//!         "checkout_v2",
+//!         10,
+//!         |_req: Req| async { "new checkout" },
+//!         |_req: Req| async { "old checkout" },
+//!     )
+//!     .cookie("uid"),
+//! );
+//! ```
+
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::handler::IntoHandler;
+use crate::{Handler, Req, Res};
+
+type KeyFn = Arc<dyn Fn(&Req) -> Option<String> + Send + Sync>;
+
+/// Variant of an experiment that served a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    /// Experiment name.
+    pub experiment: Arc<str>,
+    /// Variant name (`treatment` or `control` unless renamed).
+    pub name: &'static str,
+}
+
+/// Handler splitting traffic between two handlers.
+pub struct Split<S = ()> {
+    experiment: Arc<str>,
+    percent: u8,
+    treatment: Arc<dyn Handler<S>>,
+    control: Arc<dyn Handler<S>>,
+    names: (&'static str, &'static str),
+    key: Option<KeyFn>,
+}
+
+impl<S: Send + Sync + 'static> Split<S> {
+    /// Send `percent` of requests to `treatment` and the rest to `control`.
+    ///
+    /// Requests are keyed by the `user_id` cookie until [`key`](Self::key),
+    /// [`cookie`](Self::cookie) or [`header`](Self::header) says otherwise;
+    /// requests without a key are served by `control`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `percent` is above 100.
+    pub fn new<T, C, TA, CA>(
+        experiment: impl Into<Arc<str>>,
+        percent: u8,
+        treatment: T,
+        control: C,
+    ) -> Self
+    where
+        T: IntoHandler<S, TA>,
+        C: IntoHandler<S, CA>,
+    {
+        assert!(percent <= 100, "split percentage must be at most 100");
+        Self {
+            experiment: experiment.into(),
+            percent,
+            treatment: treatment.into_handler(),
+            control: control.into_handler(),
+            names: ("treatment", "control"),
+            key: None,
+        }
+        .cookie("user_id")
+    }
+
+    /// Rename the treatment and control variants.
+    pub fn variants(mut self, treatment: &'static str, control: &'static str) -> Self {
+        self.names = (treatment, control);
+        self
+    }
+
+    /// Key requests by a cookie.
+    pub fn cookie(self, name: &'static str) -> Self {
+        self.key(move |req: &Req| req.cookie(name).map(str::to_string))
+    }
+
+    /// Key requests by a header.
+    pub fn header(self, name: &'static str) -> Self {
+        self.key(move |req: &Req| req.header(name).map(str::to_string))
+    }
+
+    /// Key requests with a function, e.g. reading an authenticated user.
+    pub fn key<F>(mut self, key: F) -> Self
+    where
+        F: Fn(&Req) -> Option<String> + Send + Sync + 'static,
+    {
+        self.key = Some(Arc::new(key));
+        self
+    }
+
+    /// Whether `key` falls in the treatment group.
+    fn in_treatment(&self, key: &str) -> bool {
+        let bucket = fnv1a(&[self.experiment.as_bytes(), b":", key.as_bytes()]) % 100;
+        bucket < u64::from(self.percent)
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> Handler<S> for Split<S> {
+    async fn call(&self, mut req: Req, state: Arc<S>) -> Res {
+        let treatment = self
+            .key
+            .as_ref()
+            .and_then(|key| key(&req))
+            .is_some_and(|key| self.in_treatment(&key));
+        let (name, handler) = if treatment {
+            (self.names.0, &self.treatment)
+        } else {
+            (self.names.1, &self.control)
+        };
+
+        let variant = Variant {
+            experiment: Arc::clone(&self.experiment),
+            name,
+        };
+        if let Some(trace) = req.trace() {
+            trace.variant.set(variant.clone()).ok();
+        }
+        req.extensions_mut().insert(variant);
+        handler.call(req, state).await
+    }
+}
+
+/// 64-bit FNV-1a over the concatenated `parts`: stable across builds,
+/// restarts and platforms, unlike `std` hashers.
+pub(crate) fn fnv1a(parts: &[&[u8]]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    for byte in parts.iter().flat_map(|part| part.iter()) {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RustApi;
+    use crate::test::{TestClient, body_text};
+
+    fn split(percent: u8) -> Split {
+        Split::new(
+            "exp",
+            percent,
+            |req: Req| async move { req.extensions().get::<Variant>().unwrap().name },
+            |req: Req| async move { req.extensions().get::<Variant>().unwrap().name },
+        )
+        .header("x-user")
+    }
+
+    #[test]
+    fn test_distribution() {
+        let split = split(30);
+        let treated = (0..10_000)
+            .filter(|i| split.in_treatment(&i.to_string()))
+            .count();
+        assert!((2_700..3_300).contains(&treated), "{}", treated);
+        let none = self::split(0);
+        assert!(!(0..100).any(|i| none.in_treatment(&i.to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_stable_variant() {
+        let mut app = RustApi::new();
+        app.get("/", split(50));
+        let client = TestClient::new(app);
+
+        let variant_of = |user: &'static str| {
+            let request = client.get("/").header("x-user", user);
+            async move { body_text(request.send().await).await }
+        };
+        let first = variant_of("alice").await;
+        assert_eq!(variant_of("alice").await, first);
+        assert_eq!(body_text(client.get("/").send().await).await, "control");
+    }
+}
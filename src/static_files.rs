@@ -0,0 +1,842 @@
+//! Static files, fingerprinted assets and single-page apps.
+//!
+//! [`ServeDir`] serves a directory under a path prefix. With
+//! [`fingerprint`](ServeDir::fingerprint), every file is hashed at startup
+//! and also served under a name carrying its content hash
+//! (`app.css` → `app.3f2a9c1b5e7d0a46.css`) with immutable cache headers,
+//! so browsers cache assets forever and pick up changes through new URLs.
+//! Templates resolve logical names through the returned [`AssetManifest`].
+//!
+//! ```rust,no_run
+//! use rust_api::{Res, Req, RustApi, static_files::ServeDir};
+//!
+//! let mut app = RustApi::new();
+//! let assets = ServeDir::new("public").fingerprint().install(&mut app, "/static");
+//! app.get("/", move |_: Req| {
+//!     let css = assets.url("css/app.css");
+//!     async move { Res::html(format!(r#"<link rel="stylesheet" href="{}">"#, css)) }
+//! });
+//! ```
+//!
+//! Unhashed names stay reachable (e.g. for relative URLs inside CSS) and
+//! are served with `no-cache`, so browsers revalidate them. Files added
+//! after startup are served but not fingerprinted.
+//!
+//! [`ServeSpa`] serves a built single-page app with an `index.html`
+//! fallback for client-side routes.
+//!
+//! For internal file-drop tools, a disk `ServeDir` can also list
+//! directories and accept uploads and deletes from authorized clients:
+//!
+//! ```rust,no_run
+//! use rust_api::{Req, RustApi, static_files::ServeDir};
+//!
+//! let mut app = RustApi::new();
+//! ServeDir::new("/srv/drop")
+//!     .list_directories()
+//!     .allow_writes(|req: &Req| req.header("x-drop-token") == Some("s3cret"))
+//!     .install(&mut app, "/drop");
+//! ```
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use crate::html::escape;
+use crate::req::body_io_error;
+use crate::split::fnv1a;
+use crate::{CacheControl, Error, Guard, IntoRes, Req, Res, Result, RustApi};
+
+/// One year, the conventional lifetime of fingerprinted assets.
+const IMMUTABLE_MAX_AGE: u64 = 31_536_000;
+
+/// Directory served under a path prefix.
+pub struct ServeDir {
+    source: Source,
+    fingerprint: bool,
+    listing: bool,
+    writes: Option<Arc<dyn Guard>>,
+}
+
+enum Source {
+    Disk(PathBuf),
+    #[cfg(feature = "embed")]
+    Embedded(&'static include_dir::Dir<'static>),
+}
+
+impl ServeDir {
+    /// Serve files below `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            source: Source::Disk(root.into()),
+            fingerprint: false,
+            listing: false,
+            writes: None,
+        }
+    }
+
+    /// Serve files compiled into the binary (`embed` feature).
+    ///
+    /// Responses carry an `ETag` of the file's content hash and answer
+    /// matching `If-None-Match` requests with 304. Embed the directory
+    /// with the `include_dir` crate:
+    ///
+    /// ```rust,ignore
+    /// use include_dir::{Dir, include_dir};
+    /// use rust_api::{RustApi, static_files::ServeDir};
+    ///
+    /// static PUBLIC: Dir<'static> = include_dir!("$CARGO_MANIFEST_DIR/public");
+    ///
+    /// let mut app = RustApi::new();
+    /// let assets = ServeDir::embedded(&PUBLIC).fingerprint().install(&mut app, "/static");
+    /// ```
+    #[cfg(feature = "embed")]
+    pub fn embedded(dir: &'static include_dir::Dir<'static>) -> Self {
+        Self {
+            source: Source::Embedded(dir),
+            fingerprint: false,
+            listing: false,
+            writes: None,
+        }
+    }
+
+    /// Hash file contents at install time and serve hashed names as
+    /// immutable.
+    pub fn fingerprint(mut self) -> Self {
+        self.fingerprint = true;
+        self
+    }
+
+    /// List directories without an `index.html` (disk only).
+    ///
+    /// Listings are HTML, or JSON (`name`, `dir`, `size`, `modified` in
+    /// Unix seconds) when the client accepts `application/json`. Dotfiles
+    /// are left out.
+    pub fn list_directories(mut self) -> Self {
+        self.listing = true;
+        self
+    }
+
+    /// Accept `PUT` (create or replace a file) and `DELETE` for requests
+    /// `authorize` allows; others get 403 (disk only).
+    ///
+    /// Uploads stream to a temporary file renamed into place once
+    /// complete, so readers never see partial files. The app's body limit
+    /// applies.
+    pub fn allow_writes(mut self, authorize: impl Guard) -> Self {
+        self.writes = Some(Arc::new(authorize));
+        self
+    }
+
+    /// Register `GET {prefix}/` and `GET {prefix}/{*path}` and return the
+    /// asset manifest.
+    ///
+    /// Without fingerprinting the manifest maps names to plain URLs, so
+    /// templates work the same either way. With writes allowed, also
+    /// registers `PUT` and `DELETE {prefix}/{*path}`.
+    pub fn install<S: Send + Sync + 'static>(
+        self,
+        app: &mut RustApi<S>,
+        prefix: &str,
+    ) -> AssetManifest {
+        let prefix = prefix.trim_end_matches('/');
+        let mut assets = HashMap::new();
+        let files = match self.source {
+            Source::Disk(root) => {
+                if self.fingerprint {
+                    if let Err(e) = fingerprint_dir(&root, &root, &mut assets) {
+                        log::warn!("failed to fingerprint {}: {}", root.display(), e);
+                    }
+                }
+                if let Some(authorize) = self.writes {
+                    install_writes(app, prefix, root.clone(), authorize);
+                }
+                Files::Disk {
+                    root,
+                    listing: self.listing,
+                }
+            }
+            #[cfg(feature = "embed")]
+            Source::Embedded(dir) => {
+                let mut files = HashMap::new();
+                collect_embedded(dir, &mut files);
+                if self.fingerprint {
+                    for (name, file) in &files {
+                        assets.insert(name.clone(), hashed_name(name, &file.hash));
+                    }
+                }
+                Files::Embedded(files)
+            }
+        };
+        let manifest = AssetManifest {
+            inner: Arc::new(Manifest {
+                prefix: prefix.to_string(),
+                logical: assets.iter().map(|(k, v)| (v.clone(), k.clone())).collect(),
+                hashed: assets,
+            }),
+        };
+
+        let files = Arc::new(files);
+        // The catch-all needs a non-empty path, so the index gets its own route.
+        for pattern in [format!("{}/", prefix), format!("{}/{{*path}}", prefix)] {
+            let files = Arc::clone(&files);
+            let served = manifest.clone();
+            app.get(&pattern, move |req: Req| {
+                let files = Arc::clone(&files);
+                let manifest = served.clone();
+                async move { serve(&files, &manifest, &req).await }
+            });
+        }
+        manifest
+    }
+}
+
+struct Manifest {
+    prefix: String,
+    /// Logical name → fingerprinted name.
+    hashed: HashMap<String, String>,
+    /// Fingerprinted name → logical name.
+    logical: HashMap<String, String>,
+}
+
+/// Logical asset names mapped to their (fingerprinted) URLs.
+#[derive(Clone)]
+pub struct AssetManifest {
+    inner: Arc<Manifest>,
+}
+
+impl AssetManifest {
+    /// URL for `name` (relative to the served directory), fingerprinted
+    /// when known, plain otherwise.
+    pub fn url(&self, name: &str) -> String {
+        let name = name.trim_start_matches('/');
+        let file = self.inner.hashed.get(name).map_or(name, String::as_str);
+        format!("{}/{}", self.inner.prefix, file)
+    }
+
+    /// Fingerprinted name for `name`, if it was hashed at startup.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.inner.hashed.get(name).map(String::as_str)
+    }
+
+    /// Logical names and their fingerprinted names.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.inner
+            .hashed
+            .iter()
+            .map(|(name, hashed)| (name.as_str(), hashed.as_str()))
+    }
+}
+
+enum Files {
+    Disk {
+        root: PathBuf,
+        listing: bool,
+    },
+    #[cfg(feature = "embed")]
+    Embedded(HashMap<String, EmbeddedFile>),
+}
+
+async fn serve(files: &Files, manifest: &AssetManifest, req: &Req) -> Res {
+    let path = req.param("path").unwrap_or_default();
+    let (name, immutable) = match manifest.inner.logical.get(path) {
+        Some(logical) => (logical.as_str(), true),
+        None => (path, false),
+    };
+    match files {
+        Files::Disk { root, listing } => {
+            if let Some((file, len)) = find_file(root, name).await {
+                return send_file(&file, len, cache_for(immutable)).await;
+            }
+            if *listing {
+                if let Some(dir) = resolve(root, name) {
+                    if tokio::fs::metadata(&dir).await.is_ok_and(|m| m.is_dir()) {
+                        let url = format!("{}/{}", manifest.inner.prefix, name);
+                        return list_dir(&dir, url.trim_end_matches('/'), req)
+                            .await
+                            .into_res();
+                    }
+                }
+            }
+            Error::not_found("File not found").into_res()
+        }
+        #[cfg(feature = "embed")]
+        Files::Embedded(files) => {
+            let index = format!("{}/index.html", name.trim_end_matches('/'));
+            let file = files
+                .get(name)
+                .or_else(|| files.get(index.trim_start_matches('/')));
+            match file {
+                Some(file) => file.response(req, cache_for(immutable)),
+                None => Error::not_found("File not found").into_res(),
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ListingEntry {
+    name: String,
+    dir: bool,
+    size: u64,
+    modified: Option<u64>,
+}
+
+/// Render `dir`, reached at `url`, as HTML or JSON.
+async fn list_dir(dir: &Path, url: &str, req: &Req) -> Result<Res> {
+    let mut entries = Vec::new();
+    let mut read = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = read.next_entry().await? {
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        if name.starts_with('.') {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata().await else {
+            continue;
+        };
+        entries.push(ListingEntry {
+            name,
+            dir: metadata.is_dir(),
+            size: if metadata.is_dir() { 0 } else { metadata.len() },
+            modified: metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs()),
+        });
+    }
+    entries.sort_by(|a, b| b.dir.cmp(&a.dir).then_with(|| a.name.cmp(&b.name)));
+
+    let cache = CacheControl::new().no_cache();
+    if req
+        .header("accept")
+        .is_some_and(|accept| accept.contains("application/json"))
+    {
+        return Ok(Res::json(&entries).cache_control(cache));
+    }
+    let title = escape(&format!("{}/", url));
+    let mut html = format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{0}</title></head><body><h1>{0}</h1><ul>",
+        title
+    );
+    for entry in &entries {
+        let slash = if entry.dir { "/" } else { "" };
+        html.push_str(&format!(
+            "<li><a href=\"{}\">{}{}</a></li>",
+            escape(&format!("{}/{}{}", url, entry.name, slash)),
+            escape(&entry.name),
+            slash
+        ));
+    }
+    html.push_str("</ul></body></html>");
+    Ok(Res::html(html).cache_control(cache))
+}
+
+/// Register `PUT` and `DELETE {prefix}/{*path}` below `root`.
+fn install_writes<S: Send + Sync + 'static>(
+    app: &mut RustApi<S>,
+    prefix: &str,
+    root: PathBuf,
+    authorize: Arc<dyn Guard>,
+) {
+    let pattern = format!("{}/{{*path}}", prefix);
+    let root = Arc::new(root);
+    let (put_root, put_authorize) = (Arc::clone(&root), Arc::clone(&authorize));
+    app.put(&pattern, move |mut req: Req| {
+        let root = Arc::clone(&put_root);
+        let authorize = Arc::clone(&put_authorize);
+        async move {
+            match writable_path(&root, &*authorize, &req) {
+                Ok(file) => upload(&file, &mut req).await.into_res(),
+                Err(e) => e.into_res(),
+            }
+        }
+    });
+    app.delete(&pattern, move |req: Req| {
+        let root = Arc::clone(&root);
+        let authorize = Arc::clone(&authorize);
+        async move {
+            match writable_path(&root, &*authorize, &req) {
+                Ok(file) => remove(&file).await.into_res(),
+                Err(e) => e.into_res(),
+            }
+        }
+    });
+}
+
+fn writable_path(root: &Path, authorize: &dyn Guard, req: &Req) -> Result<PathBuf> {
+    if !authorize.check(req) {
+        return Err(Error::forbidden("Writes not allowed"));
+    }
+    req.param("path")
+        .and_then(|path| resolve(root, path))
+        .ok_or_else(|| Error::not_found("File not found"))
+}
+
+async fn upload(file: &Path, req: &mut Req) -> Result<Res> {
+    let existed = match tokio::fs::metadata(file).await {
+        Ok(metadata) if metadata.is_dir() => {
+            return Err(Error::Status(409, Some("Path is a directory".into())));
+        }
+        Ok(_) => true,
+        Err(_) => false,
+    };
+    if let Some(parent) = file.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let name = file
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    let temp = file.with_file_name(format!(".{}.upload-{}", name, uuid::Uuid::new_v4()));
+
+    let written = async {
+        let mut body = req.body_reader()?;
+        let mut out = tokio::fs::File::create(&temp).await?;
+        tokio::io::copy(&mut body, &mut out)
+            .await
+            .map_err(body_io_error)?;
+        out.sync_all().await?;
+        tokio::fs::rename(&temp, file).await?;
+        Ok::<_, Error>(())
+    }
+    .await;
+    if let Err(e) = written {
+        tokio::fs::remove_file(&temp).await.ok();
+        return Err(e);
+    }
+    Ok(Res::status(if existed { 204 } else { 201 }))
+}
+
+async fn remove(file: &Path) -> Result<Res> {
+    match tokio::fs::metadata(file).await {
+        Ok(metadata) if metadata.is_dir() => {
+            Err(Error::Status(409, Some("Path is a directory".into())))
+        }
+        Ok(_) => {
+            tokio::fs::remove_file(file).await?;
+            Ok(Res::status(204))
+        }
+        Err(_) => Err(Error::not_found("File not found")),
+    }
+}
+
+/// File compiled into the binary, with its content hash.
+#[cfg(feature = "embed")]
+struct EmbeddedFile {
+    contents: &'static [u8],
+    content_type: &'static str,
+    hash: String,
+}
+
+#[cfg(feature = "embed")]
+impl EmbeddedFile {
+    fn response(&self, req: &Req, cache: CacheControl) -> Res {
+        let etag = format!("\"{}\"", self.hash);
+        let not_modified = req.header("if-none-match").is_some_and(|tags| {
+            tags.split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == etag || tag == "*")
+        });
+        let res = Res::builder().header("etag", &etag).cache_control(cache);
+        if not_modified {
+            res.status(304).body(bytes::Bytes::new())
+        } else {
+            res.header("content-type", self.content_type)
+                .body(bytes::Bytes::from_static(self.contents))
+        }
+    }
+}
+
+/// Index every file in `dir` by its `/`-separated path.
+#[cfg(feature = "embed")]
+fn collect_embedded(
+    dir: &'static include_dir::Dir<'static>,
+    files: &mut HashMap<String, EmbeddedFile>,
+) {
+    for file in dir.files() {
+        let Some(name) = file.path().to_str().map(|p| p.replace('\\', "/")) else {
+            continue;
+        };
+        files.insert(
+            name,
+            EmbeddedFile {
+                contents: file.contents(),
+                content_type: content_type(file.path()),
+                hash: format!("{:016x}", fnv1a(&[file.contents()])),
+            },
+        );
+    }
+    for sub in dir.dirs() {
+        collect_embedded(sub, files);
+    }
+}
+
+/// Locate `name` below `root`, using `index.html` for directories.
+async fn find_file(root: &Path, name: &str) -> Option<(PathBuf, u64)> {
+    let mut file = resolve(root, name)?;
+    let mut metadata = tokio::fs::metadata(&file).await.ok();
+    if metadata.as_ref().is_some_and(|m| m.is_dir()) {
+        file.push("index.html");
+        metadata = tokio::fs::metadata(&file).await.ok();
+    }
+    let len = metadata.filter(|m| m.is_file())?.len();
+    Some((file, len))
+}
+
+async fn send_file(file: &Path, len: u64, cache: CacheControl) -> Res {
+    Res::file(file)
+        .await
+        .header("content-type", content_type(file))
+        .header("content-length", len.to_string())
+        .cache_control(cache)
+}
+
+fn cache_for(immutable: bool) -> CacheControl {
+    if immutable {
+        CacheControl::public()
+            .max_age(IMMUTABLE_MAX_AGE)
+            .immutable()
+    } else {
+        CacheControl::public().no_cache()
+    }
+}
+
+/// Single-page app: static assets plus an `index.html` fallback.
+///
+/// Paths that match no file get `index.html`, so client-side routes
+/// survive reloads, except under excluded prefixes (`/api` by default)
+/// and for names with an extension, which get 404. `index.html` is served
+/// with `no-cache`; files under immutable prefixes (`/assets` by default,
+/// where bundlers such as Vite emit content-hashed names) are cached for
+/// a year.
+///
+/// ```rust,no_run
+/// use rust_api::{Req, RustApi, static_files::ServeSpa};
+///
+/// let mut app = RustApi::new();
+/// app.get("/api/health", |_: Req| async { "ok" });
+/// ServeSpa::new("dist").exclude("/auth").install(&mut app);
+/// ```
+pub struct ServeSpa {
+    root: PathBuf,
+    exclude: Vec<String>,
+    immutable: Vec<String>,
+}
+
+impl ServeSpa {
+    /// Serve the app built into `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            exclude: vec!["/api".to_string()],
+            immutable: vec!["/assets".to_string()],
+        }
+    }
+
+    /// Answer 404 instead of `index.html` under `prefix`.
+    pub fn exclude(mut self, prefix: impl Into<String>) -> Self {
+        self.exclude.push(prefix.into());
+        self
+    }
+
+    /// Serve files under `prefix` as immutable; only use it for
+    /// directories holding content-hashed names.
+    pub fn immutable(mut self, prefix: impl Into<String>) -> Self {
+        self.immutable.push(prefix.into());
+        self
+    }
+
+    /// Register `GET /` and a catch-all `GET /{*path}`.
+    ///
+    /// Routes registered on the app still take precedence.
+    pub fn install<S: Send + Sync + 'static>(self, app: &mut RustApi<S>) {
+        let spa = Arc::new(self);
+        let index = Arc::clone(&spa);
+        app.get("/", move |_: Req| {
+            let spa = Arc::clone(&index);
+            async move { spa.serve("/").await }
+        });
+        app.get("/{*path}", move |req: Req| {
+            let spa = Arc::clone(&spa);
+            async move { spa.serve(req.path()).await }
+        });
+    }
+
+    async fn serve(&self, path: &str) -> Res {
+        if self.exclude.iter().any(|prefix| under(path, prefix)) {
+            return Error::not_found("Route not found").into_res();
+        }
+        let name = path.trim_start_matches('/');
+        if !name.is_empty() {
+            if let Some((file, len)) = find_file(&self.root, name).await {
+                let immutable = self.immutable.iter().any(|prefix| under(path, prefix));
+                return send_file(&file, len, cache_for(immutable)).await;
+            }
+        }
+        let last = name.rsplit('/').next().unwrap_or_default();
+        if last.contains('.') {
+            return Error::not_found("File not found").into_res();
+        }
+        match find_file(&self.root, "index.html").await {
+            Some((file, len)) => send_file(&file, len, CacheControl::new().no_cache()).await,
+            None => Error::not_found("index.html not found").into_res(),
+        }
+    }
+}
+
+/// Whether `path` is `prefix` or below it.
+fn under(path: &str, prefix: &str) -> bool {
+    let prefix = prefix.trim_end_matches('/');
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Join a request path below `root`, rejecting anything but plain names.
+fn resolve(root: &Path, path: &str) -> Option<PathBuf> {
+    let relative = Path::new(path);
+    if path.contains('\\')
+        || !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+    {
+        return None;
+    }
+    Some(root.join(relative))
+}
+
+/// Hash every file below `dir`, keyed by `/`-separated name under `root`.
+fn fingerprint_dir(
+    root: &Path,
+    dir: &Path,
+    assets: &mut HashMap<String, String>,
+) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            fingerprint_dir(root, &path, assets)?;
+            continue;
+        }
+        let Some(name) = path
+            .strip_prefix(root)
+            .ok()
+            .and_then(|p| p.to_str())
+            .map(|p| p.replace('\\', "/"))
+        else {
+            continue;
+        };
+        let hash = format!("{:016x}", fnv1a(&[&std::fs::read(&path)?]));
+        assets.insert(name.clone(), hashed_name(&name, &hash));
+    }
+    Ok(())
+}
+
+/// Insert `hash` before the extension: `css/app.css` → `css/app.{hash}.css`.
+fn hashed_name(name: &str, hash: &str) -> String {
+    let (dir, file) = name.rsplit_once('/').map_or(("", name), |(d, f)| (d, f));
+    let file = match file.split_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{}.{}.{}", stem, hash, ext),
+        _ => format!("{}.{}", file, hash),
+    };
+    if dir.is_empty() {
+        file
+    } else {
+        format!("{}/{}", dir, file)
+    }
+}
+
+/// `Content-Type` for common web asset extensions.
+fn content_type(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    match ext.as_deref() {
+        Some("html" | "htm") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js" | "mjs") => "text/javascript; charset=utf-8",
+        Some("json" | "map") => "application/json",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("xml") => "application/xml",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("avif") => "image/avif",
+        Some("ico") => "image/x-icon",
+        Some("woff") => "font/woff",
+        Some("woff2") => "font/woff2",
+        Some("wasm") => "application/wasm",
+        Some("pdf") => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{TestClient, assert_status, body_text};
+
+    #[test]
+    fn test_hashed_name() {
+        assert_eq!(hashed_name("css/app.css", "abc"), "css/app.abc.css");
+        assert_eq!(hashed_name("app.min.js", "abc"), "app.abc.min.js");
+        assert_eq!(hashed_name(".well-known", "abc"), ".well-known.abc");
+        assert_eq!(hashed_name("LICENSE", "abc"), "LICENSE.abc");
+    }
+
+    #[tokio::test]
+    async fn test_fingerprinted_assets() {
+        let root = std::env::temp_dir().join(format!("rust-api-static-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("css")).unwrap();
+        std::fs::write(root.join("css/app.css"), "body{}").unwrap();
+
+        let mut app = RustApi::new();
+        let assets = ServeDir::new(&root)
+            .fingerprint()
+            .install(&mut app, "/static/");
+        let client = TestClient::new(app);
+
+        let url = assets.url("css/app.css");
+        assert_eq!(
+            url,
+            format!("/static/css/app.{:016x}.css", fnv1a(&[b"body{}"]))
+        );
+        let res = client.get(&url).send().await;
+        assert_status(&res, 200);
+        assert_eq!(res.headers()["content-type"], "text/css; charset=utf-8");
+        assert_eq!(
+            res.headers()["cache-control"],
+            "public, max-age=31536000, immutable"
+        );
+        assert_eq!(body_text(res).await, "body{}");
+
+        let plain = client.get("/static/css/app.css").send().await;
+        assert_eq!(plain.headers()["cache-control"], "public, no-cache");
+        assert_status(&client.get("/static/../Cargo.toml").send().await, 404);
+        assert_status(&client.get("/static/css").send().await, 404);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_spa_fallback() {
+        let root = std::env::temp_dir().join(format!("rust-api-spa-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("assets")).unwrap();
+        std::fs::write(root.join("index.html"), "<div id=app>").unwrap();
+        std::fs::write(root.join("assets/index-B4x9.js"), "boot()").unwrap();
+
+        let mut app = RustApi::new();
+        app.get("/api/health", |_: Req| async { "ok" });
+        ServeSpa::new(&root).install(&mut app);
+        let client = TestClient::new(app);
+
+        for path in ["/", "/orders/42"] {
+            let res = client.get(path).send().await;
+            assert_eq!(res.headers()["cache-control"], "no-cache");
+            assert_eq!(body_text(res).await, "<div id=app>");
+        }
+        let asset = client.get("/assets/index-B4x9.js").send().await;
+        assert_eq!(
+            asset.headers()["cache-control"],
+            "public, max-age=31536000, immutable"
+        );
+        assert_eq!(
+            body_text(client.get("/api/health").send().await).await,
+            "ok"
+        );
+        assert_status(&client.get("/api/missing").send().await, 404);
+        assert_status(&client.get("/favicon.ico").send().await, 404);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(feature = "embed")]
+    #[tokio::test]
+    async fn test_embedded_assets() {
+        static PUBLIC: include_dir::Dir<'static> =
+            include_dir::include_dir!("$CARGO_MANIFEST_DIR/examples/file-serving/static");
+
+        let mut app = RustApi::new();
+        let assets = ServeDir::embedded(&PUBLIC)
+            .fingerprint()
+            .install(&mut app, "/static");
+        let client = TestClient::new(app);
+
+        let res = client.get(&assets.url("index.html")).send().await;
+        assert_status(&res, 200);
+        assert_eq!(res.headers()["content-type"], "text/html; charset=utf-8");
+        let etag = res.headers()["etag"].to_str().unwrap().to_string();
+        assert!(body_text(res).await.contains("<html"));
+
+        let cached = client
+            .get("/static/")
+            .header("if-none-match", &etag)
+            .send()
+            .await;
+        assert_status(&cached, 304);
+        assert_eq!(cached.headers()["cache-control"], "public, no-cache");
+        assert_status(&client.get("/static/missing.css").send().await, 404);
+    }
+
+    #[tokio::test]
+    async fn test_listing_and_writes() {
+        let root = std::env::temp_dir().join(format!("rust-api-drop-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("reports")).unwrap();
+        std::fs::write(root.join("notes.txt"), "hi").unwrap();
+        std::fs::write(root.join(".secret"), "x").unwrap();
+
+        let mut app = RustApi::new();
+        app.set_body_limit(16);
+        ServeDir::new(&root)
+            .list_directories()
+            .allow_writes(|req: &Req| req.header("x-token") == Some("ok"))
+            .install(&mut app, "/drop");
+        let client = TestClient::new(app);
+
+        let listing: serde_json::Value = crate::test::body_json(
+            client
+                .get("/drop/")
+                .header("accept", "application/json")
+                .send()
+                .await,
+        )
+        .await;
+        let names: Vec<_> = listing
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["reports", "notes.txt"]);
+        let html = body_text(client.get("/drop/reports").send().await).await;
+        assert!(html.contains("<h1>/drop/reports/</h1>"));
+
+        let put = |path: &'static str, token: &'static str, body: &'static str| {
+            client.put(path).header("x-token", token).body(body).send()
+        };
+        assert_status(&put("/drop/reports/q1.csv", "ok", "a,b").await, 201);
+        assert_status(&put("/drop/reports/q1.csv", "ok", "a,b,c").await, 204);
+        assert_eq!(
+            std::fs::read_to_string(root.join("reports/q1.csv")).unwrap(),
+            "a,b,c"
+        );
+        assert_status(&put("/drop/x.txt", "nope", "x").await, 403);
+        assert_status(&put("/drop/big.bin", "ok", "0123456789abcdefg").await, 413);
+        assert!(!root.join("big.bin").exists());
+        assert_eq!(std::fs::read_dir(&root).unwrap().count(), 3);
+        assert_status(&put("/drop/../escape.txt", "ok", "x").await, 404);
+
+        let delete = |path: &'static str| client.delete(path).header("x-token", "ok").send();
+        assert_status(&delete("/drop/reports/q1.csv").await, 204);
+        assert_status(&delete("/drop/reports/q1.csv").await, 404);
+        assert_status(&delete("/drop/reports").await, 409);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,335 @@
+//! Multitenancy.
+//!
+//! [`Tenancy`] resolves the tenant of each request from a subdomain, a
+//! header or a leading path segment, loads its configuration through a
+//! [`TenantProvider`] and stores a [`TenantContext`] in the request
+//! extensions. The configuration type is yours, so it can carry per-tenant
+//! state such as a database pool or limits. Handlers read it with the
+//! [`TenantContext`] extractor, or just the id with [`TenantId`].
+//!
+//! Requests naming no tenant get 400, unknown tenants 404.
+//!
+//! ```rust
+//! use rust_api::{RustApi, tenant::{StaticTenants, Tenancy, TenantContext}};
+//!
+//! struct Plan {
+//!     name: &'static str,
+//! }
+//!
+//! async fn plan(tenant: TenantContext<Plan>) -> String {
+//!     format!("{} is on {}", tenant.id, tenant.name)
+//! }
+//!
+//! let tenants = StaticTenants::new()
+//!     .tenant("acme", Plan { name: "pro" })
+//!     .tenant("globex", Plan { name: "free" });
+//! let mut app = RustApi::new();
+//! app.attach_pre_routing(Tenancy::new(tenants).subdomain("example.com").header("x-tenant"));
+//! app.get("/plan", plan);
+//! ```
+
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::{Error, FromRequest, IntoRes, Middleware, Next, Req, Res, Result};
+
+/// Source of tenant configuration.
+#[async_trait]
+pub trait TenantProvider: Send + Sync + 'static {
+    /// Per-tenant configuration and state.
+    type Config: Send + Sync + 'static;
+
+    /// Load tenant `id`; `None` if it does not exist.
+    async fn load(&self, id: &str) -> Result<Option<Arc<Self::Config>>>;
+}
+
+/// Fixed set of tenants.
+pub struct StaticTenants<T>(HashMap<String, Arc<T>>);
+
+impl<T> StaticTenants<T> {
+    /// Create an empty set.
+    pub fn new() -> Self {
+        Self(HashMap::new())
+    }
+
+    /// Add tenant `id`.
+    pub fn tenant(mut self, id: impl Into<String>, config: T) -> Self {
+        self.0.insert(id.into(), Arc::new(config));
+        self
+    }
+}
+
+impl<T> Default for StaticTenants<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl<T: Send + Sync + 'static> TenantProvider for StaticTenants<T> {
+    type Config = T;
+
+    async fn load(&self, id: &str) -> Result<Option<Arc<T>>> {
+        Ok(self.0.get(id).cloned())
+    }
+}
+
+/// Where a tenant id is read from.
+#[derive(Debug, Clone)]
+enum Source {
+    /// First label of a host under this base domain.
+    Subdomain(String),
+    /// Value of this header.
+    Header(String),
+    /// First path segment, stripped before routing.
+    PathPrefix,
+}
+
+/// Id of the tenant a request belongs to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TenantId(pub String);
+
+/// Tenant of the current request, with its configuration.
+pub struct TenantContext<T> {
+    /// Tenant id.
+    pub id: String,
+    /// Configuration loaded by the [`TenantProvider`].
+    pub config: Arc<T>,
+}
+
+impl<T> Clone for TenantContext<T> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id.clone(),
+            config: Arc::clone(&self.config),
+        }
+    }
+}
+
+impl<T> Deref for TenantContext<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.config
+    }
+}
+
+#[async_trait]
+impl<T: Send + Sync + 'static, S: Send + Sync + 'static> FromRequest<S> for TenantContext<T> {
+    async fn from_request(req: &mut Req, _state: &Arc<S>) -> Result<Self> {
+        req.extensions()
+            .get::<TenantContext<T>>()
+            .cloned()
+            .ok_or_else(|| Error::internal("Tenancy middleware not attached"))
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync + 'static> FromRequest<S> for TenantId {
+    async fn from_request(req: &mut Req, _state: &Arc<S>) -> Result<Self> {
+        req.extensions()
+            .get::<TenantId>()
+            .cloned()
+            .ok_or_else(|| Error::internal("Tenancy middleware not attached"))
+    }
+}
+
+/// Configuration and when it was loaded.
+type Cached<T> = (Instant, Arc<T>);
+
+/// Pre-routing middleware resolving the tenant of each request.
+pub struct Tenancy<P: TenantProvider> {
+    provider: P,
+    sources: Vec<Source>,
+    optional: bool,
+    cache_ttl: Option<Duration>,
+    cache: RwLock<HashMap<String, Cached<P::Config>>>,
+}
+
+impl<P: TenantProvider> Tenancy<P> {
+    /// Load tenants from `provider`. Add at least one source; they are
+    /// tried in the order added.
+    pub fn new(provider: P) -> Self {
+        Self {
+            provider,
+            sources: Vec::new(),
+            optional: false,
+            cache_ttl: None,
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Read the tenant from the subdomain of `base`, so `acme.example.com`
+    /// is tenant `acme` for base `example.com`.
+    pub fn subdomain(mut self, base: &str) -> Self {
+        let base = base.trim_start_matches('.').to_ascii_lowercase();
+        self.sources.push(Source::Subdomain(base));
+        self
+    }
+
+    /// Read the tenant from header `name`.
+    pub fn header(mut self, name: &str) -> Self {
+        self.sources.push(Source::Header(name.to_string()));
+        self
+    }
+
+    /// Read the tenant from the first path segment, so `/acme/users` is
+    /// routed as `/users` for tenant `acme`.
+    pub fn path_prefix(mut self) -> Self {
+        self.sources.push(Source::PathPrefix);
+        self
+    }
+
+    /// Let requests naming no tenant through without a context.
+    pub fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+
+    /// Keep loaded configurations for `ttl` instead of asking the provider
+    /// on every request.
+    pub fn cache_for(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Tenant id named by `req`, stripping it from the path if needed.
+    fn resolve(&self, req: &mut Req) -> Option<String> {
+        self.sources.iter().find_map(|source| match source {
+            Source::Subdomain(base) => {
+                let host = req.host()?.to_ascii_lowercase();
+                let host = host.rsplit_once(':').map_or(host.as_str(), |(h, port)| {
+                    if port.bytes().all(|b| b.is_ascii_digit()) {
+                        h
+                    } else {
+                        host.as_str()
+                    }
+                });
+                let label = host.strip_suffix(base.as_str())?.strip_suffix('.')?;
+                (!label.is_empty() && !label.contains('.')).then(|| label.to_string())
+            }
+            Source::Header(name) => req
+                .header(name)
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .map(str::to_string),
+            Source::PathPrefix => strip_path_tenant(req),
+        })
+    }
+
+    /// Configuration of tenant `id`, from the cache or the provider.
+    async fn config(&self, id: &str) -> Result<Option<Arc<P::Config>>> {
+        if let Some(ttl) = self.cache_ttl {
+            let cache = self.cache.read().unwrap();
+            if let Some((loaded, config)) = cache.get(id) {
+                if loaded.elapsed() < ttl {
+                    return Ok(Some(Arc::clone(config)));
+                }
+            }
+        }
+        let Some(config) = self.provider.load(id).await? else {
+            return Ok(None);
+        };
+        if self.cache_ttl.is_some() {
+            self.cache
+                .write()
+                .unwrap()
+                .insert(id.to_string(), (Instant::now(), Arc::clone(&config)));
+        }
+        Ok(Some(config))
+    }
+}
+
+/// Strip the first path segment, returning it.
+fn strip_path_tenant(req: &mut Req) -> Option<String> {
+    let rest = req.path().strip_prefix('/')?;
+    let end = rest.find('/').unwrap_or(rest.len());
+    if end == 0 {
+        return None;
+    }
+    let tenant = rest[..end].to_string();
+    let remaining = if end == rest.len() { "/" } else { &rest[end..] };
+    let path_and_query = match req.query() {
+        Some(query) => format!("{}?{}", remaining, query),
+        None => remaining.to_string(),
+    };
+    let mut parts = req.uri().clone().into_parts();
+    parts.path_and_query = Some(path_and_query.parse().ok()?);
+    req.set_uri(hyper::Uri::from_parts(parts).ok()?);
+    Some(tenant)
+}
+
+#[async_trait]
+impl<P: TenantProvider, S: Send + Sync + 'static> Middleware<S> for Tenancy<P> {
+    async fn handle(&self, mut req: Req, _state: Arc<S>, next: Next<S>) -> Res {
+        let Some(id) = self.resolve(&mut req) else {
+            if self.optional {
+                return next.run(req).await;
+            }
+            return Error::bad_request("Tenant not specified").into_res();
+        };
+        match self.config(&id).await {
+            Ok(Some(config)) => {
+                req.extensions_mut().insert(TenantContext {
+                    id: id.clone(),
+                    config,
+                });
+                req.extensions_mut().insert(TenantId(id));
+                next.run(req).await
+            }
+            Ok(None) => Error::not_found(format!("Unknown tenant {}", id)).into_res(),
+            Err(e) => e.into_res(),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "Tenancy"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RustApi;
+    use crate::test::{TestClient, assert_status, body_text};
+
+    #[tokio::test]
+    async fn test_tenancy_sources() {
+        let tenants = StaticTenants::new()
+            .tenant("acme", "pro")
+            .tenant("globex", "free");
+        let mut app = RustApi::new();
+        app.attach_pre_routing(
+            Tenancy::new(tenants)
+                .subdomain("example.com")
+                .header("x-tenant")
+                .path_prefix(),
+        );
+        app.get("/plan", |tenant: TenantContext<&'static str>| async move {
+            format!("{}:{}", tenant.id, tenant.config)
+        });
+        let client = TestClient::new(app);
+
+        let res = client
+            .get("/plan")
+            .header("host", "acme.example.com:8080")
+            .send()
+            .await;
+        assert_eq!(body_text(res).await, "acme:pro");
+        let res = client
+            .get("/plan")
+            .header("x-tenant", "globex")
+            .send()
+            .await;
+        assert_eq!(body_text(res).await, "globex:free");
+        let res = client.get("/acme/plan").send().await;
+        assert_eq!(body_text(res).await, "acme:pro");
+
+        assert_status(&client.get("/initech/plan").send().await, 404);
+        let res = client.get("/").header("host", "example.com").send().await;
+        assert_status(&res, 400);
+    }
+}
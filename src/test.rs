@@ -0,0 +1,385 @@
+//! In-process testing.
+//!
+//! [`TestClient`] sends requests straight into the app's pipeline —
+//! pre-routing middleware, routing, middleware and handlers — without
+//! opening a socket. The assertion and body helpers below keep handler
+//! tests short.
+//!
+//! ```rust
+//! use rust_api::{Req, Res, RustApi};
+//! use rust_api::test::{TestClient, assert_header, assert_json_eq, assert_status};
+//! use serde_json::json;
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! let mut app = RustApi::new();
+//! app.get("/ping", |_req: Req| async { Res::json(&json!({"pong": true})) });
+//!
+//! let client = TestClient::new(app);
+//! let res = client.get("/ping").send().await;
+//! assert_status(&res, 200);
+//! assert_header(&res, "content-type", "application/json");
+//! assert_json_eq(res, json!({"pong": true})).await;
+//! # }
+//! ```
+
+use bytes::Bytes;
+use http_body_util::BodyExt;
+use hyper::header::{self, HeaderMap, HeaderName, HeaderValue};
+use hyper::{Method, Uri};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::dev::Recording;
+use crate::{Error, Extensions, IntoRes, Req, Res, Result, RustApi};
+
+type Inject = Arc<dyn Fn(&mut Extensions) + Send + Sync>;
+
+/// Client dispatching requests to an app in-process.
+pub struct TestClient<S = ()> {
+    app: Arc<RustApi<S>>,
+    extensions: Vec<Inject>,
+}
+
+impl<S: Send + Sync + 'static> TestClient<S> {
+    /// Wrap an app; its routes are finalized as if it were listening.
+    pub fn new(app: RustApi<S>) -> Self {
+        Self {
+            app: app.into_in_process(),
+            extensions: Vec::new(),
+        }
+    }
+
+    /// Wrap an app, replacing its state with `state`.
+    ///
+    /// Lets handlers that use `State<S>` run against mocks instead of real
+    /// pools or clients.
+    ///
+    /// ```rust
+    /// use rust_api::{RustApi, State, test::{TestClient, body_text}};
+    ///
+    /// #[derive(Clone)]
+    /// struct AppState {
+    ///     greeting: String,
+    /// }
+    ///
+    /// fn routes(app: &mut RustApi<AppState>) {
+    ///     app.get("/", |State(state): State<AppState>| async move { state.greeting });
+    /// }
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut app = RustApi::with_state(AppState { greeting: "hello".into() });
+    /// routes(&mut app);
+    ///
+    /// let client = TestClient::with_state(app, AppState { greeting: "mocked".into() });
+    /// assert_eq!(body_text(client.get("/").send().await).await, "mocked");
+    /// # }
+    /// ```
+    pub fn with_state(mut app: RustApi<S>, state: S) -> Self {
+        app.set_state(state);
+        Self::new(app)
+    }
+
+    /// Insert an extension into every request before it enters the app.
+    ///
+    /// Values already set on the request take precedence; middleware that
+    /// inserts a value of the same type replaces it.
+    pub fn extension<T: Clone + Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.extensions
+            .push(Arc::new(move |extensions: &mut Extensions| {
+                if !extensions.contains::<T>() {
+                    extensions.insert(value.clone());
+                }
+            }));
+        self
+    }
+
+    /// Start a request.
+    pub fn request(&self, method: Method, uri: &str) -> TestRequest<'_, S> {
+        TestRequest {
+            client: self,
+            method,
+            uri: uri.to_string(),
+            headers: HeaderMap::new(),
+            body: Bytes::new(),
+            extensions: Extensions::new(),
+        }
+    }
+
+    /// Start a GET request.
+    pub fn get(&self, uri: &str) -> TestRequest<'_, S> {
+        self.request(Method::GET, uri)
+    }
+
+    /// Start a POST request.
+    pub fn post(&self, uri: &str) -> TestRequest<'_, S> {
+        self.request(Method::POST, uri)
+    }
+
+    /// Start a PUT request.
+    pub fn put(&self, uri: &str) -> TestRequest<'_, S> {
+        self.request(Method::PUT, uri)
+    }
+
+    /// Start a DELETE request.
+    pub fn delete(&self, uri: &str) -> TestRequest<'_, S> {
+        self.request(Method::DELETE, uri)
+    }
+
+    /// Start a PATCH request.
+    pub fn patch(&self, uri: &str) -> TestRequest<'_, S> {
+        self.request(Method::PATCH, uri)
+    }
+
+    /// Re-send a request captured by [`RequestRecorder`](crate::dev::RequestRecorder).
+    pub async fn replay(&self, path: impl AsRef<Path>) -> Result<Res> {
+        let recording = Recording::load(path).await?;
+        let method = Method::from_bytes(recording.method.as_bytes())
+            .map_err(|_| Error::bad_request("Invalid method in recording"))?;
+
+        let mut request = self.request(method, &recording.uri);
+        for (name, value) in &recording.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                request.headers.append(name, value);
+            }
+        }
+        request.body = recording.body_bytes()?;
+        Ok(request.send().await)
+    }
+
+    /// Dispatch a request built with [`Req::builder`].
+    pub async fn send_req(&self, mut req: Req) -> Res {
+        for inject in &self.extensions {
+            inject(req.extensions_mut());
+        }
+        self.app.handle_in_process(req).await
+    }
+}
+
+/// Request being built by a [`TestClient`].
+pub struct TestRequest<'a, S> {
+    client: &'a TestClient<S>,
+    method: Method,
+    uri: String,
+    headers: HeaderMap,
+    body: Bytes,
+    extensions: Extensions,
+}
+
+impl<S: Send + Sync + 'static> TestRequest<'_, S> {
+    /// Add a header.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name or value is invalid.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        let name = HeaderName::from_bytes(name.as_bytes()).expect("invalid header name");
+        let value = HeaderValue::from_str(value).expect("invalid header value");
+        self.headers.append(name, value);
+        self
+    }
+
+    /// Set a raw body.
+    pub fn body(mut self, body: impl Into<Bytes>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Set a JSON body and `Content-Type`.
+    pub fn json<T: Serialize>(mut self, value: &T) -> Self {
+        self.body = serde_json::to_vec(value)
+            .expect("JSON serialization failed")
+            .into();
+        self.headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        self
+    }
+
+    /// Insert an extension into this request, overriding the client's.
+    ///
+    /// Middleware that inserts a value of the same type replaces it.
+    pub fn extension<T: Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.extensions.insert(value);
+        self
+    }
+
+    /// Send the request through the app.
+    pub async fn send(self) -> Res {
+        if let Err(e) = self.uri.parse::<Uri>() {
+            return Error::bad_request(format!("Invalid URI: {}", e)).into_res();
+        }
+        let req = Req::builder()
+            .method(self.method)
+            .uri(&self.uri)
+            .headers(self.headers)
+            .body(self.body)
+            .extensions(self.extensions)
+            .build();
+        self.client.send_req(req).await
+    }
+}
+
+/// Read the whole response body.
+///
+/// # Panics
+///
+/// Panics if the body fails mid-stream.
+pub async fn body_bytes(res: Res) -> Bytes {
+    res.into_hyper()
+        .into_body()
+        .collect()
+        .await
+        .expect("failed to read response body")
+        .to_bytes()
+}
+
+/// Read the response body as text (lossy UTF-8).
+pub async fn body_text(res: Res) -> String {
+    String::from_utf8_lossy(&body_bytes(res).await).into_owned()
+}
+
+/// Deserialize the response body as JSON.
+///
+/// # Panics
+///
+/// Panics if the body is not valid JSON for `T`.
+pub async fn body_json<T: DeserializeOwned>(res: Res) -> T {
+    let bytes = body_bytes(res).await;
+    serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+        panic!(
+            "response body is not the expected JSON ({}): {}",
+            e,
+            String::from_utf8_lossy(&bytes)
+        )
+    })
+}
+
+/// Assert the response status code.
+#[track_caller]
+pub fn assert_status(res: &Res, expected: u16) {
+    let status = res.status_code();
+    assert_eq!(
+        status.as_u16(),
+        expected,
+        "unexpected response status {}",
+        status
+    );
+}
+
+/// Assert a response header's value.
+#[track_caller]
+pub fn assert_header(res: &Res, name: &str, expected: &str) {
+    let actual = res
+        .headers()
+        .get(name)
+        .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned());
+    assert_eq!(
+        actual.as_deref(),
+        Some(expected),
+        "unexpected `{}` header",
+        name
+    );
+}
+
+/// Assert the response body equals `expected` as JSON.
+pub async fn assert_json_eq(res: Res, expected: Value) {
+    let actual: Value = body_json(res).await;
+    if actual != expected {
+        panic!(
+            "JSON body mismatch\n  actual: {}\nexpected: {}",
+            pretty(&actual),
+            pretty(&expected)
+        );
+    }
+}
+
+/// Render a response as stable text for snapshot tests.
+///
+/// Status line, headers sorted by name (without `date`), a blank line and
+/// the body; JSON bodies are pretty-printed.
+pub async fn snapshot(res: Res) -> String {
+    let status = res.status_code();
+    let mut headers: Vec<(String, String)> = res
+        .headers()
+        .iter()
+        .filter(|(name, _)| *name != header::DATE)
+        .map(|(name, value)| {
+            (
+                name.to_string(),
+                String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            )
+        })
+        .collect();
+    headers.sort();
+
+    let bytes = body_bytes(res).await;
+    let body = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(json) => pretty(&json),
+        Err(_) => String::from_utf8_lossy(&bytes).into_owned(),
+    };
+
+    let mut out = format!("{}\n", status);
+    for (name, value) in headers {
+        out.push_str(&format!("{}: {}\n", name, value));
+    }
+    out.push('\n');
+    out.push_str(&body);
+    out
+}
+
+fn pretty(value: &Value) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_snapshot() {
+        let res = Res::builder()
+            .status(201)
+            .header("x-b", "2")
+            .header("date", "Thu, 01 Jan 2026 00:00:00 GMT")
+            .json(&json!({"id": 1}));
+        assert_eq!(
+            snapshot(res).await,
+            "201 Created\ncontent-type: application/json\nx-b: 2\n\n{\n  \"id\": 1\n}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_extension_injection() {
+        #[derive(Clone)]
+        struct Tenant(&'static str);
+
+        let mut app = RustApi::new();
+        app.get("/", |req: Req| async move {
+            req.extensions()
+                .get::<Tenant>()
+                .map(|tenant| tenant.0)
+                .unwrap_or("none")
+        });
+
+        let client = TestClient::new(app).extension(Tenant("default"));
+        assert_eq!(body_text(client.get("/").send().await).await, "default");
+        let res = client.get("/").extension(Tenant("override")).send().await;
+        assert_eq!(body_text(res).await, "override");
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "JSON body mismatch")]
+    async fn test_assert_json_eq_mismatch() {
+        assert_json_eq(Res::json(&json!({"a": 1})), json!({"a": 2})).await;
+    }
+}
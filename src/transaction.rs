@@ -0,0 +1,122 @@
+//! Per-request transactions.
+//!
+//! [`Transactional`] begins a transaction before the handler runs, commits
+//! it when the response is 2xx/3xx and rolls it back otherwise. Handlers
+//! reach the open transaction through the [`RequestTransaction`] extractor.
+//!
+//! ```rust,ignore
+//! use rust_api::RustApi;
+//! use rust_api::transaction::{RequestTransaction, Transactional};
+//!
+//! async fn create(tx: RequestTransaction<MyTx>) -> rust_api::Result<&'static str> {
+//!     let mut tx = tx.lock().await?;
+//!     tx.insert("ada").await?;
+//!     Ok("created")
+//! }
+//!
+//! let mut app = RustApi::new();
+//! app.attach(Transactional::new(MyProvider::new()));
+//! app.post("/users", create);
+//! ```
+
+use async_trait::async_trait;
+use std::sync::Arc;
+use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
+
+use crate::{Error, FromRequest, IntoRes, Middleware, Next, Req, Res, Result};
+
+/// Source of transactions for [`Transactional`].
+#[async_trait]
+pub trait TransactionProvider: Send + Sync + 'static {
+    /// Open transaction type.
+    type Tx: Send + 'static;
+
+    /// Begin a transaction.
+    async fn begin(&self) -> Result<Self::Tx>;
+
+    /// Commit a transaction.
+    async fn commit(&self, tx: Self::Tx) -> Result<()>;
+
+    /// Roll a transaction back.
+    async fn rollback(&self, tx: Self::Tx) -> Result<()>;
+}
+
+/// Middleware wrapping each request in a transaction.
+pub struct Transactional<P> {
+    provider: Arc<P>,
+}
+
+impl<P: TransactionProvider> Transactional<P> {
+    /// Create middleware using `provider`.
+    pub fn new(provider: P) -> Self {
+        Self {
+            provider: Arc::new(provider),
+        }
+    }
+}
+
+#[async_trait]
+impl<P, S> Middleware<S> for Transactional<P>
+where
+    P: TransactionProvider,
+    S: Send + Sync + 'static,
+{
+    async fn handle(&self, mut req: Req, _state: Arc<S>, next: Next<S>) -> Res {
+        let tx = match self.provider.begin().await {
+            Ok(tx) => tx,
+            Err(e) => return e.into_res(),
+        };
+        let slot = Arc::new(Mutex::new(Some(tx)));
+        req.extensions_mut()
+            .insert(RequestTransaction(Arc::clone(&slot)));
+
+        let res = next.run(req).await;
+
+        let Some(tx) = slot.lock().await.take() else {
+            return res;
+        };
+        let status = res.status_code();
+        if status.is_success() || status.is_redirection() {
+            if let Err(e) = self.provider.commit(tx).await {
+                log::error!("transaction commit failed: {}", e);
+                return e.into_res();
+            }
+        } else if let Err(e) = self.provider.rollback(tx).await {
+            log::error!("transaction rollback failed: {}", e);
+        }
+        res
+    }
+
+    fn name(&self) -> &'static str {
+        "Transactional"
+    }
+}
+
+/// Handle to the transaction opened by [`Transactional`].
+pub struct RequestTransaction<T>(Arc<Mutex<Option<T>>>);
+
+impl<T> Clone for RequestTransaction<T> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<T: Send + 'static> RequestTransaction<T> {
+    /// Lock the transaction for use.
+    ///
+    /// Fails once the response has been produced and the transaction finished.
+    pub async fn lock(&self) -> Result<MappedMutexGuard<'_, T>> {
+        MutexGuard::try_map(self.0.lock().await, Option::as_mut)
+            .map_err(|_| Error::internal("Transaction already finished"))
+    }
+}
+
+#[async_trait]
+impl<T: Send + 'static, S: Send + Sync + 'static> FromRequest<S> for RequestTransaction<T> {
+    async fn from_request(req: &mut Req, _state: &Arc<S>) -> Result<Self> {
+        req.extensions()
+            .get::<RequestTransaction<T>>()
+            .cloned()
+            .ok_or_else(|| Error::internal("Transactional middleware not attached"))
+    }
+}
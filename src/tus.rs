@@ -0,0 +1,703 @@
+//! Resumable uploads over the [tus](https://tus.io/protocols/resumable-upload)
+//! protocol.
+//!
+//! [`Tus`] implements tus 1.0.0 with the `creation`,
+//! `creation-with-upload`, `expiration` and `termination` extensions. A
+//! client creates an upload with `POST {prefix}`, sends bytes with
+//! `PATCH {prefix}/{id}` and, after a dropped connection, asks
+//! `HEAD {prefix}/{id}` how much arrived before continuing from there.
+//! Bytes received before an interruption are kept.
+//!
+//! ```rust,no_run
+//! use rust_api::{RustApi, tus::{DiskStore, Tus}};
+//! use std::time::Duration;
+//!
+//! let store = DiskStore::new("/var/uploads/tus");
+//! let files = store.clone();
+//! let tus = Tus::new(store)
+//!     .max_size(2 * 1024 * 1024 * 1024)
+//!     .expire_after(Duration::from_secs(24 * 60 * 60))
+//!     .on_complete(move |upload| {
+//!         let name = upload.metadata("filename").unwrap_or_default();
+//!         log::info!("{} finished at {}", name, files.path(&upload.id).display());
+//!     });
+//!
+//! let mut app = RustApi::new();
+//! tus.install(&mut app, "/files");
+//! ```
+//!
+//! Browser clients on another origin also need CORS exposing `Location`,
+//! `Upload-Offset`, `Upload-Length` and `Tus-Resumable`.
+
+use async_trait::async_trait;
+use hyper::Method;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+
+use crate::req::body_io_error;
+use crate::{Error, IntoRes, Req, Res, Result, Route, RustApi};
+
+/// Protocol version spoken by [`Tus`].
+pub const TUS_VERSION: &str = "1.0.0";
+
+const EXTENSIONS: &str = "creation,creation-with-upload,expiration,termination";
+const CHUNK_CONTENT_TYPE: &str = "application/offset+octet-stream";
+
+/// State of one upload.
+#[derive(Debug, Clone)]
+pub struct UploadInfo {
+    /// Upload id, the last segment of its URL.
+    pub id: String,
+    /// Total size announced at creation.
+    pub length: u64,
+    /// Bytes received so far.
+    pub offset: u64,
+    /// Raw `Upload-Metadata` sent at creation.
+    pub metadata: Option<String>,
+    /// Time of creation or the last received bytes.
+    pub modified: SystemTime,
+}
+
+impl UploadInfo {
+    /// Whether every byte has arrived.
+    pub fn is_complete(&self) -> bool {
+        self.offset == self.length
+    }
+
+    /// Decoded `Upload-Metadata` value for `key`.
+    ///
+    /// Keys sent without a value yield an empty string; values that are
+    /// not valid base64 UTF-8 yield `None`.
+    pub fn metadata(&self, key: &str) -> Option<String> {
+        self.metadata.as_deref()?.split(',').find_map(|pair| {
+            let mut parts = pair.trim().splitn(2, ' ');
+            if parts.next()? != key {
+                return None;
+            }
+            let value = decode_base64(parts.next().unwrap_or("").trim())?;
+            String::from_utf8(value).ok()
+        })
+    }
+}
+
+/// Storage for upload bytes and their state.
+#[async_trait]
+pub trait TusStore: Send + Sync + 'static {
+    /// Create an empty upload.
+    async fn create(&self, info: &UploadInfo) -> Result<()>;
+
+    /// Look up an upload, `None` if it does not exist.
+    async fn info(&self, id: &str) -> Result<Option<UploadInfo>>;
+
+    /// Append everything read from `body` to an upload currently holding
+    /// `offset` bytes, returning the new offset.
+    ///
+    /// Bytes written before `body` fails must stay stored, so the client
+    /// can resume after them.
+    async fn append(
+        &self,
+        id: &str,
+        offset: u64,
+        body: &mut (dyn AsyncRead + Send + Unpin),
+    ) -> Result<u64>;
+
+    /// Delete an upload. Deleting a missing upload is not an error.
+    async fn delete(&self, id: &str) -> Result<()>;
+
+    /// Ids of all stored uploads.
+    async fn list(&self) -> Result<Vec<String>>;
+}
+
+/// [`TusStore`] keeping each upload as a file plus a `.info` sidecar in a
+/// directory.
+#[derive(Debug, Clone)]
+pub struct DiskStore {
+    dir: PathBuf,
+}
+
+/// Sidecar contents; the offset and modification time come from the file.
+#[derive(Serialize, Deserialize)]
+struct Sidecar {
+    length: u64,
+    metadata: Option<String>,
+}
+
+impl DiskStore {
+    /// Store uploads in `dir`, created on first use.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// File holding the bytes of upload `id`.
+    pub fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(id)
+    }
+
+    fn sidecar(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.info", id))
+    }
+}
+
+#[async_trait]
+impl TusStore for DiskStore {
+    async fn create(&self, info: &UploadInfo) -> Result<()> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let sidecar = Sidecar {
+            length: info.length,
+            metadata: info.metadata.clone(),
+        };
+        let json = serde_json::to_vec(&sidecar).map_err(|e| Error::Json(e.to_string()))?;
+        tokio::fs::File::create_new(self.path(&info.id)).await?;
+        tokio::fs::write(self.sidecar(&info.id), json).await?;
+        Ok(())
+    }
+
+    async fn info(&self, id: &str) -> Result<Option<UploadInfo>> {
+        let Ok(json) = tokio::fs::read(self.sidecar(id)).await else {
+            return Ok(None);
+        };
+        let sidecar: Sidecar =
+            serde_json::from_slice(&json).map_err(|e| Error::Json(e.to_string()))?;
+        let Ok(file) = tokio::fs::metadata(self.path(id)).await else {
+            return Ok(None);
+        };
+        Ok(Some(UploadInfo {
+            id: id.to_string(),
+            length: sidecar.length,
+            offset: file.len(),
+            metadata: sidecar.metadata,
+            modified: file.modified()?,
+        }))
+    }
+
+    async fn append(
+        &self,
+        id: &str,
+        offset: u64,
+        body: &mut (dyn AsyncRead + Send + Unpin),
+    ) -> Result<u64> {
+        let mut file = tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(self.path(id))
+            .await?;
+        if file.metadata().await?.len() != offset {
+            return Err(Error::Status(409, Some("Upload-Offset mismatch".into())));
+        }
+        let mut written = offset;
+        let mut buf = vec![0; 64 * 1024];
+        let read = loop {
+            match body.read(&mut buf).await {
+                Ok(0) => break Ok(()),
+                Ok(n) => {
+                    file.write_all(&buf[..n]).await?;
+                    written += n as u64;
+                }
+                Err(e) => break Err(e),
+            }
+        };
+        // Keep what arrived even when the client went away.
+        file.flush().await?;
+        file.sync_data().await?;
+        read?;
+        Ok(written)
+    }
+
+    async fn delete(&self, id: &str) -> Result<()> {
+        for path in [self.path(id), self.sidecar(id)] {
+            match tokio::fs::remove_file(path).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<String>> {
+        let mut ids = Vec::new();
+        let Ok(mut read) = tokio::fs::read_dir(&self.dir).await else {
+            return Ok(ids);
+        };
+        while let Some(entry) = read.next_entry().await? {
+            if let Some(id) = entry
+                .file_name()
+                .to_str()
+                .and_then(|n| n.strip_suffix(".info"))
+            {
+                ids.push(id.to_string());
+            }
+        }
+        Ok(ids)
+    }
+}
+
+type CompleteHook = Arc<dyn Fn(&UploadInfo) + Send + Sync>;
+
+/// tus upload service.
+///
+/// Clones share the same store, hooks and upload locks.
+#[derive(Clone)]
+pub struct Tus {
+    store: Arc<dyn TusStore>,
+    max_size: Option<u64>,
+    expire_after: Option<Duration>,
+    on_complete: Option<CompleteHook>,
+    busy: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Tus {
+    /// Serve uploads kept in `store`, without size limit or expiration.
+    pub fn new(store: impl TusStore) -> Self {
+        Self {
+            store: Arc::new(store),
+            max_size: None,
+            expire_after: None,
+            on_complete: None,
+            busy: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Reject uploads larger than `bytes` with 413 (sent as `Tus-Max-Size`).
+    ///
+    /// Chunks are then only bounded by this size, not the app body limit.
+    pub fn max_size(mut self, bytes: u64) -> Self {
+        self.max_size = Some(bytes);
+        self
+    }
+
+    /// Expire incomplete uploads `after` their last received bytes.
+    ///
+    /// Responses carry `Upload-Expires`; expired uploads answer 404 and
+    /// are deleted on access or by [`purge_expired`](Self::purge_expired).
+    pub fn expire_after(mut self, after: Duration) -> Self {
+        self.expire_after = Some(after);
+        self
+    }
+
+    /// Call `hook` once an upload has received all its bytes.
+    ///
+    /// Runs on the request task; spawn slow work.
+    pub fn on_complete(mut self, hook: impl Fn(&UploadInfo) + Send + Sync + 'static) -> Self {
+        self.on_complete = Some(Arc::new(hook));
+        self
+    }
+
+    /// Register `OPTIONS` and `POST {prefix}` plus `HEAD`, `PATCH` and
+    /// `DELETE {prefix}/{id}`.
+    pub fn install<S: Send + Sync + 'static>(&self, app: &mut RustApi<S>, prefix: &str) {
+        let prefix = prefix.trim_end_matches('/').to_string();
+        let upload = format!("{}/{{id}}", prefix);
+
+        app.route(Route::new(Method::OPTIONS, prefix.clone(), {
+            let tus = self.clone();
+            move |_req: Req| {
+                let tus = tus.clone();
+                async move { tus.capabilities() }
+            }
+        }));
+        app.post(&prefix, {
+            let tus = self.clone();
+            let prefix = prefix.clone();
+            move |req: Req| {
+                let (tus, prefix) = (tus.clone(), prefix.clone());
+                async move { respond(tus.create(req, &prefix).await) }
+            }
+        });
+        app.route(Route::new(Method::HEAD, upload.clone(), {
+            let tus = self.clone();
+            move |req: Req| {
+                let tus = tus.clone();
+                async move { respond(tus.head(&req).await) }
+            }
+        }));
+        let patch = app.patch(&upload, {
+            let tus = self.clone();
+            move |req: Req| {
+                let tus = tus.clone();
+                async move { respond(tus.patch(req).await) }
+            }
+        });
+        if let Some(max) = self.max_size {
+            patch.body_limit(usize::try_from(max).unwrap_or(usize::MAX));
+        }
+        app.delete(&upload, {
+            let tus = self.clone();
+            move |req: Req| {
+                let tus = tus.clone();
+                async move { respond(tus.terminate(&req).await) }
+            }
+        });
+    }
+
+    /// Delete expired incomplete uploads, returning how many were removed.
+    ///
+    /// Run it periodically; without [`expire_after`](Self::expire_after)
+    /// nothing expires.
+    pub async fn purge_expired(&self) -> Result<usize> {
+        let mut purged = 0;
+        for id in self.store.list().await? {
+            let Some(info) = self.store.info(&id).await? else {
+                continue;
+            };
+            if self.expired(&info) {
+                self.store.delete(&id).await?;
+                purged += 1;
+            }
+        }
+        Ok(purged)
+    }
+
+    fn capabilities(&self) -> Res {
+        let res = Res::status(204)
+            .header("tus-resumable", TUS_VERSION)
+            .header("tus-version", TUS_VERSION)
+            .header("tus-extension", EXTENSIONS);
+        match self.max_size {
+            Some(max) => res.header("tus-max-size", max.to_string()),
+            None => res,
+        }
+    }
+
+    async fn create(&self, mut req: Req, prefix: &str) -> Result<Res> {
+        check_version(&req)?;
+        let length = header_u64(&req, "upload-length")?
+            .ok_or_else(|| Error::bad_request("Missing Upload-Length"))?;
+        if self.max_size.is_some_and(|max| length > max) {
+            return Err(Error::payload_too_large(
+                "Upload-Length exceeds Tus-Max-Size",
+            ));
+        }
+        let mut info = UploadInfo {
+            id: uuid::Uuid::new_v4().simple().to_string(),
+            length,
+            offset: 0,
+            metadata: req.header("upload-metadata").map(str::to_string),
+            modified: SystemTime::now(),
+        };
+        self.store.create(&info).await?;
+
+        let mut res = Res::status(201).header("location", format!("{}/{}", prefix, info.id));
+        if req.header("content-type") == Some(CHUNK_CONTENT_TYPE) {
+            let _lock = self.lock(&info.id)?;
+            info = self.append(&mut req, info).await?;
+            res = res.header("upload-offset", info.offset.to_string());
+        } else if info.is_complete() {
+            self.complete(&info);
+        }
+        Ok(self.expires(res, &info))
+    }
+
+    async fn head(&self, req: &Req) -> Result<Res> {
+        check_version(req)?;
+        let info = self.find(req).await?;
+        let res = Res::status(200)
+            .header("upload-offset", info.offset.to_string())
+            .header("upload-length", info.length.to_string())
+            .no_store();
+        let res = match &info.metadata {
+            Some(metadata) => res.header("upload-metadata", metadata),
+            None => res,
+        };
+        Ok(self.expires(res, &info))
+    }
+
+    async fn patch(&self, mut req: Req) -> Result<Res> {
+        check_version(&req)?;
+        if req.header("content-type") != Some(CHUNK_CONTENT_TYPE) {
+            return Err(Error::Status(
+                415,
+                Some(format!("Content-Type must be {}", CHUNK_CONTENT_TYPE)),
+            ));
+        }
+        let offset = header_u64(&req, "upload-offset")?
+            .ok_or_else(|| Error::bad_request("Missing Upload-Offset"))?;
+        let info = self.find(&req).await?;
+        let _lock = self.lock(&info.id)?;
+        // Re-read under the lock; a finished request may have moved it.
+        let info = self.find(&req).await?;
+        if offset != info.offset {
+            return Err(Error::Status(409, Some("Upload-Offset mismatch".into())));
+        }
+        let info = self.append(&mut req, info).await?;
+        let res = Res::status(204).header("upload-offset", info.offset.to_string());
+        Ok(self.expires(res, &info))
+    }
+
+    async fn terminate(&self, req: &Req) -> Result<Res> {
+        check_version(req)?;
+        let info = self.find(req).await?;
+        let _lock = self.lock(&info.id)?;
+        self.store.delete(&info.id).await?;
+        Ok(Res::status(204))
+    }
+
+    /// Append the request body, completing the upload if it is now full.
+    async fn append(&self, req: &mut Req, mut info: UploadInfo) -> Result<UploadInfo> {
+        let remaining = info.length - info.offset;
+        if header_u64(req, "content-length")?.is_some_and(|len| len > remaining) {
+            return Err(Error::payload_too_large("Chunk exceeds Upload-Length"));
+        }
+        let mut body = req.body_reader()?.take(remaining);
+        info.offset = self
+            .store
+            .append(&info.id, info.offset, &mut body)
+            .await
+            .map_err(|e| match e {
+                Error::Io(e) => body_io_error(e),
+                e => e,
+            })?;
+        info.modified = SystemTime::now();
+        if info.is_complete() {
+            self.complete(&info);
+        }
+        Ok(info)
+    }
+
+    /// Load the upload named in the path, deleting it if expired.
+    async fn find(&self, req: &Req) -> Result<UploadInfo> {
+        let not_found = || Error::not_found("Upload not found");
+        let id = req
+            .param("id")
+            .filter(|id| id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))
+            .ok_or_else(not_found)?;
+        let info = self.store.info(id).await?.ok_or_else(not_found)?;
+        if self.expired(&info) {
+            self.store.delete(id).await?;
+            return Err(not_found());
+        }
+        Ok(info)
+    }
+
+    fn complete(&self, info: &UploadInfo) {
+        if let Some(hook) = &self.on_complete {
+            hook(info);
+        }
+    }
+
+    fn expires_at(&self, info: &UploadInfo) -> Option<SystemTime> {
+        if info.is_complete() {
+            return None;
+        }
+        Some(info.modified + self.expire_after?)
+    }
+
+    fn expired(&self, info: &UploadInfo) -> bool {
+        self.expires_at(info)
+            .is_some_and(|at| at <= SystemTime::now())
+    }
+
+    fn expires(&self, res: Res, info: &UploadInfo) -> Res {
+        match self.expires_at(info) {
+            Some(at) => res.header("upload-expires", httpdate::fmt_http_date(at)),
+            None => res,
+        }
+    }
+
+    /// Claim an upload for one request at a time; 423 while another holds it.
+    fn lock(&self, id: &str) -> Result<UploadLock> {
+        if !self.busy.lock().unwrap().insert(id.to_string()) {
+            return Err(Error::Status(423, Some("Upload is busy".into())));
+        }
+        Ok(UploadLock {
+            busy: Arc::clone(&self.busy),
+            id: id.to_string(),
+        })
+    }
+}
+
+struct UploadLock {
+    busy: Arc<Mutex<HashSet<String>>>,
+    id: String,
+}
+
+impl Drop for UploadLock {
+    fn drop(&mut self) {
+        self.busy.lock().unwrap().remove(&self.id);
+    }
+}
+
+fn check_version(req: &Req) -> Result<()> {
+    if req.header("tus-resumable") == Some(TUS_VERSION) {
+        Ok(())
+    } else {
+        Err(Error::Status(412, Some("Unsupported Tus-Resumable".into())))
+    }
+}
+
+fn header_u64(req: &Req, name: &str) -> Result<Option<u64>> {
+    req.header(name)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| Error::bad_request(format!("Invalid {}", name)))
+        })
+        .transpose()
+}
+
+fn respond(result: Result<Res>) -> Res {
+    let res = result.into_res().header("tus-resumable", TUS_VERSION);
+    if res.status_code() == 412 {
+        return res.header("tus-version", TUS_VERSION);
+    }
+    res
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    let (mut acc, mut bits) = (0u32, 0);
+    for c in text.trim_end_matches('=').bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        acc = (acc << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{TestClient, assert_header, assert_status};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_metadata() {
+        let info = UploadInfo {
+            id: "x".into(),
+            length: 0,
+            offset: 0,
+            metadata: Some("filename d29ybGRfZG9taW5hdGlvbl9wbGFuLnBkZg==,is_confidential".into()),
+            modified: SystemTime::now(),
+        };
+        assert_eq!(
+            info.metadata("filename").as_deref(),
+            Some("world_domination_plan.pdf")
+        );
+        assert_eq!(info.metadata("is_confidential").as_deref(), Some(""));
+        assert_eq!(info.metadata("missing"), None);
+    }
+
+    #[tokio::test]
+    async fn test_resumable_upload() {
+        let dir = std::env::temp_dir().join(format!("rust-api-tus-{}", uuid::Uuid::new_v4()));
+        let store = DiskStore::new(&dir);
+        let completed = Arc::new(AtomicUsize::new(0));
+        let done = Arc::clone(&completed);
+        let tus = Tus::new(store.clone())
+            .max_size(10)
+            .expire_after(Duration::from_secs(60))
+            .on_complete(move |_| {
+                done.fetch_add(1, Ordering::SeqCst);
+            });
+        let mut app = RustApi::new();
+        tus.install(&mut app, "/files");
+        let client = TestClient::new(app);
+
+        let options = client.request(Method::OPTIONS, "/files").send().await;
+        assert_status(&options, 204);
+        assert_header(&options, "tus-max-size", "10");
+
+        assert_status(
+            &client
+                .post("/files")
+                .header("upload-length", "8")
+                .send()
+                .await,
+            412,
+        );
+        let tus_post = || client.post("/files").header("tus-resumable", TUS_VERSION);
+        assert_status(&tus_post().header("upload-length", "11").send().await, 413);
+
+        let created = tus_post()
+            .header("upload-length", "8")
+            .header("upload-metadata", "filename YS50eHQ=")
+            .send()
+            .await;
+        assert_status(&created, 201);
+        assert!(created.headers().contains_key("upload-expires"));
+        let location = created.headers()["location"].to_str().unwrap().to_string();
+
+        let patch = |offset: &str, body: &'static str| {
+            client
+                .patch(&location)
+                .header("tus-resumable", TUS_VERSION)
+                .header("content-type", CHUNK_CONTENT_TYPE)
+                .header("upload-offset", offset)
+                .body(body)
+                .send()
+        };
+        let first = patch("0", "abcd").await;
+        assert_status(&first, 204);
+        assert_header(&first, "upload-offset", "4");
+        assert_status(&patch("0", "abcd").await, 409);
+
+        let head = client
+            .request(Method::HEAD, &location)
+            .header("tus-resumable", TUS_VERSION)
+            .send()
+            .await;
+        assert_status(&head, 200);
+        assert_header(&head, "upload-offset", "4");
+        assert_header(&head, "upload-length", "8");
+        assert_header(&head, "upload-metadata", "filename YS50eHQ=");
+
+        assert_status(&patch("4", "efghij").await, 413);
+        let last = patch("4", "efgh").await;
+        assert_header(&last, "upload-offset", "8");
+        assert!(!last.headers().contains_key("upload-expires"));
+        assert_eq!(completed.load(Ordering::SeqCst), 1);
+        let id = location.rsplit('/').next().unwrap();
+        assert_eq!(std::fs::read(store.path(id)).unwrap(), b"abcdefgh");
+
+        let delete = client
+            .delete(&location)
+            .header("tus-resumable", TUS_VERSION)
+            .send()
+            .await;
+        assert_status(&delete, 204);
+        assert!(!store.path(id).exists());
+        assert_status(&patch("8", "").await, 404);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_expiration() {
+        let dir = std::env::temp_dir().join(format!("rust-api-tus-{}", uuid::Uuid::new_v4()));
+        let tus = Tus::new(DiskStore::new(&dir)).expire_after(Duration::ZERO);
+        let mut app = RustApi::new();
+        tus.install(&mut app, "/files");
+        let client = TestClient::new(app);
+
+        let created = client
+            .post("/files")
+            .header("tus-resumable", TUS_VERSION)
+            .header("upload-length", "4")
+            .send()
+            .await;
+        assert_status(&created, 201);
+        assert_eq!(tus.purge_expired().await.unwrap(), 1);
+        let location = created.headers()["location"].to_str().unwrap();
+        let head = client
+            .request(Method::HEAD, location)
+            .header("tus-resumable", TUS_VERSION)
+            .send()
+            .await;
+        assert_status(&head, 404);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
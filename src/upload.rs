@@ -0,0 +1,140 @@
+//! Uploads streamed to temporary files.
+//!
+//! ```rust,no_run
+//! use rust_api::{Res, Result, TempFileUpload};
+//!
+//! async fn upload(file: TempFileUpload) -> Result<Res> {
+//!     let size = file.len();
+//!     file.persist("/var/uploads/latest.bin").await?;
+//!     Ok(Res::text(format!("stored {} bytes", size)))
+//! }
+//! ```
+
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+
+use crate::extractors::FromRequest;
+use crate::{Req, Result};
+
+/// Request body streamed to a file in the system temp directory.
+///
+/// Memory use stays flat regardless of upload size; the app body limit
+/// (`set_body_limit`) caps the file size. The file is deleted when the
+/// value is dropped unless it is [persisted](TempFileUpload::persist).
+#[derive(Debug)]
+pub struct TempFileUpload {
+    path: PathBuf,
+    len: u64,
+    content_type: Option<String>,
+    keep: bool,
+}
+
+impl TempFileUpload {
+    /// Path of the temporary file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Size in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Check if the upload is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Content-Type sent with the upload.
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    /// Open the file for reading.
+    pub async fn open(&self) -> Result<File> {
+        Ok(File::open(&self.path).await?)
+    }
+
+    /// Move the file to a permanent location, keeping it after drop.
+    ///
+    /// Falls back to copy-and-delete when the destination is on another
+    /// filesystem.
+    pub async fn persist(mut self, dest: impl AsRef<Path>) -> Result<PathBuf> {
+        let dest = dest.as_ref().to_path_buf();
+        if tokio::fs::rename(&self.path, &dest).await.is_err() {
+            tokio::fs::copy(&self.path, &dest).await?;
+            tokio::fs::remove_file(&self.path).await.ok();
+        }
+        self.keep = true;
+        Ok(dest)
+    }
+}
+
+impl Drop for TempFileUpload {
+    fn drop(&mut self) {
+        if !self.keep {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequest<S> for TempFileUpload
+where
+    S: Send + Sync + 'static,
+{
+    async fn from_request(req: &mut Req, _state: &Arc<S>) -> Result<Self> {
+        let mut body = req.body_stream()?;
+
+        // Created before writing so partial files are removed on error.
+        let mut upload = TempFileUpload {
+            path: std::env::temp_dir().join(format!("rust-api-upload-{}", uuid::Uuid::new_v4())),
+            len: 0,
+            content_type: req.content_type().map(str::to_string),
+            keep: false,
+        };
+
+        let mut file = File::create(&upload.path).await?;
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk?;
+            file.write_all(&chunk).await?;
+            upload.len += chunk.len() as u64;
+        }
+        file.flush().await?;
+
+        Ok(upload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_upload(name: &str) -> TempFileUpload {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, b"data").unwrap();
+        TempFileUpload {
+            path,
+            len: 4,
+            content_type: None,
+            keep: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drop_and_persist() {
+        let upload = temp_upload(&format!("rust-api-test-{}", uuid::Uuid::new_v4()));
+        let path = upload.path().to_path_buf();
+        drop(upload);
+        assert!(!path.exists());
+
+        let upload = temp_upload(&format!("rust-api-test-{}", uuid::Uuid::new_v4()));
+        let dest = std::env::temp_dir().join(format!("rust-api-kept-{}", uuid::Uuid::new_v4()));
+        let kept = upload.persist(&dest).await.unwrap();
+        assert_eq!(std::fs::read(&kept).unwrap(), b"data");
+        std::fs::remove_file(kept).unwrap();
+    }
+}
@@ -0,0 +1,247 @@
+//! API versioning.
+//!
+//! Register versioned routes with `get_v`/`post_v`/... (or
+//! [`Route::version`](crate::Route::version)) and configure how a request's
+//! version is resolved with [`Versioning`]. Requests without a version get
+//! the default version, or the newest registered one when no default is set.
+//!
+//! ```rust
+//! use rust_api::{Req, Res, RustApi, versioning::Versioning};
+//! use std::time::{Duration, UNIX_EPOCH};
+//!
+//! let mut app = RustApi::new();
+//! app.set_versioning(
+//!     Versioning::new()
+//!         .from_path_prefix()
+//!         .from_accept()
+//!         .from_header("api-version")
+//!         .deprecate(1, UNIX_EPOCH + Duration::from_secs(1_735_689_600))
+//!         .sunset(1, UNIX_EPOCH + Duration::from_secs(1_767_225_600)),
+//! );
+//! app.get_v(1, "/users", |_req: Req| async { Res::text("v1") });
+//! app.get_v(2, "/users", |_req: Req| async { Res::text("v2") });
+//! ```
+
+use async_trait::async_trait;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{Error, FromRequest, Req, Result};
+
+/// Version resolved for the current request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ApiVersion(pub u32);
+
+#[async_trait]
+impl<S: Send + Sync + 'static> FromRequest<S> for ApiVersion {
+    async fn from_request(req: &mut Req, _state: &Arc<S>) -> Result<Self> {
+        req.extensions()
+            .get::<ApiVersion>()
+            .copied()
+            .ok_or_else(|| Error::bad_request("No API version requested"))
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Source {
+    PathPrefix,
+    Accept,
+    Header(HeaderName),
+}
+
+#[derive(Debug, Clone, Default)]
+struct Lifecycle {
+    deprecated: Option<SystemTime>,
+    sunset: Option<SystemTime>,
+}
+
+/// Version resolution and deprecation policy.
+#[derive(Debug, Clone, Default)]
+pub struct Versioning {
+    sources: Vec<Source>,
+    default: Option<u32>,
+    lifecycle: HashMap<u32, Lifecycle>,
+}
+
+impl Versioning {
+    /// Policy with no sources; add them in priority order.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read `/v2/...` prefixes, stripping them before routing.
+    pub fn from_path_prefix(mut self) -> Self {
+        self.sources.push(Source::PathPrefix);
+        self
+    }
+
+    /// Read `Accept: application/vnd.app.v2+json` or `application/json; version=2`.
+    pub fn from_accept(mut self) -> Self {
+        self.sources.push(Source::Accept);
+        self
+    }
+
+    /// Read a header such as `API-Version: 2`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is not a valid header name.
+    pub fn from_header(mut self, name: &str) -> Self {
+        let name = HeaderName::from_bytes(name.as_bytes()).expect("invalid header name");
+        self.sources.push(Source::Header(name));
+        self
+    }
+
+    /// Version used when the request names none.
+    pub fn default_version(mut self, version: u32) -> Self {
+        self.default = Some(version);
+        self
+    }
+
+    /// Mark `version` deprecated since `since` (`Deprecation` header).
+    pub fn deprecate(mut self, version: u32, since: SystemTime) -> Self {
+        self.lifecycle.entry(version).or_default().deprecated = Some(since);
+        self
+    }
+
+    /// Announce removal of `version` at `at` (`Sunset` header).
+    pub fn sunset(mut self, version: u32, at: SystemTime) -> Self {
+        self.lifecycle.entry(version).or_default().sunset = Some(at);
+        self
+    }
+
+    /// Resolve the request's version, stripping a path prefix if present.
+    pub(crate) fn resolve(&self, req: &mut Req) -> Option<u32> {
+        let mut version = None;
+        for source in &self.sources {
+            let found = match source {
+                Source::PathPrefix => strip_path_version(req),
+                Source::Accept => req.header("accept").and_then(accept_version),
+                Source::Header(name) => req
+                    .headers()
+                    .get(name)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(parse_version),
+            };
+            if version.is_none() {
+                version = found;
+            }
+        }
+
+        let version = version.or(self.default);
+        if let Some(v) = version {
+            req.extensions_mut().insert(ApiVersion(v));
+        }
+        version
+    }
+
+    /// Add `Deprecation`/`Sunset` headers for a deprecated `version`.
+    pub(crate) fn annotate(&self, version: u32, headers: &mut HeaderMap) {
+        let Some(lifecycle) = self.lifecycle.get(&version) else {
+            return;
+        };
+        if let Some(since) = lifecycle.deprecated {
+            let secs = since
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            if let Ok(value) = HeaderValue::from_str(&format!("@{}", secs)) {
+                headers.insert(HeaderName::from_static("deprecation"), value);
+            }
+        }
+        if let Some(at) = lifecycle.sunset {
+            if let Ok(value) = HeaderValue::from_str(&httpdate::fmt_http_date(at)) {
+                headers.insert(HeaderName::from_static("sunset"), value);
+            }
+        }
+    }
+}
+
+/// Parse `2` or `v2`.
+fn parse_version(value: &str) -> Option<u32> {
+    let value = value.trim();
+    let value = value
+        .strip_prefix('v')
+        .or_else(|| value.strip_prefix('V'))
+        .unwrap_or(value);
+    value.parse().ok()
+}
+
+/// Strip a leading `/v{n}` segment from the request path.
+fn strip_path_version(req: &mut Req) -> Option<u32> {
+    let path = req.path();
+    let rest = path.strip_prefix("/v")?;
+    let end = rest.find('/').unwrap_or(rest.len());
+    let version: u32 = rest[..end].parse().ok()?;
+
+    let remaining = if end == rest.len() { "/" } else { &rest[end..] };
+    let path_and_query = match req.query() {
+        Some(query) => format!("{}?{}", remaining, query),
+        None => remaining.to_string(),
+    };
+    let mut parts = req.uri().clone().into_parts();
+    parts.path_and_query = Some(path_and_query.parse().ok()?);
+    req.set_uri(hyper::Uri::from_parts(parts).ok()?);
+    Some(version)
+}
+
+/// Find a version in an `Accept` header.
+fn accept_version(accept: &str) -> Option<u32> {
+    accept.split(',').find_map(|range| {
+        let mut parts = range.split(';');
+        let media = parts.next()?.trim();
+        let param = parts.find_map(|p| {
+            let (key, value) = p.split_once('=')?;
+            matches!(key.trim(), "version" | "v")
+                .then(|| parse_version(value.trim_matches('"')))
+                .flatten()
+        });
+        param.or_else(|| {
+            let subtype = media.split_once('/')?.1;
+            subtype
+                .split(['.', '+', '-'])
+                .filter(|segment| segment.starts_with('v'))
+                .find_map(parse_version)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_version() {
+        assert_eq!(accept_version("application/vnd.acme.v2+json"), Some(2));
+        assert_eq!(accept_version("application/json; version=3"), Some(3));
+        assert_eq!(
+            accept_version("text/html, application/vnd.acme-v4+json"),
+            Some(4)
+        );
+        assert_eq!(accept_version("application/json"), None);
+        assert_eq!(accept_version("application/vnd.events+json"), None);
+    }
+
+    #[test]
+    fn test_annotate() {
+        let versioning = Versioning::new()
+            .deprecate(
+                1,
+                UNIX_EPOCH + std::time::Duration::from_secs(1_688_169_599),
+            )
+            .sunset(
+                1,
+                UNIX_EPOCH + std::time::Duration::from_secs(1_767_225_600),
+            );
+        let mut headers = HeaderMap::new();
+        versioning.annotate(1, &mut headers);
+        assert_eq!(headers["deprecation"], "@1688169599");
+        assert_eq!(headers["sunset"], "Thu, 01 Jan 2026 00:00:00 GMT");
+
+        let mut headers = HeaderMap::new();
+        versioning.annotate(2, &mut headers);
+        assert!(headers.is_empty());
+    }
+}
@@ -23,16 +23,51 @@
 //!     ws.upgrade(|socket| Box::pin(handle_ws(socket)))
 //! }
 //! ```
+//!
+//! ## Shutdown
+//!
+//! When the server shuts down, every open socket is sent
+//! `Close(1001 Going Away)` and `receive` returns that close frame, so
+//! handlers can flush per-connection state before returning. Handlers that
+//! only send can wait on [`WebSocket::going_away`] instead. The server waits
+//! for handlers to return for up to `set_websocket_shutdown_grace`
+//! (10 seconds by default) before exiting.
+//!
+//! ## Limits
+//!
+//! [`WebSocketLimits`], set with `RustApi::set_websocket_limits`, caps
+//! concurrent connections (upgrades beyond the cap are refused with 503, or
+//! 429 for the per-IP cap) and polices each connection's messages: peers
+//! sending too fast are closed with 1008 Policy Violation and oversized
+//! frames or messages with 1009 Message Too Big. Frame sizes are checked
+//! against the declared length, so oversized payloads are never buffered. After such a close, `receive`
+//! returns the close frame once and `None` afterwards.
+//!
+//! ## Sub-protocols
+//!
+//! [`WebSocketUpgrade::protocols`] lists the sub-protocols a client offers
+//! in `Sec-WebSocket-Protocol`, and [`WebSocketUpgrade::upgrade_protocol`]
+//! accepts one. [`SubProtocols`] serves several [`SubProtocol`]
+//! implementations on one path, picking the first one the client offers
+//! and refusing clients that offer none with 400.
 
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, Bytes, BytesMut};
 use hyper::upgrade::Upgraded;
 use hyper_util::rt::TokioIo;
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::future::Future;
+use std::io::{self, IoSlice};
+use std::net::IpAddr;
 use std::pin::Pin;
-use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::watch;
 
 use crate::extractors::FromRequest;
+use crate::route::RateLimit;
+use crate::route_table::RateLimiter;
 use crate::{Error, Req, Res, Result};
 
 /// Handler function for WebSocket connections.
@@ -44,9 +79,38 @@ pub type WebSocketHandler =
 /// Validates WebSocket handshake and provides upgrade method.
 pub struct WebSocketUpgrade {
     key: String,
+    protocols: Vec<String>,
 }
 
 impl WebSocketUpgrade {
+    fn from_req(req: &Req) -> Result<Self> {
+        if !req.is_websocket_upgrade() {
+            return Err(Error::Custom("Not a WebSocket upgrade request".into()));
+        }
+
+        let key = req
+            .websocket_key()
+            .ok_or_else(|| Error::Custom("Missing Sec-WebSocket-Key header".into()))?
+            .to_string();
+        let protocols = req
+            .headers()
+            .get_all("sec-websocket-protocol")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        Ok(WebSocketUpgrade { key, protocols })
+    }
+
+    /// Sub-protocols offered by the client, in its order of preference.
+    pub fn protocols(&self) -> &[String] {
+        &self.protocols
+    }
+
     /// Upgrade connection with handler callback.
     pub fn upgrade<F>(self, handler: F) -> Res
     where
@@ -54,6 +118,14 @@ impl WebSocketUpgrade {
     {
         Res::websocket(&self.key, handler)
     }
+
+    /// Upgrade, accepting `protocol` (one of [`protocols`](Self::protocols)).
+    pub fn upgrade_protocol<F>(self, protocol: &str, handler: F) -> Res
+    where
+        F: Fn(WebSocket) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync + 'static,
+    {
+        Res::websocket(&self.key, handler).header("sec-websocket-protocol", protocol)
+    }
 }
 
 #[async_trait::async_trait]
@@ -62,23 +134,303 @@ where
     S: Send + Sync + 'static,
 {
     async fn from_request(req: &mut Req, _state: &Arc<S>) -> Result<Self> {
-        if !req.is_websocket_upgrade() {
-            return Err(Error::Custom("Not a WebSocket upgrade request".into()));
+        WebSocketUpgrade::from_req(req)
+    }
+}
+
+/// Protocol spoken over a WebSocket, negotiated via `Sec-WebSocket-Protocol`.
+#[async_trait::async_trait]
+pub trait SubProtocol: Send + Sync + 'static {
+    /// Names this protocol answers to (e.g. `v12.stomp`).
+    fn names(&self) -> &[&'static str];
+
+    /// Serve one connection that negotiated `name`.
+    async fn serve(&self, name: &str, socket: WebSocket);
+}
+
+/// WebSocket endpoint serving one of several [`SubProtocol`]s.
+///
+/// ```rust,ignore
+/// SubProtocols::new().protocol(Stomp::new(broadcast)).install(&mut app, "/ws");
+/// ```
+#[derive(Default)]
+pub struct SubProtocols {
+    protocols: Vec<Arc<dyn SubProtocol>>,
+}
+
+impl SubProtocols {
+    /// Create an endpoint with no protocols.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve `protocol`.
+    pub fn protocol<P: SubProtocol>(mut self, protocol: P) -> Self {
+        self.protocols.push(Arc::new(protocol));
+        self
+    }
+
+    /// Register the upgrade route at `path`.
+    pub fn install<S: Send + Sync + 'static>(self, app: &mut crate::RustApi<S>, path: &str) {
+        let protocols = Arc::new(self.protocols);
+        app.get(path, move |req: Req| {
+            let protocols = Arc::clone(&protocols);
+            async move {
+                let upgrade = WebSocketUpgrade::from_req(&req)?;
+                let chosen = upgrade.protocols().iter().find_map(|offered| {
+                    protocols.iter().find_map(|protocol| {
+                        let name = protocol.names().iter().find(|name| **name == offered)?;
+                        Some((Arc::clone(protocol), *name))
+                    })
+                });
+                let Some((protocol, name)) = chosen else {
+                    let supported: Vec<&str> = protocols
+                        .iter()
+                        .flat_map(|protocol| protocol.names().iter().copied())
+                        .collect();
+                    return Err(Error::bad_request(format!(
+                        "Unsupported WebSocket sub-protocol; expected one of: {}",
+                        supported.join(", ")
+                    )));
+                };
+                Ok(upgrade.upgrade_protocol(name, move |socket| {
+                    let protocol = Arc::clone(&protocol);
+                    Box::pin(async move { protocol.serve(name, socket).await })
+                }))
+            }
+        });
+    }
+}
+
+/// Close code sent to sockets when the server shuts down.
+const GOING_AWAY: u16 = 1001;
+/// Close code sent to peers breaking the protocol.
+const PROTOCOL_ERROR: u16 = 1002;
+/// Close code reported when the peer's close frame had no code.
+const NO_STATUS: u16 = 1005;
+/// Close code reported when the connection dropped without a close frame.
+const ABNORMAL: u16 = 1006;
+/// Close code sent to peers sending malformed text.
+const INVALID_PAYLOAD: u16 = 1007;
+/// Close code and reason for a peer breaking the protocol.
+type Violation = (u16, &'static str);
+
+/// How long `close` waits for the peer to answer a close frame.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Close code sent to peers exceeding the message rate.
+const POLICY_VIOLATION: u16 = 1008;
+/// Close code sent to peers sending oversized frames or messages.
+const MESSAGE_TOO_BIG: u16 = 1009;
+
+/// Connection limits, message limits and protocol checks for WebSockets.
+///
+/// ```rust
+/// use rust_api::{RustApi, route::RateLimit, websocket::WebSocketLimits};
+///
+/// let mut app = RustApi::new();
+/// app.set_websocket_limits(
+///     WebSocketLimits::new()
+///         .max_connections(10_000)
+///         .max_connections_per_ip(20)
+///         .message_rate(RateLimit::per_second(50))
+///         .max_frame_size(16 * 1024)
+///         .max_message_size(64 * 1024),
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct WebSocketLimits {
+    max_connections: Option<usize>,
+    max_connections_per_ip: Option<usize>,
+    message_rate: Option<RateLimit>,
+    max_frame_size: usize,
+    max_message_size: usize,
+    strict: bool,
+}
+
+impl Default for WebSocketLimits {
+    fn default() -> Self {
+        Self {
+            max_connections: None,
+            max_connections_per_ip: None,
+            message_rate: None,
+            max_frame_size: 16 << 20,
+            max_message_size: 64 << 20,
+            strict: true,
         }
+    }
+}
 
-        let key = req
-            .websocket_key()
-            .ok_or_else(|| Error::Custom("Missing Sec-WebSocket-Key header".into()))?
-            .to_string();
+impl WebSocketLimits {
+    /// No connection caps or message rate; 16 MiB frames, 64 MiB
+    /// messages and strict protocol checks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Refuse upgrades with 503 while `max` sockets are open.
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.max_connections = Some(max);
+        self
+    }
+
+    /// Refuse upgrades with 429 while a client IP has `max` sockets open.
+    pub fn max_connections_per_ip(mut self, max: usize) -> Self {
+        self.max_connections_per_ip = Some(max);
+        self
+    }
+
+    /// Close connections receiving messages faster than `rate` with 1008.
+    ///
+    /// Every message except close frames counts; bursts up to
+    /// `rate.requests` are allowed.
+    pub fn message_rate(mut self, rate: RateLimit) -> Self {
+        self.message_rate = Some(rate);
+        self
+    }
+
+    /// Close connections receiving a frame over `bytes` with 1009.
+    ///
+    /// Checked against the declared length, before the payload is read.
+    pub fn max_frame_size(mut self, bytes: usize) -> Self {
+        self.max_frame_size = bytes;
+        self
+    }
+
+    /// Close connections receiving a message over `bytes` with 1009.
+    ///
+    /// Fragmented messages count all their frames.
+    pub fn max_message_size(mut self, bytes: usize) -> Self {
+        self.max_message_size = bytes;
+        self
+    }
+
+    /// Enforce RFC 6455 framing rules (default on).
+    ///
+    /// Strict mode closes connections with 1002 Protocol Error for unmasked
+    /// frames, reserved bits set, and control frames that are fragmented or
+    /// longer than 125 bytes. Turn it off to interoperate with clients that
+    /// break these rules; malformed text (1007), unknown opcodes and bad
+    /// fragment sequences (1002) are rejected either way.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+}
+
+/// Open WebSocket connections of one application.
+pub(crate) struct WebSocketRegistry {
+    limits: WebSocketLimits,
+    going_away: watch::Sender<bool>,
+    active: watch::Sender<usize>,
+    per_ip: Mutex<HashMap<IpAddr, usize>>,
+}
+
+impl Default for WebSocketRegistry {
+    fn default() -> Self {
+        Self::new(WebSocketLimits::default())
+    }
+}
+
+impl WebSocketRegistry {
+    pub(crate) fn new(limits: WebSocketLimits) -> Self {
+        Self {
+            limits,
+            going_away: watch::Sender::new(false),
+            active: watch::Sender::new(0),
+            per_ip: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Track a new connection from `ip` until the returned guard is
+    /// dropped, or refuse it when a connection limit is reached.
+    pub(crate) fn register(self: &Arc<Self>, ip: IpAddr) -> Result<Registration> {
+        let mut per_ip = self.per_ip.lock().unwrap();
+        if let Some(max) = self.limits.max_connections {
+            if *self.active.borrow() >= max {
+                return Err(Error::Status(
+                    503,
+                    Some("WebSocket connection limit reached".into()),
+                ));
+            }
+        }
+        if let Some(max) = self.limits.max_connections_per_ip {
+            let open = per_ip.entry(ip).or_default();
+            if *open >= max {
+                return Err(Error::too_many_requests(
+                    "Too many WebSocket connections from this address",
+                ));
+            }
+            *open += 1;
+        }
+        self.active.send_modify(|active| *active += 1);
+        Ok(Registration {
+            ip,
+            going_away: self.going_away.subscribe(),
+            registry: Arc::clone(self),
+        })
+    }
 
-        Ok(WebSocketUpgrade { key })
+    /// Signal every open socket to close, then wait up to `grace` for
+    /// their handlers to return.
+    pub(crate) async fn shutdown(&self, grace: Duration) {
+        self.going_away.send_replace(true);
+        let mut active = self.active.subscribe();
+        let open = *active.borrow();
+        if open == 0 {
+            return;
+        }
+        log::info!("closing {} WebSocket connection(s)", open);
+        if tokio::time::timeout(grace, active.wait_for(|active| *active == 0))
+            .await
+            .is_err()
+        {
+            log::warn!(
+                "{} WebSocket connection(s) still open after {:?}; dropping them",
+                *active.borrow(),
+                grace
+            );
+        }
     }
 }
 
+/// Registry entry of one connection.
+pub(crate) struct Registration {
+    ip: IpAddr,
+    going_away: watch::Receiver<bool>,
+    registry: Arc<WebSocketRegistry>,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let mut per_ip = self.registry.per_ip.lock().unwrap();
+        if let Some(open) = per_ip.get_mut(&self.ip) {
+            *open -= 1;
+            if *open == 0 {
+                per_ip.remove(&self.ip);
+            }
+        }
+        self.registry.active.send_modify(|active| *active -= 1);
+    }
+}
+
+type CloseCallback = Box<dyn FnOnce(&CloseFrame) + Send>;
+
 /// WebSocket connection over an upgraded HTTP connection.
 pub struct WebSocket {
     stream: TokioIo<Upgraded>,
     buffer: BytesMut,
+    fragments: Fragments,
+    strict: bool,
+    limiter: Option<RateLimiter>,
+    max_frame_size: usize,
+    max_message_size: usize,
+    registration: Registration,
+    close_sent: bool,
+    /// First close frame sent or received.
+    closing: Option<CloseFrame>,
+    /// No further frames are read.
+    closed: bool,
+    on_close: Option<CloseCallback>,
 }
 
 /// WebSocket message frame.
@@ -87,11 +439,11 @@ pub enum Message {
     /// UTF-8 text message.
     Text(String),
     /// Binary data message.
-    Binary(Vec<u8>),
+    Binary(Bytes),
     /// Ping control frame.
-    Ping(Vec<u8>),
+    Ping(Bytes),
     /// Pong control frame.
-    Pong(Vec<u8>),
+    Pong(Bytes),
     /// Close control frame.
     Close(Option<CloseFrame>),
 }
@@ -105,11 +457,66 @@ pub struct CloseFrame {
     pub reason: String,
 }
 
+impl CloseFrame {
+    fn new(code: u16, reason: &str) -> Self {
+        Self {
+            code,
+            reason: reason.into(),
+        }
+    }
+}
+
 impl WebSocket {
-    pub(crate) fn new(upgraded: Upgraded) -> Self {
+    pub(crate) fn new(upgraded: Upgraded, registration: Registration) -> Self {
+        let limits = &registration.registry.limits;
         Self {
             stream: TokioIo::new(upgraded),
             buffer: BytesMut::with_capacity(8192),
+            fragments: Fragments::default(),
+            strict: limits.strict,
+            limiter: limits.message_rate.map(RateLimiter::new),
+            max_frame_size: limits.max_frame_size,
+            max_message_size: limits.max_message_size,
+            registration,
+            close_sent: false,
+            closing: None,
+            closed: false,
+            on_close: None,
+        }
+    }
+
+    /// Run `callback` once when the connection closes.
+    ///
+    /// It receives the close frame that started the closing handshake: the
+    /// peer's (code 1005 if it sent none) or the one sent by this side.
+    /// If the connection ends without any close frame, it gets 1006
+    /// Abnormal Closure. Runs at the latest when the socket is dropped.
+    pub fn on_close<F>(&mut self, callback: F)
+    where
+        F: FnOnce(&CloseFrame) + Send + 'static,
+    {
+        self.on_close = Some(Box::new(callback));
+    }
+
+    /// Close frame that started the closing handshake, if any.
+    pub fn close_frame(&self) -> Option<&CloseFrame> {
+        self.closing.as_ref()
+    }
+
+    /// Whether the server is shutting down.
+    pub fn is_going_away(&self) -> bool {
+        *self.registration.going_away.borrow()
+    }
+
+    /// Resolve once the server starts shutting down.
+    ///
+    /// The future does not borrow the socket, so it can be awaited next to
+    /// `send` in `tokio::select!`. After it resolves, flush any state and
+    /// close with `close_with(1001, ...)`.
+    pub fn going_away(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut going_away = self.registration.going_away.clone();
+        async move {
+            let _ = going_away.wait_for(|going_away| *going_away).await;
         }
     }
 
@@ -119,163 +526,711 @@ impl WebSocket {
     }
 
     /// Send binary message.
-    pub async fn send_binary(&mut self, data: impl Into<Vec<u8>>) -> Result<()> {
+    pub async fn send_binary(&mut self, data: impl Into<Bytes>) -> Result<()> {
         self.send(Message::Binary(data.into())).await
     }
 
     /// Send message.
+    ///
+    /// Fails once a close frame has been sent.
     pub async fn send(&mut self, message: Message) -> Result<()> {
-        let frame = encode_frame(&message)?;
-        self.stream
-            .write_all(&frame)
+        if self.close_sent {
+            return Err(Error::Custom("WebSocket close frame already sent".into()));
+        }
+        if let Message::Close(frame) = &message {
+            self.close_sent = true;
+            self.closing
+                .get_or_insert_with(|| frame.clone().unwrap_or(CloseFrame::new(NO_STATUS, "")));
+        }
+        let (header, payload) = frame_parts(&message);
+        write_frame(&mut self.stream, header.as_slice(), &payload)
             .await
             .map_err(|e| Error::Custom(format!("WebSocket write error: {}", e)))?;
         Ok(())
     }
 
     /// Receive message.
+    ///
+    /// A close frame from the peer is answered with the same code before it
+    /// is returned; invalid ones fail the connection with 1002, or 1007 for
+    /// a malformed reason. On server shutdown, sends `Close(1001 Going Away)`
+    /// to the peer and returns that close frame. Returns `None` once the
+    /// connection is closed.
     pub async fn receive(&mut self) -> Result<Option<Message>> {
+        if self.closed {
+            return Ok(None);
+        }
         loop {
-            if let Some(message) = decode_frame(&mut self.buffer)? {
+            loop {
+                let header = match parse_header(&self.buffer) {
+                    Ok(Some(header)) => header,
+                    Ok(None) => break,
+                    Err((code, reason)) => return self.fail(code, reason).await,
+                };
+                if self.strict {
+                    if let Err((code, reason)) = header.check_strict() {
+                        return self.fail(code, reason).await;
+                    }
+                }
+                if header.payload_len > self.max_frame_size as u64 {
+                    return self.fail(MESSAGE_TOO_BIG, "Frame Too Big").await;
+                }
+                if header.opcode < 0x8
+                    && (self.fragments.len() as u64).saturating_add(header.payload_len)
+                        > self.max_message_size as u64
+                {
+                    return self.fail(MESSAGE_TOO_BIG, "Message Too Big").await;
+                }
+                let Some(payload) = take_payload(&mut self.buffer, &header) else {
+                    break;
+                };
+                let message = match self.fragments.push(header.fin, header.opcode, payload) {
+                    Ok(Some(message)) => message,
+                    Ok(None) => continue,
+                    Err((code, reason)) => return self.fail(code, reason).await,
+                };
+                if let Message::Close(frame) = &message {
+                    self.closed = true;
+                    self.closing.get_or_insert_with(|| {
+                        frame.clone().unwrap_or(CloseFrame::new(NO_STATUS, ""))
+                    });
+                    if !self.close_sent {
+                        // Echo the status code (RFC 6455 5.5.1).
+                        let reply = frame.as_ref().map(|frame| CloseFrame::new(frame.code, ""));
+                        self.send(Message::Close(reply)).await?;
+                    }
+                    self.run_on_close();
+                    return Ok(Some(message));
+                }
+                if self
+                    .limiter
+                    .as_ref()
+                    .is_some_and(|limiter| limiter.acquire().is_err())
+                {
+                    return self.fail(POLICY_VIOLATION, "Message rate exceeded").await;
+                }
                 return Ok(Some(message));
             }
 
-            let mut buf = vec![0u8; 4096];
-            let n = self
-                .stream
-                .read(&mut buf)
-                .await
-                .map_err(|e| Error::Custom(format!("WebSocket read error: {}", e)))?;
+            self.buffer.reserve(4096);
+            let read = tokio::select! {
+                biased;
+                _ = self.registration.going_away.wait_for(|going_away| *going_away),
+                    if !self.close_sent => None,
+                read = self.stream.read_buf(&mut self.buffer) => Some(read),
+            };
+            let Some(read) = read else {
+                let frame = CloseFrame::new(GOING_AWAY, "Going Away");
+                self.send(Message::Close(Some(frame.clone()))).await?;
+                return Ok(Some(Message::Close(Some(frame))));
+            };
+            let n = read.map_err(|e| Error::Custom(format!("WebSocket read error: {}", e)))?;
 
             if n == 0 {
+                self.closed = true;
+                self.run_on_close();
                 return Ok(None);
             }
+        }
+    }
+
+    /// Close the connection for a violation and stop reading.
+    async fn fail(&mut self, code: u16, reason: &str) -> Result<Option<Message>> {
+        self.closed = true;
+        self.buffer.clear();
+        let frame = CloseFrame::new(code, reason);
+        if !self.close_sent {
+            self.send(Message::Close(Some(frame.clone()))).await?;
+        }
+        self.run_on_close();
+        Ok(Some(Message::Close(Some(frame))))
+    }
 
-            self.buffer.extend_from_slice(&buf[..n]);
+    fn run_on_close(&mut self) {
+        if let Some(callback) = self.on_close.take() {
+            let abnormal = CloseFrame::new(ABNORMAL, "");
+            callback(self.closing.as_ref().unwrap_or(&abnormal));
         }
     }
 
     /// Close connection.
+    ///
+    /// Waits up to 5 seconds for the peer's close frame, discarding any
+    /// messages received meanwhile.
     pub async fn close(mut self) -> Result<()> {
-        self.send(Message::Close(None)).await
+        self.send(Message::Close(None)).await?;
+        self.await_close().await;
+        Ok(())
     }
 
     /// Close connection with code and reason.
+    ///
+    /// Waits like [`close`](Self::close).
     pub async fn close_with(mut self, code: u16, reason: impl Into<String>) -> Result<()> {
         self.send(Message::Close(Some(CloseFrame {
             code,
             reason: reason.into(),
         })))
-        .await
+        .await?;
+        self.await_close().await;
+        Ok(())
+    }
+
+    async fn await_close(&mut self) {
+        let drain = async { while let Ok(Some(_)) = self.receive().await {} };
+        let _ = tokio::time::timeout(CLOSE_TIMEOUT, drain).await;
     }
 }
 
-fn encode_frame(message: &Message) -> Result<Vec<u8>> {
-    let (opcode, payload): (u8, Vec<u8>) = match message {
-        Message::Text(text) => (0x1, text.as_bytes().to_vec()),
-        Message::Binary(data) => (0x2, data.clone()),
+impl Drop for WebSocket {
+    fn drop(&mut self) {
+        self.run_on_close();
+    }
+}
+
+/// Frame codec entry points for benchmarks; not a stable API.
+#[doc(hidden)]
+pub mod codec {
+    use super::{BytesMut, Message, Result};
+
+    pub fn encode(message: &Message) -> Result<Vec<u8>> {
+        super::encode_frame(message)
+    }
+
+    pub fn decode(buffer: &mut BytesMut) -> Result<Option<Message>> {
+        super::decode_frame(buffer)
+    }
+}
+
+/// Header of an unmasked server frame: up to 10 bytes.
+struct FrameHeader {
+    bytes: [u8; 10],
+    len: usize,
+}
+
+impl FrameHeader {
+    fn new(opcode: u8, payload_len: usize) -> Self {
+        let mut bytes = [0u8; 10];
+        bytes[0] = 0x80 | opcode;
+        let len = if payload_len < 126 {
+            bytes[1] = payload_len as u8;
+            2
+        } else if payload_len < 65536 {
+            bytes[1] = 126;
+            bytes[2..4].copy_from_slice(&(payload_len as u16).to_be_bytes());
+            4
+        } else {
+            bytes[1] = 127;
+            bytes[2..10].copy_from_slice(&(payload_len as u64).to_be_bytes());
+            10
+        };
+        Self { bytes, len }
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+/// Split a message into its frame header and payload, borrowing the payload
+/// except for close frames.
+fn frame_parts(message: &Message) -> (FrameHeader, Cow<'_, [u8]>) {
+    let (opcode, payload): (u8, Cow<'_, [u8]>) = match message {
+        Message::Text(text) => (0x1, Cow::Borrowed(text.as_bytes())),
+        Message::Binary(data) => (0x2, Cow::Borrowed(&data[..])),
         Message::Close(frame) => {
             let mut payload = Vec::new();
             if let Some(f) = frame {
                 payload.extend_from_slice(&f.code.to_be_bytes());
                 payload.extend_from_slice(f.reason.as_bytes());
             }
-            (0x8, payload)
+            (0x8, Cow::Owned(payload))
         }
-        Message::Ping(data) => (0x9, data.clone()),
-        Message::Pong(data) => (0xA, data.clone()),
+        Message::Ping(data) => (0x9, Cow::Borrowed(&data[..])),
+        Message::Pong(data) => (0xA, Cow::Borrowed(&data[..])),
     };
+    (FrameHeader::new(opcode, payload.len()), payload)
+}
 
-    let payload_len = payload.len();
-    let mut frame = Vec::with_capacity(10 + payload_len);
+fn encode_frame(message: &Message) -> Result<Vec<u8>> {
+    let (header, payload) = frame_parts(message);
+    let mut frame = Vec::with_capacity(header.len + payload.len());
+    frame.extend_from_slice(header.as_slice());
+    frame.extend_from_slice(&payload);
+    Ok(frame)
+}
 
-    frame.push(0x80 | opcode);
+/// Write header and payload with vectored writes, without joining them.
+async fn write_frame<W: AsyncWrite + Unpin>(
+    stream: &mut W,
+    mut header: &[u8],
+    mut payload: &[u8],
+) -> io::Result<()> {
+    while !header.is_empty() || !payload.is_empty() {
+        let n = if header.is_empty() {
+            stream.write(payload).await?
+        } else {
+            let slices = [IoSlice::new(header), IoSlice::new(payload)];
+            stream.write_vectored(&slices).await?
+        };
+        if n == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        let from_header = n.min(header.len());
+        header = &header[from_header..];
+        payload = &payload[n - from_header..];
+    }
+    stream.flush().await
+}
 
-    if payload_len < 126 {
-        frame.push(payload_len as u8);
-    } else if payload_len < 65536 {
-        frame.push(126);
-        frame.extend_from_slice(&(payload_len as u16).to_be_bytes());
-    } else {
-        frame.push(127);
-        frame.extend_from_slice(&(payload_len as u64).to_be_bytes());
+/// XOR `payload` with the masking key, eight bytes at a time.
+fn apply_mask(payload: &mut [u8], mask: [u8; 4]) {
+    let word = u64::from_ne_bytes([
+        mask[0], mask[1], mask[2], mask[3], mask[0], mask[1], mask[2], mask[3],
+    ]);
+    let mut chunks = payload.chunks_exact_mut(8);
+    for chunk in &mut chunks {
+        let value = u64::from_ne_bytes(chunk.try_into().unwrap()) ^ word;
+        chunk.copy_from_slice(&value.to_ne_bytes());
     }
+    // Chunks are a multiple of 4 long, so the remainder starts at mask[0].
+    for (i, byte) in chunks.into_remainder().iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+}
 
-    frame.extend_from_slice(&payload);
-    Ok(frame)
+/// Parsed header of an incoming frame.
+struct IncomingHeader {
+    fin: bool,
+    rsv: u8,
+    opcode: u8,
+    mask: Option<[u8; 4]>,
+    header_len: usize,
+    payload_len: u64,
 }
 
-fn decode_frame(buffer: &mut BytesMut) -> Result<Option<Message>> {
+/// Parse the header at the front of `buffer`, or `None` until it is fully
+/// buffered.
+fn parse_header(buffer: &[u8]) -> std::result::Result<Option<IncomingHeader>, Violation> {
     if buffer.len() < 2 {
         return Ok(None);
     }
 
-    let first_byte = buffer[0];
-    let second_byte = buffer[1];
-
-    let _fin = (first_byte & 0x80) != 0;
-    let opcode = first_byte & 0x0F;
-    let masked = (second_byte & 0x80) != 0;
-    let mut payload_len = (second_byte & 0x7F) as usize;
+    let fin = (buffer[0] & 0x80) != 0;
+    let rsv = (buffer[0] >> 4) & 0x07;
+    let opcode = buffer[0] & 0x0F;
+    let masked = (buffer[1] & 0x80) != 0;
 
-    let mut header_len = 2;
+    let (payload_len, mut header_len) = match buffer[1] & 0x7F {
+        126 => {
+            let Some(len) = buffer.get(2..4) else {
+                return Ok(None);
+            };
+            (u64::from(u16::from_be_bytes([len[0], len[1]])), 4)
+        }
+        127 => {
+            let Some(len) = buffer.get(2..10) else {
+                return Ok(None);
+            };
+            let len = u64::from_be_bytes(len.try_into().unwrap());
+            // RFC 6455 5.2: the most significant bit must be 0.
+            if len >> 63 != 0 {
+                return Err((PROTOCOL_ERROR, "Invalid frame length"));
+            }
+            (len, 10)
+        }
+        len => (u64::from(len), 2),
+    };
 
-    if payload_len == 126 {
-        if buffer.len() < 4 {
+    let mask = if masked {
+        let Some(key) = buffer.get(header_len..header_len + 4) else {
             return Ok(None);
+        };
+        header_len += 4;
+        Some(key.try_into().unwrap())
+    } else {
+        None
+    };
+
+    Ok(Some(IncomingHeader {
+        fin,
+        rsv,
+        opcode,
+        mask,
+        header_len,
+        payload_len,
+    }))
+}
+
+impl IncomingHeader {
+    /// RFC 6455 checks skipped in lenient mode: client frames are masked,
+    /// no extension bits are set, and control frames are short and
+    /// unfragmented.
+    fn check_strict(&self) -> std::result::Result<(), Violation> {
+        if self.mask.is_none() {
+            return Err((PROTOCOL_ERROR, "Unmasked client frame"));
         }
-        payload_len = u16::from_be_bytes([buffer[2], buffer[3]]) as usize;
-        header_len = 4;
-    } else if payload_len == 127 {
-        if buffer.len() < 10 {
-            return Ok(None);
+        if self.rsv != 0 {
+            return Err((PROTOCOL_ERROR, "Reserved bits set"));
+        }
+        if self.opcode >= 0x8 && (!self.fin || self.payload_len > 125) {
+            return Err((PROTOCOL_ERROR, "Invalid control frame"));
         }
-        payload_len = u64::from_be_bytes([
-            buffer[2], buffer[3], buffer[4], buffer[5], buffer[6], buffer[7], buffer[8], buffer[9],
-        ]) as usize;
-        header_len = 10;
+        Ok(())
     }
+}
 
-    let mask_key_start = header_len;
-    if masked {
-        header_len += 4;
+/// Remove the frame described by `header` from `buffer` and return its
+/// payload, unmasked in place, or `None` until it is fully buffered.
+///
+/// Callers check `payload_len` against their frame limit first.
+fn take_payload(buffer: &mut BytesMut, header: &IncomingHeader) -> Option<Bytes> {
+    let payload_len = header.payload_len as usize;
+    if buffer.len() < header.header_len + payload_len {
+        return None;
     }
 
-    if buffer.len() < header_len + payload_len {
-        return Ok(None);
+    buffer.advance(header.header_len);
+    let mut payload = buffer.split_to(payload_len);
+    if let Some(mask) = header.mask {
+        apply_mask(&mut payload, mask);
     }
+    Some(payload.freeze())
+}
 
-    let mut payload = buffer[header_len..header_len + payload_len].to_vec();
-
-    if masked {
-        let mask = &buffer[mask_key_start..mask_key_start + 4];
-        for (i, byte) in payload.iter_mut().enumerate() {
-            *byte ^= mask[i % 4];
-        }
+/// Decode a single unfragmented frame.
+fn decode_frame(buffer: &mut BytesMut) -> Result<Option<Message>> {
+    let violation = |(_, reason): Violation| Error::Custom(reason.into());
+    let Some(header) = parse_header(buffer).map_err(violation)? else {
+        return Ok(None);
+    };
+    if usize::try_from(header.payload_len).is_err() {
+        return Err(Error::Custom("Frame too large".into()));
     }
+    match take_payload(buffer, &header) {
+        Some(payload) => into_message(header.opcode, payload)
+            .map(Some)
+            .map_err(violation),
+        None => Ok(None),
+    }
+}
 
-    buffer.advance(header_len + payload_len);
-
+fn into_message(opcode: u8, payload: Bytes) -> std::result::Result<Message, Violation> {
     let message = match opcode {
         0x1 => Message::Text(
-            String::from_utf8(payload)
-                .map_err(|_| Error::Custom("Invalid UTF-8 in text frame".into()))?,
+            String::from_utf8(payload.into())
+                .map_err(|_| (INVALID_PAYLOAD, "Invalid UTF-8 in text"))?,
         ),
         0x2 => Message::Binary(payload),
-        0x8 => {
-            let frame = if payload.len() >= 2 {
-                let code = u16::from_be_bytes([payload[0], payload[1]]);
-                let reason = String::from_utf8_lossy(&payload[2..]).to_string();
-                Some(CloseFrame { code, reason })
-            } else {
-                None
-            };
-            Message::Close(frame)
-        }
+        0x8 => Message::Close(parse_close(&payload)?),
         0x9 => Message::Ping(payload),
         0xA => Message::Pong(payload),
-        _ => return Err(Error::Custom(format!("Unknown opcode: {}", opcode))),
+        _ => return Err((PROTOCOL_ERROR, "Unknown opcode")),
     };
 
-    Ok(Some(message))
+    Ok(message)
+}
+
+/// Parse a close frame payload, or return the close code and reason to
+/// fail the connection with.
+fn parse_close(payload: &[u8]) -> std::result::Result<Option<CloseFrame>, Violation> {
+    match payload {
+        [] => Ok(None),
+        [_] => Err((PROTOCOL_ERROR, "Invalid close frame")),
+        [high, low, reason @ ..] => {
+            let code = u16::from_be_bytes([*high, *low]);
+            // Codes peers may send (RFC 6455 7.4).
+            if !matches!(code, 1000..=1003 | 1007..=1014 | 3000..=4999) {
+                return Err((PROTOCOL_ERROR, "Invalid close code"));
+            }
+            let reason = std::str::from_utf8(reason)
+                .map_err(|_| (INVALID_PAYLOAD, "Invalid close reason"))?;
+            Ok(Some(CloseFrame::new(code, reason)))
+        }
+    }
+}
+
+/// Reassembles messages split across continuation frames.
+#[derive(Default)]
+struct Fragments {
+    pending: Option<(u8, BytesMut)>,
+}
+
+impl Fragments {
+    /// Bytes of the data message received so far.
+    fn len(&self) -> usize {
+        self.pending
+            .as_ref()
+            .map_or(0, |(_, payload)| payload.len())
+    }
+
+    /// Add a frame, returning the message once it is complete.
+    ///
+    /// Control frames may arrive between fragments and are returned as is.
+    fn push(
+        &mut self,
+        fin: bool,
+        opcode: u8,
+        payload: Bytes,
+    ) -> std::result::Result<Option<Message>, Violation> {
+        match opcode {
+            0x8..=0xF => into_message(opcode, payload).map(Some),
+            0x0 => {
+                let Some((_, data)) = &mut self.pending else {
+                    return Err((PROTOCOL_ERROR, "Unexpected continuation frame"));
+                };
+                data.extend_from_slice(&payload);
+                if !fin {
+                    return Ok(None);
+                }
+                let (opcode, data) = self.pending.take().unwrap();
+                into_message(opcode, data.freeze()).map(Some)
+            }
+            0x1 | 0x2 => {
+                if self.pending.is_some() {
+                    return Err((PROTOCOL_ERROR, "Expected continuation frame"));
+                }
+                if fin {
+                    return into_message(opcode, payload).map(Some);
+                }
+                self.pending = Some((opcode, BytesMut::from(payload)));
+                Ok(None)
+            }
+            _ => Err((PROTOCOL_ERROR, "Unknown opcode")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_mask_matches_bytewise() {
+        let mask = [0x12, 0x34, 0x56, 0x78];
+        let original: Vec<u8> = (0..=255).cycle().take(1003).collect();
+        let mut payload = original.clone();
+        apply_mask(&mut payload, mask);
+        for (i, (masked, byte)) in payload.iter().zip(&original).enumerate() {
+            assert_eq!(*masked, byte ^ mask[i % 4]);
+        }
+    }
+
+    #[test]
+    fn test_decode_masked_frame() {
+        let mask = [1, 2, 3, 4];
+        let mut payload = b"hello, websocket".to_vec();
+        apply_mask(&mut payload, mask);
+
+        let mut buffer = BytesMut::new();
+        buffer.extend_from_slice(&[0x81, 0x80 | payload.len() as u8]);
+        buffer.extend_from_slice(&mask);
+        buffer.extend_from_slice(&payload);
+
+        let message = decode_frame(&mut buffer).unwrap();
+        assert_eq!(message, Some(Message::Text("hello, websocket".into())));
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_decode_binary_without_copy() {
+        let mut buffer = BytesMut::new();
+        buffer.extend_from_slice(&[0x82, 0x83, 0, 0, 0, 0, 1, 2, 3]);
+        let payload_at = buffer[6..].as_ptr();
+
+        let Some(Message::Binary(data)) = decode_frame(&mut buffer).unwrap() else {
+            panic!("expected a binary message");
+        };
+        assert_eq!(&data[..], [1, 2, 3]);
+        assert_eq!(data.as_ptr(), payload_at);
+    }
+
+    #[test]
+    fn test_parse_header_lengths() {
+        let mut header = vec![0x82, 127];
+        header.extend_from_slice(&(1u64 << 40).to_be_bytes());
+        let parsed = parse_header(&header).unwrap().unwrap();
+        assert_eq!(parsed.payload_len, 1 << 40);
+        assert_eq!(parsed.header_len, 10);
+        assert!(parse_header(&header[..6]).unwrap().is_none());
+
+        header[2] = 0x80;
+        assert_eq!(
+            parse_header(&header).err(),
+            Some((PROTOCOL_ERROR, "Invalid frame length"))
+        );
+    }
+
+    #[test]
+    fn test_parse_close_validates_code_and_reason() {
+        assert_eq!(parse_close(&[]), Ok(None));
+        assert_eq!(
+            parse_close(b"\x03\xe8bye"),
+            Ok(Some(CloseFrame::new(1000, "bye")))
+        );
+        assert_eq!(
+            parse_close(&[0x0f, 0xa0]),
+            Ok(Some(CloseFrame::new(4000, "")))
+        );
+        assert_eq!(parse_close(&[0x03]).unwrap_err().0, PROTOCOL_ERROR);
+        assert_eq!(parse_close(&[0x03, 0xed]).unwrap_err().0, PROTOCOL_ERROR);
+        assert_eq!(
+            parse_close(&[0x03, 0xe8, 0xff]).unwrap_err().0,
+            INVALID_PAYLOAD
+        );
+    }
+
+    #[test]
+    fn test_fragments_reassemble_around_control_frames() {
+        let mut fragments = Fragments::default();
+        assert_eq!(
+            fragments
+                .push(false, 0x1, Bytes::from_static(b"hel"))
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            fragments.push(true, 0x9, Bytes::from_static(b"p")).unwrap(),
+            Some(Message::Ping(Bytes::from_static(b"p")))
+        );
+        assert_eq!(
+            fragments
+                .push(false, 0x0, Bytes::from_static(b"lo, "))
+                .unwrap(),
+            None
+        );
+        assert_eq!(fragments.len(), 7);
+        assert_eq!(
+            fragments
+                .push(true, 0x0, Bytes::from_static(b"world"))
+                .unwrap(),
+            Some(Message::Text("hello, world".into()))
+        );
+
+        assert!(fragments.push(true, 0x0, Bytes::new()).is_err());
+        fragments
+            .push(false, 0x2, Bytes::from_static(&[1]))
+            .unwrap();
+        assert!(fragments.push(true, 0x2, Bytes::from_static(&[2])).is_err());
+        assert_eq!(
+            Fragments::default().push(true, 0x1, Bytes::from_static(&[0xff])),
+            Err((INVALID_PAYLOAD, "Invalid UTF-8 in text"))
+        );
+    }
+
+    #[test]
+    fn test_strict_checks() {
+        let check = |frame: &[u8]| parse_header(frame).unwrap().unwrap().check_strict();
+        assert_eq!(check(&[0x81, 0x80, 0, 0, 0, 0]), Ok(()));
+        assert_eq!(
+            check(&[0x81, 0x00]),
+            Err((PROTOCOL_ERROR, "Unmasked client frame"))
+        );
+        assert_eq!(
+            check(&[0xC1, 0x80, 0, 0, 0, 0]),
+            Err((PROTOCOL_ERROR, "Reserved bits set"))
+        );
+        assert_eq!(
+            check(&[0x09, 0x80, 0, 0, 0, 0]),
+            Err((PROTOCOL_ERROR, "Invalid control frame"))
+        );
+        assert_eq!(
+            check(&[0x89, 0xFE, 0, 126, 0, 0, 0, 0]),
+            Err((PROTOCOL_ERROR, "Invalid control frame"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_frame_vectored() {
+        let message = Message::Binary(vec![7; 300].into());
+        let (header, payload) = frame_parts(&message);
+        let mut out = Vec::new();
+        write_frame(&mut out, header.as_slice(), &payload)
+            .await
+            .unwrap();
+        assert_eq!(out, encode_frame(&message).unwrap());
+        assert_eq!(&out[..4], &[0x82, 126, 1, 44]);
+    }
+
+    #[tokio::test]
+    async fn test_registry_shutdown_waits_for_handlers() {
+        let ip = IpAddr::from([127, 0, 0, 1]);
+        let registry = Arc::new(WebSocketRegistry::default());
+        let mut flushing = registry.register(ip).unwrap();
+        let stuck = registry.register(ip).unwrap();
+        let handler = tokio::spawn(async move {
+            let _ = flushing.going_away.wait_for(|going_away| *going_away).await;
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        });
+
+        let started = std::time::Instant::now();
+        registry.shutdown(Duration::from_millis(200)).await;
+        assert!(started.elapsed() >= Duration::from_millis(200));
+        assert!(handler.is_finished());
+        assert_eq!(*registry.active.borrow(), 1);
+
+        drop(stuck);
+        let started = std::time::Instant::now();
+        registry.shutdown(Duration::from_secs(5)).await;
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_registry_connection_limits() {
+        let registry = Arc::new(WebSocketRegistry::new(
+            WebSocketLimits::new()
+                .max_connections(3)
+                .max_connections_per_ip(2),
+        ));
+        let a = IpAddr::from([10, 0, 0, 1]);
+        let b = IpAddr::from([10, 0, 0, 2]);
+        let status = |result: Result<Registration>| match result {
+            Err(Error::Status(code, _)) => code,
+            _ => panic!("expected a refusal"),
+        };
+
+        let first = registry.register(a).unwrap();
+        let _second = registry.register(a).unwrap();
+        assert_eq!(status(registry.register(a)), 429);
+        let _third = registry.register(b).unwrap();
+        assert_eq!(status(registry.register(b)), 503);
+
+        drop(first);
+        assert!(registry.register(a).is_ok());
+        assert_eq!(registry.per_ip.lock().unwrap().get(&a), Some(&1));
+    }
+
+    #[tokio::test]
+    async fn test_sub_protocol_negotiation() {
+        use crate::test::{TestClient, assert_header, assert_status};
+
+        struct Echo;
+
+        #[async_trait::async_trait]
+        impl SubProtocol for Echo {
+            fn names(&self) -> &[&'static str] {
+                &["v12.echo", "v11.echo"]
+            }
+
+            async fn serve(&self, _name: &str, _socket: WebSocket) {}
+        }
+
+        let mut app = crate::RustApi::new();
+        SubProtocols::new().protocol(Echo).install(&mut app, "/ws");
+        let client = TestClient::new(app);
+        let upgrade = |protocols: &'static str| {
+            client
+                .get("/ws")
+                .header("upgrade", "websocket")
+                .header("connection", "Upgrade")
+                .header("sec-websocket-version", "13")
+                .header("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ==")
+                .header("sec-websocket-protocol", protocols)
+        };
+
+        let res = upgrade("mqtt, v11.echo, v12.echo").send().await;
+        assert_status(&res, 101);
+        assert_header(&res, "sec-websocket-protocol", "v11.echo");
+        assert_status(&upgrade("mqtt").send().await, 400);
+    }
 }